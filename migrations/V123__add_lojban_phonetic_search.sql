@@ -0,0 +1,31 @@
+-- Typo-tolerant valsi lookup for "did you mean" suggestions.
+-- lojban_phonetic_key folds the spelling confusions learners make most often:
+--   h -> '        (apostrophe is often typed as h)
+--   c, x, q -> k  (English readers type c/x for k and vice versa)
+--   j -> z
+--   w -> u
+--   . and ,       dropped (pauses and syllable breaks)
+--   repeated vowels collapsed (vowel length is not phonemic)
+CREATE OR REPLACE FUNCTION lojban_phonetic_key(input TEXT)
+RETURNS TEXT
+LANGUAGE sql
+IMMUTABLE
+PARALLEL SAFE
+AS $$
+    SELECT regexp_replace(
+        translate(lower(input), 'hcxqjw.,', '''kkkzu'),
+        '([aeiouy])\1+',
+        '\1',
+        'g'
+    )
+$$;
+
+CREATE INDEX IF NOT EXISTS idx_valsi_phonetic_key
+ON valsi (lojban_phonetic_key(word));
+
+CREATE INDEX IF NOT EXISTS idx_valsi_word_trgm
+ON valsi USING gin (word gin_trgm_ops);
+
+-- Lets the search queries OR a phonetic valsi match into their ILIKE filter
+CREATE INDEX IF NOT EXISTS idx_definitions_valsiword_phonetic_key
+ON definitions (lojban_phonetic_key(cached_valsiword));
@@ -0,0 +1,22 @@
+-- Extra columns needed to report on search history
+ALTER TABLE user_search_history
+    ADD COLUMN search_mode TEXT,
+    ADD COLUMN result_count INTEGER,
+    ADD COLUMN languages INTEGER[];
+
+CREATE INDEX IF NOT EXISTS idx_search_history_query_lower
+ON user_search_history (lower(search_query));
+
+CREATE INDEX IF NOT EXISTS idx_search_history_mode_created
+ON user_search_history (search_mode, created_at);
+
+INSERT INTO permissions (name, description) VALUES
+('view_search_analytics', 'Can view aggregated dictionary search analytics')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission_id)
+SELECT r.role, p.id
+FROM permissions p
+CROSS JOIN (VALUES ('admin'), ('moderator'), ('editor')) AS r(role)
+WHERE p.name = 'view_search_analytics'
+ON CONFLICT (role, permission_id) DO NOTHING;
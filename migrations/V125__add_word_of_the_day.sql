@@ -0,0 +1,46 @@
+-- Editorially curated entries waiting to be featured as word of the day
+CREATE TABLE featured_queue (
+    id SERIAL PRIMARY KEY,
+    valsiid INTEGER NOT NULL REFERENCES valsi(valsiid) ON DELETE CASCADE,
+    definitionid INTEGER REFERENCES definitions(definitionid) ON DELETE SET NULL,
+    note TEXT,
+    -- NULL means "next free day"
+    scheduled_for DATE UNIQUE,
+    added_by INTEGER REFERENCES users(userid) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    used_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_featured_queue_pending ON featured_queue(created_at) WHERE used_at IS NULL;
+
+-- One featured valsi per day
+CREATE TABLE featured_words (
+    feature_date DATE PRIMARY KEY,
+    valsiid INTEGER NOT NULL REFERENCES valsi(valsiid) ON DELETE CASCADE,
+    definitionid INTEGER REFERENCES definitions(definitionid) ON DELETE SET NULL,
+    note TEXT,
+    source TEXT NOT NULL CHECK (source IN ('queue', 'auto')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    emails_sent_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_featured_words_valsiid ON featured_words(valsiid);
+
+-- Users who want the word of the day by email
+CREATE TABLE word_of_the_day_subscriptions (
+    user_id INTEGER PRIMARY KEY REFERENCES users(userid) ON DELETE CASCADE,
+    -- Preferred definition language; NULL falls back to the featured definition
+    langid INTEGER REFERENCES languages(langid) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO permissions (name, description) VALUES
+('manage_featured_words', 'Can curate the word of the day queue')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission_id)
+SELECT r.role, p.id
+FROM permissions p
+CROSS JOIN (VALUES ('admin'), ('moderator'), ('editor')) AS r(role)
+WHERE p.name = 'manage_featured_words'
+ON CONFLICT (role, permission_id) DO NOTHING;
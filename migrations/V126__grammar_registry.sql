@@ -0,0 +1,39 @@
+-- Versioned PEG grammars per language. `word` grammars drive word analysis,
+-- `sentence` grammars drive full-text parsing. At most one active version per
+-- (langid, kind); languages without an active version use the bundled files.
+CREATE TABLE grammars (
+    id SERIAL PRIMARY KEY,
+    langid INTEGER NOT NULL REFERENCES languages(langid),
+    kind TEXT NOT NULL CHECK (kind IN ('word', 'sentence')),
+    version INTEGER NOT NULL,
+    grammar_text TEXT NOT NULL,
+    notes TEXT,
+    is_active BOOLEAN NOT NULL DEFAULT false,
+    created_by INTEGER REFERENCES users(userid) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    activated_at TIMESTAMP WITH TIME ZONE,
+    UNIQUE (langid, kind, version)
+);
+
+CREATE UNIQUE INDEX idx_grammars_one_active ON grammars(langid, kind) WHERE is_active;
+
+-- Sample sentences every new grammar version is tested against before activation
+CREATE TABLE grammar_samples (
+    id SERIAL PRIMARY KEY,
+    langid INTEGER NOT NULL REFERENCES languages(langid),
+    kind TEXT NOT NULL CHECK (kind IN ('word', 'sentence')),
+    sentence TEXT NOT NULL,
+    -- false for negative samples the grammar must reject
+    should_parse BOOLEAN NOT NULL DEFAULT true,
+    created_by INTEGER REFERENCES users(userid) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (langid, kind, sentence)
+);
+
+INSERT INTO permissions (name, description) VALUES
+('manage_grammars', 'Can upload, test and activate parser grammars')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission_id)
+SELECT 'admin', id FROM permissions WHERE name = 'manage_grammars'
+ON CONFLICT (role, permission_id) DO NOTHING;
@@ -0,0 +1,32 @@
+-- Runs of the active grammar (and Tersmu) over the muplis corpus. Results are keyed by
+-- sentence text rather than muplis id because the muplis table is rebuilt on every sync.
+CREATE TABLE grammar_regression_runs (
+    id SERIAL PRIMARY KEY,
+    langid INTEGER NOT NULL REFERENCES languages(langid),
+    -- Grammar kind used for the parser pass
+    kind TEXT NOT NULL CHECK (kind IN ('word', 'sentence')),
+    -- Stored grammar version under test; NULL when the bundled grammar was active
+    grammar_id INTEGER REFERENCES grammars(id) ON DELETE SET NULL,
+    status TEXT NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'completed', 'failed')),
+    total INTEGER NOT NULL DEFAULT 0,
+    parser_passed INTEGER NOT NULL DEFAULT 0,
+    tersmu_passed INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    triggered_by INTEGER REFERENCES users(userid) ON DELETE SET NULL,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE UNIQUE INDEX idx_grammar_regression_one_running
+    ON grammar_regression_runs(langid) WHERE status = 'running';
+
+CREATE TABLE grammar_regression_results (
+    run_id INTEGER NOT NULL REFERENCES grammar_regression_runs(id) ON DELETE CASCADE,
+    sentence TEXT NOT NULL,
+    parser_ok BOOLEAN NOT NULL,
+    parser_error TEXT,
+    tersmu_ok BOOLEAN NOT NULL,
+    tersmu_error TEXT,
+    canonical TEXT,
+    PRIMARY KEY (run_id, sentence)
+);
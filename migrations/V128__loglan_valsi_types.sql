@@ -0,0 +1,22 @@
+-- Valsi types belong to a source language; NULL means usable for any (e.g. phrase)
+ALTER TABLE valsitypes ADD COLUMN IF NOT EXISTS source_langid INTEGER REFERENCES languages(langid);
+
+UPDATE valsitypes SET source_langid = 1 WHERE typeid <> 15 AND source_langid IS NULL;
+
+-- Loglan word classes, as reported by the Loglan word classifier
+INSERT INTO valsitypes (typeid, descriptor, source_langid)
+SELECT t.typeid, t.descriptor, 58
+FROM (VALUES
+    (20::smallint, 'primitive'),
+    (21::smallint, 'complex'),
+    (22::smallint, 'borrowing'),
+    (23::smallint, 'little word'),
+    (24::smallint, 'compound little word'),
+    (25::smallint, 'name'),
+    (26::smallint, 'affix')
+) AS t(typeid, descriptor)
+WHERE EXISTS (SELECT 1 FROM languages WHERE langid = 58)
+ON CONFLICT (typeid) DO NOTHING;
+
+-- Per-source-language landing data lists the newest valsi
+CREATE INDEX IF NOT EXISTS idx_valsi_source_langid_time ON valsi(source_langid, time DESC);
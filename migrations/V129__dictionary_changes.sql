@@ -0,0 +1,65 @@
+-- Change log of definitions for delta exports (/export/changes)
+CREATE TABLE dictionary_changes (
+    change_id BIGSERIAL PRIMARY KEY,
+    definition_id INTEGER NOT NULL,
+    langid INTEGER NOT NULL,
+    source_langid INTEGER NOT NULL,
+    -- Kept so deletions can still name the word
+    word TEXT NOT NULL,
+    change_type TEXT NOT NULL CHECK (change_type IN ('added', 'updated', 'deleted')),
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_dictionary_changes_lookup ON dictionary_changes(langid, source_langid, changed_at);
+
+CREATE OR REPLACE FUNCTION log_dictionary_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' OR (TG_OP = 'UPDATE' AND OLD.langid <> NEW.langid) THEN
+        INSERT INTO dictionary_changes (definition_id, langid, source_langid, word, change_type)
+        SELECT OLD.definitionid, OLD.langid, v.source_langid, v.word, 'deleted'
+        FROM valsi v WHERE v.valsiid = OLD.valsiid;
+    END IF;
+
+    IF TG_OP = 'INSERT' OR (TG_OP = 'UPDATE' AND OLD.langid <> NEW.langid) THEN
+        INSERT INTO dictionary_changes (definition_id, langid, source_langid, word, change_type)
+        SELECT NEW.definitionid, NEW.langid, v.source_langid, v.word, 'added'
+        FROM valsi v WHERE v.valsiid = NEW.valsiid;
+    ELSIF TG_OP = 'UPDATE' THEN
+        INSERT INTO dictionary_changes (definition_id, langid, source_langid, word, change_type)
+        SELECT NEW.definitionid, NEW.langid, v.source_langid, v.word, 'updated'
+        FROM valsi v WHERE v.valsiid = NEW.valsiid;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER dictionary_changes_insert_delete_trigger
+AFTER INSERT OR DELETE ON definitions
+FOR EACH ROW EXECUTE FUNCTION log_dictionary_change();
+
+-- cached_search_text follows the word, rafsi, notes and keywords (kept in sync by
+-- sync_definition_cache_fields), so keyword edits are logged too; embeddings and
+-- other caches are not
+CREATE TRIGGER dictionary_changes_update_trigger
+AFTER UPDATE ON definitions
+FOR EACH ROW
+WHEN (
+    (OLD.langid, OLD.valsiid, OLD.definition, OLD.notes, OLD.selmaho, OLD.jargon,
+     OLD.etymology, OLD.cached_typeid, OLD.cached_search_text)
+    IS DISTINCT FROM
+    (NEW.langid, NEW.valsiid, NEW.definition, NEW.notes, NEW.selmaho, NEW.jargon,
+     NEW.etymology, NEW.cached_typeid, NEW.cached_search_text)
+)
+EXECUTE FUNCTION log_dictionary_change();
+
+-- Daily delta files, generated alongside the full cached exports
+CREATE TABLE dictionary_export_diffs (
+    language_tag TEXT NOT NULL,
+    day DATE NOT NULL,
+    content BYTEA NOT NULL,
+    change_count INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (language_tag, day)
+);
@@ -0,0 +1,9 @@
+-- Export contents move to files in EXPORT_CACHE_DIR; the table only keeps metadata.
+-- Existing rows are dropped and regenerated by the nightly export job.
+DELETE FROM cached_dictionary_exports;
+
+ALTER TABLE cached_dictionary_exports
+    DROP COLUMN content,
+    ADD COLUMN file_path TEXT NOT NULL,
+    ADD COLUMN size_bytes BIGINT NOT NULL,
+    ADD COLUMN checksum TEXT NOT NULL;
@@ -0,0 +1,24 @@
+-- Exports queued by users (/export/jobs) and generated in the background. Finished
+-- files live under EXPORT_CACHE_DIR/jobs and are removed with their row after a week.
+CREATE TABLE export_jobs (
+    job_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(userid) ON DELETE CASCADE,
+    language_tag TEXT NOT NULL,
+    format TEXT NOT NULL,
+    options JSONB NOT NULL DEFAULT '{}',
+    status TEXT NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'running', 'completed', 'failed')),
+    progress SMALLINT NOT NULL DEFAULT 0 CHECK (progress BETWEEN 0 AND 100),
+    stage TEXT,
+    error TEXT,
+    file_path TEXT,
+    filename TEXT,
+    content_type TEXT,
+    size_bytes BIGINT,
+    checksum TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_export_jobs_user ON export_jobs(user_id, created_at DESC);
@@ -0,0 +1,8 @@
+-- Filtered exports are cached too, one row per canonical filter string (variant).
+-- The nightly export regenerates the full exports, whose variant is empty.
+ALTER TABLE cached_dictionary_exports ADD COLUMN variant TEXT NOT NULL DEFAULT '';
+
+ALTER TABLE cached_dictionary_exports
+    DROP CONSTRAINT cached_dictionary_exports_language_tag_format_key,
+    ADD CONSTRAINT cached_dictionary_exports_language_tag_format_variant_key
+        UNIQUE (language_tag, format, variant);
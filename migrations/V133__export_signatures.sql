@@ -0,0 +1,2 @@
+-- Detached Ed25519 signatures of cached export files (NULL when no signing key is set)
+ALTER TABLE cached_dictionary_exports ADD COLUMN signature BYTEA;
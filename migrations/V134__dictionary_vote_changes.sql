@@ -0,0 +1,37 @@
+-- Votes decide which definition is the best guess for a word, and only best guesses
+-- are exported. The best guess is recalculated after the vote is stored, so every
+-- definition of the word is logged as updated; /export/changes reports the ones that
+-- left the export as deleted.
+CREATE OR REPLACE FUNCTION log_dictionary_vote_change()
+RETURNS TRIGGER AS $$
+DECLARE
+    vote RECORD;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        vote := OLD;
+    ELSE
+        vote := NEW;
+    END IF;
+
+    INSERT INTO dictionary_changes (definition_id, langid, source_langid, word, change_type)
+    SELECT d.definitionid, d.langid, v.source_langid, v.word, 'updated'
+    FROM definitions d
+    JOIN valsi v ON v.valsiid = d.valsiid
+    WHERE d.valsiid = vote.valsiid AND d.langid = vote.langid;
+
+    -- A vote moved to another word changes that word's best guess as well
+    IF TG_OP = 'UPDATE' AND (OLD.valsiid, OLD.langid) IS DISTINCT FROM (NEW.valsiid, NEW.langid) THEN
+        INSERT INTO dictionary_changes (definition_id, langid, source_langid, word, change_type)
+        SELECT d.definitionid, d.langid, v.source_langid, v.word, 'updated'
+        FROM definitions d
+        JOIN valsi v ON v.valsiid = d.valsiid
+        WHERE d.valsiid = OLD.valsiid AND d.langid = OLD.langid;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER dictionary_changes_vote_trigger
+AFTER INSERT OR DELETE OR UPDATE OF value, valsiid, langid, definitionid ON definitionvotes
+FOR EACH ROW EXECUTE FUNCTION log_dictionary_vote_change();
@@ -0,0 +1,3 @@
+-- SHA-256 of the public key that made the signature; signatures of another key are
+-- replaced when requested
+ALTER TABLE cached_dictionary_exports ADD COLUMN signing_key_fingerprint TEXT;
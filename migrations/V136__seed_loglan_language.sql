@@ -0,0 +1,24 @@
+-- V128 only added the Loglan valsi types where language 58 already existed, so fresh
+-- databases ended up without them. Seed the language and add the types unconditionally.
+INSERT INTO languages (langid, tag, englishname, lojbanname, realname, forlojban, url)
+VALUES (58, 'art-loglan', 'Loglan', 'loglan', 'Loglan', '', 'https://www.loglan.org/')
+ON CONFLICT (langid) DO NOTHING;
+
+INSERT INTO valsitypes (typeid, descriptor, source_langid)
+VALUES
+    (20, 'primitive', 58),
+    (21, 'complex', 58),
+    (22, 'borrowing', 58),
+    (23, 'little word', 58),
+    (24, 'compound little word', 58),
+    (25, 'name', 58),
+    (26, 'affix', 58)
+ON CONFLICT (typeid) DO NOTHING;
+
+-- Fail loudly instead of leaving Loglan valsi without types
+DO $$
+BEGIN
+    IF (SELECT COUNT(*) FROM valsitypes WHERE source_langid = 58) < 7 THEN
+        RAISE EXCEPTION 'Loglan valsi types 20-26 are missing or assigned to another language';
+    END IF;
+END $$;
@@ -0,0 +1,5 @@
+-- Export signatures now cover the file's SHA-256 instead of its raw contents; drop the
+-- old ones so they are made again under the new scheme when requested
+UPDATE cached_dictionary_exports SET signature = NULL, signing_key_fingerprint = NULL;
+COMMENT ON COLUMN cached_dictionary_exports.signature IS
+    'Ed25519 signature of the 32-byte SHA-256 digest in checksum';
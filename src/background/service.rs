@@ -1,7 +1,8 @@
 use crate::{
     db,
     error::{AppError, AppResult},
-    export::service::export_all_dictionaries,
+    export::{jobs::remove_expired_export_jobs, service::export_all_dictionaries},
+    jbovlaste::service::{ensure_word_of_the_day, send_word_of_the_day_emails},
     mailarchive::{check_for_new_emails, import_maildir},
     muplis,
     notifications::run_email_notifications,
@@ -293,6 +294,30 @@ pub async fn spawn_background_tasks(pool: Pool, maildir_path: String) {
         }
     });
 
+    // Pick the word of the day and email it to subscribers. Runs hourly so that a
+    // restart around midnight never skips a day; both steps are idempotent per day.
+    let pool_clone = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            let today = chrono::Utc::now().date_naive();
+            match ensure_word_of_the_day(&pool_clone, today).await {
+                Ok(Some(word)) => info!("Featured {} as word of the day for {}", word, today),
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Failed to pick word of the day: {}", e);
+                    continue;
+                }
+            }
+            match send_word_of_the_day_emails(&pool_clone, today).await {
+                Ok(0) => {}
+                Ok(sent) => info!("Sent {} word of the day emails", sent),
+                Err(e) => error!("Failed to send word of the day emails: {}", e),
+            }
+        }
+    });
+
     // Spawn email notification processor
     let email_pool = pool.clone();
     tokio::spawn(async move {
@@ -347,6 +372,9 @@ pub async fn spawn_background_tasks(pool: Pool, maildir_path: String) {
             if let Err(e) = export_all_dictionaries(&pool_clone).await {
                 error!("Failed to export dictionaries: {}", e);
             }
+            if let Err(e) = remove_expired_export_jobs(&pool_clone).await {
+                error!("Failed to remove expired export jobs: {}", e);
+            }
         }
     });
 }
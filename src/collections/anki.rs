@@ -0,0 +1,1015 @@
+//! Anki package (`.apkg`) export and import of collections.
+//!
+//! Packages use the legacy `collection.anki2` schema (version 11), which every Anki
+//! release can import. Each flashcard direction gets its own note type so Anki holds
+//! exactly the cards studied here, and flashcard levels become subdecks. Importing
+//! reverses the mapping.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rusqlite::{params, Connection, OpenFlags};
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use zip::write::{FileOptions, ZipWriter};
+use zip::{CompressionMethod, ZipArchive};
+
+use super::dto::CollectionFullExport;
+use super::models::{CardProgress, CardReview, ImportedDeck, ImportedNote, ImportedReview};
+use super::service::parse_export_direction;
+use crate::{
+    flashcards::models::FlashcardDirection, language::math, utils::escape_xml, AppError, AppResult,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null,
+    scm integer not null, ver integer not null, dty integer not null,
+    usn integer not null, ls integer not null, conf text not null,
+    models text not null, decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null,
+    mod integer not null, usn integer not null, tags text not null,
+    flds text not null, sfld integer not null, csum integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null,
+    ord integer not null, mod integer not null, usn integer not null,
+    type integer not null, queue integer not null, due integer not null,
+    ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null,
+    odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null,
+    ease integer not null, ivl integer not null, lastIvl integer not null,
+    factor integer not null, time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+/// Note type ids are fixed so repeated imports reuse the same note types.
+const NOTE_TYPE_ID_BASE: i64 = 1_700_000_000_000;
+
+/// Largest archive entry read on import; guards against zip bombs
+pub(super) const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+/// Largest image read on import, the same limit collection item images have
+pub(super) const MAX_IMAGE_SIZE: u64 = 5 * 1024 * 1024;
+
+const FIELDS: [&str; 5] = ["Front", "Back", "Notes", "FrontImage", "BackImage"];
+
+const FRONT: &str = "{{Front}}{{#FrontImage}}<div>{{FrontImage}}</div>{{/FrontImage}}";
+const BACK: &str = "{{Back}}{{#BackImage}}<div>{{BackImage}}</div>{{/BackImage}}";
+const NOTES: &str = "{{#Notes}}<div class=\"notes\">{{Notes}}</div>{{/Notes}}";
+const CSS: &str = ".card { font-family: sans-serif; font-size: 20px; text-align: center; }\n\
+                   .notes { font-size: 16px; opacity: 0.8; margin-top: 1em; }\n\
+                   img { max-width: 100%; }";
+
+struct CardTemplate {
+    name: &'static str,
+    /// `user_flashcard_progress.card_side` the template studies
+    side: &'static str,
+    qfmt: String,
+    afmt: String,
+    /// Field that must be non-empty for Anki to generate the card
+    required_field: usize,
+}
+
+fn basic(name: &'static str, side: &'static str, front_first: bool) -> CardTemplate {
+    let (question, answer) = if front_first {
+        (FRONT, BACK)
+    } else {
+        (BACK, FRONT)
+    };
+    CardTemplate {
+        name,
+        side,
+        qfmt: question.to_string(),
+        afmt: format!("{{{{FrontSide}}}}<hr id=answer>{}{}", answer, NOTES),
+        required_field: if front_first { 0 } else { 1 },
+    }
+}
+
+fn typed(name: &'static str, side: &'static str, front_first: bool) -> CardTemplate {
+    let (question, answer_field) = if front_first {
+        (FRONT, "Back")
+    } else {
+        (BACK, "Front")
+    };
+    CardTemplate {
+        name,
+        side,
+        qfmt: format!("{}<br>{{{{type:{}}}}}", question, answer_field),
+        afmt: format!(
+            "{}<hr id=answer>{{{{type:{}}}}}{}",
+            question, answer_field, NOTES
+        ),
+        required_field: if front_first { 0 } else { 1 },
+    }
+}
+
+/// Note type id offset, name and templates for a direction. Template `ord`s follow
+/// the order of the list.
+fn note_type(direction: &FlashcardDirection) -> (i64, &'static str, Vec<CardTemplate>) {
+    match direction {
+        FlashcardDirection::Direct => {
+            (0, "Lensisku: direct", vec![basic("Direct", "direct", true)])
+        }
+        FlashcardDirection::Reverse => (
+            1,
+            "Lensisku: reverse",
+            vec![basic("Reverse", "reverse", false)],
+        ),
+        FlashcardDirection::Both => (
+            2,
+            "Lensisku: both directions",
+            vec![
+                basic("Direct", "direct", true),
+                basic("Reverse", "reverse", false),
+            ],
+        ),
+        FlashcardDirection::FillIn => (
+            3,
+            "Lensisku: fill in",
+            vec![typed("Fill in", "direct", true)],
+        ),
+        FlashcardDirection::FillInReverse => (
+            4,
+            "Lensisku: fill in (reverse)",
+            vec![typed("Fill in (reverse)", "reverse", false)],
+        ),
+        FlashcardDirection::FillInBoth => (
+            5,
+            "Lensisku: fill in (both directions)",
+            vec![
+                typed("Fill in", "direct", true),
+                typed("Fill in (reverse)", "reverse", false),
+            ],
+        ),
+        FlashcardDirection::JustInformation => (
+            6,
+            "Lensisku: information",
+            vec![CardTemplate {
+                name: "Information",
+                side: "direct",
+                qfmt: format!("{}<hr>{}{}", FRONT, BACK, NOTES),
+                afmt: "{{FrontSide}}".to_string(),
+                required_field: 0,
+            }],
+        ),
+        // Anki has no multiple choice; quizzes become plain recall cards. Quiz progress
+        // is always kept on the direct side.
+        FlashcardDirection::QuizDirect => {
+            (7, "Lensisku: quiz", vec![basic("Quiz", "direct", true)])
+        }
+        FlashcardDirection::QuizReverse => (
+            8,
+            "Lensisku: quiz (reverse)",
+            vec![basic("Quiz (reverse)", "direct", false)],
+        ),
+        FlashcardDirection::QuizBoth => (
+            9,
+            "Lensisku: quiz (both directions)",
+            vec![
+                basic("Quiz", "direct", true),
+                basic("Quiz (reverse)", "reverse", false),
+            ],
+        ),
+    }
+}
+
+fn note_type_json(
+    id: i64,
+    name: &str,
+    templates: &[CardTemplate],
+    deck_id: i64,
+    now: i64,
+) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "type": 0,
+        "mod": now,
+        "usn": 0,
+        "sortf": 0,
+        "did": deck_id,
+        "tmpls": templates
+            .iter()
+            .enumerate()
+            .map(|(ord, t)| json!({
+                "name": t.name,
+                "ord": ord,
+                "qfmt": t.qfmt,
+                "afmt": t.afmt,
+                "bqfmt": "",
+                "bafmt": "",
+                "did": null,
+            }))
+            .collect::<Vec<_>>(),
+        "flds": FIELDS
+            .iter()
+            .enumerate()
+            .map(|(ord, field)| json!({
+                "name": field,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            }))
+            .collect::<Vec<_>>(),
+        "css": CSS,
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}",
+        "latexsvg": false,
+        "req": templates
+            .iter()
+            .enumerate()
+            .map(|(ord, t)| json!([ord, "any", [t.required_field]]))
+            .collect::<Vec<_>>(),
+        "tags": [],
+        "vers": [],
+    })
+}
+
+fn deck_json(id: i64, name: &str, description: &str, now: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "desc": description,
+        "mod": now,
+        "usn": 0,
+        "conf": 1,
+        "dyn": 0,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "extendNew": 0,
+        "extendRev": 0,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+fn deck_config_json(now: i64) -> Value {
+    json!({
+        "1": {
+            "id": 1,
+            "name": "Default",
+            "mod": now,
+            "usn": 0,
+            "dyn": false,
+            "maxTaken": 60,
+            "timer": 0,
+            "autoplay": true,
+            "replayq": true,
+            "new": {
+                "delays": [1.0, 10.0],
+                "ints": [1, 4, 0],
+                "initialFactor": 2500,
+                "order": 1,
+                "perDay": 20,
+                "bury": false,
+            },
+            "rev": {
+                "perDay": 200,
+                "ease4": 1.3,
+                "ivlFct": 1.0,
+                "maxIvl": 36500,
+                "hardFactor": 1.2,
+                "bury": false,
+            },
+            "lapse": {
+                "delays": [10.0],
+                "mult": 0.0,
+                "minInt": 1,
+                "leechFails": 8,
+                "leechAction": 1,
+            },
+        }
+    })
+}
+
+/// Field content: math rendered as Unicode, HTML-escaped, newlines as `<br>`.
+fn field_html(text: &str) -> String {
+    escape_xml(&math::to_unicode_lossy(text)).replace('\n', "<br>")
+}
+
+/// Decodes a `data:` URL from the JSON export into bytes and a file extension.
+fn decode_image(data_url: &str) -> Option<(Vec<u8>, &'static str)> {
+    let (mime, data) = data_url.strip_prefix("data:")?.split_once(";base64,")?;
+    let extension = match mime {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => "img",
+    };
+    Some((BASE64.decode(data).ok()?, extension))
+}
+
+/// Anki's duplicate check: the first 32 bits of the SHA-1 of the sort field.
+fn field_checksum(text: &str) -> i64 {
+    let digest = Sha1::digest(text.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as i64
+}
+
+struct CardState {
+    card_type: i64,
+    queue: i64,
+    due: i64,
+    ivl: i64,
+    factor: i64,
+    reps: i64,
+    /// FSRS memory state, as Anki stores it in `cards.data`
+    data: String,
+}
+
+fn card_state(progress: Option<&CardProgress>, new_position: i64, crt: i64) -> CardState {
+    let new_card = CardState {
+        card_type: 0,
+        queue: 0,
+        due: new_position,
+        ivl: 0,
+        factor: 0,
+        reps: 0,
+        data: String::new(),
+    };
+    let Some(progress) = progress else {
+        return new_card;
+    };
+
+    let data = if progress.stability > 0.0 {
+        json!({
+            "s": (progress.stability * 1000.0).round() / 1000.0,
+            "d": (progress.difficulty * 1000.0).round() / 1000.0,
+        })
+        .to_string()
+    } else {
+        String::new()
+    };
+    let next_review = progress
+        .next_review_at
+        .map(|t| t.timestamp())
+        .unwrap_or_else(|| Utc::now().timestamp());
+    let factor = (progress.ease_factor * 1000.0).round() as i64;
+    let reps = progress.review_count as i64;
+
+    match progress.status.as_str() {
+        "learning" => CardState {
+            card_type: 1,
+            queue: 1,
+            due: next_review,
+            ivl: 0,
+            factor,
+            reps,
+            data,
+        },
+        "review" | "graduated" => CardState {
+            card_type: 2,
+            queue: 2,
+            due: (next_review - crt).div_euclid(86_400),
+            ivl: (progress.interval as i64 + 1439) / 1440,
+            factor,
+            reps,
+            data,
+        },
+        _ => CardState { data, ..new_card },
+    }
+}
+
+fn sqlite_error(e: rusqlite::Error) -> AppError {
+    AppError::Internal(format!("Failed to write Anki collection: {}", e))
+}
+
+fn zip_error(e: zip::result::ZipError) -> AppError {
+    AppError::Internal(format!("Failed to write Anki package: {}", e))
+}
+
+/// Builds the `.apkg` for a full collection export. `progress` and `reviews` are one
+/// user's scheduling state and history; pass them empty for a fresh deck.
+pub fn build_apkg(
+    collection_id: i32,
+    export: &CollectionFullExport,
+    progress: &[CardProgress],
+    reviews: &[CardReview],
+) -> AppResult<Vec<u8>> {
+    let now = Utc::now();
+    let now_secs = now.timestamp();
+    let base_id = now.timestamp_millis();
+
+    // Day boundary before any scheduled or reviewed card, so review due days stay positive
+    let crt = progress
+        .iter()
+        .filter_map(|p| p.next_review_at)
+        .chain(reviews.iter().map(|r| r.review_time))
+        .map(|t| t.timestamp())
+        .fold(now_secs, i64::min);
+    let crt = crt - crt.rem_euclid(86_400);
+
+    // The collection is the top deck; each level is a subdeck, in level order
+    let root_name = export.collection.name.replace("::", ":");
+    let mut decks = serde_json::Map::new();
+    decks.insert("1".to_string(), deck_json(1, "Default", "", now_secs));
+    decks.insert(
+        base_id.to_string(),
+        deck_json(
+            base_id,
+            &root_name,
+            export.collection.description.as_deref().unwrap_or(""),
+            now_secs,
+        ),
+    );
+    let mut item_decks: HashMap<usize, i64> = HashMap::new();
+    for (i, level) in export.levels.iter().enumerate() {
+        let deck_id = base_id + 1 + i as i64;
+        let name = format!(
+            "{}::{:02} {}",
+            root_name,
+            i + 1,
+            level.name.replace("::", ":")
+        );
+        decks.insert(
+            deck_id.to_string(),
+            deck_json(
+                deck_id,
+                &name,
+                level.description.as_deref().unwrap_or(""),
+                now_secs,
+            ),
+        );
+        for &position in &level.item_positions {
+            item_decks.entry(position).or_insert(deck_id);
+        }
+    }
+
+    let progress_by_side: HashMap<(i32, &str), &CardProgress> = progress
+        .iter()
+        .map(|p| ((p.item_id, p.card_side.as_str()), p))
+        .collect();
+    let mut reviews_by_side: HashMap<(i32, &str), Vec<&CardReview>> = HashMap::new();
+    for review in reviews {
+        reviews_by_side
+            .entry((review.item_id, review.card_side.as_str()))
+            .or_default()
+            .push(review);
+    }
+
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("collection.anki2");
+    let mut conn = Connection::open(&db_path).map_err(sqlite_error)?;
+    conn.execute_batch(SCHEMA).map_err(sqlite_error)?;
+    let tx = conn.transaction().map_err(sqlite_error)?;
+
+    let mut note_types = serde_json::Map::new();
+    let mut media: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut revlog_ids: HashSet<i64> = HashSet::new();
+    let mut first_note_type = None;
+
+    for (index, item) in export.items.iter().enumerate() {
+        let direction = parse_export_direction(item.direction.as_ref());
+        let (offset, type_name, templates) = note_type(&direction);
+        let note_type_id = NOTE_TYPE_ID_BASE + offset;
+        let deck_id = item_decks.get(&index).copied().unwrap_or(base_id);
+        note_types
+            .entry(note_type_id.to_string())
+            .or_insert_with(|| {
+                note_type_json(note_type_id, type_name, &templates, deck_id, now_secs)
+            });
+        first_note_type = first_note_type.or(Some(note_type_id));
+
+        let front = item
+            .word
+            .as_deref()
+            .or(item.free_content_front.as_deref())
+            .unwrap_or_default();
+        let back = item
+            .definition
+            .as_deref()
+            .or(item.free_content_back.as_deref())
+            .unwrap_or_default();
+        let notes = [&item.definition_notes, &item.collection_note]
+            .into_iter()
+            .flatten()
+            .filter(|n| !n.trim().is_empty())
+            .map(|n| field_html(n))
+            .collect::<Vec<_>>()
+            .join("<br><br>");
+
+        let mut image_field = |url: &Option<String>, side: &str| {
+            url.as_deref()
+                .and_then(decode_image)
+                .map(|(bytes, extension)| {
+                    let name = format!("lensisku-{}-{}.{}", item.item_id, side, extension);
+                    media.push((name.clone(), bytes));
+                    format!("<img src=\"{}\">", name)
+                })
+                .unwrap_or_default()
+        };
+        let front_image = image_field(&item.front_image_url, "front");
+        let back_image = image_field(&item.back_image_url, "back");
+
+        let note_id = base_id + index as i64;
+        let sort_field = math::to_unicode_lossy(front);
+        let fields = [
+            field_html(front),
+            field_html(back),
+            notes,
+            front_image,
+            back_image,
+        ]
+        .join("\u{1f}");
+        let tags = match &item.word_type {
+            Some(word_type) => format!(" lensisku {} ", word_type.replace(' ', "_")),
+            None => " lensisku ".to_string(),
+        };
+        tx.execute(
+            "INSERT INTO notes (id, guid, mid, mod, usn, tags, flds, sfld, csum, flags, data)
+             VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?8, 0, '')",
+            params![
+                note_id,
+                format!("lensisku-{}-{}", collection_id, item.item_id),
+                note_type_id,
+                now_secs,
+                tags,
+                fields,
+                sort_field,
+                field_checksum(&sort_field),
+            ],
+        )
+        .map_err(sqlite_error)?;
+
+        for (ord, template) in templates.iter().enumerate() {
+            let card_id = base_id + (index * 2 + ord) as i64;
+            let key = (item.item_id, template.side);
+            let mut state = card_state(progress_by_side.get(&key).copied(), index as i64 + 1, crt);
+            // Information cards are reference material, not study material
+            if direction == FlashcardDirection::JustInformation {
+                state.queue = -1;
+            }
+            let card_reviews = reviews_by_side.get(&key).map(Vec::as_slice).unwrap_or(&[]);
+            let lapses = card_reviews
+                .iter()
+                .filter(|r| r.rating == 1 && r.elapsed_days > 0)
+                .count() as i64;
+
+            tx.execute(
+                "INSERT INTO cards (id, nid, did, ord, mod, usn, type, queue, due, ivl, factor,
+                                    reps, lapses, left, odue, odid, flags, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7, ?8, ?9, ?10, ?11, ?12, 0, 0, 0, 0, ?13)",
+                params![
+                    card_id,
+                    note_id,
+                    deck_id,
+                    ord as i64,
+                    now_secs,
+                    state.card_type,
+                    state.queue,
+                    state.due,
+                    state.ivl,
+                    state.factor,
+                    state.reps,
+                    lapses,
+                    state.data,
+                ],
+            )
+            .map_err(sqlite_error)?;
+
+            for review in card_reviews {
+                // Revlog ids are millisecond timestamps and must be unique
+                let mut revlog_id = review.review_time.timestamp_millis();
+                while !revlog_ids.insert(revlog_id) {
+                    revlog_id += 1;
+                }
+                tx.execute(
+                    "INSERT INTO revlog (id, cid, usn, ease, ivl, lastIvl, factor, time, type)
+                     VALUES (?1, ?2, 0, ?3, ?4, ?5, ?6, 0, ?7)",
+                    params![
+                        revlog_id,
+                        card_id,
+                        review.rating,
+                        review.scheduled_days,
+                        review.elapsed_days,
+                        state.factor,
+                        if review.elapsed_days > 0 { 1 } else { 0 },
+                    ],
+                )
+                .map_err(sqlite_error)?;
+            }
+        }
+    }
+
+    let conf = json!({
+        "nextPos": export.items.len() + 1,
+        "estTimes": true,
+        "activeDecks": [base_id],
+        "curDeck": base_id,
+        "curModel": first_note_type.unwrap_or(NOTE_TYPE_ID_BASE),
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "dueCounts": true,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+        // The review log holds four-button ratings for learning cards too
+        "schedVer": 2,
+    });
+    tx.execute(
+        "INSERT INTO col (id, crt, mod, scm, ver, dty, usn, ls, conf, models, decks, dconf, tags)
+         VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            crt,
+            now.timestamp_millis(),
+            conf.to_string(),
+            Value::Object(note_types).to_string(),
+            Value::Object(decks).to_string(),
+            deck_config_json(now_secs).to_string(),
+        ],
+    )
+    .map_err(sqlite_error)?;
+    tx.commit().map_err(sqlite_error)?;
+    conn.close().map_err(|(_, e)| sqlite_error(e))?;
+
+    let collection = std::fs::read(&db_path)?;
+
+    // Media files are numbered in the archive; the `media` map restores their names
+    let media_map: serde_json::Map<String, Value> = media
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| (i.to_string(), Value::String(name.clone())))
+        .collect();
+
+    let mut buffer = Vec::new();
+    {
+        let mut zip = ZipWriter::new(Cursor::new(&mut buffer));
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file("collection.anki2", deflated)
+            .map_err(zip_error)?;
+        zip.write_all(&collection)?;
+        zip.start_file("media", deflated).map_err(zip_error)?;
+        zip.write_all(Value::Object(media_map).to_string().as_bytes())?;
+        for (i, (_, bytes)) in media.iter().enumerate() {
+            zip.start_file(i.to_string(), stored).map_err(zip_error)?;
+            zip.write_all(bytes)?;
+        }
+        zip.finish().map_err(zip_error)?;
+    }
+    Ok(buffer)
+}
+
+fn bad_package(e: impl std::fmt::Display) -> AppError {
+    AppError::BadRequest(format!("Invalid package: {}", e))
+}
+
+/// Reads one archive entry into memory, refusing entries over `limit` bytes.
+pub(super) fn read_zip_entry(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+    limit: u64,
+) -> AppResult<Vec<u8>> {
+    let too_large = || bad_package(format!("{} is larger than {} bytes", name, limit));
+    let entry = archive.by_name(name).map_err(bad_package)?;
+    if entry.size() > limit {
+        return Err(too_large());
+    }
+    // The declared size can lie, so the read is capped as well
+    let mut content = Vec::new();
+    entry.take(limit + 1).read_to_end(&mut content)?;
+    if content.len() as u64 > limit {
+        return Err(too_large());
+    }
+    Ok(content)
+}
+
+/// MIME type of an image file name, for the formats collection items accept.
+pub(super) fn image_mime(file_name: &str) -> Option<&'static str> {
+    let extension = file_name.rsplit('.').next()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// `src` of the first `<img>` in a field.
+pub(super) fn first_image(html: &str) -> Option<String> {
+    static IMG: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"(?i)<img[^>]*\ssrc\s*=\s*["']?([^"'\s>]+)"#).expect("valid image regex")
+    });
+    IMG.captures(html).map(|c| c[1].to_string())
+}
+
+/// Plain text of a field: line breaks kept, tags and sound references dropped.
+pub(super) fn field_text(html: &str) -> String {
+    static BREAKS: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)<br\s*/?>|</div>|</p>|</li>").expect("valid break regex"));
+    static SOUND: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\[sound:[^\]]*\]").expect("valid sound regex"));
+    let text = BREAKS.replace_all(html, "\n");
+    let text = SOUND.replace_all(&text, "");
+    crate::utils::remove_html_tags(&text)
+        .replace("&nbsp;", " ")
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Cloze deletions (`{{c1::answer::hint}}`) hidden for the question side.
+fn cloze_question(text: &str) -> String {
+    CLOZE
+        .replace_all(text, |c: &regex::Captures| match c.get(2) {
+            Some(hint) => format!("[{}]", hint.as_str()),
+            None => "[…]".to_string(),
+        })
+        .into_owned()
+}
+
+fn cloze_answer(text: &str) -> String {
+    CLOZE.replace_all(text, "$1").into_owned()
+}
+
+static CLOZE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{c\d+::(.*?)(?:::(.*?))?\}\}").expect("valid cloze regex"));
+
+/// Subdeck below the top deck, without the `NN ` ordering prefix [`build_apkg`] adds.
+fn level_name(deck: &str, root: &str) -> Option<String> {
+    static ORDER_PREFIX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^\d+ ").expect("valid prefix regex"));
+    let level = deck.strip_prefix(root)?.strip_prefix("::")?;
+    Some(ORDER_PREFIX.replace(level, "").into_owned()).filter(|l| !l.is_empty())
+}
+
+/// Reads the notes, cards and review log of an `.apkg`. Only legacy collections
+/// (`collection.anki2`/`collection.anki21`) are supported; newer Anki versions write
+/// them when "Support older Anki versions" is ticked in the export dialog.
+pub fn read_apkg(package: &[u8]) -> AppResult<ImportedDeck> {
+    let mut archive = ZipArchive::new(Cursor::new(package)).map_err(bad_package)?;
+    let names: HashSet<String> = archive.file_names().map(str::to_string).collect();
+    let collection_file = if names.contains("collection.anki21") {
+        "collection.anki21"
+    } else if names.contains("collection.anki21b") {
+        return Err(AppError::BadRequest(
+            "This package uses the latest Anki format; export it again with \"Support older Anki versions\" enabled".to_string(),
+        ));
+    } else if names.contains("collection.anki2") {
+        "collection.anki2"
+    } else {
+        return Err(AppError::BadRequest(
+            "Not an Anki package: no collection found".to_string(),
+        ));
+    };
+
+    // `media` maps archive entry names ("0", "1", …) to the file names fields refer to
+    let media: HashMap<String, String> = if names.contains("media") {
+        let media_json = read_zip_entry(&mut archive, "media", MAX_ENTRY_SIZE)?;
+        serde_json::from_slice::<HashMap<String, String>>(&media_json)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(entry, file_name)| (file_name, entry))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join(collection_file);
+    std::fs::write(
+        &db_path,
+        read_zip_entry(&mut archive, collection_file, MAX_ENTRY_SIZE)?,
+    )?;
+    let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(sqlite_error)?;
+
+    let (models, decks, conf): (String, String, String) = conn
+        .query_row("SELECT models, decks, conf FROM col", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(sqlite_error)?;
+    let models: HashMap<String, Value> = serde_json::from_str(&models).map_err(bad_package)?;
+    let deck_names: HashMap<i64, String> = serde_json::from_str::<HashMap<String, Value>>(&decks)
+        .map_err(bad_package)?
+        .values()
+        .filter_map(|deck| Some((deck["id"].as_i64()?, deck["name"].as_str()?.to_string())))
+        .collect();
+    let conf: Value = serde_json::from_str(&conf).map_err(bad_package)?;
+    // The v1 scheduler showed three buttons (Again, Good, Easy) for learning cards
+    let three_button_learning = conf["schedVer"].as_i64().unwrap_or(1) == 1;
+
+    let mut cards_by_note: HashMap<i64, Vec<(i64, i64, i64)>> = HashMap::new();
+    let mut statement = conn
+        .prepare("SELECT id, nid, did, ord FROM cards ORDER BY nid, ord")
+        .map_err(sqlite_error)?;
+    let cards = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get(2)?,
+                row.get(3)?,
+            ))
+        })
+        .map_err(sqlite_error)?;
+    for card in cards {
+        let (card_id, note_id, deck_id, ord) = card.map_err(sqlite_error)?;
+        cards_by_note
+            .entry(note_id)
+            .or_default()
+            .push((card_id, deck_id, ord));
+    }
+
+    let mut revlog: HashMap<i64, Vec<(i64, i64, i64, i64)>> = HashMap::new();
+    let mut statement = conn
+        .prepare("SELECT cid, id, ease, ivl, type FROM revlog ORDER BY id")
+        .map_err(sqlite_error)?;
+    let entries = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?),
+            ))
+        })
+        .map_err(sqlite_error)?;
+    for entry in entries {
+        let (card_id, review) = entry.map_err(sqlite_error)?;
+        revlog.entry(card_id).or_default().push(review);
+    }
+
+    // The top deck most cards live in names the collection; its subdecks become levels
+    let mut root_counts: HashMap<&str, usize> = HashMap::new();
+    for (_, deck_id, _) in cards_by_note.values().flatten() {
+        if let Some(name) = deck_names.get(deck_id) {
+            let root = name.split("::").next().unwrap_or(name);
+            *root_counts.entry(root).or_default() += 1;
+        }
+    }
+    let root = root_counts
+        .into_iter()
+        .max_by_key(|(name, count)| (*count, std::cmp::Reverse(*name)))
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| "Anki import".to_string());
+
+    let mut statement = conn
+        .prepare("SELECT id, mid, flds FROM notes ORDER BY id")
+        .map_err(sqlite_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(sqlite_error)?;
+
+    let mut notes = Vec::new();
+    for row in rows {
+        let (note_id, model_id, fields) = row.map_err(sqlite_error)?;
+        let Some(note_cards) = cards_by_note.get(&note_id) else {
+            continue;
+        };
+        let model = models.get(&model_id.to_string());
+        let is_cloze = model.and_then(|m| m["type"].as_i64()) == Some(1);
+        let typed = note_cards.iter().any(|(_, _, ord)| {
+            model
+                .and_then(|m| m["tmpls"].as_array())
+                .and_then(|templates| templates.iter().find(|t| t["ord"].as_i64() == Some(*ord)))
+                .and_then(|t| t["qfmt"].as_str())
+                .is_some_and(|qfmt| qfmt.contains("{{type:"))
+        });
+        let has_direct = note_cards.iter().any(|(_, _, ord)| *ord == 0);
+        let has_reverse = !is_cloze && note_cards.iter().any(|(_, _, ord)| *ord == 1);
+        let direction = match (has_direct, has_reverse, typed) {
+            (true, true, false) => FlashcardDirection::Both,
+            (true, true, true) => FlashcardDirection::FillInBoth,
+            (false, true, false) => FlashcardDirection::Reverse,
+            (false, true, true) => FlashcardDirection::FillInReverse,
+            (_, false, true) => FlashcardDirection::FillIn,
+            (_, false, false) => FlashcardDirection::Direct,
+        };
+
+        let fields: Vec<&str> = fields.split('\u{1f}').collect();
+        let first = fields.first().copied().unwrap_or_default();
+        let (front_html, back_html, extra_fields) = if is_cloze {
+            (
+                cloze_question(first),
+                cloze_answer(first),
+                &fields[1.min(fields.len())..],
+            )
+        } else {
+            (
+                first.to_string(),
+                fields.get(1).copied().unwrap_or_default().to_string(),
+                &fields[2.min(fields.len())..],
+            )
+        };
+        let extra: Vec<String> = extra_fields
+            .iter()
+            .map(|field| field_text(field))
+            .filter(|text| !text.is_empty())
+            .collect();
+
+        let mut image = |html: &str| -> AppResult<Option<(Vec<u8>, String)>> {
+            let Some(file_name) = first_image(html) else {
+                return Ok(None);
+            };
+            match (media.get(&file_name), image_mime(&file_name)) {
+                (Some(entry), Some(mime)) => Ok(Some((
+                    read_zip_entry(&mut archive, entry, MAX_IMAGE_SIZE)?,
+                    mime.to_string(),
+                ))),
+                _ => Ok(None),
+            }
+        };
+        let front_image = image(&front_html)?;
+        let back_image = image(&back_html)?;
+
+        let level = note_cards
+            .first()
+            .and_then(|(_, deck_id, _)| deck_names.get(deck_id))
+            .and_then(|deck| level_name(deck, &root));
+
+        let mut reviews = Vec::new();
+        for (card_id, _, ord) in note_cards {
+            let card_side = match ord {
+                0 => "direct",
+                1 if !is_cloze => "reverse",
+                _ => continue,
+            };
+            // Ease 0 marks manual rescheduling rather than an answer
+            for &(review_id, ease, ivl, kind) in revlog.get(card_id).into_iter().flatten() {
+                let Some(reviewed_at) = DateTime::<Utc>::from_timestamp_millis(review_id) else {
+                    continue;
+                };
+                if !(1..=4).contains(&ease) {
+                    continue;
+                }
+                let learning = kind == 0 || kind == 2;
+                let rating = match ease {
+                    2 | 3 if learning && three_button_learning => ease + 1,
+                    _ => ease,
+                };
+                reviews.push(ImportedReview {
+                    card_side,
+                    rating: rating as i32,
+                    reviewed_at,
+                    // Negative intervals are learning steps in seconds
+                    interval_days: ivl.max(0) as i32,
+                    learning,
+                });
+            }
+        }
+
+        notes.push(ImportedNote {
+            front: field_text(&front_html),
+            back: field_text(&back_html),
+            notes: Some(extra.join("\n\n")).filter(|n| !n.is_empty()),
+            front_image,
+            back_image,
+            direction,
+            level,
+            reviews,
+        });
+    }
+
+    Ok(ImportedDeck { name: root, notes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_directions_to_card_sides() {
+        let (_, _, templates) = note_type(&FlashcardDirection::FillInBoth);
+        let sides: Vec<_> = templates.iter().map(|t| t.side).collect();
+        assert_eq!(sides, ["direct", "reverse"]);
+        assert!(templates[1].qfmt.contains("{{type:Front}}"));
+
+        let (_, _, templates) = note_type(&FlashcardDirection::QuizReverse);
+        assert_eq!(templates[0].side, "direct");
+        assert!(templates[0].qfmt.starts_with("{{Back}}"));
+    }
+}
@@ -9,7 +9,7 @@ use deadpool_postgres::Pool;
 use serde_json::json;
 
 use super::{dto::*, service};
-use crate::auth::Claims;
+use crate::{auth::Claims, AppError};
 
 #[utoipa::path(
     post,
@@ -711,6 +711,62 @@ pub async fn export_collection_full(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/collections/{id}/export/apkg",
+    tag = "collections",
+    params(
+        ("id" = i32, Path, description = "Collection ID"),
+        ("include_progress" = Option<bool>, Query, description = "Include the requesting user's FSRS state and review history")
+    ),
+    responses(
+        (status = 200, description = "Anki package (.apkg)", content_type = "application/zip"),
+        (status = 403, description = "Access denied"),
+        (status = 404, description = "Collection not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("bearer_auth" = [])),
+    summary = "Export collection as Anki package",
+    description = "Exports the collection as an Anki .apkg. Each flashcard direction becomes a note type with matching card templates (fill-in cards use typed answers, quizzes become recall cards), item images are embedded as media, and flashcard levels become subdecks. With include_progress=true and authentication, the user's FSRS memory state, due dates and review log are included so they can continue studying in Anki. Same access as get collection (public or owner)."
+)]
+#[get("/{id}/export/apkg")]
+pub async fn export_collection_apkg(
+    pool: web::Data<Pool>,
+    claims: Option<Claims>,
+    id: web::Path<i32>,
+    query: web::Query<AnkiExportQuery>,
+) -> impl Responder {
+    let collection_id = id.into_inner();
+    match service::export_collection_apkg(
+        &pool,
+        collection_id,
+        claims.map(|c| c.sub),
+        query.include_progress.unwrap_or(false),
+    )
+    .await
+    {
+        Ok(package) => HttpResponse::Ok()
+            .content_type("application/zip")
+            .append_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"collection-{}.apkg\"", collection_id),
+            ))
+            .body(package),
+        Err(e) => {
+            let msg = e.to_string();
+            if msg.contains("Access denied") || msg.contains("Unauthorized") {
+                HttpResponse::Forbidden().finish()
+            } else if msg.contains("not found") {
+                HttpResponse::NotFound().finish()
+            } else {
+                HttpResponse::InternalServerError().json(json!({
+                    "error": format!("Failed to export collection: {}", e)
+                }))
+            }
+        }
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/collections/import/full",
@@ -739,6 +795,45 @@ pub async fn import_full(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/collections/import/deck",
+    tag = "collections",
+    params(
+        ("format" = Option<String>, Query, description = "anki or mnemosyne; detected from the upload when omitted"),
+        ("collection_id" = Option<i32>, Query, description = "Add to this owned collection instead of creating one"),
+        ("name" = Option<String>, Query, description = "Name of the new collection (defaults to the deck name)"),
+        ("match_langid" = Option<i32>, Query, description = "Link notes whose front is a Lojban word to its definition in this language"),
+        ("include_history" = Option<bool>, Query, description = "Import the Anki review log as review history")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream", description = "Anki .apkg, Mnemosyne .cards or Mnemosyne XML file"),
+    responses(
+        (status = 200, description = "Deck imported", body = ImportDeckResponse),
+        (status = 400, description = "Unreadable or unsupported deck"),
+        (status = 403, description = "Not the owner of the target collection"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("bearer_auth" = [])),
+    summary = "Import Anki or Mnemosyne deck",
+    description = "Imports an Anki package (.apkg with collection.anki2 or collection.anki21) or a Mnemosyne deck (.cards package, cards.xml or Mnemosyne 1.x XML) sent as the raw request body. Each note becomes a collection item with a flashcard whose direction follows the note's card templates; with match_langid, notes whose front is a Lojban word are linked to that word's definition instead of being imported as free content. Images are imported (packages with images over 5 MB are rejected), subdecks and tags become flashcard levels, and with include_history=true the Anki review log is replayed through FSRS into the user's review history and progress."
+)]
+#[post("/import/deck")]
+pub async fn import_deck(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    query: web::Query<ImportDeckQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    match service::import_deck(&pool, claims.sub, body.to_vec(), &query).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(json!({ "error": msg })),
+        Err(AppError::Auth(_)) => HttpResponse::Forbidden().finish(),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to import deck: {}", e)
+        })),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/collections/{collection_id}/search",
@@ -221,6 +221,12 @@ pub struct CollectionFullExport {
     pub levels: Vec<LevelExport>,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnkiExportQuery {
+    /// Carry the requesting user's FSRS state and review history into the package
+    pub include_progress: Option<bool>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct LevelExport {
     pub name: String,
@@ -259,3 +265,29 @@ pub struct ImportFullResponse {
     pub levels_created: i32,
     pub warnings: Vec<String>,
 }
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ImportDeckQuery {
+    /// "anki" or "mnemosyne"; detected from the upload when omitted
+    pub format: Option<String>,
+    /// Add to this collection (must be owned) instead of creating a new one
+    pub collection_id: Option<i32>,
+    /// Name of the new collection; defaults to the deck name
+    pub name: Option<String>,
+    /// Link notes whose front matches a Lojban word to its best definition in this language
+    pub match_langid: Option<i32>,
+    /// Convert the Anki review log into review history and FSRS state
+    pub include_history: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportDeckResponse {
+    pub collection: CollectionResponse,
+    pub imported_count: i32,
+    /// Notes linked to an existing definition rather than imported as free content
+    pub matched_count: i32,
+    pub skipped_count: i32,
+    pub levels_created: i32,
+    pub reviews_imported: i32,
+    pub warnings: Vec<String>,
+}
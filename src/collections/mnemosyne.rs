@@ -0,0 +1,241 @@
+//! Mnemosyne import: `.cards` packages and `cards.xml` from Mnemosyne 2, and the XML
+//! export of Mnemosyne 1.x.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use xml::reader::{EventReader, XmlEvent};
+use zip::ZipArchive;
+
+use super::anki::{
+    field_text, first_image, image_mime, read_zip_entry, MAX_ENTRY_SIZE, MAX_IMAGE_SIZE,
+};
+use super::models::{ImportedDeck, ImportedNote};
+use crate::{flashcards::models::FlashcardDirection, AppError, AppResult};
+
+/// openSM2sync log entry types
+const ADDED_CARD: &str = "6";
+const ADDED_TAG: &str = "10";
+const ADDED_FACT: &str = "16";
+
+#[derive(Default)]
+struct XmlNode {
+    name: String,
+    attributes: HashMap<String, String>,
+    text: String,
+    children: Vec<XmlNode>,
+}
+
+impl XmlNode {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    fn child_text(&self, name: &str) -> Option<&str> {
+        self.children
+            .iter()
+            .find(|child| child.name == name)
+            .map(|child| child.text.as_str())
+            .filter(|text| !text.trim().is_empty())
+    }
+}
+
+fn parse_xml(data: &[u8]) -> AppResult<XmlNode> {
+    let mut stack = vec![XmlNode::default()];
+    for event in EventReader::new(data) {
+        match event.map_err(|e| AppError::BadRequest(format!("Invalid Mnemosyne XML: {}", e)))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(XmlNode {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|a| (a.name.local_name, a.value))
+                    .collect(),
+                ..Default::default()
+            }),
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&text);
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                if stack.len() > 1 {
+                    if let (Some(node), Some(parent)) = (stack.pop(), stack.last_mut()) {
+                        parent.children.push(node);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stack
+        .pop()
+        .and_then(|document| document.children.into_iter().next())
+        .ok_or_else(|| AppError::BadRequest("Empty Mnemosyne file".to_string()))
+}
+
+/// Mnemosyne cloze cards mark deletions with square brackets.
+static CLOZE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[([^\]]+)\]").expect("valid cloze regex"));
+
+/// Reads a Mnemosyne `.cards` package (zipped `cards.xml` plus media) or bare XML.
+/// Mnemosyne's repetition history isn't imported.
+pub fn read_mnemosyne(package: &[u8]) -> AppResult<ImportedDeck> {
+    let mut archive = match package.starts_with(b"PK") {
+        true => Some(
+            ZipArchive::new(Cursor::new(package))
+                .map_err(|e| AppError::BadRequest(format!("Invalid package: {}", e)))?,
+        ),
+        false => None,
+    };
+    let xml = match archive.as_mut() {
+        Some(archive) => read_zip_entry(archive, "cards.xml", MAX_ENTRY_SIZE)?,
+        None => package.to_vec(),
+    };
+    let root = parse_xml(&xml)?;
+
+    let mut image = |html: &str| -> AppResult<Option<(Vec<u8>, String)>> {
+        let (Some(archive), Some(file_name)) = (archive.as_mut(), first_image(html)) else {
+            return Ok(None);
+        };
+        let Some(mime) = image_mime(&file_name) else {
+            return Ok(None);
+        };
+        if !archive.file_names().any(|name| name == file_name) {
+            return Ok(None);
+        }
+        Ok(Some((
+            read_zip_entry(archive, &file_name, MAX_IMAGE_SIZE)?,
+            mime.to_string(),
+        )))
+    };
+
+    let mut notes = Vec::new();
+    match root.name.as_str() {
+        "openSM2sync" => {
+            let logs_of = |kind: &'static str| {
+                root.children
+                    .iter()
+                    .filter(move |log| log.attribute("type") == Some(kind))
+            };
+            let tags: HashMap<&str, &str> = logs_of(ADDED_TAG)
+                .filter_map(|log| Some((log.attribute("o_id")?, log.child_text("name")?)))
+                .collect();
+            let mut cards_by_fact: HashMap<&str, Vec<&XmlNode>> = HashMap::new();
+            for card in logs_of(ADDED_CARD) {
+                if let Some(fact) = card.attribute("fact") {
+                    cards_by_fact.entry(fact).or_default().push(card);
+                }
+            }
+
+            for fact in logs_of(ADDED_FACT) {
+                let Some(cards) = fact.attribute("o_id").and_then(|id| cards_by_fact.get(id))
+                else {
+                    continue;
+                };
+                let views: Vec<&str> = cards
+                    .iter()
+                    .filter_map(|card| card.attribute("fact_v"))
+                    .collect();
+                let has_reverse = views.iter().any(|view| view.ends_with(".2"));
+                let has_direct = views.iter().any(|view| view.ends_with(".1"));
+                let direction = match (has_direct, has_reverse) {
+                    (true, true) => FlashcardDirection::Both,
+                    (false, true) => FlashcardDirection::Reverse,
+                    _ => FlashcardDirection::Direct,
+                };
+
+                // Front-to-back facts use f/b, vocabulary facts f/m_1 with p_1 as pronunciation
+                let (front, back) = match fact.child_text("text") {
+                    Some(cloze) => (
+                        CLOZE.replace_all(cloze, "[…]").into_owned(),
+                        CLOZE.replace_all(cloze, "$1").into_owned(),
+                    ),
+                    None => (
+                        fact.child_text("f").unwrap_or_default().to_string(),
+                        fact.child_text("b")
+                            .or_else(|| fact.child_text("m_1"))
+                            .unwrap_or_default()
+                            .to_string(),
+                    ),
+                };
+                let extra: Vec<String> = ["p_1", "n"]
+                    .iter()
+                    .filter_map(|field| fact.child_text(field))
+                    .map(field_text)
+                    .filter(|text| !text.is_empty())
+                    .collect();
+                let level = cards
+                    .first()
+                    .and_then(|card| card.attribute("tags"))
+                    .and_then(|ids| ids.split(',').find_map(|id| tags.get(id.trim())))
+                    .filter(|tag| **tag != "__UNTAGGED__")
+                    .map(|tag| tag.to_string());
+
+                notes.push(ImportedNote {
+                    front_image: image(&front)?,
+                    back_image: image(&back)?,
+                    front: field_text(&front),
+                    back: field_text(&back),
+                    notes: Some(extra.join("\n\n")).filter(|n| !n.is_empty()),
+                    direction,
+                    level,
+                    reviews: Vec::new(),
+                });
+            }
+        }
+        "mnemosyne" => {
+            for item in root.children.iter().filter(|node| node.name == "item") {
+                let front = item.child_text("Q").unwrap_or_default();
+                let back = item.child_text("A").unwrap_or_default();
+                notes.push(ImportedNote {
+                    front_image: image(front)?,
+                    back_image: image(back)?,
+                    front: field_text(front),
+                    back: field_text(back),
+                    notes: None,
+                    direction: FlashcardDirection::Direct,
+                    level: item.child_text("cat").map(str::to_string),
+                    reviews: Vec::new(),
+                });
+            }
+        }
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Unrecognised Mnemosyne file (root element <{}>)",
+                other
+            )))
+        }
+    }
+
+    Ok(ImportedDeck {
+        name: "Mnemosyne import".to_string(),
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_mnemosyne_2_cards_xml() {
+        let xml = br#"<openSM2sync number_of_entries="4">
+            <log type="10" o_id="t1"><name>gismu</name></log>
+            <log type="16" o_id="f1"><f>klama</f><b>come, go</b></log>
+            <log type="6" o_id="c1" card_t="2" fact="f1" fact_v="2.1" tags="t1"></log>
+            <log type="6" o_id="c2" card_t="2" fact="f1" fact_v="2.2" tags="t1"></log>
+        </openSM2sync>"#;
+        let deck = read_mnemosyne(xml).unwrap();
+        assert_eq!(deck.notes.len(), 1);
+        let note = &deck.notes[0];
+        assert_eq!(
+            (note.front.as_str(), note.back.as_str()),
+            ("klama", "come, go")
+        );
+        assert_eq!(note.direction, FlashcardDirection::Both);
+        assert_eq!(note.level.as_deref(), Some("gismu"));
+    }
+}
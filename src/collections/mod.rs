@@ -1,5 +1,7 @@
+mod anki;
 pub mod controller;
 pub mod dto;
+mod mnemosyne;
 pub mod models;
 mod service;
 
@@ -11,6 +13,7 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         web::scope("collections")
             .service(controller::list_public_collections)
             .service(controller::export_collection_full)
+            .service(controller::export_collection_apkg)
             .service(controller::get_collection)
             .service(controller::list_collection_items)
             .service(controller::get_item_image)
@@ -18,6 +21,8 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .service(
                 web::scope("")
                     .wrap(HttpAuthentication::bearer(crate::auth::validator))
+                    // Deck packages with media are far larger than the default body limit
+                    .app_data(web::PayloadConfig::new(64 * 1024 * 1024))
                     .service(controller::create_collection)
                     .service(controller::list_collections)
                     .service(controller::update_collection)
@@ -31,7 +36,8 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                     .service(controller::update_item_images)
                     .service(controller::import_json)
                     .service(controller::import_collection_from_json)
-                    .service(controller::import_full),
+                    .service(controller::import_full)
+                    .service(controller::import_deck),
             ),
     );
 }
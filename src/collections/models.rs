@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::flashcards::models::FlashcardDirection;
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Collection {
     pub collection_id: i32,
@@ -32,3 +34,62 @@ pub struct ImageData {
     pub data: String, // Base64 encoded image data
     pub mime_type: String,
 }
+
+/// A user's scheduling state for one side of a flashcard, as carried into Anki exports.
+#[derive(Debug)]
+pub struct CardProgress {
+    pub item_id: i32,
+    pub card_side: String,
+    pub status: String,
+    pub ease_factor: f64,
+    /// Current interval in minutes
+    pub interval: i32,
+    pub review_count: i32,
+    pub stability: f64,
+    pub difficulty: f64,
+    pub next_review_at: Option<DateTime<Utc>>,
+}
+
+/// One entry of a user's review history for a flashcard side.
+#[derive(Debug)]
+pub struct CardReview {
+    pub item_id: i32,
+    pub card_side: String,
+    pub rating: i32,
+    pub elapsed_days: i32,
+    pub scheduled_days: i32,
+    pub review_time: DateTime<Utc>,
+}
+
+/// A deck read from an Anki or Mnemosyne package, before it becomes a collection.
+#[derive(Debug)]
+pub struct ImportedDeck {
+    pub name: String,
+    pub notes: Vec<ImportedNote>,
+}
+
+#[derive(Debug)]
+pub struct ImportedNote {
+    /// Plain text of the question and answer sides
+    pub front: String,
+    pub back: String,
+    pub notes: Option<String>,
+    /// Image bytes and MIME type
+    pub front_image: Option<(Vec<u8>, String)>,
+    pub back_image: Option<(Vec<u8>, String)>,
+    pub direction: FlashcardDirection,
+    /// Subdeck or tag the note was filed under, imported as a flashcard level
+    pub level: Option<String>,
+    pub reviews: Vec<ImportedReview>,
+}
+
+#[derive(Debug)]
+pub struct ImportedReview {
+    pub card_side: &'static str,
+    /// FSRS rating, 1 (again) to 4 (easy)
+    pub rating: i32,
+    pub reviewed_at: DateTime<Utc>,
+    pub interval_days: i32,
+    /// Learning or relearning step rather than a scheduled review
+    pub learning: bool,
+}
@@ -1,13 +1,19 @@
 use crate::utils::remove_html_tags;
 use super::dto::SkippedItemInfo;
 use super::dto::*;
+use super::models::{CardProgress, CardReview, ImportedReview};
+use super::{anki, mnemosyne};
 use crate::{
-    auth_utils::verify_collection_ownership, export::models::CollectionExportItem,
-    flashcards::models::FlashcardDirection, utils::validate_item_image, AppError, AppResult,
+    auth_utils::verify_collection_ownership,
+    export::models::CollectionExportItem,
+    flashcards::models::{FlashcardDirection, FlashcardStatus},
+    utils::validate_item_image,
+    AppError, AppResult,
 };
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::{DateTime, Utc};
 use deadpool_postgres::{Pool, Transaction};
+use fsrs::{FSRSItem, FSRSReview, FSRS};
 
 pub async fn create_collection(
     pool: &Pool,
@@ -654,7 +660,7 @@ pub async fn import_collection_from_json(
 }
 
 /// Parse direction string from export; defaults to Both if missing or invalid.
-fn parse_export_direction(s: Option<&String>) -> FlashcardDirection {
+pub(super) fn parse_export_direction(s: Option<&String>) -> FlashcardDirection {
     let s = match s {
         Some(x) => x.to_lowercase(),
         None => return FlashcardDirection::Both,
@@ -920,6 +926,358 @@ pub async fn import_full(
     })
 }
 
+/// Whether an upload is an Anki package: a zip holding `collection.anki2`/`.anki21(b)`.
+/// Zip entry names are stored uncompressed, so scanning the bytes is enough.
+fn is_apkg(package: &[u8]) -> bool {
+    package.starts_with(b"PK") && package.windows(16).any(|w| w == b"collection.anki2")
+}
+
+/// Imports an Anki `.apkg` or a Mnemosyne deck. Notes become free-content items, or
+/// items of an existing definition when `match_langid` is set and the front is a Lojban
+/// word; subdecks (Anki) or tags (Mnemosyne) become flashcard levels. With
+/// `include_history`, the Anki review log is replayed through FSRS so scheduling
+/// continues from the real memory state.
+pub async fn import_deck(
+    pool: &Pool,
+    user_id: i32,
+    package: Vec<u8>,
+    query: &ImportDeckQuery,
+) -> AppResult<ImportDeckResponse> {
+    let format = query.format.clone();
+    let match_words = query.match_langid.is_some();
+    let (deck, canonical_forms) = tokio::task::spawn_blocking(move || {
+        let deck = match format.as_deref() {
+            Some("anki") | Some("apkg") => anki::read_apkg(&package),
+            Some("mnemosyne") => mnemosyne::read_mnemosyne(&package),
+            Some(other) => Err(AppError::BadRequest(format!(
+                "Unsupported deck format: {}",
+                other
+            ))),
+            None if is_apkg(&package) => anki::read_apkg(&package),
+            None => mnemosyne::read_mnemosyne(&package),
+        }?;
+        // Canonical forms only serve to match notes to dictionary words, and Tersmu blocks
+        let canonical_forms: Vec<Option<String>> = deck
+            .notes
+            .iter()
+            .map(|note| {
+                if match_words && !note.front.is_empty() {
+                    crate::tersmu::get_canonical_form(&note.front)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Ok::<_, AppError>((deck, canonical_forms))
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    let mut client = pool
+        .get()
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let transaction = client
+        .transaction()
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let collection_id = match query.collection_id {
+        Some(collection_id) => {
+            verify_collection_ownership(&transaction, collection_id, user_id).await?;
+            collection_id
+        }
+        None => {
+            let name = sanitize_html(query.name.as_deref().unwrap_or(&deck.name));
+            transaction
+                .query_one(
+                    "INSERT INTO collections (user_id, name, is_public)
+                     VALUES ($1, $2, true)
+                     RETURNING collection_id",
+                    &[&user_id, &name],
+                )
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .get("collection_id")
+        }
+    };
+
+    let mut position: i32 = transaction
+        .query_one(
+            "SELECT COALESCE(MAX(position) + 1, 0) FROM collection_items WHERE collection_id = $1",
+            &[&collection_id],
+        )
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .get(0);
+
+    let fsrs = FSRS::new(Some(&[])).map_err(|e| AppError::Internal(e.to_string()))?;
+    let include_history = query.include_history.unwrap_or(false);
+    let mut imported_count = 0i32;
+    let mut matched_count = 0i32;
+    let mut skipped_count = 0i32;
+    let mut reviews_imported = 0i32;
+    let mut warnings: Vec<String> = Vec::new();
+    // Level name -> (level_id, cards added so far)
+    let mut levels: std::collections::HashMap<String, (i32, i32)> =
+        std::collections::HashMap::new();
+    let mut levels_created = 0i32;
+
+    for (note, canonical_form) in deck.notes.iter().zip(&canonical_forms) {
+        if note.front.is_empty() && note.back.is_empty() && note.front_image.is_none() {
+            skipped_count += 1;
+            continue;
+        }
+
+        let definition_id: Option<i32> = match query.match_langid {
+            Some(langid) if !note.front.is_empty() => transaction
+                .query_opt(
+                    "SELECT vbg.definitionid
+                     FROM valsi v
+                     JOIN valsibestguesses vbg ON vbg.valsiid = v.valsiid
+                     WHERE vbg.langid = $2 AND v.source_langid = 1
+                       AND (v.word = $1 OR v.word = $3)
+                     LIMIT 1",
+                    &[&note.front, &langid, canonical_form],
+                )
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .map(|row| row.get(0)),
+            _ => None,
+        };
+
+        if let Some(definition_id) = definition_id {
+            let exists: bool = transaction
+                .query_one(
+                    "SELECT EXISTS(SELECT 1 FROM collection_items
+                                   WHERE collection_id = $1 AND definition_id = $2)",
+                    &[&collection_id, &definition_id],
+                )
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .get(0);
+            if exists {
+                warnings.push(format!(
+                    "'{}' is already in the collection, skipped",
+                    note.front
+                ));
+                skipped_count += 1;
+                continue;
+            }
+            matched_count += 1;
+        }
+
+        let (front, back) = match definition_id {
+            Some(_) => (None, None),
+            None => (
+                Some(sanitize_html(&note.front)),
+                Some(sanitize_html(&note.back)),
+            ),
+        };
+        let notes = note.notes.as_deref().map(sanitize_html);
+        let item_id: i32 = transaction
+            .query_one(
+                "INSERT INTO collection_items (collection_id, definition_id, free_content_front, free_content_back, notes, position, canonical_form)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING item_id",
+                &[
+                    &collection_id,
+                    &definition_id,
+                    &front,
+                    &back,
+                    &notes,
+                    &position,
+                    canonical_form,
+                ],
+            )
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .get(0);
+
+        for (side, image) in [("front", &note.front_image), ("back", &note.back_image)] {
+            let Some((image_data, mime_type)) = image else {
+                continue;
+            };
+            transaction
+                .execute(
+                    "INSERT INTO collection_item_images (item_id, image_data, mime_type, side) VALUES ($1, $2, $3, $4)",
+                    &[&item_id, image_data, mime_type, &side],
+                )
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        }
+
+        let flashcard_id: i32 = transaction
+            .query_one(
+                "INSERT INTO flashcards (collection_id, position, item_id, direction)
+                 VALUES ($1, $2, $3, $4)
+                 RETURNING id",
+                &[&collection_id, &position, &item_id, &note.direction],
+            )
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .get(0);
+        initialize_flashcard_progress(&transaction, user_id, flashcard_id, "direct").await?;
+        initialize_flashcard_progress(&transaction, user_id, flashcard_id, "reverse").await?;
+
+        if let Some(level) = &note.level {
+            if !levels.contains_key(level) {
+                let level_id: i32 = transaction
+                    .query_one(
+                        "INSERT INTO flashcard_levels (collection_id, name, position)
+                         VALUES ($1, $2, (SELECT COALESCE(MAX(position) + 1, 0) FROM flashcard_levels WHERE collection_id = $1))
+                         RETURNING level_id",
+                        &[&collection_id, &sanitize_html(level)],
+                    )
+                    .await
+                    .map_err(|e| AppError::Database(e.to_string()))?
+                    .get(0);
+                levels.insert(level.clone(), (level_id, 0));
+                levels_created += 1;
+            }
+            if let Some((level_id, count)) = levels.get_mut(level) {
+                transaction
+                    .execute(
+                        "INSERT INTO flashcard_level_items (level_id, flashcard_id, position)
+                         VALUES ($1, $2, $3)
+                         ON CONFLICT (level_id, flashcard_id) DO NOTHING",
+                        &[&*level_id, &flashcard_id, &*count],
+                    )
+                    .await
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                *count += 1;
+            }
+        }
+
+        if include_history {
+            for side in ["direct", "reverse"] {
+                let side_reviews: Vec<&ImportedReview> = note
+                    .reviews
+                    .iter()
+                    .filter(|r| r.card_side == side)
+                    .collect();
+                if side_reviews.is_empty() {
+                    continue;
+                }
+                reviews_imported += import_review_history(
+                    &transaction,
+                    &fsrs,
+                    user_id,
+                    flashcard_id,
+                    side,
+                    &side_reviews,
+                )
+                .await?;
+            }
+        }
+
+        position += 1;
+        imported_count += 1;
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(ImportDeckResponse {
+        collection: get_collection(pool, collection_id, Some(user_id)).await?,
+        imported_count,
+        matched_count,
+        skipped_count,
+        levels_created,
+        reviews_imported,
+        warnings,
+    })
+}
+
+/// Writes one card side's review log to `flashcard_review_history`, computing the FSRS
+/// memory state after each review, and moves its progress to the state after the last.
+async fn import_review_history(
+    transaction: &Transaction<'_>,
+    fsrs: &FSRS,
+    user_id: i32,
+    flashcard_id: i32,
+    card_side: &str,
+    reviews: &[&ImportedReview],
+) -> AppResult<i32> {
+    let mut history: Vec<FSRSReview> = Vec::with_capacity(reviews.len());
+    let mut previous: Option<DateTime<Utc>> = None;
+    let mut state = None;
+
+    for review in reviews {
+        let elapsed_days = previous
+            .map(|p| (review.reviewed_at - p).num_days().max(0))
+            .unwrap_or(0) as u32;
+        history.push(FSRSReview {
+            rating: review.rating as u32,
+            delta_t: elapsed_days,
+        });
+        let memory = fsrs
+            .memory_state(
+                FSRSItem {
+                    reviews: history.clone(),
+                },
+                None,
+            )
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        transaction
+            .execute(
+                "INSERT INTO flashcard_review_history
+                 (user_id, flashcard_id, card_side, rating, elapsed_days, scheduled_days, state, review_time)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &user_id,
+                    &flashcard_id,
+                    &card_side,
+                    &review.rating,
+                    &(elapsed_days as i32),
+                    &review.interval_days,
+                    &serde_json::json!({
+                        "stability": memory.stability,
+                        "difficulty": memory.difficulty
+                    }),
+                    &review.reviewed_at,
+                ],
+            )
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        previous = Some(review.reviewed_at);
+        state = Some((memory, review));
+    }
+
+    let Some((memory, last)) = state else {
+        return Ok(0);
+    };
+    let status = match last.learning || last.interval_days == 0 {
+        true => FlashcardStatus::Learning,
+        false => FlashcardStatus::Review,
+    };
+    let next_review_at = last.reviewed_at + chrono::Duration::days(last.interval_days as i64);
+    transaction
+        .execute(
+            "UPDATE user_flashcard_progress
+             SET stability = $4, difficulty = $5, interval = $6, status = $7,
+                 last_reviewed_at = $8, next_review_at = $9, review_count = $10
+             WHERE user_id = $1 AND flashcard_id = $2 AND card_side = $3 AND NOT archived",
+            &[
+                &user_id,
+                &flashcard_id,
+                &card_side,
+                &(memory.stability as f64),
+                &(memory.difficulty as f64),
+                &(last.interval_days * 1440),
+                &status,
+                &last.reviewed_at,
+                &next_review_at,
+                &(reviews.len() as i32),
+            ],
+        )
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(reviews.len() as i32)
+}
+
 /// Full collection export: collection metadata, items (with flashcard direction when present), and levels.
 pub async fn export_collection_full(
     pool: &Pool,
@@ -1089,6 +1447,88 @@ pub async fn export_collection_full(
     })
 }
 
+/// Anki package of a collection. With `include_progress`, the requesting user's
+/// scheduling state and review history are carried over.
+pub async fn export_collection_apkg(
+    pool: &Pool,
+    collection_id: i32,
+    user_id: Option<i32>,
+    include_progress: bool,
+) -> AppResult<Vec<u8>> {
+    let export = export_collection_full(pool, collection_id, user_id).await?;
+    let (progress, reviews) = match user_id.filter(|_| include_progress) {
+        Some(user_id) => fetch_card_progress(pool, collection_id, user_id).await?,
+        None => (Vec::new(), Vec::new()),
+    };
+
+    tokio::task::spawn_blocking(move || {
+        anki::build_apkg(collection_id, &export, &progress, &reviews)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+async fn fetch_card_progress(
+    pool: &Pool,
+    collection_id: i32,
+    user_id: i32,
+) -> AppResult<(Vec<CardProgress>, Vec<CardReview>)> {
+    let client = pool
+        .get()
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let progress = client
+        .query(
+            "SELECT f.item_id, p.card_side, p.status::text AS status, p.ease_factor,
+                    p.interval, p.review_count, p.stability, p.difficulty, p.next_review_at
+             FROM user_flashcard_progress p
+             JOIN flashcards f ON f.id = p.flashcard_id
+             WHERE p.user_id = $1 AND f.collection_id = $2 AND NOT p.archived",
+            &[&user_id, &collection_id],
+        )
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map(|row| CardProgress {
+            item_id: row.get("item_id"),
+            card_side: row.get("card_side"),
+            status: row.get("status"),
+            ease_factor: row.get("ease_factor"),
+            interval: row.get("interval"),
+            review_count: row.get("review_count"),
+            stability: row.get("stability"),
+            difficulty: row.get("difficulty"),
+            next_review_at: row.get("next_review_at"),
+        })
+        .collect();
+
+    let reviews = client
+        .query(
+            "SELECT f.item_id, h.card_side, h.rating, h.elapsed_days, h.scheduled_days,
+                    h.review_time
+             FROM flashcard_review_history h
+             JOIN flashcards f ON f.id = h.flashcard_id
+             WHERE h.user_id = $1 AND f.collection_id = $2
+             ORDER BY h.review_time",
+            &[&user_id, &collection_id],
+        )
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .iter()
+        .map(|row| CardReview {
+            item_id: row.get("item_id"),
+            card_side: row.get("card_side"),
+            rating: row.get("rating"),
+            elapsed_days: row.get("elapsed_days"),
+            scheduled_days: row.get("scheduled_days"),
+            review_time: row.get("review_time"),
+        })
+        .collect();
+
+    Ok((progress, reviews))
+}
+
 // Helper function to initialize flashcard progress
 async fn initialize_flashcard_progress(
     transaction: &Transaction<'_>,
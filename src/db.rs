@@ -21,6 +21,10 @@ pub async fn enable_extensions(pool: &Pool) -> AppResult<()> {
         .execute("CREATE EXTENSION IF NOT EXISTS vector", &[])
         .await
         .map_err(|e| AppError::Database(e.to_string()))?; // Map error explicitly
+    client
+        .execute("CREATE EXTENSION IF NOT EXISTS fuzzystrmatch", &[])
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
     Ok(())
 }
 
@@ -0,0 +1,67 @@
+//! File-backed export cache. Export files live in `EXPORT_CACHE_DIR` (default
+//! `export-cache`); `cached_dictionary_exports` only keeps their metadata.
+
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub fn cache_dir() -> PathBuf {
+    std::env::var("EXPORT_CACHE_DIR")
+        .unwrap_or_else(|_| "export-cache".to_string())
+        .into()
+}
+
+/// Passes writes through while counting bytes and computing their SHA-256, so
+/// exports are checksummed as they stream to disk.
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        HashingWriter {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// The inner writer, the number of bytes written and their hex SHA-256.
+    pub fn finish(self) -> (W, u64, String) {
+        (self.inner, self.size, hex::encode(self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_and_counts_written_bytes() {
+        let mut writer = HashingWriter::new(Vec::new());
+        writer.write_all(b"ab").unwrap();
+        writer.write_all(b"c").unwrap();
+        let (content, size, checksum) = writer.finish();
+        assert_eq!(content, b"abc");
+        assert_eq!(size, 3);
+        assert_eq!(
+            checksum,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -1,13 +1,76 @@
-use super::models::CachedExport;
-use super::service;
-use actix_web::{get, web, HttpResponse, Responder};
+use super::models::{
+    CachedExport, CachedExportFile, CreateExportJobRequest, DailyDiff, ExportContent, ExportJob,
+    ExportManifest,
+};
+use super::{jobs, service, signing};
+use actix_files::NamedFile;
+use actix_web::{
+    get,
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType, HeaderValue},
+    post, web, Either, HttpRequest, HttpResponse, Responder,
+};
+use actix_web_lab::sse::Sse;
+use chrono::NaiveDate;
 use deadpool_postgres::Pool;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::time::Duration;
 
 use crate::{
     auth::Claims,
-    export::models::{ExportFormat, ExportOptions},
+    export::models::{ChangesQuery, ExportFormat, ExportOptions},
 };
 
+/// Streams a cached export from disk. Range requests are served by `NamedFile`; the
+/// file's SHA-256 is the ETag, so revalidation survives regeneration of identical
+/// content.
+async fn cached_file_response(req: &HttpRequest, file: CachedExportFile) -> HttpResponse {
+    let etag = format!("\"{}\"", file.checksum);
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish();
+    }
+
+    let named_file = match NamedFile::open_async(&file.path).await {
+        Ok(named_file) => named_file,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let mut response = named_file
+        .use_etag(false)
+        .set_content_type(
+            file.content_type
+                .parse()
+                .unwrap_or(actix_web::mime::APPLICATION_OCTET_STREAM),
+        )
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file.filename)],
+        })
+        .into_response(req);
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
+}
+
+async fn export_response(req: &HttpRequest, content: ExportContent) -> HttpResponse {
+    match content {
+        ExportContent::Cached(file) => cached_file_response(req, file).await,
+        ExportContent::Generated { file, temp_path } => {
+            // The response holds the file open, so its path can be removed right away
+            let response = cached_file_response(req, file).await;
+            drop(temp_path);
+            response
+        }
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/export/cached",
@@ -35,47 +98,169 @@ pub async fn list_cached_exports(pool: web::Data<Pool>) -> impl Responder {
     tag = "export",
     params(
         ("language_tag" = String, Path, description = "Language tag"),
-        ("format" = String, Path, description = "Export format (pdf, latex, xml, json)")
+        ("format" = String, Path, description = "Export format (pdf, latex, xml, json, tsv, stardict, dictd, epub, tei, ontolex-ttl, ontolex-jsonld)")
     ),
     responses(
         (status = 200, description = "Cached export file"),
+        (status = 206, description = "Requested byte range of the file"),
+        (status = 304, description = "File unchanged (If-None-Match)"),
         (status = 404, description = "Export not found"),
         (status = 500, description = "Internal server error")
     ),
     security(
         ("bearer_auth" = [])
     ),
-    summary = "Download a cached dictionary export"
+    summary = "Download a cached dictionary export",
+    description = "Streams the cached file with its SHA-256 as ETag; supports Range and If-None-Match requests."
 )]
 #[get("/cached/{language_tag}/{format}")]
 pub async fn download_cached_export(
+    req: HttpRequest,
     pool: web::Data<Pool>,
     path: web::Path<(String, String)>,
 ) -> impl Responder {
     let (language_tag, format) = path.into_inner();
 
     match service::get_cached_export(&pool, &language_tag, &format).await {
-        Ok((content, content_type, filename)) => HttpResponse::Ok()
-            .content_type(content_type)
+        Ok(file) => cached_file_response(&req, file).await,
+        Err(e) if e.to_string() == "Export not found" => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/cached/{language_tag}/{format}/signature",
+    tag = "export",
+    params(
+        ("language_tag" = String, Path, description = "Language tag"),
+        ("format" = String, Path, description = "Export format")
+    ),
+    responses(
+        (status = 200, description = "Raw 64-byte Ed25519 signature of the cached file's SHA-256 digest"),
+        (status = 404, description = "Export not found or not signed"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Download the detached signature of a cached dictionary export",
+    description = "The signature covers the 32-byte SHA-256 digest of the file, the checksum listed in /export/manifest. Verify with the key from /export/signing-key, e.g. `openssl dgst -sha256 -binary FILE > FILE.sha256` and `openssl pkeyutl -verify -rawin -pubin -inkey key.pem -in FILE.sha256 -sigfile FILE.sig`."
+)]
+#[get("/cached/{language_tag}/{format}/signature")]
+pub async fn download_export_signature(
+    pool: web::Data<Pool>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (language_tag, format) = path.into_inner();
+
+    match service::get_export_signature(&pool, &language_tag, &format).await {
+        Ok((filename, signature)) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
             .append_header((
                 "Content-Disposition",
-                format!("attachment; filename=\"{}\"", filename),
+                format!("attachment; filename=\"{}.sig\"", filename),
             ))
-            .body(content),
-        Err(e) if e.to_string() == "Export not found" => HttpResponse::NotFound().finish(),
+            .body(signature),
+        Err(e) if e.to_string() == "Export not found" || e.to_string() == "Export not signed" => {
+            HttpResponse::NotFound().body(e.to_string())
+        }
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/export/manifest",
+    tag = "export",
+    responses(
+        (status = 200, description = "SHA-256 and size of every cached export", body = ExportManifest),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Manifest of the cached dictionary exports",
+    description = "Exports are reproducible: they carry the date of the latest change instead of the generation time, so a checksum only changes with the content and unchanged files can be skipped. The manifest's SHA-256 is its ETag; its signature is at /export/manifest/signature. `signing_key_fingerprint` is the SHA-256 of the raw public key from /export/signing-key, which made every signature served."
+)]
+#[get("/manifest")]
+pub async fn export_manifest(pool: web::Data<Pool>) -> impl Responder {
+    match service::export_manifest(&pool).await {
+        Ok(manifest) => HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header((
+                header::ETAG,
+                format!("\"{}\"", hex::encode(Sha256::digest(&manifest))),
+            ))
+            .body(manifest),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/manifest/signature",
+    tag = "export",
+    responses(
+        (status = 200, description = "Raw 64-byte Ed25519 signature of the manifest as currently served"),
+        (status = 404, description = "Exports are not signed"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Download the detached signature of the export manifest"
+)]
+#[get("/manifest/signature")]
+pub async fn export_manifest_signature(pool: web::Data<Pool>) -> impl Responder {
+    match service::export_manifest(&pool).await {
+        Ok(manifest) => match signing::sign(&manifest) {
+            Some(signature) => HttpResponse::Ok()
+                .content_type("application/octet-stream")
+                .append_header((
+                    "Content-Disposition",
+                    "attachment; filename=\"manifest.json.sig\"",
+                ))
+                .body(signature),
+            None => HttpResponse::NotFound().body("Exports are not signed"),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/signing-key",
+    tag = "export",
+    responses(
+        (status = 200, description = "Ed25519 public key as PEM"),
+        (status = 404, description = "Exports are not signed")
+    ),
+    summary = "Public key of export signatures"
+)]
+#[get("/signing-key")]
+pub async fn export_signing_key() -> impl Responder {
+    match signing::public_key_pem() {
+        Some(pem) => HttpResponse::Ok()
+            .content_type("application/x-pem-file")
+            .body(pem),
+        None => HttpResponse::NotFound().body("Exports are not signed"),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/export/dictionary/{lang}",
     tag = "export",
     params(
         ("lang" = String, Path, description = "Language tag"),
-        ("format" = Option<String>, Query, description = "Export format (pdf, latex, xml, json)"),
+        ("format" = Option<String>, Query, description = "Export format (pdf, latex, xml, json, tsv, stardict, dictd, epub, tei, ontolex-ttl, ontolex-jsonld)"),
         ("positive_scores_only" = Option<bool>, Query, description = "Include only entries with positive scores"),
-        ("collection_id" = Option<i32>, Query, description = "Export only definitions from specific collection")
+        ("collection_id" = Option<i32>, Query, description = "Export only definitions from specific collection"),
+        ("source_langid" = Option<i32>, Query, description = "Source language of the exported valsi (default 1, Lojban; 58 for Loglan)"),
+        ("word_types" = Option<String>, Query, description = "Comma-separated word types, e.g. gismu,lujvo"),
+        ("selmaho" = Option<String>, Query, description = "Comma-separated selma'o"),
+        ("min_score" = Option<f32>, Query, description = "Minimum vote score"),
+        ("author" = Option<String>, Query, description = "Username of the definitions' author"),
+        ("created_after" = Option<String>, Query, description = "RFC 3339 timestamp; only definitions created at or after it"),
+        ("created_before" = Option<String>, Query, description = "RFC 3339 timestamp; only definitions created before it"),
+        ("include_notes" = Option<bool>, Query, description = "Include notes (default true)"),
+        ("include_etymology" = Option<bool>, Query, description = "Include etymology (default true)"),
+        ("include_keywords" = Option<bool>, Query, description = "Include gloss and place keywords, and the keyword chapter of PDF and LaTeX exports (default true)"),
+        ("include_user" = Option<bool>, Query, description = "Include the author of each definition (default true)"),
+        ("paper" = Option<String>, Query, description = "PDF and LaTeX paper size: a4 (default), a5, b5 or letter"),
+        ("columns" = Option<u8>, Query, description = "PDF and LaTeX text columns: 1 or 2 (default)")
     ),
     responses(
         (status = 200, description = "Dictionary exported successfully"),
@@ -85,37 +270,29 @@ pub async fn download_cached_export(
     security(
         ("bearer_auth" = [])
     ),
-    summary = "Export dictionary for specified language"
+    summary = "Export dictionary for specified language",
+    description = "Dictionary exports are cached per combination of filters and fields, so repeated requests for the same variant are served from disk. Collection exports, exports filtered by author, date or score, and variants beyond the cache limit are generated on every request, streamed through a temporary file."
 )]
 #[get("/dictionary/{lang}")]
 pub async fn export_dictionary(
+    req: HttpRequest,
     pool: web::Data<Pool>,
     lang: web::Path<String>,
     query: web::Query<ExportOptions>,
     claims: Option<Claims>,
 ) -> impl Responder {
-    let format = match query.format.as_deref().unwrap_or("pdf") {
-        "pdf" => ExportFormat::Pdf,
-        "latex" | "tex" => ExportFormat::LaTeX,
-        "xml" => ExportFormat::Xml,
-        "json" => ExportFormat::Json,
-        "tsv" => ExportFormat::Tsv,
-        _ => {
-            return HttpResponse::BadRequest()
-                .body("Invalid format. Supported formats: pdf, latex, xml, json, tsv");
-        }
+    let format: ExportFormat = match query.format.as_deref().unwrap_or("pdf").parse() {
+        Ok(format) => format,
+        Err(e) => return HttpResponse::BadRequest().body(e),
     };
+    if let Err(e) = query.pdf_layout() {
+        return HttpResponse::BadRequest().body(e);
+    }
 
     match service::export_with_access_check(&pool, &lang, format, &query, claims.map(|c| c.sub))
         .await
     {
-        Ok((content, content_type, filename)) => HttpResponse::Ok()
-            .content_type(content_type)
-            .append_header((
-                "Content-Disposition",
-                format!("attachment; filename=\"{}\"", filename),
-            ))
-            .body(content),
+        Ok(content) => export_response(&req, content).await,
         Err(e) => match e.to_string().as_str() {
             "Access denied" => HttpResponse::Forbidden().finish(),
             "Invalid language tag" => HttpResponse::BadRequest().body(e.to_string()),
@@ -123,3 +300,256 @@ pub async fn export_dictionary(
         },
     }
 }
+
+#[utoipa::path(
+    get,
+    path = "/export/changes",
+    tag = "export",
+    params(
+        ("lang" = String, Query, description = "Language tag"),
+        ("since" = String, Query, description = "RFC 3339 timestamp at most 90 days ago; use the until of the previous sync or the created_at of the full export you started from"),
+        ("source_langid" = Option<i32>, Query, description = "Source language of the valsi (default 1, Lojban)")
+    ),
+    responses(
+        (status = 200, description = "Definitions added, updated and deleted since the timestamp, with entries in the JSON export format"),
+        (status = 400, description = "Invalid language tag, or since is more than 90 days ago"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "List dictionary changes since a timestamp",
+    description = "Delta feed for mirrors and offline apps: one change per definition since `since`. Added and updated definitions carry their full export entry, deleted ones (including definitions that stopped being the best guess for their word) only their ID and word. Deleted may also list definitions that were never in your copy, such as an alternative definition that was edited or voted on without becoming the best guess; ignore IDs you don't have. Store `until` from the response and pass it as `since` next time; it trails the current time while other writes are still in progress, so none are missed. The change log covers the last 90 days; older mirrors have to start from a new full export."
+)]
+#[get("/changes")]
+pub async fn dictionary_changes(
+    pool: web::Data<Pool>,
+    query: web::Query<ChangesQuery>,
+) -> impl Responder {
+    match service::dictionary_changes(&pool, &query.lang, query.since, None, query.source_langid)
+        .await
+    {
+        Ok(changes) => HttpResponse::Ok().json(changes),
+        Err(e)
+            if e.to_string() == "Invalid language tag"
+                || e.to_string().starts_with("Changes are only kept") =>
+        {
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/changes/daily/{lang}",
+    tag = "export",
+    params(
+        ("lang" = String, Path, description = "Language tag")
+    ),
+    responses(
+        (status = 200, description = "Daily diff files, newest first", body = Vec<DailyDiff>),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "List daily dictionary diff files"
+)]
+#[get("/changes/daily/{lang}")]
+pub async fn list_daily_diffs(pool: web::Data<Pool>, lang: web::Path<String>) -> impl Responder {
+    match service::list_daily_diffs(&pool, &lang).await {
+        Ok(diffs) => HttpResponse::Ok().json(diffs),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/changes/daily/{lang}/{day}",
+    tag = "export",
+    params(
+        ("lang" = String, Path, description = "Language tag"),
+        ("day" = String, Path, description = "UTC day (YYYY-MM-DD)")
+    ),
+    responses(
+        (status = 200, description = "Changes of that day, in the /export/changes format"),
+        (status = 404, description = "No diff for that day"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Download a daily dictionary diff"
+)]
+#[get("/changes/daily/{lang}/{day}")]
+pub async fn download_daily_diff(
+    pool: web::Data<Pool>,
+    path: web::Path<(String, NaiveDate)>,
+) -> impl Responder {
+    let (lang, day) = path.into_inner();
+
+    match service::get_daily_diff(&pool, &lang, day).await {
+        Ok((content, filename)) => HttpResponse::Ok()
+            .content_type("application/json")
+            .append_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", filename),
+            ))
+            .body(content),
+        Err(e) if e.to_string() == "Export not found" => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+fn export_job_error(e: Box<dyn Error + Send + Sync>) -> HttpResponse {
+    match e.to_string().as_str() {
+        "Export job not found" => HttpResponse::NotFound().body(e.to_string()),
+        "Export job not finished" => HttpResponse::Conflict().body(e.to_string()),
+        "Too many export jobs" => HttpResponse::TooManyRequests().body(e.to_string()),
+        "Access denied" => HttpResponse::Forbidden().finish(),
+        message if message.starts_with("Invalid") => HttpResponse::BadRequest().body(e.to_string()),
+        _ => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/export/jobs",
+    tag = "export",
+    request_body = CreateExportJobRequest,
+    responses(
+        (status = 202, description = "Export job queued", body = ExportJob),
+        (status = 400, description = "Invalid language, format or page layout"),
+        (status = 403, description = "Collection not accessible"),
+        (status = 429, description = "Too many queued or running export jobs"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "Queue a dictionary or collection export",
+    description = "Generates the export in the background with the same options as /export/dictionary/{lang}. Follow it with /export/jobs/{job_id} or its events stream and download the file once the job is completed. Finished jobs are kept for a week."
+)]
+#[post("/jobs")]
+pub async fn create_export_job(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    request: web::Json<CreateExportJobRequest>,
+) -> impl Responder {
+    match jobs::create_export_job(&pool, claims.sub, &request).await {
+        Ok(job) => HttpResponse::Accepted().json(job),
+        Err(e) => export_job_error(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/jobs",
+    tag = "export",
+    responses(
+        (status = 200, description = "The user's export jobs, newest first", body = Vec<ExportJob>),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "List your export jobs"
+)]
+#[get("/jobs")]
+pub async fn list_export_jobs(pool: web::Data<Pool>, claims: Claims) -> impl Responder {
+    match jobs::list_export_jobs(&pool, claims.sub).await {
+        Ok(jobs) => HttpResponse::Ok().json(jobs),
+        Err(e) => export_job_error(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/jobs/{job_id}",
+    tag = "export",
+    params(
+        ("job_id" = i32, Path, description = "Export job ID")
+    ),
+    responses(
+        (status = 200, description = "Status and progress of the job", body = ExportJob),
+        (status = 404, description = "Job not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "Get an export job"
+)]
+#[get("/jobs/{job_id}")]
+pub async fn get_export_job(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    job_id: web::Path<i32>,
+) -> impl Responder {
+    match jobs::get_export_job(&pool, *job_id, claims.sub).await {
+        Ok(job) => HttpResponse::Ok().json(job),
+        Err(e) => export_job_error(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/jobs/{job_id}/events",
+    tag = "export",
+    params(
+        ("job_id" = i32, Path, description = "Export job ID")
+    ),
+    responses(
+        (status = 200, description = "Server-sent events with the job state, named queued, running, completed or failed"),
+        (status = 404, description = "Job not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "Follow the progress of an export job",
+    description = "Sends the current state, then every change of status, progress or stage. The stream closes after the completed or failed event."
+)]
+#[get("/jobs/{job_id}/events")]
+pub async fn export_job_events(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    job_id: web::Path<i32>,
+) -> impl Responder {
+    match jobs::get_export_job(&pool, *job_id, claims.sub).await {
+        Ok(job) => Either::Left(
+            Sse::from_infallible_stream(jobs::export_job_events(
+                pool.get_ref().clone(),
+                job,
+                claims.sub,
+            ))
+            .with_keep_alive(Duration::from_secs(15)),
+        ),
+        Err(e) => Either::Right(export_job_error(e)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/jobs/{job_id}/download",
+    tag = "export",
+    params(
+        ("job_id" = i32, Path, description = "Export job ID")
+    ),
+    responses(
+        (status = 200, description = "Exported file"),
+        (status = 206, description = "Requested byte range of the file"),
+        (status = 304, description = "File unchanged (If-None-Match)"),
+        (status = 404, description = "Job not found or its file expired"),
+        (status = 409, description = "Job has not completed"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "Download the file of a completed export job"
+)]
+#[get("/jobs/{job_id}/download")]
+pub async fn download_export_job(
+    req: HttpRequest,
+    pool: web::Data<Pool>,
+    claims: Claims,
+    job_id: web::Path<i32>,
+) -> impl Responder {
+    match jobs::get_export_job_file(&pool, *job_id, claims.sub).await {
+        Ok(file) => cached_file_response(&req, file).await,
+        Err(e) => export_job_error(e),
+    }
+}
@@ -0,0 +1,500 @@
+//! Dictionary files for offline lookup: StarDict, dictd and Kindle-style EPUB.
+//!
+//! All three are built from the same [`DictionaryEntry`] rows as the JSON export. Entry
+//! bodies are plain text (StarDict `m` type, dictd) or XHTML (EPUB), with LaTeX math
+//! rendered as Unicode.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{Cursor, Write};
+
+use chrono::{Datelike, NaiveDate};
+use flate2::{Compress, Compression, FlushCompress, Status};
+use zip::write::{FileOptions, ZipWriter};
+use zip::CompressionMethod;
+
+use super::models::{DictionaryEntry, DictionaryInfo};
+use crate::language::math;
+use crate::utils::escape_xml;
+
+type ExportResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Uncompressed chunk size used by `dictzip`; small enough that a deflated chunk
+/// always fits the 16-bit chunk length of the `RA` header.
+const DICTZIP_CHUNK_LEN: usize = 58315;
+
+fn headword_line(entry: &DictionaryEntry) -> String {
+    let mut line = format!("{} [{}]", entry.word, entry.word_type);
+    if let Some(rafsi) = entry.rafsi.as_deref().filter(|r| !r.trim().is_empty()) {
+        line.push_str(&format!(
+            " -{}-",
+            rafsi.split_whitespace().collect::<Vec<_>>().join("- -")
+        ));
+    }
+    if let Some(selmaho) = entry.selmaho.as_deref().filter(|s| !s.trim().is_empty()) {
+        line.push_str(&format!(" ({})", selmaho));
+    }
+    line
+}
+
+/// Plain-text entry body: headword line, definition, then notes.
+fn entry_text(entry: &DictionaryEntry) -> String {
+    let mut text = headword_line(entry);
+    text.push('\n');
+    text.push_str(math::to_unicode_lossy(&entry.definition).trim());
+    for extra in [&entry.notes, &entry.collection_note].into_iter().flatten() {
+        if !extra.trim().is_empty() {
+            text.push_str("\n\n");
+            text.push_str(math::to_unicode_lossy(extra).trim());
+        }
+    }
+    text.push('\n');
+    text
+}
+
+/// Zip entry timestamp for the content date; zip would otherwise stamp the current time.
+pub(super) fn zip_time(date: NaiveDate) -> zip::DateTime {
+    zip::DateTime::from_date_and_time(
+        date.year().clamp(1980, 2107) as u16,
+        date.month() as u8,
+        date.day() as u8,
+        0,
+        0,
+        0,
+    )
+    .unwrap_or_default()
+}
+
+fn zip_files(
+    files: &[(String, Vec<u8>, CompressionMethod)],
+    date: NaiveDate,
+) -> ExportResult<Vec<u8>> {
+    let mut buffer = Vec::new();
+    {
+        let mut zip = ZipWriter::new(Cursor::new(&mut buffer));
+        for (name, content, method) in files {
+            zip.start_file(
+                name.as_str(),
+                FileOptions::default()
+                    .compression_method(*method)
+                    .last_modified_time(zip_time(date)),
+            )?;
+            zip.write_all(content)?;
+        }
+        zip.finish()?;
+    }
+    Ok(buffer)
+}
+
+fn deflate_chunk(
+    compress: &mut Compress,
+    chunk: &[u8],
+    flush: FlushCompress,
+) -> ExportResult<Vec<u8>> {
+    let mut out = Vec::with_capacity(chunk.len() + 1024);
+    let start = compress.total_in();
+    loop {
+        let consumed = (compress.total_in() - start) as usize;
+        let status = compress.compress_vec(&chunk[consumed..], &mut out, flush)?;
+        let all_consumed = (compress.total_in() - start) as usize == chunk.len();
+        match (flush, status) {
+            (FlushCompress::Finish, Status::StreamEnd) => return Ok(out),
+            // A flush is complete once deflate stops filling the whole output buffer
+            (FlushCompress::Finish, _) => {}
+            _ if all_consumed && out.len() < out.capacity() => return Ok(out),
+            _ => {}
+        }
+        out.reserve(1024);
+    }
+}
+
+/// Compresses `data` in the `dictzip` format: a gzip member whose deflate stream is
+/// fully flushed every [`DICTZIP_CHUNK_LEN`] bytes, with the compressed chunk sizes in
+/// an `RA` extra field so readers can seek without inflating the whole file.
+pub fn dictzip(data: &[u8]) -> ExportResult<Vec<u8>> {
+    let mut chunks: Vec<&[u8]> = data.chunks(DICTZIP_CHUNK_LEN).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    if 10 + 2 * chunks.len() > u16::MAX as usize {
+        return Err("Dictionary too large for dictzip".into());
+    }
+
+    let mut compress = Compress::new(Compression::best(), false);
+    let mut deflated = Vec::with_capacity(chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        let flush = if i + 1 == chunks.len() {
+            FlushCompress::Finish
+        } else {
+            FlushCompress::Full
+        };
+        deflated.push(deflate_chunk(&mut compress, chunk, flush)?);
+    }
+
+    let field_len = 6 + 2 * deflated.len() as u16;
+    // ID1 ID2 CM=deflate FLG=FEXTRA MTIME=0 XFL=best OS=unix
+    let mut out = vec![0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 2, 3];
+    out.extend_from_slice(&(field_len + 4).to_le_bytes());
+    out.extend_from_slice(b"RA");
+    out.extend_from_slice(&field_len.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(DICTZIP_CHUNK_LEN as u16).to_le_bytes());
+    out.extend_from_slice(&(deflated.len() as u16).to_le_bytes());
+    for chunk in &deflated {
+        out.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+    }
+    for chunk in &deflated {
+        out.extend_from_slice(chunk);
+    }
+    out.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    Ok(out)
+}
+
+/// StarDict index order: ASCII case-insensitive, ties broken byte-wise.
+fn stardict_cmp(a: &str, b: &str) -> Ordering {
+    let fold = |s: &str| {
+        s.bytes()
+            .map(|b| b.to_ascii_lowercase())
+            .collect::<Vec<_>>()
+    };
+    fold(a).cmp(&fold(b)).then_with(|| a.cmp(b))
+}
+
+fn ifo_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// StarDict 2.4.2 dictionary (`.ifo`, `.idx`, `.dict.dz`), zipped.
+pub fn stardict(info: &DictionaryInfo, entries: &[DictionaryEntry]) -> ExportResult<Vec<u8>> {
+    let mut sorted: Vec<&DictionaryEntry> = entries
+        .iter()
+        .filter(|entry| entry.word.len() < 256)
+        .collect();
+    sorted.sort_by(|a, b| stardict_cmp(&a.word, &b.word));
+
+    let mut dict = Vec::new();
+    let mut idx = Vec::new();
+    for entry in &sorted {
+        let text = entry_text(entry);
+        idx.extend_from_slice(entry.word.as_bytes());
+        idx.push(0);
+        idx.extend_from_slice(&(dict.len() as u32).to_be_bytes());
+        idx.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        dict.extend_from_slice(text.as_bytes());
+    }
+
+    let ifo = format!(
+        "StarDict's dict ifo file\nversion=2.4.2\nbookname={}\nwordcount={}\nidxfilesize={}\n\
+         sametypesequence=m\nwebsite=https://lensisku.lojban.org\ndescription={}\ndate={}\n",
+        ifo_value(&info.title),
+        sorted.len(),
+        idx.len(),
+        ifo_value(&format!(
+            "{} ({} → {})",
+            info.title, info.source_tag, info.target_tag
+        )),
+        info.date.format("%Y.%m.%d"),
+    );
+
+    zip_files(
+        &[
+            (
+                format!("{}.ifo", info.basename),
+                ifo.into_bytes(),
+                CompressionMethod::Deflated,
+            ),
+            (
+                format!("{}.idx", info.basename),
+                idx,
+                CompressionMethod::Deflated,
+            ),
+            (
+                format!("{}.dict.dz", info.basename),
+                dictzip(&dict)?,
+                CompressionMethod::Stored,
+            ),
+        ],
+        info.date,
+    )
+}
+
+/// dictd's base64 number encoding: most significant digit first, no padding.
+fn dictd_b64(mut n: u64) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut digits = vec![ALPHABET[(n % 64) as usize]];
+    n /= 64;
+    while n > 0 {
+        digits.push(ALPHABET[(n % 64) as usize]);
+        n /= 64;
+    }
+    digits.iter().rev().map(|&d| d as char).collect()
+}
+
+/// dictd database (`.index`, `.dict.dz`), zipped. The headers mark it as UTF-8 and
+/// all-chars so apostrophes and dots in valsi stay searchable.
+pub fn dictd(info: &DictionaryInfo, entries: &[DictionaryEntry]) -> ExportResult<Vec<u8>> {
+    let headers = [
+        ("00-database-allchars", String::new()),
+        ("00-database-utf8", String::new()),
+        ("00-database-short", format!("{}\n", info.title)),
+        (
+            "00-database-url",
+            "https://lensisku.lojban.org\n".to_string(),
+        ),
+        (
+            "00-database-info",
+            format!(
+                "{} ({} → {}), updated {}\n",
+                info.title,
+                info.source_tag,
+                info.target_tag,
+                info.date.format("%Y-%m-%d")
+            ),
+        ),
+    ];
+
+    let mut records: Vec<(&str, String)> = headers
+        .iter()
+        .map(|(word, text)| (*word, format!("{}\n{}", word, text)))
+        .collect();
+    records.extend(
+        entries
+            .iter()
+            .map(|entry| (entry.word.as_str(), entry_text(entry))),
+    );
+    records.sort_by(|(a, _), (b, _)| a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)));
+
+    let mut dict = Vec::new();
+    let mut index = String::new();
+    for (word, text) in &records {
+        index.push_str(&format!(
+            "{}\t{}\t{}\n",
+            word,
+            dictd_b64(dict.len() as u64),
+            dictd_b64(text.len() as u64)
+        ));
+        dict.extend_from_slice(text.as_bytes());
+    }
+
+    zip_files(
+        &[
+            (
+                format!("{}.index", info.basename),
+                index.into_bytes(),
+                CompressionMethod::Deflated,
+            ),
+            (
+                format!("{}.dict.dz", info.basename),
+                dictzip(&dict)?,
+                CompressionMethod::Stored,
+            ),
+        ],
+        info.date,
+    )
+}
+
+fn html_paragraphs(text: &str) -> String {
+    math::to_unicode_lossy(text)
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>", escape_xml(p).replace('\n', "<br/>")))
+        .collect()
+}
+
+/// One Kindle lookup entry. Only the headword is indexed (`idx:orth` without
+/// `idx:infl`), since valsi don't inflect.
+fn epub_entry(entry: &DictionaryEntry) -> String {
+    let word = escape_xml(&entry.word);
+    let mut html = format!(
+        "<idx:entry name=\"default\" scriptable=\"yes\" spell=\"yes\">\n\
+         <idx:orth value=\"{}\"><b>{}</b></idx:orth> <i>{}</i>",
+        word,
+        word,
+        escape_xml(&entry.word_type)
+    );
+    if let Some(rafsi) = entry.rafsi.as_deref().filter(|r| !r.trim().is_empty()) {
+        html.push_str(&format!(
+            " <span class=\"rafsi\">-{}-</span>",
+            escape_xml(rafsi.trim())
+        ));
+    }
+    if let Some(selmaho) = entry.selmaho.as_deref().filter(|s| !s.trim().is_empty()) {
+        html.push_str(&format!(
+            " <span class=\"selmaho\">{}</span>",
+            escape_xml(selmaho)
+        ));
+    }
+    html.push_str(&html_paragraphs(&entry.definition));
+    for extra in [&entry.notes, &entry.collection_note].into_iter().flatten() {
+        html.push_str(&html_paragraphs(extra));
+    }
+    html.push_str("\n</idx:entry>\n<hr/>\n");
+    html
+}
+
+fn section_key(word: &str) -> char {
+    word.chars()
+        .next()
+        .filter(|c| c.is_alphabetic())
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .unwrap_or('#')
+}
+
+fn xhtml_page(title: &str, lang: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+         xmlns:idx=\"https://kindlegen.s3.amazonaws.com/AmazonKindlePublishingGuidelines.pdf\" \
+         xml:lang=\"{lang}\" lang=\"{lang}\">\n\
+         <head><meta charset=\"UTF-8\"/><title>{title}</title></head>\n\
+         <body>\n{body}</body>\n</html>\n",
+        lang = escape_xml(lang),
+        title = escape_xml(title),
+        body = body
+    )
+}
+
+/// EPUB 3 dictionary with Kindle `idx:` lookup markup; Kindle Previewer converts it to
+/// MOBI/KF8, and EPUB readers use the `dictionary` type and language metadata.
+pub fn epub(info: &DictionaryInfo, entries: &[DictionaryEntry]) -> ExportResult<Vec<u8>> {
+    let mut sections: BTreeMap<char, String> = BTreeMap::new();
+    for entry in entries {
+        sections
+            .entry(section_key(&entry.word))
+            .or_default()
+            .push_str(&epub_entry(entry));
+    }
+
+    let mut files = vec![
+        (
+            "mimetype".to_string(),
+            b"application/epub+zip".to_vec(),
+            CompressionMethod::Stored,
+        ),
+        (
+            "META-INF/container.xml".to_string(),
+            b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+              <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+              <rootfiles><rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles>\n\
+              </container>\n"
+                .to_vec(),
+            CompressionMethod::Deflated,
+        ),
+    ];
+
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+    );
+    let mut spine = String::new();
+    let mut toc = String::new();
+    for (i, (key, body)) in sections.iter().enumerate() {
+        let href = format!("section-{}.xhtml", i + 1);
+        let heading = key.to_uppercase().collect::<String>();
+        manifest.push_str(&format!(
+            "<item id=\"s{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            i + 1,
+            href
+        ));
+        spine.push_str(&format!("<itemref idref=\"s{}\"/>\n", i + 1));
+        toc.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            href,
+            escape_xml(&heading)
+        ));
+        files.push((
+            format!("OEBPS/{}", href),
+            xhtml_page(
+                &heading,
+                &info.source_tag,
+                &format!("<h1>{}</h1>\n{}", escape_xml(&heading), body),
+            )
+            .into_bytes(),
+            CompressionMethod::Deflated,
+        ));
+    }
+
+    let nav = xhtml_page(
+        &info.title,
+        &info.target_tag,
+        &format!(
+            "<nav epub:type=\"toc\"><h1>{}</h1>\n<ol>\n{}</ol></nav>\n",
+            escape_xml(&info.title),
+            toc
+        ),
+    );
+    files.push((
+        "OEBPS/nav.xhtml".to_string(),
+        nav.into_bytes(),
+        CompressionMethod::Deflated,
+    ));
+
+    let opf = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"uid\">\n\
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+         <dc:identifier id=\"uid\">urn:lensisku:{basename}</dc:identifier>\n\
+         <dc:title>{title}</dc:title>\n\
+         <dc:language>{source}</dc:language>\n\
+         <dc:language>{target}</dc:language>\n\
+         <dc:type>dictionary</dc:type>\n\
+         <meta property=\"dcterms:modified\">{modified}</meta>\n\
+         <meta property=\"source-language\">{source}</meta>\n\
+         <meta property=\"target-language\">{target}</meta>\n\
+         <x-metadata>\n\
+         <DictionaryInLanguage>{source}</DictionaryInLanguage>\n\
+         <DictionaryOutLanguage>{target}</DictionaryOutLanguage>\n\
+         <DefaultLookupIndex>default</DefaultLookupIndex>\n\
+         </x-metadata>\n\
+         </metadata>\n\
+         <manifest>\n{manifest}</manifest>\n\
+         <spine>\n{spine}</spine>\n\
+         </package>\n",
+        basename = escape_xml(&info.basename),
+        title = escape_xml(&info.title),
+        source = escape_xml(&info.source_tag),
+        target = escape_xml(&info.target_tag),
+        modified = info.date.format("%Y-%m-%dT00:00:00Z"),
+        manifest = manifest,
+        spine = spine
+    );
+    files.push((
+        "OEBPS/content.opf".to_string(),
+        opf.into_bytes(),
+        CompressionMethod::Deflated,
+    ));
+
+    zip_files(&files, info.date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn dictzip_is_valid_gzip() {
+        let data: Vec<u8> = (0..200_000u32)
+            .flat_map(|i| (i % 251).to_le_bytes())
+            .collect();
+        let compressed = dictzip(&data).unwrap();
+        assert_eq!(&compressed[12..14], b"RA");
+
+        let mut inflated = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, data);
+    }
+
+    #[test]
+    fn dictd_numbers_and_stardict_order() {
+        assert_eq!(dictd_b64(0), "A");
+        assert_eq!(dictd_b64(63), "/");
+        assert_eq!(dictd_b64(64), "BA");
+        assert_eq!(stardict_cmp("Bangu", "bangu"), Ordering::Less);
+        assert_eq!(stardict_cmp("bangu", "Cadzu"), Ordering::Less);
+    }
+}
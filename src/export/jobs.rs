@@ -0,0 +1,413 @@
+//! Export jobs: exports queued by users and generated in the background, so large
+//! formats (PDF above all) don't have to finish within an HTTP request. Job state lives
+//! in `export_jobs`; finished files are written to `EXPORT_CACHE_DIR/jobs`.
+
+use actix_web_lab::sse;
+use deadpool_postgres::Pool;
+use futures::{future, Stream, StreamExt};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::error::Error;
+use std::io::BufWriter;
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
+
+use super::cache::{self, HashingWriter};
+use super::models::{
+    CachedExportFile, CreateExportJobRequest, ExportFormat, ExportJob, ExportOptions,
+};
+use super::service;
+
+/// Exports generated at once, across all users (`EXPORT_JOB_CONCURRENCY`, default 2)
+static EXPORT_SLOTS: Lazy<Semaphore> = Lazy::new(|| {
+    Semaphore::new(
+        std::env::var("EXPORT_JOB_CONCURRENCY")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|&slots| slots > 0)
+            .unwrap_or(2),
+    )
+});
+
+/// Queued and running jobs a single user may have
+const MAX_ACTIVE_JOBS_PER_USER: i64 = 3;
+
+/// Finished jobs and their files are removed after this many days
+const JOB_RETENTION_DAYS: i32 = 7;
+
+const EXPORT_JOB_COLUMNS: &str = "job_id, language_tag, format, status, progress, stage,
+        error, filename, size_bytes, checksum, created_at, started_at, finished_at";
+
+fn export_job_from_row(row: &tokio_postgres::Row) -> ExportJob {
+    ExportJob {
+        job_id: row.get("job_id"),
+        language_tag: row.get("language_tag"),
+        format: row.get("format"),
+        status: row.get("status"),
+        progress: row.get("progress"),
+        stage: row.get("stage"),
+        error: row.get("error"),
+        filename: row.get("filename"),
+        size_bytes: row.get("size_bytes"),
+        checksum: row.get("checksum"),
+        created_at: row.get("created_at"),
+        started_at: row.get("started_at"),
+        finished_at: row.get("finished_at"),
+    }
+}
+
+/// Validates the request, records the job and starts it in the background. The job
+/// waits for a free export slot before it runs.
+pub async fn create_export_job(
+    pool: &Pool,
+    user_id: i32,
+    request: &CreateExportJobRequest,
+) -> Result<ExportJob, Box<dyn Error + Send + Sync>> {
+    let format: ExportFormat = request.options.format.as_deref().unwrap_or("pdf").parse()?;
+    let options = ExportOptions {
+        format: Some(format.to_string()),
+        ..request.options.clone()
+    };
+    options.pdf_layout()?;
+    service::check_export_access(pool, &request.lang, &options, Some(user_id)).await?;
+
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    // Serializes concurrent requests of the same user between the count and the insert
+    transaction
+        .execute(
+            "SELECT 1 FROM users WHERE userid = $1 FOR UPDATE",
+            &[&user_id],
+        )
+        .await?;
+    let active: i64 = transaction
+        .query_one(
+            "SELECT COUNT(*) FROM export_jobs
+             WHERE user_id = $1 AND status IN ('queued', 'running')",
+            &[&user_id],
+        )
+        .await?
+        .get(0);
+    if active >= MAX_ACTIVE_JOBS_PER_USER {
+        return Err("Too many export jobs".into());
+    }
+
+    let row = transaction
+        .query_one(
+            &format!(
+                "INSERT INTO export_jobs (user_id, language_tag, format, options)
+                 VALUES ($1, $2, $3, $4)
+                 RETURNING {}",
+                EXPORT_JOB_COLUMNS
+            ),
+            &[
+                &user_id,
+                &request.lang,
+                &format.to_string(),
+                &serde_json::to_value(&options)?,
+            ],
+        )
+        .await?;
+    transaction.commit().await?;
+    let job = export_job_from_row(&row);
+
+    let pool = pool.clone();
+    let (job_id, lang) = (job.job_id, request.lang.clone());
+    tokio::spawn(async move {
+        run_export_job(&pool, job_id, &lang, format, &options).await;
+    });
+
+    Ok(job)
+}
+
+async fn run_export_job(
+    pool: &Pool,
+    job_id: i32,
+    lang: &str,
+    format: ExportFormat,
+    options: &ExportOptions,
+) {
+    let result = match EXPORT_SLOTS.acquire().await {
+        Ok(_slot) => generate_job_file(pool, job_id, lang, format, options).await,
+        Err(e) => Err(e.into()),
+    };
+
+    if let Err(e) = finish_export_job(pool, job_id, result).await {
+        warn!(
+            "Failed to record the outcome of export job {}: {}",
+            job_id, e
+        );
+    }
+}
+
+async fn finish_export_job(
+    pool: &Pool,
+    job_id: i32,
+    result: Result<(CachedExportFile, u64), Box<dyn Error + Send + Sync>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    match result {
+        Ok((file, size)) => {
+            info!("Export job {} finished: {}", job_id, file.filename);
+            client
+                .execute(
+                    "UPDATE export_jobs
+                     SET status = 'completed', progress = 100, stage = NULL,
+                         file_path = $2, filename = $3, content_type = $4,
+                         size_bytes = $5, checksum = $6, finished_at = NOW()
+                     WHERE job_id = $1",
+                    &[
+                        &job_id,
+                        &file.path.to_string_lossy().into_owned(),
+                        &file.filename,
+                        &file.content_type,
+                        &(size as i64),
+                        &file.checksum,
+                    ],
+                )
+                .await?;
+        }
+        Err(e) => {
+            warn!("Export job {} failed: {}", job_id, e);
+            // xelatex failures carry the whole log; the first line says what went wrong
+            let message = e.to_string();
+            let error = message.lines().next().unwrap_or("Export failed");
+            client
+                .execute(
+                    "UPDATE export_jobs
+                     SET status = 'failed', error = $2, finished_at = NOW()
+                     WHERE job_id = $1",
+                    &[&job_id, &error],
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs the export into `EXPORT_CACHE_DIR/jobs`, recording progress on the job row
+/// as the export reports it.
+async fn generate_job_file(
+    pool: &Pool,
+    job_id: i32,
+    lang: &str,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<(CachedExportFile, u64), Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "UPDATE export_jobs
+             SET status = 'running', stage = 'Starting', started_at = NOW()
+             WHERE job_id = $1",
+            &[&job_id],
+        )
+        .await?;
+
+    let dir = cache::cache_dir().join("jobs");
+    std::fs::create_dir_all(&dir)?;
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<(i16, String)>();
+
+    let export = async move {
+        let report = move |progress: i16, stage: &str| {
+            let _ = progress_tx.send((progress, stage.to_string()));
+        };
+        let mut temp_file = tempfile::NamedTempFile::new_in(&dir)?;
+        let mut out = HashingWriter::new(BufWriter::new(temp_file.as_file_mut()));
+        let (content_type, filename) = service::write_export(
+            pool,
+            lang,
+            format,
+            options,
+            options.collection_id,
+            &mut out,
+            &report,
+        )
+        .await?;
+        let (buffered, size, checksum) = out.finish();
+        buffered
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+
+        let path = dir.join(format!("{}-{}", job_id, filename));
+        temp_file.persist(&path)?;
+        Ok::<_, Box<dyn Error + Send + Sync>>((
+            CachedExportFile {
+                path,
+                content_type,
+                filename,
+                checksum,
+            },
+            size,
+        ))
+    };
+
+    // Ends once the export drops its sender
+    let record_progress = async {
+        while let Some((progress, stage)) = progress_rx.recv().await {
+            if let Err(e) = client
+                .execute(
+                    "UPDATE export_jobs SET progress = $2, stage = $3 WHERE job_id = $1",
+                    &[&job_id, &progress, &stage],
+                )
+                .await
+            {
+                warn!("Failed to record progress of export job {}: {}", job_id, e);
+            }
+        }
+    };
+
+    let (result, ()) = tokio::join!(export, record_progress);
+    result
+}
+
+pub async fn get_export_job(
+    pool: &Pool,
+    job_id: i32,
+    user_id: i32,
+) -> Result<ExportJob, Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    client
+        .query_opt(
+            &format!(
+                "SELECT {} FROM export_jobs WHERE job_id = $1 AND user_id = $2",
+                EXPORT_JOB_COLUMNS
+            ),
+            &[&job_id, &user_id],
+        )
+        .await?
+        .as_ref()
+        .map(export_job_from_row)
+        .ok_or_else(|| "Export job not found".into())
+}
+
+pub async fn list_export_jobs(
+    pool: &Pool,
+    user_id: i32,
+) -> Result<Vec<ExportJob>, Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!(
+                "SELECT {} FROM export_jobs WHERE user_id = $1 ORDER BY created_at DESC",
+                EXPORT_JOB_COLUMNS
+            ),
+            &[&user_id],
+        )
+        .await?;
+    Ok(rows.iter().map(export_job_from_row).collect())
+}
+
+/// The file of a completed job.
+pub async fn get_export_job_file(
+    pool: &Pool,
+    job_id: i32,
+    user_id: i32,
+) -> Result<CachedExportFile, Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            "SELECT status, file_path, content_type, filename, checksum
+             FROM export_jobs WHERE job_id = $1 AND user_id = $2",
+            &[&job_id, &user_id],
+        )
+        .await?
+        .ok_or("Export job not found")?;
+    if row.get::<_, String>("status") != "completed" {
+        return Err("Export job not finished".into());
+    }
+
+    let file = CachedExportFile {
+        path: row.get::<_, String>("file_path").into(),
+        content_type: row.get("content_type"),
+        filename: row.get("filename"),
+        checksum: row.get("checksum"),
+    };
+    if !file.path.is_file() {
+        return Err("Export job not found".into());
+    }
+    Ok(file)
+}
+
+/// Server-sent events for a job: its state whenever status, progress or stage change,
+/// named after the status. The stream ends after the job completes or fails.
+pub fn export_job_events(
+    pool: Pool,
+    job: ExportJob,
+    user_id: i32,
+) -> impl Stream<Item = sse::Event> {
+    let job_id = job.job_id;
+    let updates = futures::stream::unfold(job.clone(), move |previous| {
+        let pool = pool.clone();
+        async move {
+            if previous.is_finished() {
+                return None;
+            }
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let job = match get_export_job(&pool, job_id, user_id).await {
+                    Ok(job) => job,
+                    Err(e) => {
+                        warn!("Failed to poll export job {}: {}", job_id, e);
+                        return None;
+                    }
+                };
+                if (&job.status, job.progress, &job.stage)
+                    != (&previous.status, previous.progress, &previous.stage)
+                {
+                    return Some((job.clone(), job));
+                }
+            }
+        }
+    });
+
+    futures::stream::once(future::ready(job))
+        .chain(updates)
+        .filter_map(|job| {
+            future::ready(
+                sse::Data::new_json(&job)
+                    .ok()
+                    .map(|data| data.event(job.status.clone()).into()),
+            )
+        })
+}
+
+/// Jobs still queued or running at startup were cut short by a restart.
+pub async fn fail_interrupted_export_jobs(
+    pool: &Pool,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    Ok(client
+        .execute(
+            "UPDATE export_jobs
+             SET status = 'failed', error = 'Interrupted by server restart', finished_at = NOW()
+             WHERE status IN ('queued', 'running')",
+            &[],
+        )
+        .await?)
+}
+
+/// Deletes finished jobs past the retention period together with their files.
+pub async fn remove_expired_export_jobs(pool: &Pool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "DELETE FROM export_jobs
+             WHERE finished_at < NOW() - make_interval(days => $1)
+             RETURNING file_path",
+            &[&JOB_RETENTION_DAYS],
+        )
+        .await?;
+
+    for path in rows
+        .iter()
+        .filter_map(|row| row.get::<_, Option<String>>(0))
+    {
+        if let Err(e) = std::fs::remove_file(&path) {
+            warn!("Failed to remove expired export file {}: {}", path, e);
+        }
+    }
+    info!("Removed {} expired export jobs", rows.len());
+    Ok(())
+}
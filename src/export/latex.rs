@@ -0,0 +1,244 @@
+//! Navigation aids of the PDF/LaTeX dictionary: hyperlinked `{word}` cross-references,
+//! thumb index tabs and the rafsi and selma'o index chapters.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::service::escape_all;
+
+/// Thumb tabs are only drawn when a chapter has at most this many initials; scripts
+/// with thousands of them (CJK) get none.
+const MAX_THUMB_TABS: usize = 30;
+
+/// Clears the thumb tab, e.g. at the start of a chapter.
+pub const CLEAR_THUMB: &str = "\\thumb{}{0}{1}";
+
+/// Macros behind the thumb tabs. `\thumb{letter}{slot}{slots}` puts a mark on the page;
+/// marks (unlike macros) follow where entries end up, and xelatex runs only once, so the
+/// headers draw the page's last tab relative to themselves instead of with absolute
+/// positioning. Tabs sit at the outer page edge, one slot per initial down the page.
+pub const THUMB_INDEX_PREAMBLE: &str = r#"
+\usepackage{xcolor}
+\newmarks\thumbmarks
+\newlength{\thumbheight}
+\newlength{\thumbraise}
+\newcommand{\thumb}[3]{\marks\thumbmarks{\noexpand\thumbdata{#1}{#2}{#3}}}
+\newcommand{\thumbbox}[3]{%
+  \setlength{\thumbheight}{\dimexpr(\paperheight-40mm)/#3\relax}%
+  \setlength{\thumbraise}{\dimexpr 1in+\topmargin+\headheight-20mm-\thumbheight*(#2+1)\relax}%
+  \raisebox{\thumbraise}{\makebox[7mm]{\textcolor{black!75}{\rule{7mm}{\thumbheight}}%
+    \hspace{-7mm}\makebox[7mm]{\raisebox{\dimexpr.5\thumbheight-.5ex\relax}{%
+      \color{white}\sffamily\bfseries #1}}}}}
+\newcommand{\thumbtableft}{% at the start of even page headers
+  \def\thumbdata##1##2##3{\ifx&##1&\else
+    \llap{\thumbbox{##1}{##2}{##3}\hspace{\dimexpr 1in+\evensidemargin-7mm\relax}}\fi}%
+  \botmarks\thumbmarks}
+\newcommand{\thumbtabright}{% at the end of odd page headers
+  \def\thumbdata##1##2##3{\ifx&##1&\else
+    \rlap{\hspace{\dimexpr\paperwidth-1in-\oddsidemargin-\textwidth-7mm\relax}%
+      \thumbbox{##1}{##2}{##3}}\fi}%
+  \botmarks\thumbmarks}"#;
+
+/// Hyperlink target of a valsi entry. Words are hex-encoded since they may contain
+/// characters hyperref can't use in names.
+fn anchor(word: &str) -> String {
+    format!("valsi:{}", hex::encode(word.as_bytes()))
+}
+
+pub fn hypertarget(word: &str) -> String {
+    format!("\\hypertarget{{{}}}{{}}", anchor(word))
+}
+
+pub fn hyperlink(word: &str, escaped_word: &str) -> String {
+    format!("\\hyperlink{{{}}}{{{}}}", anchor(word), escaped_word)
+}
+
+/// Escapes `text` with `escape` and turns `{word}` references to words in `targets`
+/// into hyperlinks. Math (`$…$`) and references to other words are escaped as before.
+pub fn link_references(
+    text: &str,
+    targets: &HashSet<String>,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let bytes = text.as_bytes();
+    let mut result = String::with_capacity(text.len());
+    let mut plain_start = 0;
+    let mut in_math = false;
+    let mut i = 0;
+
+    // `$`, `{` and `}` are ASCII, so byte offsets always fall on char boundaries
+    while i < bytes.len() {
+        match bytes[i] {
+            b'$' => in_math = !in_math,
+            b'{' if !in_math => {
+                if let Some(length) = text[i + 1..].find(['{', '}', '$']) {
+                    let end = i + 1 + length;
+                    let word = &text[i + 1..end];
+                    if bytes[end] == b'}' && targets.contains(word) {
+                        result.push_str(&escape(&text[plain_start..i]));
+                        result.push_str(&hyperlink(word, &escape(word)));
+                        plain_start = end + 1;
+                        i = plain_start;
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    result.push_str(&escape(&text[plain_start..]));
+    result
+}
+
+/// First letter or digit of a word, lowercased.
+fn initial(word: &str) -> Option<char> {
+    word.chars()
+        .find(|c| c.is_alphanumeric())
+        .and_then(|c| c.to_lowercase().next())
+}
+
+/// Thumb tabs of a chapter, one slot per initial in order of appearance.
+pub struct ThumbIndex {
+    slots: HashMap<char, usize>,
+    current: Option<char>,
+}
+
+impl ThumbIndex {
+    pub fn new<'a>(words: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut slots = HashMap::new();
+        for initial in words.into_iter().filter_map(initial) {
+            let next = slots.len();
+            slots.entry(initial).or_insert(next);
+        }
+        if slots.len() > MAX_THUMB_TABS {
+            slots.clear();
+        }
+        ThumbIndex {
+            slots,
+            current: None,
+        }
+    }
+
+    /// Switches the tab when `word` starts a new initial.
+    pub fn mark(&mut self, word: &str) -> String {
+        let Some(initial) = initial(word) else {
+            return String::new();
+        };
+        match self.slots.get(&initial) {
+            Some(&slot) if self.current != Some(initial) => {
+                self.current = Some(initial);
+                format!(
+                    "\\thumb{{{}}}{{{}}}{{{}}}",
+                    initial.to_uppercase(),
+                    slot,
+                    self.slots.len()
+                )
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// A word with the rafsi and selma'o shown in the index chapters.
+pub struct IndexEntry {
+    pub word: String,
+    pub rafsi: Option<String>,
+    pub selmaho: Option<String>,
+}
+
+fn index_chapter(title: &str, lines: impl Iterator<Item = String>) -> String {
+    let mut chapter = format!(
+        "\n\\chapter{{{}}}{}\\markboth{{{}}}{{{}}}",
+        title, CLEAR_THUMB, title, title
+    );
+    for line in lines {
+        chapter.push_str("\n\n\\noindent ");
+        chapter.push_str(&line);
+    }
+    chapter
+}
+
+/// Every rafsi with the word it belongs to, sorted by rafsi; empty without rafsi.
+pub fn rafsi_index(entries: &[IndexEntry]) -> String {
+    let mut rafsi: Vec<(&str, &str)> = entries
+        .iter()
+        .flat_map(|entry| {
+            entry
+                .rafsi
+                .iter()
+                .flat_map(|rafsi| rafsi.split_whitespace())
+                .map(|rafsi| (rafsi, entry.word.as_str()))
+        })
+        .collect();
+    if rafsi.is_empty() {
+        return String::new();
+    }
+    rafsi.sort();
+
+    index_chapter(
+        "lo rafsi",
+        rafsi.into_iter().map(|(rafsi, word)| {
+            format!(
+                "{{\\ttfamily\\bfseries {}}}\\enspace {}",
+                escape_all(rafsi),
+                hyperlink(word, &escape_all(word))
+            )
+        }),
+    )
+}
+
+/// The words of every selma'o, sorted by selma'o; empty without selma'o.
+pub fn selmaho_index(entries: &[IndexEntry]) -> String {
+    let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for entry in entries {
+        if let Some(selmaho) = entry.selmaho.as_deref().map(str::trim) {
+            if !selmaho.is_empty() {
+                groups.entry(selmaho).or_default().push(&entry.word);
+            }
+        }
+    }
+    if groups.is_empty() {
+        return String::new();
+    }
+
+    index_chapter(
+        "lo selma'o",
+        groups.into_iter().map(|(selmaho, words)| {
+            let links: Vec<String> = words
+                .into_iter()
+                .map(|word| hyperlink(word, &escape_all(word)))
+                .collect();
+            format!(
+                "{{\\sffamily\\bfseries {}}}\\enspace {}",
+                escape_all(selmaho),
+                links.join(", ")
+            )
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_known_references_outside_math() {
+        let targets: HashSet<String> = ["klama".to_string()].into();
+        let linked = link_references("$x_{1}$ {klama} to {unknown}", &targets, |s: &str| {
+            s.replace('_', "\\_")
+        });
+        assert_eq!(
+            linked,
+            format!("$x\\_{{1}}$ {} to {{unknown}}", hyperlink("klama", "klama"))
+        );
+    }
+
+    #[test]
+    fn thumb_tabs_follow_initials() {
+        let words = [".alis.", "alga", "bangu"];
+        let mut thumbs = ThumbIndex::new(words);
+        let marks: Vec<String> = words.iter().map(|word| thumbs.mark(word)).collect();
+        assert_eq!(marks, ["\\thumb{A}{0}{2}", "", "\\thumb{B}{1}{2}"]);
+    }
+}
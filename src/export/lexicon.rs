@@ -0,0 +1,450 @@
+//! Exports for linking the dictionary with other lexical resources: TEI Lex-0 XML and
+//! OntoLex-Lemon RDF as Turtle or JSON-LD.
+//!
+//! Both are built from the same [`DictionaryEntry`] rows as the JSON export, with LaTeX
+//! math rendered as Unicode. Valsi become entries with their word as the lemma, rafsi
+//! become variant forms, word types and selma'o grammatical information, definitions
+//! and notes a sense, and gloss and place keywords translation equivalents in the
+//! definition language. The RDF graph is built once as JSON-LD and written as Turtle
+//! from there, so both serializations carry the same triples.
+
+use std::error::Error;
+
+use serde_json::{json, Map, Value};
+use url::Url;
+
+use super::models::{DictionaryEntry, DictionaryInfo};
+use crate::language::math;
+use crate::utils::escape_xml;
+
+type ExportResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+fn text(value: &str) -> String {
+    math::to_unicode_lossy(value).trim().to_string()
+}
+
+fn rafsi(entry: &DictionaryEntry) -> Vec<&str> {
+    entry
+        .rafsi
+        .as_deref()
+        .map(|rafsi| rafsi.split_whitespace().collect())
+        .unwrap_or_default()
+}
+
+fn selmaho(entry: &DictionaryEntry) -> Option<&str> {
+    entry
+        .selmaho
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+/// Notes and collection notes, in that order.
+fn notes(entry: &DictionaryEntry) -> impl Iterator<Item = String> + '_ {
+    [&entry.notes, &entry.collection_note]
+        .into_iter()
+        .flatten()
+        .map(|note| text(note))
+        .filter(|note| !note.is_empty())
+}
+
+/// Gloss keywords followed by place keywords, each with its kind.
+fn keywords(entry: &DictionaryEntry) -> impl Iterator<Item = (&'static str, &str, Option<&str>)> {
+    [
+        ("gloss", &entry.gloss_keywords),
+        ("place", &entry.place_keywords),
+    ]
+    .into_iter()
+    .flat_map(|(kind, keywords)| {
+        keywords
+            .iter()
+            .flatten()
+            .map(move |keyword| (kind, keyword.word.as_str(), keyword.meaning.as_deref()))
+    })
+}
+
+/// `xml:id` of an entry; definition IDs are unique across the export.
+fn entry_id(entry: &DictionaryEntry, index: usize) -> String {
+    match entry.definition_id {
+        Some(id) => format!("d{}", id),
+        None => format!("e{}", index + 1),
+    }
+}
+
+fn tei_entry(entry: &DictionaryEntry, index: usize, info: &DictionaryInfo) -> String {
+    let id = entry_id(entry, index);
+    let mut xml = format!(
+        "<entry xml:id=\"{}\" xml:lang=\"{}\" type=\"mainEntry\">\n\
+         <form type=\"lemma\"><orth>{}</orth></form>\n",
+        id,
+        escape_xml(&info.source_tag),
+        escape_xml(&entry.word)
+    );
+    for rafsi in rafsi(entry) {
+        xml.push_str(&format!(
+            "<form type=\"variant\"><orth type=\"rafsi\">{}</orth></form>\n",
+            escape_xml(rafsi)
+        ));
+    }
+    xml.push_str(&format!(
+        "<gramGrp><gram type=\"pos\">{}</gram>",
+        escape_xml(&entry.word_type)
+    ));
+    if let Some(selmaho) = selmaho(entry) {
+        xml.push_str(&format!(
+            "<gram type=\"selmaho\">{}</gram>",
+            escape_xml(selmaho)
+        ));
+    }
+    xml.push_str("</gramGrp>\n");
+
+    xml.push_str(&format!(
+        "<sense xml:id=\"{}.1\">\n<def xml:lang=\"{}\">{}</def>\n",
+        id,
+        escape_xml(&info.target_tag),
+        escape_xml(&text(&entry.definition))
+    ));
+    for note in notes(entry) {
+        xml.push_str(&format!("<note>{}</note>\n", escape_xml(&note)));
+    }
+    for (kind, word, meaning) in keywords(entry) {
+        xml.push_str(&format!(
+            "<cit type=\"translationEquivalent\" subtype=\"{}\" xml:lang=\"{}\"><quote>{}</quote>",
+            kind,
+            escape_xml(&info.target_tag),
+            escape_xml(word)
+        ));
+        if let Some(meaning) = meaning {
+            xml.push_str(&format!("<usg type=\"hint\">{}</usg>", escape_xml(meaning)));
+        }
+        xml.push_str("</cit>\n");
+    }
+    xml.push_str("</sense>\n");
+
+    if let Some(etymology) = entry.etymology.as_deref().map(text) {
+        if !etymology.is_empty() {
+            xml.push_str(&format!("<etym>{}</etym>\n", escape_xml(&etymology)));
+        }
+    }
+    xml.push_str("</entry>\n");
+    xml
+}
+
+/// TEI Lex-0 dictionary: one `<entry>` per valsi with a single sense.
+pub fn tei_lex0(info: &DictionaryInfo, entries: &[DictionaryEntry]) -> ExportResult<Vec<u8>> {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <TEI xmlns=\"http://www.tei-c.org/ns/1.0\" xml:lang=\"{}\">\n\
+         <teiHeader>\n<fileDesc>\n\
+         <titleStmt><title>{}</title></titleStmt>\n\
+         <publicationStmt><publisher>jbovlaste</publisher><date when=\"{}\"/></publicationStmt>\n\
+         <sourceDesc><p>Exported from jbovlaste</p></sourceDesc>\n\
+         </fileDesc>\n</teiHeader>\n<text>\n<body>\n",
+        escape_xml(&info.target_tag),
+        escape_xml(&info.title),
+        info.date.format("%Y-%m-%d")
+    );
+    for (index, entry) in entries.iter().enumerate() {
+        xml.push_str(&tei_entry(entry, index, info));
+    }
+    xml.push_str("</body>\n</text>\n</TEI>\n");
+    Ok(xml.into_bytes())
+}
+
+const ONTOLEX_PREFIXES: [(&str, &str); 7] = [
+    ("ontolex", "http://www.w3.org/ns/lemon/ontolex#"),
+    ("lime", "http://www.w3.org/ns/lemon/lime#"),
+    ("vartrans", "http://www.w3.org/ns/lemon/vartrans#"),
+    ("lexinfo", "http://www.lexinfo.net/ontology/3.0/lexinfo#"),
+    ("skos", "http://www.w3.org/2004/02/skos/core#"),
+    ("dct", "http://purl.org/dc/terms/"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+];
+
+/// Entries are identified by their page on the site, `FRONTEND_URL/valsi/{word}`.
+fn entry_iri(base_url: &Url, word: &str) -> String {
+    let mut iri = base_url.clone();
+    if let Ok(mut segments) = iri.path_segments_mut() {
+        segments.pop_if_empty().push("valsi").push(word);
+    }
+    iri.to_string()
+}
+
+fn literal(value: &str, lang: &str) -> Value {
+    json!({ "@value": value, "@language": lang })
+}
+
+fn form(written: &str, lang: &str) -> Value {
+    json!({ "@type": "ontolex:Form", "ontolex:writtenRep": literal(written, lang) })
+}
+
+fn ontolex_entry(entry: &DictionaryEntry, info: &DictionaryInfo, base_url: &Url) -> Value {
+    let source = info.source_tag.as_str();
+    let target = info.target_tag.as_str();
+    let entry_type = if entry.word.contains(' ') {
+        "ontolex:MultiwordExpression"
+    } else {
+        "ontolex:Word"
+    };
+
+    let mut node = Map::new();
+    node.insert("@id".into(), entry_iri(base_url, &entry.word).into());
+    node.insert("@type".into(), json!(["ontolex:LexicalEntry", entry_type]));
+    node.insert("rdfs:label".into(), literal(&entry.word, source));
+    node.insert("dct:type".into(), entry.word_type.clone().into());
+    node.insert("ontolex:canonicalForm".into(), form(&entry.word, source));
+    let affixes: Vec<Value> = rafsi(entry)
+        .into_iter()
+        .map(|affix| {
+            let mut affix_form = form(affix, source);
+            affix_form["dct:type"] = "rafsi".into();
+            affix_form
+        })
+        .collect();
+    if !affixes.is_empty() {
+        node.insert("ontolex:otherForm".into(), affixes.into());
+    }
+    if let Some(selmaho) = selmaho(entry) {
+        node.insert(
+            "lexinfo:partOfSpeech".into(),
+            json!({ "@type": "lexinfo:PartOfSpeech", "rdfs:label": selmaho }),
+        );
+    }
+    if let Some(etymology) = entry.etymology.as_deref().map(text) {
+        if !etymology.is_empty() {
+            node.insert("skos:historyNote".into(), literal(&etymology, target));
+        }
+    }
+
+    let mut sense = Map::new();
+    sense.insert("@type".into(), "ontolex:LexicalSense".into());
+    sense.insert(
+        "skos:definition".into(),
+        literal(&text(&entry.definition), target),
+    );
+    let sense_notes: Vec<Value> = notes(entry).map(|note| literal(&note, target)).collect();
+    if !sense_notes.is_empty() {
+        sense.insert("skos:note".into(), sense_notes.into());
+    }
+    node.insert("ontolex:sense".into(), sense.into());
+
+    let translations: Vec<Value> = keywords(entry)
+        .map(|(kind, word, meaning)| {
+            let mut translation = json!({
+                "@type": "ontolex:LexicalEntry",
+                "dct:type": format!("{} keyword", kind),
+                "ontolex:canonicalForm": form(word, target),
+            });
+            if let Some(meaning) = meaning {
+                translation["ontolex:sense"] = json!({
+                    "@type": "ontolex:LexicalSense",
+                    "skos:definition": literal(meaning, target),
+                });
+            }
+            translation
+        })
+        .collect();
+    if !translations.is_empty() {
+        node.insert("vartrans:translatableAs".into(), translations.into());
+    }
+    node.into()
+}
+
+/// The OntoLex-Lemon graph as a JSON-LD document: a `lime:Lexicon` and its entries.
+fn ontolex_graph(info: &DictionaryInfo, entries: &[DictionaryEntry], base_url: &Url) -> Value {
+    let entry_nodes: Vec<Value> = entries
+        .iter()
+        .map(|entry| ontolex_entry(entry, info, base_url))
+        .collect();
+    let lexicon = json!({
+        "@id": "_:lexicon",
+        "@type": "lime:Lexicon",
+        "dct:title": info.title,
+        "lime:language": info.source_tag,
+        "lime:entry": entry_nodes
+            .iter()
+            .map(|node| json!({ "@id": node["@id"] }))
+            .collect::<Vec<_>>(),
+    });
+
+    let context: Map<String, Value> = ONTOLEX_PREFIXES
+        .iter()
+        .map(|(prefix, iri)| (prefix.to_string(), Value::from(*iri)))
+        .collect();
+    json!({
+        "@context": context,
+        "@graph": std::iter::once(lexicon).chain(entry_nodes).collect::<Vec<_>>(),
+    })
+}
+
+pub fn ontolex_jsonld(
+    info: &DictionaryInfo,
+    entries: &[DictionaryEntry],
+    base_url: &Url,
+) -> ExportResult<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&ontolex_graph(
+        info, entries, base_url,
+    ))?)
+}
+
+fn turtle_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn turtle_iri(iri: &str) -> String {
+    if iri.starts_with("_:") {
+        return iri.to_string();
+    }
+    // Characters Turtle doesn't allow in IRIs that URL path encoding leaves alone
+    format!(
+        "<{}>",
+        iri.replace('\\', "%5C")
+            .replace('^', "%5E")
+            .replace('|', "%7C")
+    )
+}
+
+/// A JSON-LD value of [`ontolex_graph`] as a Turtle object: node references as IRIs,
+/// other nodes as blank node property lists.
+fn turtle_object(value: &Value, indent: &str) -> String {
+    match value {
+        Value::Object(node) if node.contains_key("@value") => {
+            let mut literal = turtle_string(node["@value"].as_str().unwrap_or_default());
+            if let Some(lang) = node.get("@language").and_then(Value::as_str) {
+                literal.push('@');
+                literal.push_str(lang);
+            }
+            literal
+        }
+        Value::Object(node) if node.len() == 1 && node.contains_key("@id") => {
+            turtle_iri(node["@id"].as_str().unwrap_or_default())
+        }
+        Value::Object(node) => {
+            let inner = format!("{}    ", indent);
+            format!(
+                "[\n{}{}\n{}]",
+                inner,
+                turtle_predicates(node, &inner),
+                indent
+            )
+        }
+        Value::String(value) => turtle_string(value),
+        other => turtle_string(&other.to_string()),
+    }
+}
+
+/// `predicate object, object ;` lines of a node; prefixed names are used as they are.
+fn turtle_predicates(node: &Map<String, Value>, indent: &str) -> String {
+    node.iter()
+        .filter(|(key, _)| key.as_str() != "@id")
+        .map(|(key, value)| {
+            let values: Vec<&Value> = match value {
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            let (predicate, objects): (&str, Vec<String>) = if key == "@type" {
+                (
+                    "a",
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .map(String::from)
+                        .collect(),
+                )
+            } else {
+                (
+                    key.as_str(),
+                    values.iter().map(|v| turtle_object(v, indent)).collect(),
+                )
+            };
+            format!("{} {}", predicate, objects.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(&format!(" ;\n{}", indent))
+}
+
+pub fn ontolex_turtle(
+    info: &DictionaryInfo,
+    entries: &[DictionaryEntry],
+    base_url: &Url,
+) -> ExportResult<Vec<u8>> {
+    let graph = ontolex_graph(info, entries, base_url);
+    let mut turtle: String = ONTOLEX_PREFIXES
+        .iter()
+        .map(|(prefix, iri)| format!("@prefix {}: <{}> .\n", prefix, iri))
+        .collect();
+
+    for node in graph["@graph"].as_array().into_iter().flatten() {
+        if let Value::Object(node) = node {
+            turtle.push_str(&format!(
+                "\n{}\n    {} .\n",
+                turtle_iri(node["@id"].as_str().unwrap_or_default()),
+                turtle_predicates(node, "    ")
+            ));
+        }
+    }
+    Ok(turtle.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jbovlaste::KeywordMapping;
+
+    fn klama() -> DictionaryEntry {
+        DictionaryEntry {
+            word: "klama".to_string(),
+            word_type: "gismu".to_string(),
+            rafsi: Some("kla".to_string()),
+            selmaho: None,
+            definition: "$x_{1}$ \"comes\" to $x_{2}$".to_string(),
+            definition_id: Some(7),
+            notes: None,
+            etymology: None,
+            jargon: None,
+            collection_note: None,
+            score: 1.0,
+            gloss_keywords: Some(vec![KeywordMapping {
+                word: "come".to_string(),
+                meaning: None,
+            }]),
+            place_keywords: None,
+            user: None,
+        }
+    }
+
+    #[test]
+    fn turtle_carries_the_jsonld_triples() {
+        let info = DictionaryInfo {
+            basename: "dictionary-en".to_string(),
+            title: "Lojban – English".to_string(),
+            source_tag: "jbo".to_string(),
+            target_tag: "en".to_string(),
+            date: Default::default(),
+        };
+        let base_url = Url::parse("https://example.com").unwrap();
+        let turtle =
+            String::from_utf8(ontolex_turtle(&info, &[klama()], &base_url).unwrap()).unwrap();
+
+        assert!(turtle.contains("lime:entry <https://example.com/valsi/klama>"));
+        assert!(turtle.contains(
+            "<https://example.com/valsi/klama>\n    a ontolex:LexicalEntry, ontolex:Word ;"
+        ));
+        assert!(turtle.contains("skos:definition \"x₁ \\\"comes\\\" to x₂\"@en"));
+        assert!(turtle.contains("ontolex:writtenRep \"kla\"@jbo"));
+        assert!(turtle.contains("ontolex:writtenRep \"come\"@en"));
+    }
+}
@@ -1,6 +1,12 @@
+mod cache;
 pub mod controller;
+pub mod dictfile;
+pub mod jobs;
+mod latex;
+mod lexicon;
 pub mod models;
 pub mod service;
+mod signing;
 
 use actix_web::web;
 use actix_web_httpauth::middleware::HttpAuthentication;
@@ -9,11 +15,23 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("export")
             .service(controller::download_cached_export)
+            .service(controller::download_export_signature)
+            .service(controller::export_manifest)
+            .service(controller::export_manifest_signature)
+            .service(controller::export_signing_key)
             .service(controller::list_cached_exports)
+            .service(controller::dictionary_changes)
+            .service(controller::list_daily_diffs)
+            .service(controller::download_daily_diff)
             .service(
                 web::scope("")
                     .wrap(HttpAuthentication::bearer(crate::auth::validator))
-                    .service(controller::export_dictionary),
+                    .service(controller::export_dictionary)
+                    .service(controller::create_export_job)
+                    .service(controller::list_export_jobs)
+                    .service(controller::get_export_job)
+                    .service(controller::export_job_events)
+                    .service(controller::download_export_job),
             ),
     );
 }
@@ -1,5 +1,6 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use utoipa::ToSchema;
 
 use crate::jbovlaste::KeywordMapping;
@@ -11,6 +12,18 @@ pub enum ExportFormat {
     Xml,
     Json,
     Tsv,
+    /// StarDict `.ifo`/`.idx`/`.dict.dz`, zipped
+    StarDict,
+    /// dictd `.index`/`.dict.dz`, zipped
+    Dictd,
+    /// EPUB 3 with Kindle lookup markup
+    Epub,
+    /// TEI Lex-0 XML
+    Tei,
+    /// OntoLex-Lemon RDF as Turtle
+    OntolexTurtle,
+    /// OntoLex-Lemon RDF as JSON-LD
+    OntolexJsonLd,
 }
 
 impl std::fmt::Display for ExportFormat {
@@ -21,6 +34,37 @@ impl std::fmt::Display for ExportFormat {
             ExportFormat::Xml => write!(f, "xml"),
             ExportFormat::Json => write!(f, "json"),
             ExportFormat::Tsv => write!(f, "tsv"),
+            ExportFormat::StarDict => write!(f, "stardict"),
+            ExportFormat::Dictd => write!(f, "dictd"),
+            ExportFormat::Epub => write!(f, "epub"),
+            ExportFormat::Tei => write!(f, "tei"),
+            ExportFormat::OntolexTurtle => write!(f, "ontolex-ttl"),
+            ExportFormat::OntolexJsonLd => write!(f, "ontolex-jsonld"),
+        }
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "pdf" => Ok(ExportFormat::Pdf),
+            "latex" | "tex" => Ok(ExportFormat::LaTeX),
+            "xml" => Ok(ExportFormat::Xml),
+            "json" => Ok(ExportFormat::Json),
+            "tsv" => Ok(ExportFormat::Tsv),
+            "stardict" => Ok(ExportFormat::StarDict),
+            "dictd" => Ok(ExportFormat::Dictd),
+            "epub" => Ok(ExportFormat::Epub),
+            "tei" => Ok(ExportFormat::Tei),
+            "ontolex-ttl" => Ok(ExportFormat::OntolexTurtle),
+            "ontolex-jsonld" => Ok(ExportFormat::OntolexJsonLd),
+            _ => Err(
+                "Invalid format. Supported formats: pdf, latex, xml, json, tsv, stardict, \
+                 dictd, epub, tei, ontolex-ttl, ontolex-jsonld"
+                    .to_string(),
+            ),
         }
     }
 }
@@ -32,7 +76,11 @@ impl ExportFormat {
             ExportFormat::LaTeX => "application/x-latex",
             ExportFormat::Xml => "application/xml",
             ExportFormat::Json => "application/json",
-            ExportFormat::Tsv => "application/zip",
+            ExportFormat::Tsv | ExportFormat::StarDict | ExportFormat::Dictd => "application/zip",
+            ExportFormat::Epub => "application/epub+zip",
+            ExportFormat::Tei => "application/tei+xml",
+            ExportFormat::OntolexTurtle => "text/turtle",
+            ExportFormat::OntolexJsonLd => "application/ld+json",
         }
     }
 
@@ -43,15 +91,204 @@ impl ExportFormat {
             ExportFormat::Xml => "xml",
             ExportFormat::Json => "json",
             ExportFormat::Tsv => "zip",
+            ExportFormat::StarDict => "stardict.zip",
+            ExportFormat::Dictd => "dictd.zip",
+            ExportFormat::Epub => "epub",
+            ExportFormat::Tei => "tei.xml",
+            ExportFormat::OntolexTurtle => "ttl",
+            ExportFormat::OntolexJsonLd => "jsonld",
         }
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 pub struct ExportOptions {
+    /// Export format (pdf, latex, xml, json, tsv, stardict, dictd, epub, tei, ontolex-ttl,
+    /// ontolex-jsonld); defaults to pdf
     pub format: Option<String>,
     pub positive_scores_only: Option<bool>,
     pub collection_id: Option<i32>,
+    /// Source language of the exported valsi; defaults to Lojban (1)
+    pub source_langid: Option<i32>,
+    /// Comma-separated word types, e.g. `gismu,lujvo`
+    pub word_types: Option<String>,
+    /// Comma-separated selma'o
+    pub selmaho: Option<String>,
+    /// Minimum vote score
+    pub min_score: Option<f32>,
+    /// Username of the definitions' author
+    pub author: Option<String>,
+    /// Only definitions created at or after this time
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only definitions created before this time
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_before: Option<DateTime<Utc>>,
+    /// Fields to include; all default to true
+    pub include_notes: Option<bool>,
+    pub include_etymology: Option<bool>,
+    pub include_keywords: Option<bool>,
+    pub include_user: Option<bool>,
+    /// PDF/LaTeX paper size: a4 (default), a5, b5 or letter
+    pub paper: Option<String>,
+    /// PDF/LaTeX text columns: 1 or 2 (default)
+    pub columns: Option<u8>,
+}
+
+/// Page layout of PDF and LaTeX exports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PdfLayout {
+    /// `\documentclass` paper option, e.g. `a4paper`
+    pub paper: &'static str,
+    pub columns: u8,
+}
+
+impl Default for PdfLayout {
+    fn default() -> Self {
+        PdfLayout {
+            paper: "a4paper",
+            columns: 2,
+        }
+    }
+}
+
+/// Splits a comma-separated filter into trimmed, sorted, distinct values.
+fn filter_values(list: &Option<String>) -> Option<Vec<String>> {
+    let mut values: Vec<String> = list
+        .as_deref()?
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect();
+    values.sort();
+    values.dedup();
+    Some(values).filter(|values| !values.is_empty())
+}
+
+impl ExportOptions {
+    pub fn source_langid(&self) -> i32 {
+        self.source_langid.unwrap_or(1)
+    }
+
+    pub fn word_types(&self) -> Option<Vec<String>> {
+        filter_values(&self.word_types)
+    }
+
+    pub fn selmaho(&self) -> Option<Vec<String>> {
+        filter_values(&self.selmaho)
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author
+            .as_deref()
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+    }
+
+    pub fn include_notes(&self) -> bool {
+        self.include_notes.unwrap_or(true)
+    }
+
+    pub fn include_etymology(&self) -> bool {
+        self.include_etymology.unwrap_or(true)
+    }
+
+    pub fn include_keywords(&self) -> bool {
+        self.include_keywords.unwrap_or(true)
+    }
+
+    pub fn include_user(&self) -> bool {
+        self.include_user.unwrap_or(true)
+    }
+
+    /// Validated paper size and columns.
+    pub fn pdf_layout(&self) -> Result<PdfLayout, String> {
+        let default = PdfLayout::default();
+        let paper = match self.paper.as_deref() {
+            None => default.paper,
+            Some("a4") => "a4paper",
+            Some("a5") => "a5paper",
+            Some("b5") => "b5paper",
+            Some("letter") => "letterpaper",
+            Some(_) => {
+                return Err("Invalid paper size. Supported sizes: a4, a5, b5, letter".to_string())
+            }
+        };
+        let columns = match self.columns {
+            None => default.columns,
+            Some(columns @ (1 | 2)) => columns,
+            Some(_) => return Err("Invalid number of columns. Supported: 1, 2".to_string()),
+        };
+        Ok(PdfLayout { paper, columns })
+    }
+
+    /// Whether the export is typeset, i.e. the page layout applies.
+    fn is_typeset(&self) -> bool {
+        matches!(
+            self.format.as_deref().unwrap_or("pdf").parse(),
+            Ok(ExportFormat::Pdf | ExportFormat::LaTeX)
+        )
+    }
+
+    /// Whether the export may be cached. Author, date and score filters take
+    /// open-ended values, so each would add another cached file.
+    pub fn is_cacheable(&self) -> bool {
+        self.author().is_none()
+            && self.created_after.is_none()
+            && self.created_before.is_none()
+            && self.min_score.is_none()
+    }
+
+    /// Canonical form of the filters, field selection and page layout, empty for the
+    /// full export.
+    /// Part of the export cache key, so equivalent requests share a cached file.
+    pub fn cache_variant(&self) -> String {
+        let mut parts = Vec::new();
+        if self.source_langid() != 1 {
+            parts.push(format!("source_langid={}", self.source_langid()));
+        }
+        if self.positive_scores_only.unwrap_or(false) {
+            parts.push("positive_scores_only".to_string());
+        }
+        if let Some(word_types) = self.word_types() {
+            parts.push(format!("word_types={}", word_types.join(",")));
+        }
+        if let Some(selmaho) = self.selmaho() {
+            parts.push(format!("selmaho={}", selmaho.join(",")));
+        }
+        if let Some(min_score) = self.min_score {
+            parts.push(format!("min_score={}", min_score));
+        }
+        if let Some(author) = self.author() {
+            parts.push(format!("author={}", author));
+        }
+        if let Some(after) = self.created_after {
+            parts.push(format!("created_after={}", after.to_rfc3339()));
+        }
+        if let Some(before) = self.created_before {
+            parts.push(format!("created_before={}", before.to_rfc3339()));
+        }
+        for (field, included) in [
+            ("notes", self.include_notes()),
+            ("etymology", self.include_etymology()),
+            ("keywords", self.include_keywords()),
+            ("user", self.include_user()),
+        ] {
+            if !included {
+                parts.push(format!("exclude={}", field));
+            }
+        }
+        if let Some(layout) = self.pdf_layout().ok().filter(|_| self.is_typeset()) {
+            let default = PdfLayout::default();
+            if layout.paper != default.paper {
+                parts.push(format!("paper={}", layout.paper));
+            }
+            if layout.columns != default.columns {
+                parts.push(format!("columns={}", layout.columns));
+            }
+        }
+        parts.join("&")
+    }
 }
 
 #[derive(Serialize)]
@@ -133,6 +370,17 @@ pub struct CollectionExportItem {
     pub direction: Option<String>,
 }
 
+/// Naming and language metadata for the lookup dictionary formats.
+pub struct DictionaryInfo {
+    /// File name stem shared by the files inside the archive
+    pub basename: String,
+    pub title: String,
+    pub source_tag: String,
+    pub target_tag: String,
+    /// Date of the latest change to the content, used in place of the current date
+    pub date: NaiveDate,
+}
+
 #[derive(Debug)]
 pub struct ValsiRow {
     pub word: String,
@@ -160,6 +408,170 @@ pub struct CachedExport {
     pub language_realname: String,
     pub format: String,
     pub filename: String,
+    pub size_bytes: i64,
+    /// Hex SHA-256 of the file, also sent as its ETag
+    pub checksum: String,
     #[schema(value_type = String, format = DateTime)]
     pub created_at: DateTime<Utc>,
 }
+
+#[derive(Serialize, ToSchema)]
+pub struct ExportManifestEntry {
+    pub language_tag: String,
+    pub format: String,
+    pub filename: String,
+    pub size_bytes: i64,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// Whether a detached signature is available
+    pub signed: bool,
+}
+
+/// Checksums of all full cached exports, ordered by language and format.
+#[derive(Serialize, ToSchema)]
+pub struct ExportManifest {
+    /// Hex SHA-256 of the raw Ed25519 public key signing the exports, if any
+    pub signing_key_fingerprint: Option<String>,
+    pub exports: Vec<ExportManifestEntry>,
+}
+
+/// An export file in the file-backed cache.
+pub struct CachedExportFile {
+    pub path: PathBuf,
+    pub content_type: String,
+    pub filename: String,
+    pub checksum: String,
+}
+
+pub enum ExportContent {
+    /// Collection exports and uncacheable filtered exports, generated per request into
+    /// a temporary file that is deleted when `temp_path` is dropped
+    Generated {
+        file: CachedExportFile,
+        temp_path: tempfile::TempPath,
+    },
+    Cached(CachedExportFile),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    pub lang: String,
+    pub since: DateTime<Utc>,
+    /// Source language of the valsi; defaults to Lojban (1)
+    pub source_langid: Option<i32>,
+}
+
+/// Definitions added, updated or removed within a time window, one change per
+/// definition. A changed definition that is no longer the best guess for its word
+/// counts as deleted, since it has left the full export.
+#[derive(Serialize)]
+pub struct DictionaryChanges {
+    pub language_tag: String,
+    pub since: DateTime<Utc>,
+    /// End of the window; pass it as `since` on the next sync
+    pub until: DateTime<Utc>,
+    pub added: Vec<DictionaryEntry>,
+    pub updated: Vec<DictionaryEntry>,
+    pub deleted: Vec<DeletedEntry>,
+}
+
+#[derive(Serialize)]
+pub struct DeletedEntry {
+    pub definition_id: i32,
+    pub word: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DailyDiff {
+    pub language_tag: String,
+    #[schema(value_type = String, format = Date)]
+    pub day: NaiveDate,
+    pub change_count: i32,
+    pub filename: String,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateExportJobRequest {
+    /// Language tag
+    pub lang: String,
+    #[serde(flatten)]
+    pub options: ExportOptions,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExportJob {
+    pub job_id: i32,
+    pub language_tag: String,
+    pub format: String,
+    /// queued, running, completed or failed
+    pub status: String,
+    /// Percentage, 100 once completed
+    pub progress: i16,
+    /// What a running job is doing
+    pub stage: Option<String>,
+    pub error: Option<String>,
+    pub filename: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub checksum: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl ExportJob {
+    pub fn is_finished(&self) -> bool {
+        self.status == "completed" || self.status == "failed"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_variant_is_canonical() {
+        assert_eq!(ExportOptions::default().cache_variant(), "");
+
+        let options = ExportOptions {
+            format: Some("pdf".to_string()),
+            source_langid: Some(1),
+            word_types: Some(" lujvo,gismu,,lujvo".to_string()),
+            include_notes: Some(false),
+            include_user: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.cache_variant(),
+            "word_types=gismu,lujvo&exclude=notes"
+        );
+
+        let layout = ExportOptions {
+            paper: Some("a5".to_string()),
+            columns: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(layout.cache_variant(), "paper=a5paper");
+        let json = ExportOptions {
+            format: Some("json".to_string()),
+            ..layout
+        };
+        assert_eq!(json.cache_variant(), "");
+        assert!(json.is_cacheable());
+
+        let by_author = ExportOptions {
+            author: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert!(by_author.is_cacheable());
+        let by_score = ExportOptions {
+            min_score: Some(0.5),
+            ..by_author
+        };
+        assert!(!by_score.is_cacheable());
+    }
+}
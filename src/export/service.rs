@@ -1,31 +1,54 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::DateTime;
 use chrono::Datelike;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
 use chrono::Utc;
 use deadpool_postgres::Pool;
 use deadpool_postgres::Transaction;
 use log::info;
 use log::{debug, error};
 use std::error::Error;
-use std::io::{Cursor, Write};
+use std::io::{BufWriter, Cursor, Write};
+use std::path::PathBuf;
 use std::process::Command;
 use tempfile::tempdir;
+use url::Url;
 use xml::writer::{EventWriter, XmlEvent};
 use zip::write::{FileOptions, ZipWriter};
 
+use super::cache::{self, HashingWriter};
+use super::dictfile;
+use super::latex::{self, IndexEntry, ThumbIndex};
+use super::lexicon;
 use super::models::CachedExport;
+use super::models::CachedExportFile;
 use super::models::CollectionExportItem;
+use super::models::DailyDiff;
+use super::models::DeletedEntry;
+use super::models::DictionaryChanges;
 use super::models::DictionaryEntry;
+use super::models::DictionaryInfo;
+use super::models::ExportContent;
 use super::models::NaturalEntry;
 use super::models::User;
 use super::models::ValsiRow;
-use super::models::{ExportFormat, ExportOptions};
+use super::models::{ExportFormat, ExportOptions, PdfLayout};
+use super::models::{ExportManifest, ExportManifestEntry};
+use super::signing;
 use crate::jbovlaste::KeywordMapping;
-use std::collections::HashMap;
-
-pub async fn generate_pdf(
+use crate::language::math;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// Typesets `latex_content` with xelatex and copies the resulting PDF into `out`.
+/// `date` becomes `SOURCE_DATE_EPOCH`, so the PDF's timestamps and ID only change with
+/// the content.
+pub async fn generate_pdf<W: Write + Send>(
     latex_content: &str,
-) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    date: NaiveDate,
+    out: &mut W,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Create a temporary directory for working files
     let dir = tempdir()?;
     let dir_path = dir.path();
@@ -38,10 +61,19 @@ pub async fn generate_pdf(
     debug!("LaTeX file written to: {:?}", file_path);
 
     // Set HOME to temp dir to avoid permission issues
-    let mut command = std::process::Command::new("xelatex");
+    // tokio's Command, so a long typesetting run doesn't block the worker thread
+    let mut command = tokio::process::Command::new("xelatex");
     command
         .current_dir(dir_path)
         .env("HOME", dir_path)
+        .env(
+            "SOURCE_DATE_EPOCH",
+            date.and_time(NaiveTime::MIN)
+                .and_utc()
+                .timestamp()
+                .to_string(),
+        )
+        .env("FORCE_SOURCE_DATE", "1")
         // .arg("-no-shell-escape") // Arbitrary command execution is prevented by xelatex by default. See https://github.com/tectonic-typesetting/tectonic/issues/38
         .arg("-interaction=nonstopmode")
         .arg("-halt-on-error")
@@ -55,7 +87,7 @@ pub async fn generate_pdf(
 
     // Run xelatex and capture output
     debug!("Executing command: {:?}", command);
-    let output = match command.output() {
+    let output = match command.output().await {
         Ok(out) => out,
         Err(e) => {
             error!("Failed to execute xelatex: {}", e);
@@ -101,14 +133,14 @@ pub async fn generate_pdf(
         return Err(error_msg.into());
     }
 
-    // Read the generated PDF
+    // Stream the generated PDF
     let pdf_path = dir_path.join("output.pdf");
     debug!("Attempting to read PDF from: {:?}", pdf_path);
 
-    match std::fs::read(&pdf_path) {
-        Ok(content) => {
-            debug!("Successfully read PDF of size {} bytes", content.len());
-            Ok(content)
+    match std::fs::File::open(&pdf_path).and_then(|mut pdf| std::io::copy(&mut pdf, out)) {
+        Ok(size) => {
+            debug!("Successfully copied PDF of size {} bytes", size);
+            Ok(())
         }
         Err(e) => {
             let error_msg = format!("Failed to read generated PDF: {}", e);
@@ -120,10 +152,14 @@ pub async fn generate_pdf(
 }
 
 // Constants
+/// Filtered variants cached per language and format; further variants are generated
+/// per request until stale ones are removed
+const MAX_CACHED_VARIANTS: i64 = 32;
+
 const JAPANESE: &str = "ja";
 const GUASPI: &str = "art-guaspi";
 
-fn escape_all(term: &str) -> String {
+pub(super) fn escape_all(term: &str) -> String {
     let mut result = term.to_string();
     result = result.replace('\\', "\\textbackslash{}");
     result = result.replace('{', "\\{");
@@ -181,7 +217,12 @@ fn format_lojban_heading(word: &str, valsi_type: &str) -> String {
     } else {
         format_normal_heading(&escaped_word)
     };
-    format!("{}{}", heading, markboth(&escaped_word))
+    format!(
+        "{}{}{}",
+        heading,
+        latex::hypertarget(word),
+        markboth(&escaped_word)
+    )
 }
 
 fn format_normal_heading(escaped_word: &str) -> String {
@@ -230,18 +271,31 @@ fn format_selmaho(selmaho: &Option<String>) -> String {
     }
 }
 
-fn format_definition(definition: &str, lang: &str) -> String {
+/// `targets` are the words `{word}` references link to.
+fn format_definition(definition: &str, lang: &str, targets: &HashSet<String>) -> String {
     let carets_are_literal = lang == GUASPI;
-    format!(" {}", escape_tex(definition, carets_are_literal))
+    format!(
+        " {}",
+        latex::link_references(definition, targets, |text| escape_tex(
+            text,
+            carets_are_literal
+        ))
+    )
 }
 
-fn format_notes(notes: &Option<String>) -> String {
+fn format_notes(notes: &Option<String>, targets: &HashSet<String>) -> String {
     match notes {
         Some(n) if !n.is_empty() => {
             if sniff_tex(n) {
-                format!(" \\textemdash{{}} {}", escape_tex(n, false))
+                format!(
+                    " \\textemdash{{}} {}",
+                    latex::link_references(n, targets, |text| escape_tex(text, false))
+                )
             } else {
-                format!(" \\textemdash{{}} {}", escape_all(n))
+                format!(
+                    " \\textemdash{{}} {}",
+                    latex::link_references(n, targets, escape_all)
+                )
             }
         }
         _ => String::new(),
@@ -266,7 +320,7 @@ fn format_meaning(meaning: &Option<String>) -> String {
 }
 
 fn format_valsi(valsi: &str) -> String {
-    format!(" {}", escape_all(valsi))
+    format!(" {}", latex::hyperlink(valsi, &escape_all(valsi)))
 }
 
 fn format_place(place: i32) -> String {
@@ -276,35 +330,44 @@ fn format_place(place: i32) -> String {
         String::new()
     }
 }
-fn latex_header(title: &str, lang: &str) -> String {
-    let now = chrono::Local::now();
+/// `date` is the content date (see [`content_date`]), so unchanged dictionaries
+/// typeset identically.
+fn latex_header(title: &str, lang: &str, layout: &PdfLayout, date: NaiveDate) -> String {
     let jbo_date = format!(
         "de'i li {} pi'e {} pi'e {}",
-        now.year(),
-        now.month(),
-        now.day()
+        date.year(),
+        date.month(),
+        date.day()
     );
 
     format!("{}\n\\title{{{}}}\n\\author{{lo jboce'u}}\n\\date{{{}}}\n\n\\begin{{document}}\n\n\\maketitle",
-        latex_preamble(lang),
+        latex_preamble(lang, layout),
         title,
         jbo_date
     )
 }
 
-fn latex_preamble(lang: &str) -> String {
+fn latex_preamble(lang: &str, layout: &PdfLayout) -> String {
     format!(
         "{}{}{}",
-        latex_preamble_intro(),
+        latex_preamble_intro(layout),
         latex_preamble_fonts(lang),
         latex_preamble_outro()
     )
 }
 
-fn latex_preamble_intro() -> String {
-    r#"%!TEX encoding = UTF-8 Unicode
+fn latex_preamble_intro(layout: &PdfLayout) -> String {
+    let columns = if layout.columns == 1 {
+        "onecolumn"
+    } else {
+        "twocolumn"
+    };
+    format!(
+        r#"%!TEX encoding = UTF-8 Unicode
 %!TEX TS-program = xelatex
-\documentclass[notitlepage,twocolumn,a4paper,10pt]{book}
+\documentclass[notitlepage,{},{},10pt]{{book}}"#,
+        columns, layout.paper
+    ) + r#"
 \renewcommand\chaptername{ni'o ni'o}
 
 \usepackage{underscore}
@@ -319,7 +382,6 @@ fn latex_preamble_intro() -> String {
 
 % Font definitions mostly from http://linuxlibertine.sourceforge.net/Libertine-XeTex-EN.pdf
 \defaultfontfeatures{Scale=MatchLowercase}% to adjust all used fonts to the same x-height"#
-        .to_string()
 }
 
 fn latex_preamble_fonts(lang: &str) -> String {
@@ -358,15 +420,19 @@ fn latex_preamble_cjk_fonts(lang: &str) -> String {
 }
 
 fn latex_preamble_outro() -> String {
-    r#"
+    latex::THUMB_INDEX_PREAMBLE.to_string()
+        + r#"
 \fancyhead{}          % empty out the header
 \fancyfoot{}          % empty out the footer
-\fancyhead[LE,LO]{\rightmark} % left side, odd and even pages
-\fancyhead[RE,RO]{\leftmark}  % right side, odd and even pages
+\fancyhead[LE]{\thumbtableft\rightmark} % left side, even pages with the thumb tab
+\fancyhead[LO]{\rightmark}               % left side, odd pages
+\fancyhead[RE]{\leftmark}                % right side, even pages
+\fancyhead[RO]{\leftmark\thumbtabright}  % right side, odd pages with the thumb tab
 \fancyfoot[LE,RO]{\thepage}   % left side even, right side odd
 
-\setlength{\parindent}{1 em}"#
-        .to_string()
+\setlength{\parindent}{1 em}
+
+\usepackage[hidelinks]{hyperref} % cross-references; loaded last, as hyperref expects"#
 }
 
 fn latex_footer() -> String {
@@ -402,13 +468,77 @@ pub async fn verify_collection_access(
     Ok(is_public || user_id == Some(owner_id))
 }
 
-pub async fn export_with_access_check(
+/// Entry filters of an export as SQL parameters. Queries bind them after their own
+/// parameters and add [`EntryFilters::conditions`]; only `vbg` and `v` need to be in
+/// scope.
+struct EntryFilters {
+    positive_scores_only: bool,
+    word_types: Option<Vec<String>>,
+    selmaho: Option<Vec<String>>,
+    min_score: Option<f32>,
+    author: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+}
+
+impl EntryFilters {
+    fn new(options: &ExportOptions) -> Self {
+        EntryFilters {
+            positive_scores_only: options.positive_scores_only.unwrap_or(false),
+            word_types: options.word_types(),
+            selmaho: options.selmaho(),
+            min_score: options.min_score,
+            author: options.author().map(str::to_string),
+            created_after: options.created_after,
+            created_before: options.created_before,
+        }
+    }
+
+    /// Conditions whose parameters start at `$first`; unset filters match everything.
+    fn conditions(first: usize) -> String {
+        let [positive, types, selmaho, score, author, after, before] =
+            std::array::from_fn::<usize, 7, _>(|i| first + i);
+        format!(
+            "AND (NOT ${positive}::bool
+                  OR (SELECT COALESCE(SUM(value), 0) FROM definitionvotes
+                      WHERE definitionid = vbg.definitionid) > 0)
+             AND (${types}::text[] IS NULL
+                  OR v.typeid IN (SELECT typeid FROM valsitypes WHERE descriptor = ANY(${types})))
+             AND (${score}::real IS NULL
+                  OR (SELECT COALESCE(SUM(value), 0) FROM definitionvotes
+                      WHERE definitionid = vbg.definitionid) >= ${score})
+             AND EXISTS (
+                SELECT 1 FROM definitions fd
+                LEFT JOIN users fu ON fu.userid = fd.userid
+                WHERE fd.definitionid = vbg.definitionid
+                AND (${selmaho}::text[] IS NULL OR fd.selmaho = ANY(${selmaho}))
+                AND (${author}::text IS NULL OR fu.username = ${author})
+                AND (${after}::timestamptz IS NULL OR fd.created_at >= ${after})
+                AND (${before}::timestamptz IS NULL OR fd.created_at < ${before})
+             )"
+        )
+    }
+
+    fn params(&self) -> [&(dyn postgres_types::ToSql + Sync); 7] {
+        [
+            &self.positive_scores_only,
+            &self.word_types,
+            &self.selmaho,
+            &self.min_score,
+            &self.author,
+            &self.created_after,
+            &self.created_before,
+        ]
+    }
+}
+
+/// Rejects unknown languages and collections the user may not read.
+pub async fn check_export_access(
     pool: &Pool,
     lang: &str,
-    format: ExportFormat,
     options: &ExportOptions,
     user_id: Option<i32>,
-) -> Result<(Vec<u8>, String, String), Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let mut client = pool.get().await?;
     let mut transaction = client.transaction().await?;
 
@@ -422,6 +552,17 @@ pub async fn export_with_access_check(
     }
 
     transaction.commit().await?;
+    Ok(())
+}
+
+pub async fn export_with_access_check(
+    pool: &Pool,
+    lang: &str,
+    format: ExportFormat,
+    options: &ExportOptions,
+    user_id: Option<i32>,
+) -> Result<ExportContent, Box<dyn std::error::Error + Send + Sync>> {
+    check_export_access(pool, lang, options, user_id).await?;
     export_dictionary(pool, lang, format, options, options.collection_id).await
 }
 
@@ -431,50 +572,147 @@ pub async fn export_dictionary(
     format: ExportFormat,
     options: &ExportOptions,
     collection_id: Option<i32>,
-) -> Result<(Vec<u8>, String, String), Box<dyn std::error::Error + Send + Sync>> {
-    // For collection exports, bypass cache
-    if collection_id.is_some() {
-        return generate_export(pool, lang, format, options, collection_id).await;
+) -> Result<ExportContent, Box<dyn std::error::Error + Send + Sync>> {
+    // Collections change with every edit, so their exports aren't cached; dictionary
+    // exports are cached per filter variant, up to MAX_CACHED_VARIANTS per format
+    let generated = || generate_export(pool, lang, format, options, collection_id);
+    if collection_id.is_some() || !options.is_cacheable() {
+        return generated().await;
     }
 
-    let mut client = pool.get().await?;
-    let transaction = client.transaction().await?;
-
-    // Try to get from cache first using transaction
-    if let Some(row) = transaction
+    let variant = options.cache_variant();
+    let client = pool.get().await?;
+    let row = client
         .query_opt(
-            "SELECT content, content_type, filename
+            "SELECT file_path, content_type, filename, checksum,
+                    created_at > NOW() - INTERVAL '4 days' AS fresh
              FROM cached_dictionary_exports
-             WHERE language_tag = $1 AND format = $2
-             AND created_at > NOW() - INTERVAL '4 days'",
-            &[&lang, &format.to_string()],
+             WHERE language_tag = $1 AND format = $2 AND variant = $3",
+            &[&lang, &format.to_string(), &variant],
         )
-        .await?
-    {
-        // Commit transaction since we successfully found a cached result
-        transaction.commit().await?;
-        return Ok((
-            row.get("content"),
-            row.get("content_type"),
-            row.get("filename"),
-        ));
+        .await?;
+    // Regenerating a known variant replaces its row; a new one must fit under the cap
+    let over_cap = row.is_none()
+        && !variant.is_empty()
+        && client
+            .query_one(
+                "SELECT COUNT(*) FROM cached_dictionary_exports
+                 WHERE language_tag = $1 AND format = $2 AND variant <> ''",
+                &[&lang, &format.to_string()],
+            )
+            .await?
+            .get::<_, i64>(0)
+            >= MAX_CACHED_VARIANTS;
+    drop(client);
+    let cached = row
+        .filter(|row| row.get::<_, bool>("fresh"))
+        .map(cached_export_file)
+        .filter(|file| file.path.is_file());
+
+    match cached {
+        Some(file) => Ok(ExportContent::Cached(file)),
+        None if over_cap => generated().await,
+        None => Ok(ExportContent::Cached(
+            cache_export(pool, lang, format, options).await?,
+        )),
     }
+}
 
-    // Commit transaction since we'll generate a new export
-    transaction.commit().await?;
+fn cached_export_file(row: tokio_postgres::Row) -> CachedExportFile {
+    CachedExportFile {
+        path: PathBuf::from(row.get::<_, String>("file_path")),
+        content_type: row.get("content_type"),
+        filename: row.get("filename"),
+        checksum: row.get("checksum"),
+    }
+}
+
+/// Generates a dictionary export into the file cache and records its metadata under
+/// the options' cache variant. The file is written under a temporary name and renamed
+/// into place, so concurrent downloads never see a partial file.
+pub async fn cache_export(
+    pool: &Pool,
+    lang: &str,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<CachedExportFile, Box<dyn std::error::Error + Send + Sync>> {
+    let dir = cache::cache_dir();
+    std::fs::create_dir_all(&dir)?;
+    let mut temp_file = tempfile::NamedTempFile::new_in(&dir)?;
+
+    let mut out = HashingWriter::new(BufWriter::new(temp_file.as_file_mut()));
+    let (content_type, filename) =
+        write_export(pool, lang, format, options, None, &mut out, &|_, _| {}).await?;
+    let (buffered, size, checksum) = out.finish();
+    buffered
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+
+    // Variants share the download name, so their files are told apart by a prefix
+    let variant = options.cache_variant();
+    let path = match variant.as_str() {
+        "" => dir.join(&filename),
+        _ => {
+            let variant_hash = hex::encode(Sha256::digest(variant.as_bytes()));
+            dir.join(format!("{}-{}", &variant_hash[..16], filename))
+        }
+    };
+    temp_file.persist(&path)?;
+    let signature = signing::sign_checksum(&checksum)?;
+    let fingerprint = signature.as_ref().and(signing::key_fingerprint());
+
+    let client = pool.get().await?;
+    client
+        .execute(
+            "INSERT INTO cached_dictionary_exports
+             (language_tag, format, variant, file_path, size_bytes, checksum, content_type,
+              filename, signature, signing_key_fingerprint)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+             ON CONFLICT (language_tag, format, variant)
+             DO UPDATE SET
+                file_path = EXCLUDED.file_path,
+                size_bytes = EXCLUDED.size_bytes,
+                checksum = EXCLUDED.checksum,
+                content_type = EXCLUDED.content_type,
+                filename = EXCLUDED.filename,
+                signature = EXCLUDED.signature,
+                signing_key_fingerprint = EXCLUDED.signing_key_fingerprint,
+                created_at = CURRENT_TIMESTAMP",
+            &[
+                &lang,
+                &format.to_string(),
+                &variant,
+                &path.to_string_lossy().into_owned(),
+                &(size as i64),
+                &checksum,
+                &content_type,
+                &filename,
+                &signature,
+                &fingerprint,
+            ],
+        )
+        .await?;
 
-    // If not in cache, generate new export
-    generate_export(pool, lang, format, options, collection_id).await
+    Ok(CachedExportFile {
+        path,
+        content_type,
+        filename,
+        checksum,
+    })
 }
 
 fn zip_tsv_content(
     tsv_content: &str,
     filename: &str,
+    date: NaiveDate,
 ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
     let mut zip_buffer = Vec::new();
     {
         let mut zip = ZipWriter::new(Cursor::new(&mut zip_buffer));
-        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let options = FileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(dictfile::zip_time(date));
 
         zip.start_file(filename, options)?;
         zip.write_all(tsv_content.as_bytes())?;
@@ -483,56 +721,193 @@ fn zip_tsv_content(
     Ok(zip_buffer)
 }
 
+/// Generates an uncached export into a temporary file in the cache directory, so it
+/// streams to disk and back like a cached one instead of being held in memory.
 async fn generate_export(
     pool: &Pool,
     lang: &str,
     format: ExportFormat,
     options: &ExportOptions,
     collection_id: Option<i32>,
-) -> Result<(Vec<u8>, String, String), Box<dyn std::error::Error + Send + Sync>> {
-    let filename = match collection_id {
-        Some(id) => format!("collection-{}-{}.{}", id, lang, format.file_extension()),
-        None => format!("dictionary-{}.{}", lang, format.file_extension()),
+) -> Result<ExportContent, Box<dyn std::error::Error + Send + Sync>> {
+    let dir = cache::cache_dir();
+    std::fs::create_dir_all(&dir)?;
+    let mut temp_file = tempfile::NamedTempFile::new_in(&dir)?;
+
+    let mut out = HashingWriter::new(BufWriter::new(temp_file.as_file_mut()));
+    let (content_type, filename) = write_export(
+        pool,
+        lang,
+        format,
+        options,
+        collection_id,
+        &mut out,
+        &|_, _| {},
+    )
+    .await?;
+    let (buffered, _, checksum) = out.finish();
+    buffered.into_inner().map_err(|e| e.into_error())?;
+
+    let temp_path = temp_file.into_temp_path();
+    Ok(ExportContent::Generated {
+        file: CachedExportFile {
+            path: temp_path.to_path_buf(),
+            content_type,
+            filename,
+            checksum,
+        },
+        temp_path,
+    })
+}
+
+/// Writes an export to `out` and returns its content type and file name. PDF and XML,
+/// the largest formats, stream straight into `out`. `progress` is told the percentage
+/// done and the current stage.
+pub async fn write_export<W: Write + Send>(
+    pool: &Pool,
+    lang: &str,
+    format: ExportFormat,
+    options: &ExportOptions,
+    collection_id: Option<i32>,
+    out: &mut W,
+    progress: &(dyn Fn(i16, &str) + Sync),
+) -> Result<(String, String), Box<dyn std::error::Error + Send + Sync>> {
+    let source_langid = options.source_langid();
+    let basename = match (collection_id, source_langid) {
+        (Some(id), _) => format!("collection-{}-{}", id, lang),
+        (None, 1) => format!("dictionary-{}", lang),
+        (None, source) => format!("dictionary-{}-source-{}", lang, source),
     };
+    let filename = format!("{}.{}", basename, format.file_extension());
     let content_type = format.content_type().to_string();
 
     let mut client = pool.get().await?;
     let mut transaction = client.transaction().await?;
+    progress(5, "Loading entries");
+    let date = content_date(&mut transaction, lang).await?;
 
-    let content = match format {
+    match format {
         ExportFormat::Pdf => {
-            let latex = generate_latex(&mut transaction, lang, collection_id).await?;
+            let latex =
+                generate_latex(&mut transaction, lang, collection_id, options, date).await?;
             transaction.commit().await?;
-            generate_pdf(&latex).await?
+            progress(40, "Typesetting PDF");
+            generate_pdf(&latex, date, out).await?;
         }
         ExportFormat::LaTeX => {
-            let latex = generate_latex(&mut transaction, lang, collection_id).await?;
+            let latex =
+                generate_latex(&mut transaction, lang, collection_id, options, date).await?;
             transaction.commit().await?;
-            latex.into_bytes()
+            progress(90, "Writing file");
+            out.write_all(latex.as_bytes())?;
         }
         ExportFormat::Xml => {
-            let xml = generate_xml(&mut transaction, lang, options, collection_id).await?;
+            progress(10, "Writing entries");
+            generate_xml(&mut transaction, lang, options, collection_id, &mut *out).await?;
             transaction.commit().await?;
-            xml.into_bytes()
         }
         ExportFormat::Json => {
             let json = generate_json(&mut transaction, lang, options, collection_id).await?;
             transaction.commit().await?;
-            json.into_bytes()
+            progress(90, "Writing file");
+            out.write_all(json.as_bytes())?;
         }
         ExportFormat::Tsv => {
             let tsv = generate_tsv(&mut transaction, lang, options, collection_id).await?;
             transaction.commit().await?;
-            // Determine the TSV filename (without .zip extension)
-            let tsv_filename = match collection_id {
-                Some(id) => format!("collection-{}-{}.tsv", id, lang),
-                None => format!("dictionary-{}.tsv", lang),
+            progress(80, "Compressing");
+            out.write_all(&zip_tsv_content(&tsv, &format!("{}.tsv", basename), date)?)?;
+        }
+        ExportFormat::StarDict | ExportFormat::Dictd | ExportFormat::Epub => {
+            let entries =
+                fetch_dictionary_entries(&mut transaction, lang, options, collection_id, None)
+                    .await?;
+            let info =
+                dictionary_info(&mut transaction, lang, source_langid, basename, date).await?;
+            transaction.commit().await?;
+            progress(60, "Building dictionary files");
+            let content = match format {
+                ExportFormat::StarDict => dictfile::stardict(&info, &entries)?,
+                ExportFormat::Dictd => dictfile::dictd(&info, &entries)?,
+                _ => dictfile::epub(&info, &entries)?,
             };
-            zip_tsv_content(&tsv, &tsv_filename)?
+            out.write_all(&content)?;
         }
-    };
+        ExportFormat::Tei | ExportFormat::OntolexTurtle | ExportFormat::OntolexJsonLd => {
+            let entries =
+                fetch_dictionary_entries(&mut transaction, lang, options, collection_id, None)
+                    .await?;
+            let info =
+                dictionary_info(&mut transaction, lang, source_langid, basename, date).await?;
+            transaction.commit().await?;
+            progress(60, "Building lexicon");
+            let base_url = Url::parse(
+                &std::env::var("FRONTEND_URL")
+                    .unwrap_or_else(|_| "https://example.com".to_string()),
+            )?;
+            let content = match format {
+                ExportFormat::Tei => lexicon::tei_lex0(&info, &entries)?,
+                ExportFormat::OntolexTurtle => lexicon::ontolex_turtle(&info, &entries, &base_url)?,
+                _ => lexicon::ontolex_jsonld(&info, &entries, &base_url)?,
+            };
+            out.write_all(&content)?;
+        }
+    }
 
-    Ok((content, content_type, filename))
+    Ok((content_type, filename))
+}
+
+/// Date of the latest change to the definitions in `lang`. Exports carry it instead of
+/// the current date, so regenerating unchanged content gives byte-identical files.
+async fn content_date(
+    transaction: &mut Transaction<'_>,
+    lang: &str,
+) -> Result<NaiveDate, Box<dyn std::error::Error + Send + Sync>> {
+    let row = transaction
+        .query_one(
+            "SELECT GREATEST(
+                 (SELECT MAX(dc.changed_at) FROM dictionary_changes dc
+                  WHERE dc.langid = l.langid),
+                 (SELECT MAX(d.created_at) FROM definitions d WHERE d.langid = l.langid)
+             )
+             FROM languages l WHERE l.tag = $1",
+            &[&lang],
+        )
+        .await?;
+    Ok(row
+        .get::<_, Option<DateTime<Utc>>>(0)
+        .map(|changed_at| changed_at.date_naive())
+        .unwrap_or_default())
+}
+
+async fn dictionary_info(
+    transaction: &mut Transaction<'_>,
+    lang: &str,
+    source_langid: i32,
+    basename: String,
+    date: NaiveDate,
+) -> Result<DictionaryInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let row = transaction
+        .query_one(
+            "SELECT s.tag AS source_tag, s.realname AS source_name,
+                    l.tag AS target_tag, l.realname AS target_name
+             FROM languages s, languages l
+             WHERE s.langid = $1 AND l.tag = $2",
+            &[&source_langid, &lang],
+        )
+        .await?;
+
+    Ok(DictionaryInfo {
+        basename,
+        title: format!(
+            "{} – {} dictionary",
+            row.get::<_, String>("source_name"),
+            row.get::<_, String>("target_name")
+        ),
+        source_tag: row.get("source_tag"),
+        target_tag: row.get("target_tag"),
+        date,
+    })
 }
 
 async fn fetch_keywords_for_export(
@@ -558,7 +933,8 @@ async fn fetch_keywords_for_export(
             "SELECT k.definitionid, n.word, n.meaning
              FROM keywordmapping k
              JOIN natlangwords n ON k.natlangwordid = n.wordid
-             WHERE k.definitionid = ANY($1) AND k.place = 0",
+             WHERE k.definitionid = ANY($1) AND k.place = 0
+             ORDER BY k.definitionid, n.word, n.meaning",
             &[&def_ids],
         )
         .await?;
@@ -579,7 +955,7 @@ async fn fetch_keywords_for_export(
              FROM keywordmapping k
              JOIN natlangwords n ON k.natlangwordid = n.wordid
              WHERE k.definitionid = ANY($1) AND k.place > 0
-             ORDER BY k.definitionid, k.place",
+             ORDER BY k.definitionid, k.place, n.word",
             &[&def_ids],
         )
         .await?;
@@ -596,12 +972,13 @@ async fn fetch_keywords_for_export(
     Ok((gloss_map, place_map))
 }
 
-async fn generate_xml(
+async fn generate_xml<W: Write + Send>(
     transaction: &mut Transaction<'_>,
     lang: &str,
     options: &ExportOptions,
     collection_id: Option<i32>,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    out: W,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     if let Some(id) = collection_id {
         // Handle collection export
         let query = "
@@ -643,7 +1020,8 @@ async fn generate_xml(
                 CollectionExportItem::from_row(row, front_image_url, back_image_url)
             })
             .collect();
-        return Ok(serde_json::to_string_pretty(&entries)?);
+        serde_json::to_writer_pretty(out, &entries)?;
+        return Ok(());
     }
 
     if let Some(id) = collection_id {
@@ -688,10 +1066,11 @@ async fn generate_xml(
                 CollectionExportItem::from_row(row, front_image_url, back_image_url)
             })
             .collect();
-        return Ok(serde_json::to_string_pretty(&entries)?);
+        serde_json::to_writer_pretty(out, &entries)?;
+        return Ok(());
     }
 
-    let mut writer = EventWriter::new(Cursor::new(Vec::new()));
+    let mut writer = EventWriter::new(out);
 
     writer.write(XmlEvent::StartDocument {
         version: xml::common::XmlVersion::Version10,
@@ -716,12 +1095,6 @@ async fn generate_xml(
     writer.write(XmlEvent::end_element())?;
     writer.write(XmlEvent::end_element())?;
 
-    let score_condition = if options.positive_scores_only.unwrap_or(false) {
-        "AND (SELECT COALESCE(SUM(value), 0) FROM definitionvotes WHERE definitionid = vbg.definitionid) > 0"
-    } else {
-        ""
-    };
-
     let collection_join = collection_id
         .map(|_| "JOIN collection_items ci ON ci.definition_id = vbg.definitionid")
         .unwrap_or("");
@@ -739,14 +1112,21 @@ async fn generate_xml(
          JOIN definitions d ON d.definitionid = vbg.definitionid
          JOIN valsitypes t ON t.typeid = v.typeid
          {}
-         WHERE vbg.langid = $1 {} {}
-         AND v.source_langid = 1
-         ORDER BY lower(v.word)",
-        collection_join, score_condition, collection_condition
+         WHERE vbg.langid = $1 {}
+         AND v.source_langid = $2
+         {}
+         ORDER BY lower(v.word), v.word",
+        collection_join,
+        collection_condition,
+        EntryFilters::conditions(3)
     );
 
     let langid = lang_info.get::<_, i32>("langid");
-    let rows = transaction.query(&query, &[&langid]).await?;
+    let source_langid = options.source_langid();
+    let filters = EntryFilters::new(options);
+    let mut params: Vec<&(dyn postgres_types::ToSql + Sync)> = vec![&langid, &source_langid];
+    params.extend(filters.params());
+    let rows = transaction.query(&query, &params).await?;
 
     // Collect all definition IDs
     let def_ids: Vec<i32> = rows
@@ -755,7 +1135,11 @@ async fn generate_xml(
         .collect();
 
     // Fetch gloss keywords and place keywords for all definitions
-    let (gloss_map, place_map) = fetch_keywords_for_export(transaction, &def_ids).await?;
+    let (gloss_map, place_map) = if options.include_keywords() {
+        fetch_keywords_for_export(transaction, &def_ids).await?
+    } else {
+        Default::default()
+    };
 
     writer.write(XmlEvent::start_element("entries"))?;
     for row in rows.iter() {
@@ -789,7 +1173,10 @@ async fn generate_xml(
         writer.write(XmlEvent::Characters(&row.get::<_, String>("definition")))?;
         writer.write(XmlEvent::end_element())?;
 
-        if let Some(notes) = row.get::<_, Option<String>>("notes") {
+        if let Some(notes) = row
+            .get::<_, Option<String>>("notes")
+            .filter(|_| options.include_notes())
+        {
             writer.write(XmlEvent::start_element("notes"))?;
             writer.write(XmlEvent::Characters(&notes))?;
             writer.write(XmlEvent::end_element())?;
@@ -854,8 +1241,8 @@ async fn generate_xml(
     writer.write(XmlEvent::end_element())?;
     writer.write(XmlEvent::end_element())?;
 
-    let result = writer.into_inner().into_inner();
-    String::from_utf8(result).map_err(|e| e.into())
+    writer.into_inner().flush()?;
+    Ok(())
 }
 
 // Helper function to create CollectionExportItem from a row
@@ -893,6 +1280,8 @@ async fn generate_latex(
     transaction: &mut Transaction<'_>,
     lang: &str,
     collection_id: Option<i32>,
+    options: &ExportOptions,
+    date: NaiveDate,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     if let Some(id) = collection_id {
         // Handle collection export
@@ -945,6 +1334,7 @@ async fn generate_latex(
         )
         .await?;
 
+    let layout = options.pdf_layout()?;
     let lang_realname: String = lang_row.get("realname");
     let escaped_lang = escape_all(&lang_realname);
 
@@ -964,13 +1354,16 @@ async fn generate_latex(
         // Generate LaTeX specifically for a collection
         generate_collection_latex(transaction, lang, collection_id.unwrap()).await?
     } else {
-        // Generate standard dictionary chapters
-        generate_chapters(transaction, lang, &escaped_lang, None).await?
+        // Generate standard dictionary chapters, followed by the indexes
+        let mut chapters =
+            generate_chapters(transaction, lang, &escaped_lang, None, options).await?;
+        chapters.push_str(&generate_index_chapters(transaction, lang, options).await?);
+        chapters
     };
 
     Ok(format!(
         "{}\n{}\n{}",
-        latex_header(&title, lang),
+        latex_header(&title, lang, &layout, date),
         content,
         latex_footer()
     ))
@@ -981,12 +1374,38 @@ async fn generate_chapters(
     lang: &str,
     escaped_lang: &str,
     collection_id: Option<i32>,
+    options: &ExportOptions,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     let lang_id: i32 = transaction
         .query_one("SELECT langid FROM languages WHERE tag = $1", &[&lang])
         .await?
         .get(0);
 
+    if options.source_langid() != 1 {
+        // Other source languages get plain "source – target" chapter titles
+        let source_name: String = transaction
+            .query_one(
+                "SELECT realname FROM languages WHERE langid = $1",
+                &[&options.source_langid()],
+            )
+            .await?
+            .get(0);
+        let source_name = escape_all(&source_name);
+        let titles = (
+            format!("{} – {}", source_name, escaped_lang),
+            format!("{} – {}", escaped_lang, source_name),
+        );
+        return generate_source_and_natural_chapters(
+            transaction,
+            lang_id,
+            lang,
+            titles,
+            collection_id,
+            options,
+        )
+        .await;
+    }
+
     if escaped_lang == "lojban" {
         generate_lojban_chapter(
             transaction,
@@ -994,15 +1413,28 @@ async fn generate_chapters(
             lang,
             "lo smuni be bau la .lojban.",
             collection_id,
+            options,
         )
         .await
     } else {
-        generate_lojban_and_natural_chapters(
+        let titles = if collection_id.is_some() {
+            (
+                "fanva fo la .lojban.".to_string(),
+                "fanva fi la .lojban.".to_string(),
+            )
+        } else {
+            (
+                format!("fanva fi la'o zoi {} zoi", escaped_lang),
+                format!("fanva fo la'o zoi {} zoi", escaped_lang),
+            )
+        };
+        generate_source_and_natural_chapters(
             transaction,
             lang_id,
             lang,
-            escaped_lang,
+            titles,
             collection_id,
+            options,
         )
         .await
     }
@@ -1014,11 +1446,55 @@ async fn generate_lojban_chapter(
     lang: &str,
     title: &str,
     collection_id: Option<i32>,
+    options: &ExportOptions,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let entries = generate_lojban_entries(transaction, lang_id, lang, collection_id).await?;
+    let entries =
+        generate_lojban_entries(transaction, lang_id, lang, collection_id, options).await?;
     Ok(format!("\\chapter{{{}}}{}", title, entries))
 }
 
+/// The rafsi and selma'o indexes of the entries matching the export filters.
+async fn generate_index_chapters(
+    transaction: &mut Transaction<'_>,
+    lang: &str,
+    options: &ExportOptions,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let query = format!(
+        "SELECT v.word, c.rafsi, c.selmaho
+         FROM valsibestguesses vbg
+         JOIN languages l ON l.langid = vbg.langid
+         JOIN valsi v ON v.valsiid = vbg.valsiid
+         JOIN convenientdefinitions c ON c.definitionid = vbg.definitionid
+         WHERE l.tag = $1
+         AND v.source_langid = $2
+         AND (c.rafsi IS NOT NULL OR c.selmaho IS NOT NULL)
+         {}
+         ORDER BY lower(v.word), v.word",
+        EntryFilters::conditions(3)
+    );
+
+    let filters = EntryFilters::new(options);
+    let source_langid = options.source_langid();
+    let mut params: Vec<&(dyn postgres_types::ToSql + Sync)> = vec![&lang, &source_langid];
+    params.extend(filters.params());
+    let entries: Vec<IndexEntry> = transaction
+        .query(&query, &params)
+        .await?
+        .iter()
+        .map(|row| IndexEntry {
+            word: row.get("word"),
+            rafsi: row.get("rafsi"),
+            selmaho: row.get("selmaho"),
+        })
+        .collect();
+
+    Ok(format!(
+        "{}{}",
+        latex::rafsi_index(&entries),
+        latex::selmaho_index(&entries)
+    ))
+}
+
 async fn generate_collection_latex(
     transaction: &mut Transaction<'_>,
     lang: &str, // lang tag needed for escape_tex logic
@@ -1046,7 +1522,7 @@ async fn generate_collection_latex(
         if row.get::<_, Option<i32>>("definition_id").is_some() {
             // Format as definition-based item
             let valsi_row = ValsiRow::from_collection_row(&row)?;
-            entries.push_str(&format_lojban_entry(&valsi_row, lang));
+            entries.push_str(&format_lojban_entry(&valsi_row, lang, &HashSet::new()));
         } else {
             // Format as free-content item
             entries.push_str(&format_free_content_entry(&row, lang));
@@ -1056,12 +1532,12 @@ async fn generate_collection_latex(
     Ok(entries)
 }
 
-fn format_lojban_entry(valsi_row: &ValsiRow, lang: &str) -> String {
+fn format_lojban_entry(valsi_row: &ValsiRow, lang: &str, targets: &HashSet<String>) -> String {
     let mut entry = format_lojban_heading(&valsi_row.word, &valsi_row.descriptor);
     entry.push_str(&format_rafsi(&valsi_row.rafsi));
     entry.push_str(&format_selmaho(&valsi_row.selmaho));
-    entry.push_str(&format_definition(&valsi_row.definition, lang));
-    entry.push_str(&format_notes(&valsi_row.notes));
+    entry.push_str(&format_definition(&valsi_row.definition, lang, targets));
+    entry.push_str(&format_notes(&valsi_row.notes, targets));
     if let Some(note) = &valsi_row.collection_note {
         if !note.is_empty() {
             entry.push_str(&format_collection_note(note));
@@ -1095,7 +1571,7 @@ fn format_free_content_entry(row: &tokio_postgres::Row, lang: &str) -> String {
     format!(
         "\n\n{{\\sffamily\\bfseries {}}} \\enspace {} {}",
         escape_all(&front),
-        format_definition(&back, lang),
+        format_definition(&back, lang, &HashSet::new()),
         format_collection_note(&note.unwrap_or_default())
     )
 }
@@ -1104,6 +1580,11 @@ fn replace_newlines(s: &str) -> String {
     s.replace(['\n', '\r'], " ")
 }
 
+/// Single-line text with LaTeX math rendered as Unicode, e.g. `$x_1$` as `x₁`.
+fn plain_text(s: &str) -> String {
+    replace_newlines(&math::to_unicode_lossy(s))
+}
+
 fn format_collection_note(note: &str) -> String {
     if !note.is_empty() {
         if sniff_tex(note) {
@@ -1116,34 +1597,35 @@ fn format_collection_note(note: &str) -> String {
     }
 }
 
-async fn generate_lojban_and_natural_chapters(
+/// `titles` are the chapter titles from and to the source language.
+async fn generate_source_and_natural_chapters(
     transaction: &mut Transaction<'_>,
     lang_id: i32,
     lang: &str,
-    escaped_lang: &str,
+    titles: (String, String),
     collection_id: Option<i32>,
+    options: &ExportOptions,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let (vlaste_from_jbo, vlaste_to_jbo) = if collection_id.is_some() {
-        (
-            "fanva fo la .lojban.".to_string(),
-            "fanva fi la .lojban.".to_string(),
-        )
-    } else {
-        (
-            format!("fanva fi la'o zoi {} zoi", escaped_lang),
-            format!("fanva fo la'o zoi {} zoi", escaped_lang),
-        )
-    };
-
-    let lojban_chapter =
-        generate_lojban_chapter(transaction, lang_id, lang, &vlaste_from_jbo, collection_id)
-            .await?;
+    let (vlaste_from_jbo, vlaste_to_jbo) = titles;
+
+    let lojban_chapter = generate_lojban_chapter(
+        transaction,
+        lang_id,
+        lang,
+        &vlaste_from_jbo,
+        collection_id,
+        options,
+    )
+    .await?;
 
-    // Check if there are any natural language entries before generating that chapter
-    let has_natural_entries = check_natural_entries(transaction, lang_id, collection_id).await?;
+    // The natural language chapter is an index of the keywords; check if there are any
+    // entries before generating it
+    let has_natural_entries = options.include_keywords()
+        && check_natural_entries(transaction, lang_id, collection_id, options).await?;
 
     if has_natural_entries {
-        let natural_chapter = generate_natural_chapter(transaction, lang_id, collection_id).await?;
+        let natural_chapter =
+            generate_natural_chapter(transaction, lang_id, collection_id, options).await?;
         Ok(format!(
             "{}\n\\chapter{{{}}}{}",
             lojban_chapter, vlaste_to_jbo, natural_chapter
@@ -1157,6 +1639,7 @@ async fn check_natural_entries(
     transaction: &mut Transaction<'_>,
     lang_id: i32,
     collection_id: Option<i32>,
+    options: &ExportOptions,
 ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
     let collection_join = collection_id
         .map(|_| "JOIN collection_items ci ON ci.definition_id = vbg.definitionid")
@@ -1174,12 +1657,19 @@ async fn check_natural_entries(
             JOIN natlangwords nlw ON nlw.wordid = nlwbg.natlangwordid
             {}
             WHERE vbg.langid = $1 {}
-            AND v.source_langid = 1
+            AND v.source_langid = $2
+            {}
         )",
-        collection_join, collection_condition
+        collection_join,
+        collection_condition,
+        EntryFilters::conditions(3)
     );
 
-    let row = transaction.query_one(&query, &[&lang_id]).await?;
+    let filters = EntryFilters::new(options);
+    let source_langid = options.source_langid();
+    let mut params: Vec<&(dyn postgres_types::ToSql + Sync)> = vec![&lang_id, &source_langid];
+    params.extend(filters.params());
+    let row = transaction.query_one(&query, &params).await?;
     Ok(row.get(0))
 }
 
@@ -1188,6 +1678,7 @@ async fn generate_lojban_entries(
     lang_id: i32,
     lang: &str,
     collection_id: Option<i32>,
+    options: &ExportOptions,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     let mut entries = String::new();
     let collection_join = collection_id
@@ -1211,26 +1702,41 @@ async fn generate_lojban_entries(
          JOIN valsitypes t ON t.typeid = v.typeid
          {}
          {}
-         AND v.source_langid = 1
-         ORDER BY lower(v.word)",
-        collection_note_select, collection_join, where_clause
+         AND v.source_langid = $2
+         {}
+         ORDER BY lower(v.word), v.word",
+        collection_note_select,
+        collection_join,
+        where_clause,
+        EntryFilters::conditions(3)
     );
 
-    let params: Vec<&(dyn postgres_types::ToSql + Sync)> = vec![&lang_id];
+    let filters = EntryFilters::new(options);
+    let source_langid = options.source_langid();
+    let mut params: Vec<&(dyn postgres_types::ToSql + Sync)> = vec![&lang_id, &source_langid];
+    params.extend(filters.params());
 
     let rows = transaction.query(&query, &params[..]).await?;
 
+    // Cross-references link to entries of this chapter
+    let targets: HashSet<String> = rows.iter().map(|row| row.get("word")).collect();
+    let mut thumbs = ThumbIndex::new(rows.iter().map(|row| row.get::<_, &str>("word")));
+    entries.push_str(latex::CLEAR_THUMB);
+
     for row in rows {
         let valsi_row = ValsiRow {
             word: row.get("word"),
             rafsi: row.get("rafsi"),
             selmaho: row.get("selmaho"),
             definition: row.get("definition"),
-            notes: row.get("notes"),
+            notes: row
+                .get::<_, Option<String>>("notes")
+                .filter(|_| options.include_notes()),
             collection_note: row.get("collection_note"),
             descriptor: row.get("descriptor"),
         };
-        entries.push_str(&format_lojban_entry(&valsi_row, lang));
+        entries.push_str(&thumbs.mark(&valsi_row.word));
+        entries.push_str(&format_lojban_entry(&valsi_row, lang, &targets));
     }
 
     Ok(entries)
@@ -1240,6 +1746,7 @@ async fn generate_natural_chapter(
     transaction: &mut Transaction<'_>,
     lang_id: i32,
     collection_id: Option<i32>,
+    options: &ExportOptions,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     let collection_join = collection_id
         .map(|_| "JOIN collection_items ci ON ci.definition_id = vbg.definitionid")
@@ -1259,20 +1766,30 @@ async fn generate_natural_chapter(
          JOIN natlangwords nlw ON nlw.wordid = nlwbg.natlangwordid
          {}
          WHERE vbg.langid = $1 {}
-         AND v.source_langid = 1
+         AND v.source_langid = $2
          AND EXISTS (
           SELECT 1
           FROM keywordmapping km
           WHERE km.natlangwordid = nlw.wordid and km.definitionid=nlwbg.definitionid
          )
-         ORDER BY nlw.word",
-        collection_note_select, collection_join, collection_condition
+         {}
+         ORDER BY nlw.word, nlw.meaning, v.word, nlwbg.place",
+        collection_note_select,
+        collection_join,
+        collection_condition,
+        EntryFilters::conditions(3)
     );
 
-    let rows = transaction.query(&query, &[&lang_id]).await?;
-    let mut entries = String::new();
+    let filters = EntryFilters::new(options);
+    let source_langid = options.source_langid();
+    let mut params: Vec<&(dyn postgres_types::ToSql + Sync)> = vec![&lang_id, &source_langid];
+    params.extend(filters.params());
+    let rows = transaction.query(&query, &params).await?;
+    let mut thumbs = ThumbIndex::new(rows.iter().map(|row| row.get::<_, &str>("word")));
+    let mut entries = latex::CLEAR_THUMB.to_string();
 
     for row in rows {
+        entries.push_str(&thumbs.mark(row.get("word")));
         let entry = format_natural_entry(NaturalEntry {
             word: row.get("word"),
             meaning: row.get("meaning"),
@@ -1371,12 +1888,6 @@ async fn generate_tsv(
         return Ok(tsv);
     }
 
-    let score_condition = if options.positive_scores_only.unwrap_or(false) {
-        "AND (SELECT COALESCE(SUM(value), 0) FROM definitionvotes WHERE definitionid = vbg.definitionid) > 0"
-    } else {
-        ""
-    };
-
     let collection_join = collection_id
         .map(|_| "JOIN collection_items ci ON ci.definition_id = vbg.definitionid")
         .unwrap_or("");
@@ -1397,10 +1908,14 @@ async fn generate_tsv(
          JOIN definitions d ON d.definitionid = vbg.definitionid
          JOIN valsitypes t ON t.typeid = v.typeid
          {}
-         WHERE vbg.langid = $1 {} {}
-         AND v.source_langid = 1
-         ORDER BY lower(v.word)",
-        collection_note_select, collection_join, score_condition, collection_condition
+         WHERE vbg.langid = $1 {}
+         AND v.source_langid = $2
+         {}
+         ORDER BY lower(v.word), v.word",
+        collection_note_select,
+        collection_join,
+        collection_condition,
+        EntryFilters::conditions(3)
     );
 
     let langid = transaction
@@ -1408,7 +1923,11 @@ async fn generate_tsv(
         .await?
         .get::<_, i32>("langid");
 
-    let rows = transaction.query(&query, &[&langid]).await?;
+    let source_langid = options.source_langid();
+    let filters = EntryFilters::new(options);
+    let mut params: Vec<&(dyn postgres_types::ToSql + Sync)> = vec![&langid, &source_langid];
+    params.extend(filters.params());
+    let rows = transaction.query(&query, &params).await?;
 
     // Collect all definition IDs
     let def_ids: Vec<i32> = rows
@@ -1417,7 +1936,11 @@ async fn generate_tsv(
         .collect();
 
     // Fetch gloss keywords and place keywords for all definitions
-    let (gloss_map, place_map) = fetch_keywords_for_export(transaction, &def_ids).await?;
+    let (gloss_map, place_map) = if options.include_keywords() {
+        fetch_keywords_for_export(transaction, &def_ids).await?
+    } else {
+        Default::default()
+    };
 
     // Determine maximum number of gloss words and place keywords
     let max_gloss_count = gloss_map.values().map(|v| v.len()).max().unwrap_or(0);
@@ -1425,7 +1948,11 @@ async fn generate_tsv(
 
     let mut tsv = String::new();
     // Write header
-    tsv.push_str("word\ttype\trafsi\tselmaho\tdefinition\tnotes\tjargon\tcollection_note\tscore");
+    tsv.push_str("word\ttype\trafsi\tselmaho\tdefinition");
+    if options.include_notes() {
+        tsv.push_str("\tnotes");
+    }
+    tsv.push_str("\tjargon\tcollection_note\tscore");
 
     // Add gloss word columns
     for i in 1..=max_gloss_count {
@@ -1456,13 +1983,18 @@ async fn generate_tsv(
 
         // Start row with basic fields
         tsv.push_str(&format!(
-            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            "{}\t{}\t{}\t{}\t{}",
             replace_newlines(&word),
             replace_newlines(&descriptor),
             replace_newlines(&rafsi.unwrap_or_default()),
             replace_newlines(&selmaho.unwrap_or_default()),
-            replace_newlines(&definition),
-            replace_newlines(&notes.unwrap_or_default()),
+            plain_text(&definition),
+        ));
+        if options.include_notes() {
+            tsv.push_str(&format!("\t{}", plain_text(&notes.unwrap_or_default())));
+        }
+        tsv.push_str(&format!(
+            "\t{}\t{}\t{}",
             replace_newlines(&jargon.unwrap_or_default()),
             replace_newlines(&collection_note.unwrap_or_default()),
             score
@@ -1541,8 +2073,8 @@ fn generate_collection_tsv(
             replace_newlines(&word_type.unwrap_or_default()),
             replace_newlines(&rafsi.unwrap_or_default()),
             replace_newlines(&selmaho.unwrap_or_default()),
-            replace_newlines(&definition.unwrap_or_default()),
-            replace_newlines(&definition_notes.unwrap_or_default()),
+            plain_text(&definition.unwrap_or_default()),
+            plain_text(&definition_notes.unwrap_or_default()),
             replace_newlines(&jargon.unwrap_or_default()),
             replace_newlines(&free_content_front.unwrap_or_default()),
             replace_newlines(&free_content_back.unwrap_or_default()),
@@ -1602,12 +2134,19 @@ async fn generate_json(
         return Ok(serde_json::to_string_pretty(&entries)?);
     }
 
-    let score_condition = if options.positive_scores_only.unwrap_or(false) {
-        "AND (SELECT COALESCE(SUM(value), 0) FROM definitionvotes WHERE definitionid = vbg.definitionid) > 0"
-    } else {
-        ""
-    };
+    let entries = fetch_dictionary_entries(transaction, lang, options, collection_id, None).await?;
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
 
+/// Best-guess definitions in `lang` as [`DictionaryEntry`] rows, ordered by word;
+/// with `definition_ids`, only those of them.
+async fn fetch_dictionary_entries(
+    transaction: &mut Transaction<'_>,
+    lang: &str,
+    options: &ExportOptions,
+    collection_id: Option<i32>,
+    definition_ids: Option<&[i32]>,
+) -> Result<Vec<DictionaryEntry>, Box<dyn std::error::Error + Send + Sync>> {
     let collection_join = collection_id
         .map(|_| "JOIN collection_items ci ON ci.definition_id = vbg.definitionid")
         .unwrap_or("");
@@ -1629,10 +2168,15 @@ async fn generate_json(
          JOIN valsitypes t ON t.typeid = v.typeid
          LEFT JOIN users u ON u.userid = d.userid
          {}
-         WHERE vbg.langid = $1 {} {}
-         AND v.source_langid = 1
-         ORDER BY lower(v.word)",
-        collection_note_select, collection_join, score_condition, collection_condition
+         WHERE vbg.langid = $1 {}
+         AND v.source_langid = $2
+         AND ($3::int[] IS NULL OR vbg.definitionid = ANY($3))
+         {}
+         ORDER BY lower(v.word), v.word",
+        collection_note_select,
+        collection_join,
+        collection_condition,
+        EntryFilters::conditions(4)
     );
 
     let langid = transaction
@@ -1640,7 +2184,12 @@ async fn generate_json(
         .await?
         .get::<_, i32>("langid");
 
-    let rows = transaction.query(&query, &[&langid]).await?;
+    let source_langid = options.source_langid();
+    let filters = EntryFilters::new(options);
+    let mut params: Vec<&(dyn postgres_types::ToSql + Sync)> =
+        vec![&langid, &source_langid, &definition_ids];
+    params.extend(filters.params());
+    let rows = transaction.query(&query, &params).await?;
 
     // Collect all definition IDs
     let def_ids: Vec<i32> = rows
@@ -1649,7 +2198,11 @@ async fn generate_json(
         .collect();
 
     // Fetch gloss keywords and place keywords for all definitions
-    let (gloss_map, place_map) = fetch_keywords_for_export(transaction, &def_ids).await?;
+    let (gloss_map, place_map) = if options.include_keywords() {
+        fetch_keywords_for_export(transaction, &def_ids).await?
+    } else {
+        Default::default()
+    };
 
     let entries: Vec<DictionaryEntry> = rows
         .into_iter()
@@ -1662,22 +2215,29 @@ async fn generate_json(
                 rafsi: row.get("rafsi"),
                 selmaho: row.get("selmaho"),
                 definition: row.get("definition"),
-                notes: row.get("notes"),
-                etymology: row.get("etymology"),
+                notes: row
+                    .get::<_, Option<String>>("notes")
+                    .filter(|_| options.include_notes()),
+                etymology: row
+                    .get::<_, Option<String>>("etymology")
+                    .filter(|_| options.include_etymology()),
                 jargon: row.get("jargon"),
                 collection_note: row.get("collection_note"),
                 score: row.get("score"),
                 gloss_keywords: gloss_map.get(&definition_id).cloned(),
                 place_keywords: place_map.get(&definition_id).cloned(),
-                user: row.get("username").map(|user| User {
-                    username: user,
-                    realname: row.get("realname"),
-                }),
+                user: row
+                    .get::<_, Option<String>>("username")
+                    .filter(|_| options.include_user())
+                    .map(|user| User {
+                        username: user,
+                        realname: row.get("realname"),
+                    }),
             }
         })
         .collect();
 
-    Ok(serde_json::to_string_pretty(&entries)?)
+    Ok(entries)
 }
 
 pub async fn list_cached_exports(
@@ -1688,9 +2248,11 @@ pub async fn list_cached_exports(
 
     let rows = transaction
         .query(
-            "SELECT cde.language_tag, l.realname AS language_realname, cde.format, cde.filename, cde.created_at
+            "SELECT cde.language_tag, l.realname AS language_realname, cde.format, cde.filename,
+                cde.size_bytes, cde.checksum, cde.created_at
          FROM cached_dictionary_exports cde
          JOIN languages l ON cde.language_tag = l.tag
+         WHERE cde.variant = ''
          ORDER BY l.realname",
             &[],
         )
@@ -1703,6 +2265,8 @@ pub async fn list_cached_exports(
             language_realname: row.get("language_realname"),
             format: row.get("format"),
             filename: row.get("filename"),
+            size_bytes: row.get("size_bytes"),
+            checksum: row.get("checksum"),
             created_at: row.get("created_at"),
         })
         .collect();
@@ -1715,30 +2279,96 @@ pub async fn get_cached_export(
     pool: &Pool,
     language_tag: &str,
     format: &str,
-) -> Result<(Vec<u8>, String, String), Box<dyn Error + Send + Sync>> {
-    let mut client = pool.get().await?;
-    let transaction = client.transaction().await?;
+) -> Result<CachedExportFile, Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
 
-    let row = transaction
+    client
         .query_opt(
-            "SELECT content, content_type, filename
+            "SELECT file_path, content_type, filename, checksum
          FROM cached_dictionary_exports
-         WHERE language_tag = $1 AND format = $2",
+         WHERE language_tag = $1 AND format = $2 AND variant = ''",
             &[&language_tag, &format],
         )
+        .await?
+        .map(cached_export_file)
+        .filter(|file| file.path.is_file())
+        .ok_or_else(|| "Export not found".into())
+}
+
+/// Manifest of the full cached exports, serialized. The bytes only change with the
+/// exports and the signing key, so the manifest can be signed and compared as a whole.
+pub async fn export_manifest(pool: &Pool) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    // Every full export can be signed on request while a key is configured
+    let signing_key_fingerprint = signing::key_fingerprint();
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT language_tag, format, filename, size_bytes, checksum
+             FROM cached_dictionary_exports
+             WHERE variant = ''
+             ORDER BY language_tag, format",
+            &[],
+        )
         .await?;
 
-    let result = match row {
-        Some(row) => Ok((
-            row.get("content"),
-            row.get("content_type"),
-            row.get("filename"),
-        )),
-        None => Err("Export not found".into()),
-    };
+    let exports = rows
+        .into_iter()
+        .map(|row| ExportManifestEntry {
+            language_tag: row.get("language_tag"),
+            format: row.get("format"),
+            filename: row.get("filename"),
+            size_bytes: row.get("size_bytes"),
+            sha256: row.get("checksum"),
+            signed: signing_key_fingerprint.is_some(),
+        })
+        .collect();
+    Ok(serde_json::to_vec_pretty(&ExportManifest {
+        signing_key_fingerprint,
+        exports,
+    })?)
+}
 
-    transaction.commit().await?;
-    result
+/// The detached signature of a full cached export and its file name. A signature made
+/// before the current key was configured is replaced by a fresh one.
+pub async fn get_export_signature(
+    pool: &Pool,
+    language_tag: &str,
+    format: &str,
+) -> Result<(String, Vec<u8>), Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            "SELECT file_path, filename, checksum, signature, signing_key_fingerprint
+             FROM cached_dictionary_exports
+             WHERE language_tag = $1 AND format = $2 AND variant = ''",
+            &[&language_tag, &format],
+        )
+        .await?
+        .ok_or("Export not found")?;
+    let fingerprint = signing::key_fingerprint().ok_or("Export not signed")?;
+    let filename: String = row.get("filename");
+
+    let stored: Option<String> = row.get("signing_key_fingerprint");
+    if let Some(signature) = row
+        .get::<_, Option<Vec<u8>>>("signature")
+        .filter(|_| stored.as_deref() == Some(fingerprint.as_str()))
+    {
+        return Ok((filename, signature));
+    }
+
+    let file_path: String = row.get("file_path");
+    let checksum: String = row.get("checksum");
+    let signature = signing::sign_checksum(&checksum)?.ok_or("Export not signed")?;
+    // The file_path check skips the update if the export was regenerated meanwhile
+    client
+        .execute(
+            "UPDATE cached_dictionary_exports
+             SET signature = $4, signing_key_fingerprint = $5
+             WHERE language_tag = $1 AND format = $2 AND variant = '' AND file_path = $3",
+            &[&language_tag, &format, &file_path, &signature, &fingerprint],
+        )
+        .await?;
+    Ok((filename, signature))
 }
 
 pub async fn export_all_dictionaries(pool: &Pool) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -1755,7 +2385,7 @@ pub async fn export_all_dictionaries(pool: &Pool) -> Result<(), Box<dyn Error +
     // Check existing cached exports
     let cached_exports_rows = transaction
         .query(
-            "SELECT language_tag, format, MAX(created_at) as last_export FROM cached_dictionary_exports GROUP BY language_tag, format",
+            "SELECT language_tag, format, MAX(created_at) as last_export FROM cached_dictionary_exports WHERE variant = '' GROUP BY language_tag, format",
             &[],
         )
         .await?;
@@ -1767,14 +2397,21 @@ pub async fn export_all_dictionaries(pool: &Pool) -> Result<(), Box<dyn Error +
         let last_export: DateTime<Utc> = row.get("last_export");
         cached_exports.insert((lang_tag, format), last_export);
     }
+    transaction.commit().await?;
 
-    for lang in languages {
+    for lang in &languages {
         for format in &[
             ExportFormat::Pdf,
             ExportFormat::LaTeX,
             ExportFormat::Xml,
             ExportFormat::Json,
             ExportFormat::Tsv,
+            ExportFormat::StarDict,
+            ExportFormat::Dictd,
+            ExportFormat::Epub,
+            ExportFormat::Tei,
+            ExportFormat::OntolexTurtle,
+            ExportFormat::OntolexJsonLd,
         ] {
             let format_str = format.to_string();
             if let Some(last_export_time) = cached_exports.get(&(lang.clone(), format_str.clone()))
@@ -1795,37 +2432,294 @@ pub async fn export_all_dictionaries(pool: &Pool) -> Result<(), Box<dyn Error +
                 lang, format
             );
 
-            match export_dictionary(pool, &lang, *format, &Default::default(), None).await {
-                Ok((content, content_type, filename)) => {
-                    if let Err(e) = transaction
-                        .execute(
-                            "INSERT INTO cached_dictionary_exports
-                             (language_tag, format, content, content_type, filename)
-                             VALUES ($1, $2, $3, $4, $5)
-                             ON CONFLICT (language_tag, format)
-                             DO UPDATE SET
-                                content = EXCLUDED.content,
-                                content_type = EXCLUDED.content_type,
-                                filename = EXCLUDED.filename,
-                                created_at = CURRENT_TIMESTAMP",
-                            &[
-                                &lang,
-                                &format.to_string(),
-                                &content,
-                                &content_type,
-                                &filename,
-                            ],
-                        )
-                        .await
-                    {
-                        error!("Failed to cache export for {}: {}", lang, e);
-                    }
-                }
-                Err(e) => error!("Failed to export {} dictionary to {}: {}", lang, format, e),
+            if let Err(e) = cache_export(pool, lang, *format, &Default::default()).await {
+                error!("Failed to export {} dictionary to {}: {}", lang, format, e);
+            }
+        }
+    }
+
+    if let Err(e) = generate_daily_diffs(pool, &languages).await {
+        error!("Failed to generate daily dictionary diffs: {}", e);
+    }
+    if let Err(e) = remove_stale_export_variants(pool).await {
+        error!("Failed to remove stale filtered exports: {}", e);
+    }
+    Ok(())
+}
+
+/// Filtered variants are only generated on request; once they are too old to be
+/// served, their rows and files go.
+async fn remove_stale_export_variants(pool: &Pool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "DELETE FROM cached_dictionary_exports
+             WHERE variant <> '' AND created_at <= NOW() - INTERVAL '4 days'
+             RETURNING file_path",
+            &[],
+        )
+        .await?;
+    for row in &rows {
+        let path: String = row.get("file_path");
+        if let Err(e) = std::fs::remove_file(&path) {
+            error!("Failed to remove cached export {}: {}", path, e);
+        }
+    }
+    Ok(())
+}
+
+/// Days the change log and the daily delta files are kept
+const CHANGE_RETENTION_DAYS: i64 = 90;
+
+/// Changes to the `lang` dictionary in `(since, until]`, read from the
+/// `dictionary_changes` log; `until` defaults to now. `since` must lie within the last
+/// [`CHANGE_RETENTION_DAYS`], older log rows are pruned.
+///
+/// Log rows carry their transaction's start time but only become visible on commit, so
+/// `until` is held back to just before the oldest open transaction started. Rows of
+/// transactions still running then land in a later window instead of being skipped.
+pub async fn dictionary_changes(
+    pool: &Pool,
+    lang: &str,
+    since: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+    source_langid: Option<i32>,
+) -> Result<DictionaryChanges, Box<dyn Error + Send + Sync>> {
+    if since < Utc::now() - chrono::Duration::days(CHANGE_RETENTION_DAYS) {
+        return Err("Changes are only kept for 90 days; start from a new full export".into());
+    }
+
+    let mut client = pool.get().await?;
+    let mut transaction = client.transaction().await?;
+
+    let Some(langid) = transaction
+        .query_opt("SELECT langid FROM languages WHERE tag = $1", &[&lang])
+        .await?
+        .map(|row| row.get::<_, i32>("langid"))
+    else {
+        return Err("Invalid language tag".into());
+    };
+    let horizon: DateTime<Utc> = transaction
+        .query_one(
+            "SELECT LEAST(CURRENT_TIMESTAMP, MIN(xact_start)) - INTERVAL '1 microsecond'
+             FROM pg_stat_activity
+             WHERE datname = current_database() AND backend_type = 'client backend'
+               AND pid <> pg_backend_pid()",
+            &[],
+        )
+        .await?
+        .get(0);
+    let until = until.map_or(horizon, |until| until.min(horizon));
+    let source_langid = source_langid.unwrap_or(1);
+
+    let rows = transaction
+        .query(
+            "SELECT definition_id,
+                    (array_agg(change_type ORDER BY change_id))[1] AS first_change,
+                    (array_agg(change_type ORDER BY change_id DESC))[1] AS last_change,
+                    (array_agg(word ORDER BY change_id DESC))[1] AS word
+             FROM dictionary_changes
+             WHERE langid = $1 AND source_langid = $2
+             AND changed_at > $3 AND changed_at <= $4
+             GROUP BY definition_id",
+            &[&langid, &source_langid, &since, &until],
+        )
+        .await?;
+
+    let mut deleted = Vec::new();
+    // Definition ID -> (added within the window, word)
+    let mut changed: HashMap<i32, (bool, String)> = HashMap::new();
+    for row in rows {
+        let definition_id: i32 = row.get("definition_id");
+        let first_change: String = row.get("first_change");
+        let last_change: String = row.get("last_change");
+        let word: String = row.get("word");
+        match (first_change.as_str(), last_change.as_str()) {
+            // Added and removed again within the window: nothing to sync
+            ("added", "deleted") => {}
+            (_, "deleted") => deleted.push(DeletedEntry {
+                definition_id,
+                word,
+            }),
+            (first, _) => {
+                changed.insert(definition_id, (first == "added", word));
             }
         }
     }
 
+    let ids: Vec<i32> = changed.keys().copied().collect();
+    let entries = if ids.is_empty() {
+        Vec::new()
+    } else {
+        let options = ExportOptions {
+            source_langid: Some(source_langid),
+            ..Default::default()
+        };
+        fetch_dictionary_entries(&mut transaction, lang, &options, None, Some(&ids)).await?
+    };
     transaction.commit().await?;
+
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    for entry in entries {
+        match entry.definition_id.and_then(|id| changed.remove(&id)) {
+            Some((true, _)) => added.push(entry),
+            Some((false, _)) => updated.push(entry),
+            None => {}
+        }
+    }
+    // Whatever wasn't found is not a best guess now. Definitions added within the window
+    // were never exported; the others may have left the export, or never been in it.
+    deleted.extend(changed.into_iter().filter(|(_, (added, _))| !added).map(
+        |(definition_id, (_, word))| DeletedEntry {
+            definition_id,
+            word,
+        },
+    ));
+    deleted.sort_by_key(|entry| entry.definition_id);
+
+    Ok(DictionaryChanges {
+        language_tag: lang.to_string(),
+        since,
+        until,
+        added,
+        updated,
+        deleted,
+    })
+}
+
+/// Past days checked for a missing delta file, so a postponed day is filled in later
+const DAILY_DIFF_CATCH_UP_DAYS: i64 = 3;
+
+fn daily_diff_filename(lang: &str, day: NaiveDate) -> String {
+    format!("changes-{}-{}.json", lang, day)
+}
+
+/// Stores the previous UTC days' changes of each language as daily delta files, and
+/// prunes delta files and change log rows older than [`CHANGE_RETENTION_DAYS`]. A day
+/// whose changes may still be uncommitted is left for the next run.
+async fn generate_daily_diffs(
+    pool: &Pool,
+    languages: &[String],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let today = Utc::now().date_naive();
+    let client = pool.get().await?;
+    client
+        .execute(
+            "DELETE FROM dictionary_export_diffs WHERE day < $1",
+            &[&(today - chrono::Duration::days(CHANGE_RETENTION_DAYS))],
+        )
+        .await?;
+    let pruned = client
+        .execute(
+            "DELETE FROM dictionary_changes WHERE changed_at < $1",
+            &[&(Utc::now() - chrono::Duration::days(CHANGE_RETENTION_DAYS))],
+        )
+        .await?;
+    if pruned > 0 {
+        info!("Pruned {} dictionary change log rows", pruned);
+    }
+    for days_ago in (1..=DAILY_DIFF_CATCH_UP_DAYS).rev() {
+        let day = today - chrono::Duration::days(days_ago);
+        generate_daily_diffs_for(pool, languages, day).await?;
+    }
     Ok(())
 }
+
+async fn generate_daily_diffs_for(
+    pool: &Pool,
+    languages: &[String],
+    day: NaiveDate,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let since = day.and_time(chrono::NaiveTime::MIN).and_utc();
+    let until = (day + chrono::Duration::days(1))
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc();
+
+    let client = pool.get().await?;
+    let existing: HashSet<String> = client
+        .query(
+            "SELECT language_tag FROM dictionary_export_diffs WHERE day = $1",
+            &[&day],
+        )
+        .await?
+        .iter()
+        .map(|row| row.get("language_tag"))
+        .collect();
+
+    for lang in languages.iter().filter(|lang| !existing.contains(*lang)) {
+        let changes = match dictionary_changes(pool, lang, since, Some(until), None).await {
+            Ok(changes) if changes.until < until => {
+                info!(
+                    "Postponing {} changes for {} until open transactions finish",
+                    lang, day
+                );
+                continue;
+            }
+            Ok(changes) => changes,
+            Err(e) => {
+                error!("Failed to collect {} changes for {}: {}", lang, day, e);
+                continue;
+            }
+        };
+        let change_count =
+            (changes.added.len() + changes.updated.len() + changes.deleted.len()) as i32;
+        client
+            .execute(
+                "INSERT INTO dictionary_export_diffs (language_tag, day, content, change_count)
+                 VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (language_tag, day) DO NOTHING",
+                &[lang, &day, &serde_json::to_vec(&changes)?, &change_count],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn list_daily_diffs(
+    pool: &Pool,
+    lang: &str,
+) -> Result<Vec<DailyDiff>, Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT language_tag, day, change_count, created_at
+             FROM dictionary_export_diffs
+             WHERE language_tag = $1
+             ORDER BY day DESC",
+            &[&lang],
+        )
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let day: NaiveDate = row.get("day");
+            DailyDiff {
+                filename: daily_diff_filename(lang, day),
+                language_tag: row.get("language_tag"),
+                day,
+                change_count: row.get("change_count"),
+                created_at: row.get("created_at"),
+            }
+        })
+        .collect())
+}
+
+pub async fn get_daily_diff(
+    pool: &Pool,
+    lang: &str,
+    day: NaiveDate,
+) -> Result<(Vec<u8>, String), Box<dyn Error + Send + Sync>> {
+    let client = pool.get().await?;
+    match client
+        .query_opt(
+            "SELECT content FROM dictionary_export_diffs WHERE language_tag = $1 AND day = $2",
+            &[&lang, &day],
+        )
+        .await?
+    {
+        Some(row) => Ok((row.get("content"), daily_diff_filename(lang, day))),
+        None => Err("Export not found".into()),
+    }
+}
@@ -0,0 +1,84 @@
+//! Detached Ed25519 signatures of cached exports and the export manifest.
+//!
+//! The server key is a PKCS#8 file (PEM or DER) named by `EXPORT_SIGNING_KEY`, e.g. from
+//! `openssl genpkey -algorithm ed25519 -out export-signing-key.pem`. Without it nothing
+//! is signed. Signatures are the raw 64 bytes. An export's signature covers its 32-byte
+//! SHA-256 digest, which is computed while the export streams to disk, so signing never
+//! reads the file back:
+//! `openssl dgst -sha256 -binary FILE > FILE.sha256` and then
+//! `openssl pkeyutl -verify -rawin -pubin -inkey key.pem -in FILE.sha256 -sigfile FILE.sig`
+//! check it against the public key from `/export/signing-key`. The manifest is small and
+//! signed as it is served. Stored signatures carry the key's fingerprint, so those of a
+//! rotated key are never served.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+
+static SIGNING_KEY: Lazy<Option<Ed25519KeyPair>> = Lazy::new(|| match load_signing_key() {
+    Ok(key) => key,
+    Err(e) => {
+        warn!("Export signing disabled: {}", e);
+        None
+    }
+});
+
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo, followed by the 32-byte key.
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+fn load_signing_key() -> Result<Option<Ed25519KeyPair>, Box<dyn Error + Send + Sync>> {
+    let Ok(path) = std::env::var("EXPORT_SIGNING_KEY") else {
+        return Ok(None);
+    };
+    let contents = std::fs::read(&path)?;
+    let der = match std::str::from_utf8(&contents) {
+        Ok(pem) if pem.contains("-----BEGIN") => BASE64.decode(
+            pem.lines()
+                .filter(|line| !line.starts_with("-----"))
+                .collect::<String>(),
+        )?,
+        _ => contents,
+    };
+    // openssl writes version 1 PKCS#8, without the public key
+    let key = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+        .map_err(|e| format!("Invalid key in {}: {}", path, e))?;
+    info!("Signing exports with the key in {}", path);
+    Ok(Some(key))
+}
+
+pub fn sign(message: &[u8]) -> Option<Vec<u8>> {
+    SIGNING_KEY
+        .as_ref()
+        .map(|key| key.sign(message).as_ref().to_vec())
+}
+
+/// Signature of an export, made over the digest behind its hex SHA-256 `checksum`.
+pub fn sign_checksum(checksum: &str) -> Result<Option<Vec<u8>>, hex::FromHexError> {
+    Ok(sign(&hex::decode(checksum)?))
+}
+
+/// Hex SHA-256 of the raw public key, naming the key that made a signature.
+pub fn key_fingerprint() -> Option<String> {
+    static FINGERPRINT: Lazy<Option<String>> = Lazy::new(|| {
+        SIGNING_KEY
+            .as_ref()
+            .map(|key| hex::encode(Sha256::digest(key.public_key().as_ref())))
+    });
+    FINGERPRINT.clone()
+}
+
+/// The public key as a PEM SubjectPublicKeyInfo.
+pub fn public_key_pem() -> Option<String> {
+    let key = SIGNING_KEY.as_ref()?;
+    let mut der = ED25519_SPKI_PREFIX.to_vec();
+    der.extend_from_slice(key.public_key().as_ref());
+    Some(format!(
+        "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+        BASE64.encode(der)
+    ))
+}
@@ -5,25 +5,31 @@ use chrono::Utc;
 use deadpool_postgres::Pool;
 use serde_json::json;
 
-use super::dto::ClientIdGroup;
+use super::dto::{
+    ClientIdGroup, FeatureQueueEntry, FeatureQueueRequest, QueryErrorResponse,
+    SearchAnalyticsQuery, SearchAnalyticsResponse, SourceLanguageOverview, StructuredSearchQuery,
+    WordOfTheDay, WordOfTheDayQuery, WordOfTheDaySubscriptionRequest,
+};
+use super::query_language;
 use super::{BulkImportRequest, SearchDefinitionsQuery, UserVoteResponse};
 use crate::auth::Claims;
 // Removed unused Permission import
 use crate::jbovlaste::broadcast::Broadcaster;
 use crate::jbovlaste::dto::{ListDefinitionsQuery, NonLojbanDefinitionsQuery};
+use crate::jbovlaste::models::SearchHistoryEntry;
 use crate::jbovlaste::service::validate_image;
+use crate::jbovlaste::valsi_validation::ValsiValidationError;
 use crate::jbovlaste::{
     service, AddDefinitionRequest, AddValsiResponse, BulkImportParams, BulkVoteRequest,
     BulkVoteResponse, DefinitionDetail, DefinitionListResponse, GetImageDefinitionQuery,
     ImageUploadRequest, RecentChangesQuery, RecentChangesResponse, SearchDefinitionsParams,
+    SimilarDefinitionsQuery, SimilarDefinitionsResponse, SourceLanguageQuery,
     UpdateDefinitionRequest, UpdateDefinitionResponse, ValsiDefinitionsQuery, ValsiDetail,
     ValsiTypeListResponse, VoteRequest, VoteResponse,
 };
-use crate::language::{validate_mathjax, MathJaxValidationOptions};
+use crate::language::{validate_mathjax, MathJaxValidationOptions, WorkerParsers};
 use crate::middleware::cache::{generate_search_cache_key, RedisCache};
-use camxes_rs::peg::grammar::Peg;
-use std::collections::HashMap;
-use std::sync::Arc;
+use crate::AppError;
 
 #[utoipa::path(
     get,
@@ -47,6 +53,7 @@ pub async fn semantic_search(
     pool: web::Data<Pool>,
     redis_cache: web::Data<RedisCache>,
     query: web::Query<SearchDefinitionsQuery>,
+    claims: Option<Claims>,
 ) -> impl Responder {
     let page = query.page.unwrap_or(1);
     let per_page = query.per_page.unwrap_or(20);
@@ -131,13 +138,23 @@ pub async fn semantic_search(
         )
         .await
     {
-        Ok(response) => HttpResponse::Ok().json(DefinitionListResponse {
-            definitions: response.definitions,
-            total: response.total,
-            page,
-            per_page,
-            decomposition: vec![],
-        }),
+        Ok(response) => {
+            spawn_record_search(
+                pool.clone(),
+                &query,
+                claims.map(|c| c.sub),
+                "semantic",
+                response.total,
+            );
+            HttpResponse::Ok().json(DefinitionListResponse {
+                definitions: response.definitions,
+                total: response.total,
+                page,
+                per_page,
+                decomposition: vec![],
+                suggestions: vec![],
+            })
+        }
         Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
     }
 }
@@ -171,6 +188,50 @@ pub async fn list_definitions(
     }
 }
 
+#[utoipa::path(
+    get,
+    tag = "jbovlaste",
+    path = "/jbovlaste/definitions/query",
+    params(
+        ("query" = StructuredSearchQuery, Query, description = "Structured query and pagination parameters")
+    ),
+    responses(
+        (status = 200, description = "Definitions matching the query", body = DefinitionListResponse),
+        (status = 400, description = "Query could not be parsed", body = QueryErrorResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "Search definitions with a query expression",
+    description = "Searches definitions using a query language. Supported fields: `word`, `selmaho`, \
+                  `type`, `lang`, `source`, `score` (with `>`, `>=`, `<`, `<=`), `rafsi`, `user`, `gloss`, \
+                  `def` and `notes`; bare words and \"quoted phrases\" match anywhere in the entry. \
+                  Terms are combined with implicit AND, or explicitly with AND/OR/NOT and parentheses; \
+                  `-term` negates. `*` is a wildcard."
+)]
+#[get("/definitions/query")]
+pub async fn structured_search(
+    pool: web::Data<Pool>,
+    query: web::Query<StructuredSearchQuery>,
+    claims: Option<Claims>,
+) -> impl Responder {
+    let expr = match query_language::parse(&query.q) {
+        Ok(expr) => expr,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(QueryErrorResponse {
+                error: e.message,
+                position: e.position,
+            })
+        }
+    };
+
+    match service::structured_search(&pool, &expr, &query, claims.map(|c| c.sub)).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    }
+}
+
 #[utoipa::path(
     get,
     tag = "jbovlaste",
@@ -235,6 +296,7 @@ pub async fn search_definitions(
 
     // Use fast search if explicitly requested via 'fast' parameter, or for non-logged-in users
     let use_fast_search = query.fast.unwrap_or(false) || claims.is_none();
+    let user_id = claims.as_ref().map(|c| c.sub);
 
     let cache_key = generate_search_cache_key(&query);
 
@@ -282,17 +344,101 @@ pub async fn search_definitions(
         )
         .await
     {
-        Ok(response) => HttpResponse::Ok().json(DefinitionListResponse {
-            definitions: response.definitions,
-            decomposition: response.decomposition,
-            total: response.total,
-            page,
-            per_page,
-        }),
+        Ok(response) => {
+            let search_mode = if use_fast_search { "fast" } else { "full" };
+            spawn_record_search(pool.clone(), &query, user_id, search_mode, response.total);
+            HttpResponse::Ok().json(DefinitionListResponse {
+                definitions: response.definitions,
+                decomposition: response.decomposition,
+                total: response.total,
+                page,
+                per_page,
+                suggestions: response.suggestions,
+            })
+        }
         Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
     }
 }
 
+/// Stores a first-page search in `user_search_history` without delaying the response.
+fn spawn_record_search(
+    pool: web::Data<Pool>,
+    query: &SearchDefinitionsQuery,
+    user_id: Option<i32>,
+    search_mode: &'static str,
+    result_count: i64,
+) {
+    let search_query = query.search.as_deref().unwrap_or("").trim().to_string();
+    // Paging through results would count the same search several times
+    if search_query.is_empty() || query.page.unwrap_or(1) != 1 {
+        return;
+    }
+
+    let languages = query.languages.as_ref().and_then(|langs| {
+        langs
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(str::parse::<i32>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+    });
+
+    let entry = SearchHistoryEntry {
+        user_id,
+        search_query,
+        search_mode,
+        result_count,
+        languages,
+        search_params: json!({
+            "selmaho": query.selmaho,
+            "word_type": query.word_type,
+            "username": query.username,
+            "source_langid": query.source_langid,
+            "sort_by": query.sort_by,
+        }),
+    };
+
+    actix_web::rt::spawn(async move {
+        if let Err(e) = service::record_search(&pool, &entry).await {
+            log::warn!("Failed to record search history: {}", e);
+        }
+    });
+}
+
+#[utoipa::path(
+    get,
+    tag = "jbovlaste",
+    path = "/jbovlaste/search-analytics",
+    params(
+        ("query" = SearchAnalyticsQuery, Query, description = "Aggregation window and limits")
+    ),
+    responses(
+        (status = 200, description = "Aggregated search analytics", body = SearchAnalyticsResponse),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["view_search_analytics"])
+    ),
+    summary = "Get search analytics",
+    description = "Aggregates recorded dictionary searches: most frequent queries, queries that lexical \
+                  search never answered, queries only semantic search answered, and per-language search \
+                  volume over time. Intended for dictionary editors looking for missing entries."
+)]
+#[get("/search-analytics")]
+#[protect("view_search_analytics")]
+pub async fn get_search_analytics(
+    pool: web::Data<Pool>,
+    query: web::Query<SearchAnalyticsQuery>,
+) -> impl Responder {
+    match service::get_search_analytics(&pool, &query).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to compute search analytics: {}", e)
+        })),
+    }
+}
+
 #[utoipa::path(
     get,
     tag = "jbovlaste",
@@ -302,6 +448,7 @@ pub async fn search_definitions(
                   etymologies, and metadata. Returns a 404 if the valsi is not found.",
     params(
         ("id_or_word" = String, Path, description = "Valsi ID or word"),
+        ("source_langid" = Option<i32>, Query, description = "Source language of the valsi for word lookups (default 1, Lojban)"),
     ),
     responses(
         (status = 200, description = "Valsi details", body = ValsiDetail),
@@ -316,8 +463,15 @@ pub async fn search_definitions(
 pub async fn get_entry_details(
     pool: web::Data<Pool>,
     id_or_word: web::Path<String>,
+    query: web::Query<SourceLanguageQuery>,
 ) -> impl Responder {
-    match service::get_entry_details(&pool, &id_or_word.into_inner()).await {
+    match service::get_entry_details(
+        &pool,
+        &id_or_word.into_inner(),
+        query.source_langid.unwrap_or(1),
+    )
+    .await
+    {
         Ok(valsi_detail) => HttpResponse::Ok().json(json!({
             "valsi": valsi_detail
         })),
@@ -342,7 +496,8 @@ pub async fn get_entry_details(
     params(
         ("id_or_word" = String, Path, description = "Valsi ID or word"),
         ("langid" = Option<i32>, Query, description = "Preferred language ID"),
-        ("username" = Option<String>, Query, description = "Preferred username")
+        ("username" = Option<String>, Query, description = "Preferred username"),
+        ("source_langid" = Option<i32>, Query, description = "Source language of the valsi for word lookups (default 1, Lojban)")
     ),
     responses(
         (status = 200, description = "List of definitions", body = Vec<DefinitionDetail>),
@@ -371,6 +526,7 @@ pub async fn get_definitions_by_entry(
         claims.map(|c| c.sub),
         query.langid,
         query.username.clone(),
+        query.source_langid.unwrap_or(1),
         &redis_cache,
     )
     .await
@@ -392,11 +548,14 @@ pub async fn get_definitions_by_entry(
     path = "/jbovlaste/valsi",
     summary = "Add new definition",
     description = "Creates a new definition. The word type is automatically \
-                  determined based on Lojban morphology rules. Includes validation of the word structure.",
+                  determined based on Lojban (or, with `source_langid` 58, Loglan) morphology rules, \
+                  or checked against `word_type` when given. \
+                  New Lojban words must not be too similar to existing gismu and fu'ivla must pass the \
+                  slinku'i test; rejections carry a machine-readable `validation.code`.",
     request_body = AddDefinitionRequest,
     responses(
         (status = 200, description = "Valsi added successfully", body = AddValsiResponse),
-        (status = 400, description = "Invalid request"),
+        (status = 400, description = "Invalid request or failed morphological validation", body = AddValsiResponse),
         (status = 403, description = "Insufficient permissions"),
         (status = 500, description = "Internal server error")
     ),
@@ -409,7 +568,7 @@ pub async fn get_definitions_by_entry(
 pub async fn add_definition(
     pool: web::Data<Pool>,
     claims: Claims,
-    parsers: web::Data<Arc<HashMap<i32, Peg>>>,
+    parsers: web::Data<WorkerParsers>,
     redis_cache: web::Data<RedisCache>,
     request: web::Json<AddDefinitionRequest>,
 ) -> impl Responder {
@@ -420,6 +579,7 @@ pub async fn add_definition(
                 word_type: String::new(),
                 definition_id: 0,
                 error: Some(e),
+                validation: None,
             });
         }
     }
@@ -427,7 +587,7 @@ pub async fn add_definition(
     match service::add_definition(
         &pool,
         &claims,
-        parsers.get_ref().clone(),
+        parsers.word_parsers(),
         &request,
         &redis_cache,
         true,
@@ -439,13 +599,24 @@ pub async fn add_definition(
             word_type,
             definition_id,
             error: None,
+            validation: None,
         }),
-        Err(e) => HttpResponse::InternalServerError().json(AddValsiResponse {
-            success: false,
-            word_type: String::new(),
-            definition_id: 0,
-            error: Some(e.to_string()),
-        }),
+        Err(e) => match e.downcast::<ValsiValidationError>() {
+            Ok(validation) => HttpResponse::BadRequest().json(AddValsiResponse {
+                success: false,
+                word_type: validation.analyzed_type.clone().unwrap_or_default(),
+                definition_id: 0,
+                error: Some(validation.message.clone()),
+                validation: Some(*validation),
+            }),
+            Err(e) => HttpResponse::InternalServerError().json(AddValsiResponse {
+                success: false,
+                word_type: String::new(),
+                definition_id: 0,
+                error: Some(e.to_string()),
+                validation: None,
+            }),
+        },
     }
 }
 
@@ -482,6 +653,61 @@ pub async fn get_definition(
     }
 }
 
+#[utoipa::path(
+    get,
+    tag = "jbovlaste",
+    path = "/jbovlaste/definition/{id}/similar",
+    params(
+        ("id" = i32, Path, description = "Definition ID"),
+        ("query" = SimilarDefinitionsQuery, Query, description = "Similarity filters")
+    ),
+    responses(
+        (status = 200, description = "Nearest definitions by semantic similarity", body = SimilarDefinitionsResponse),
+        (status = 404, description = "Definition not found or not yet embedded"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Find similar definitions",
+    description = "Returns definitions whose embeddings are closest to the given definition, excluding \
+                  the definition's own valsi. Results are restricted to the same definition language unless \
+                  `any_language` is set, and can be filtered by word type and selmaho."
+)]
+#[get("/definition/{id}/similar")]
+pub async fn get_similar_definitions(
+    pool: web::Data<Pool>,
+    redis_cache: web::Data<RedisCache>,
+    id: web::Path<i32>,
+    query: web::Query<SimilarDefinitionsQuery>,
+) -> impl Responder {
+    let definition_id = id.into_inner();
+    let cache_key = format!(
+        "similar:{}:{}:{}:{}:{}",
+        definition_id,
+        query.limit.unwrap_or(10),
+        query.any_language.unwrap_or(false),
+        query.word_type.unwrap_or(0),
+        query.selmaho.as_deref().unwrap_or("")
+    );
+
+    match redis_cache
+        .get_or_set(
+            &cache_key,
+            || async { service::get_similar_definitions(&pool, definition_id, &query).await },
+            // Embeddings are recalculated in the background, so keep neighbours fresh-ish
+            Some(std::time::Duration::from_secs(3600)),
+        )
+        .await
+    {
+        Ok(Some(definitions)) => HttpResponse::Ok().json(SimilarDefinitionsResponse {
+            definition_id,
+            definitions,
+        }),
+        Ok(None) => HttpResponse::NotFound().json(json!({
+            "error": "Definition not found or has no embedding yet"
+        })),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/jbovlaste/definition_image/{definition_id}/image",
@@ -528,7 +754,7 @@ pub async fn get_definition_image(
     tag = "jbovlaste",
     path = "/jbovlaste/valsi/{id}",
     summary = "Update definition",
-    description = "Updates an existing definition with new content. Includes validation of any MathJax/LaTeX \
+    description = "Updates an existing definition with new content. Validates any MathJax/LaTeX \
                   content and maintains version history of the changes.",
     params(
         ("id" = i32, Path, description = "Definition ID")
@@ -554,7 +780,9 @@ pub async fn update_definition(
 ) -> impl Responder {
     let definition_id = id.into_inner();
 
-    let options = MathJaxValidationOptions { use_tectonic: true };
+    let options = MathJaxValidationOptions {
+        use_tectonic: false,
+    };
 
     if let Err(e) = validate_mathjax(&req.definition, options).await {
         return HttpResponse::BadRequest().json(UpdateDefinitionResponse {
@@ -865,7 +1093,7 @@ pub async fn list_active_imports(broadcaster: web::Data<Broadcaster>) -> impl Re
 pub async fn bulk_import_definitions(
     pool: web::Data<Pool>,
     claims: Claims,
-    parsers: web::Data<Arc<HashMap<i32, Peg>>>,
+    parsers: web::Data<WorkerParsers>,
     redis_cache: web::Data<RedisCache>,
     broadcaster: web::Data<Broadcaster>,
     request: web::Json<BulkImportRequest>,
@@ -892,6 +1120,9 @@ pub async fn bulk_import_definitions(
         log::error!("Failed to serialize job_id event JSON");
     }
 
+    // Snapshot the parsers so a grammar reload mid-import doesn't change the rules
+    let word_parsers = parsers.word_parsers();
+
     // Spawn the import task
     actix_web::rt::spawn(async move {
         let params = BulkImportParams {
@@ -904,7 +1135,7 @@ pub async fn bulk_import_definitions(
         let result = service::bulk_import_definitions(
             &pool,
             &claims,
-            parsers.get_ref().clone(), // Pass parser map
+            word_parsers, // Pass parser map
             params,
             &broadcaster, // Pass broadcaster reference
             &redis_cache,
@@ -989,6 +1220,9 @@ pub async fn bulk_import_definitions(
     get,
     path = "/jbovlaste/types",
     tag = "jbovlaste",
+    params(
+        ("source_langid" = Option<i32>, Query, description = "Only types usable for this source language")
+    ),
     responses(
         (status = 200, description = "List of valsi types", body = ValsiTypeListResponse),
         (status = 500, description = "Internal server error")
@@ -998,16 +1232,58 @@ pub async fn bulk_import_definitions(
     ),
     summary = "List valsi types",
     description = "Get all valid valsi (word) types in the dictionary. Types include gismu, \
-                  cmavo, lujvo, etc. Used for categorizing and filtering words."
+                  cmavo, lujvo, etc. for Lojban and primitive, complex, borrowing, etc. for Loglan. \
+                  Used for categorizing and filtering words."
 )]
 #[get("/types")]
-pub async fn list_valsi_types(pool: web::Data<Pool>) -> impl Responder {
-    match service::list_valsi_types(&pool).await {
+pub async fn list_valsi_types(
+    pool: web::Data<Pool>,
+    query: web::Query<SourceLanguageQuery>,
+) -> impl Responder {
+    match service::list_valsi_types(&pool, query.source_langid).await {
         Ok(types) => HttpResponse::Ok().json(ValsiTypeListResponse { types }),
         Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/jbovlaste/sources/{source_langid}",
+    tag = "jbovlaste",
+    params(
+        ("source_langid" = i32, Path, description = "Source language ID, e.g. 1 for Lojban or 58 for Loglan")
+    ),
+    responses(
+        (status = 200, description = "Source language overview", body = SourceLanguageOverview),
+        (status = 404, description = "Language not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Get source language overview",
+    description = "Landing page data for one source language of the dictionary: valsi and \
+                  definition counts, valsi per word type, definitions per definition language \
+                  and the most recently added valsi. Cached for ten minutes."
+)]
+#[get("/sources/{source_langid}")]
+pub async fn get_source_language_overview(
+    pool: web::Data<Pool>,
+    redis_cache: web::Data<RedisCache>,
+    source_langid: web::Path<i32>,
+) -> impl Responder {
+    match service::get_source_language_overview(&pool, source_langid.into_inner(), &redis_cache)
+        .await
+    {
+        Ok(overview) => HttpResponse::Ok().json(overview),
+        Err(e) => match e.downcast_ref::<AppError>() {
+            Some(AppError::NotFound(message)) => HttpResponse::NotFound().json(json!({
+                "error": message
+            })),
+            _ => HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to load source language overview: {}", e)
+            })),
+        },
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/jbovlaste/definition_image/{id}/image",
@@ -1238,3 +1514,275 @@ pub async fn list_client_definitions_handler(
         }
     }
 }
+
+#[utoipa::path(
+    get,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day",
+    params(
+        ("query" = WordOfTheDayQuery, Query, description = "Optional definition language")
+    ),
+    responses(
+        (status = 200, description = "Today's featured valsi", body = WordOfTheDay),
+        (status = 404, description = "No word has been featured yet"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Get the word of the day",
+    description = "Returns today's featured valsi, chosen daily from the curated queue or automatically \
+                  from well-voted definitions; until today's is picked, the previous one. Pass `lang` to \
+                  show the best definition in another language."
+)]
+#[get("/word-of-the-day")]
+pub async fn get_word_of_the_day(
+    pool: web::Data<Pool>,
+    query: web::Query<WordOfTheDayQuery>,
+) -> impl Responder {
+    let today = Utc::now().date_naive();
+    match service::get_word_of_the_day(&pool, today, query.lang.as_deref()).await {
+        Ok(Some(entry)) => HttpResponse::Ok().json(entry),
+        Ok(None) => HttpResponse::NotFound().json(json!({
+            "error": "No word of the day available"
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to get word of the day: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day/history",
+    params(
+        ("query" = WordOfTheDayQuery, Query, description = "Definition language and number of days")
+    ),
+    responses(
+        (status = 200, description = "Past words of the day, newest first", body = Vec<WordOfTheDay>),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Get word of the day history",
+    description = "Lists previously featured valsi, newest first, up to 365 days."
+)]
+#[get("/word-of-the-day/history")]
+pub async fn get_word_of_the_day_history(
+    pool: web::Data<Pool>,
+    query: web::Query<WordOfTheDayQuery>,
+) -> impl Responder {
+    match service::get_word_of_the_day_history(
+        &pool,
+        Utc::now().date_naive(),
+        query.lang.as_deref(),
+        query.limit.unwrap_or(30),
+    )
+    .await
+    {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to get word of the day history: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day/feed",
+    params(
+        ("query" = WordOfTheDayQuery, Query, description = "Feed format, definition language and length")
+    ),
+    responses(
+        (status = 200, description = "Atom or RSS feed", content_type = "application/xml"),
+        (status = 400, description = "Unknown feed format"),
+        (status = 500, description = "Internal server error")
+    ),
+    summary = "Get word of the day feed",
+    description = "Atom (default) or RSS 2.0 feed of recent words of the day."
+)]
+#[get("/word-of-the-day/feed")]
+pub async fn get_word_of_the_day_feed(
+    pool: web::Data<Pool>,
+    query: web::Query<WordOfTheDayQuery>,
+) -> impl Responder {
+    let (rss, content_type) = match query.format.as_deref().unwrap_or("atom") {
+        "atom" => (false, "application/atom+xml; charset=utf-8"),
+        "rss" => (true, "application/rss+xml; charset=utf-8"),
+        other => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!("Unknown feed format '{}', expected atom or rss", other)
+            }))
+        }
+    };
+
+    match service::get_word_of_the_day_feed(
+        &pool,
+        Utc::now().date_naive(),
+        query.lang.as_deref(),
+        query.limit.unwrap_or(30),
+        rss,
+    )
+    .await
+    {
+        Ok(xml) => HttpResponse::Ok().content_type(content_type).body(xml),
+        Err(e) => HttpResponse::InternalServerError()
+            .body(format!("Error generating word of the day feed: {}", e)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day/queue",
+    responses(
+        (status = 200, description = "Pending curated entries", body = Vec<FeatureQueueEntry>),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_featured_words"])
+    ),
+    summary = "List the word of the day queue",
+    description = "Lists curated entries that have not been featured yet, pinned dates first."
+)]
+#[get("/word-of-the-day/queue")]
+#[protect("manage_featured_words")]
+pub async fn list_feature_queue(pool: web::Data<Pool>) -> impl Responder {
+    match service::list_feature_queue(&pool).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to list word of the day queue: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day/queue",
+    request_body = FeatureQueueRequest,
+    responses(
+        (status = 201, description = "Entry queued", body = FeatureQueueEntry),
+        (status = 400, description = "Unknown valsi, mismatched definition or date already taken"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_featured_words"])
+    ),
+    summary = "Queue a word of the day",
+    description = "Adds a valsi to the curated queue, optionally pinned to a date and with a specific \
+                  definition and editorial note. Queued entries take precedence over automatic picks."
+)]
+#[post("/word-of-the-day/queue")]
+#[protect("manage_featured_words")]
+pub async fn add_to_feature_queue(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    request: web::Json<FeatureQueueRequest>,
+) -> impl Responder {
+    match service::add_to_feature_queue(&pool, claims.sub, &request).await {
+        Ok(entry) => HttpResponse::Created().json(entry),
+        Err(e) => match e.downcast::<AppError>() {
+            Ok(error) => match *error {
+                AppError::BadRequest(message) => HttpResponse::BadRequest().json(json!({
+                    "error": message
+                })),
+                error => HttpResponse::InternalServerError().json(json!({
+                    "error": format!("Failed to queue word of the day: {}", error)
+                })),
+            },
+            Err(e) => HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to queue word of the day: {}", e)
+            })),
+        },
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day/queue/{id}",
+    params(
+        ("id" = i32, Path, description = "Queue entry ID")
+    ),
+    responses(
+        (status = 200, description = "Entry removed"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "Entry not found or already featured"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_featured_words"])
+    ),
+    summary = "Remove a queued word of the day"
+)]
+#[delete("/word-of-the-day/queue/{id}")]
+#[protect("manage_featured_words")]
+pub async fn remove_from_feature_queue(
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+) -> impl Responder {
+    match service::remove_from_feature_queue(&pool, id.into_inner()).await {
+        Ok(true) => HttpResponse::Ok().json(json!({ "success": true })),
+        Ok(false) => HttpResponse::NotFound().json(json!({
+            "error": "Queue entry not found or already featured"
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to remove queue entry: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day/subscription",
+    request_body = WordOfTheDaySubscriptionRequest,
+    responses(
+        (status = 200, description = "Subscribed to the daily email"),
+        (status = 400, description = "Unknown language"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "Subscribe to the word of the day email",
+    description = "Opts the current user into a daily email with the word of the day. Sending again \
+                  updates the preferred definition language."
+)]
+#[post("/word-of-the-day/subscription")]
+pub async fn subscribe_word_of_the_day(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    request: web::Json<WordOfTheDaySubscriptionRequest>,
+) -> impl Responder {
+    match service::subscribe_word_of_the_day(&pool, claims.sub, request.lang.as_deref()).await {
+        Ok(()) => HttpResponse::Ok().json(json!({ "success": true })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": e.to_string()
+        })),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "jbovlaste",
+    path = "/jbovlaste/word-of-the-day/subscription",
+    responses(
+        (status = 200, description = "Unsubscribed from the daily email"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    summary = "Unsubscribe from the word of the day email"
+)]
+#[delete("/word-of-the-day/subscription")]
+pub async fn unsubscribe_word_of_the_day(pool: web::Data<Pool>, claims: Claims) -> impl Responder {
+    match service::unsubscribe_word_of_the_day(&pool, claims.sub).await {
+        Ok(()) => HttpResponse::Ok().json(json!({ "success": true })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to unsubscribe: {}", e)
+        })),
+    }
+}
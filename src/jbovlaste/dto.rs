@@ -1,5 +1,6 @@
+use super::valsi_validation::ValsiValidationError;
 use super::{models::KeywordMapping, DefinitionDetail, RecentChange};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::{Validate, ValidationError};
@@ -20,6 +21,28 @@ pub struct SearchDefinitionsQuery {
     pub fast: Option<bool>,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StructuredSearchQuery {
+    /// Query expression, e.g. `selmaho:UI type:cmavo lang:en score>2 rafsi:kla "go to"`
+    #[schema(example = "type:gismu (rafsi:kla OR gloss:go) -score<0")]
+    pub q: String,
+    #[schema(default = 1)]
+    pub page: Option<i64>,
+    #[schema(default = 20)]
+    pub per_page: Option<i64>,
+    #[schema(default = "word", example = "score")]
+    pub sort_by: Option<String>,
+    #[schema(default = "asc", example = "desc")]
+    pub sort_order: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryErrorResponse {
+    pub error: String,
+    /// Character offset of the problem in the query string
+    pub position: usize,
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct NonLojbanDefinitionsQuery {
     pub page: Option<i64>,
@@ -57,11 +80,39 @@ pub struct DefinitionListResponse {
     pub per_page: i64,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub decomposition: Vec<String>,
+    /// "Did you mean" spellings, only populated when the search found nothing
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
 }
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SimilarDefinitionsQuery {
+    #[schema(default = 10)]
+    pub limit: Option<i64>,
+    /// Include definitions written in any language, not just the source definition's language
+    #[schema(default = false)]
+    pub any_language: Option<bool>,
+    pub word_type: Option<i16>,
+    pub selmaho: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SimilarDefinitionsResponse {
+    pub definition_id: i32,
+    pub definitions: Vec<DefinitionDetail>,
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct ValsiDefinitionsQuery {
     pub langid: Option<i32>,
     pub username: Option<String>,
+    /// Source language of the valsi when looking it up by word; defaults to Lojban (1)
+    pub source_langid: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SourceLanguageQuery {
+    /// Source language of the valsi (`languages.langid`, e.g. 1 for Lojban, 58 for Loglan)
+    pub source_langid: Option<i32>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -87,6 +138,10 @@ pub struct AddDefinitionRequest {
     pub image: Option<ImageData>,
     #[serde(default)]
     pub metadata: Option<serde_json::Value>,
+    /// Declared valsi type (e.g. `gismu`, `experimental cmavo`, `zei-lujvo`); Lojban words
+    /// must have the matching form. Inferred from the word when omitted.
+    #[serde(default)]
+    pub word_type: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -95,6 +150,9 @@ pub struct AddValsiResponse {
     pub word_type: String,
     pub definition_id: i32,
     pub error: Option<String>,
+    /// Why the word was rejected, for Lojban words that fail morphological validation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation: Option<ValsiValidationError>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -223,3 +281,148 @@ pub struct ImageData {
     pub data: String, // Base64 encoded image data
     pub mime_type: String,
 }
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SearchAnalyticsQuery {
+    /// Number of days of history to aggregate
+    #[schema(default = 30)]
+    pub days: Option<i32>,
+    /// Maximum number of queries per list
+    #[schema(default = 50)]
+    pub limit: Option<i64>,
+    /// Trend bucket size: day, week or month
+    #[schema(default = "day")]
+    pub interval: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchQueryStat {
+    pub query: String,
+    pub searches: i64,
+    pub avg_results: Option<f64>,
+    #[schema(value_type = String, format = DateTime)]
+    pub last_searched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LanguageTrendPoint {
+    #[schema(value_type = String, format = DateTime)]
+    pub period: DateTime<Utc>,
+    /// `None` for searches that were not restricted to specific languages
+    pub langid: Option<i32>,
+    pub language_name: Option<String>,
+    pub searches: i64,
+    pub zero_result_searches: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchAnalyticsResponse {
+    pub days: i32,
+    pub total_searches: i64,
+    pub top_queries: Vec<SearchQueryStat>,
+    /// Queries that never returned a result in lexical search
+    pub zero_result_queries: Vec<SearchQueryStat>,
+    /// Queries that only semantic search could answer
+    pub semantic_only_queries: Vec<SearchQueryStat>,
+    pub language_trends: Vec<LanguageTrendPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WordOfTheDay {
+    #[schema(value_type = String, format = Date)]
+    pub date: NaiveDate,
+    pub valsiid: i32,
+    pub word: String,
+    pub type_name: String,
+    pub definitionid: Option<i32>,
+    pub definition: Option<String>,
+    pub notes: Option<String>,
+    pub langid: Option<i32>,
+    /// Editorial note added when the entry was queued
+    pub note: Option<String>,
+    /// "queue" for curated entries, "auto" for scheduler picks
+    pub source: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WordOfTheDayQuery {
+    /// Language tag of the definition to show instead of the featured one, e.g. `en`
+    pub lang: Option<String>,
+    /// Number of past days to return (history and feeds only)
+    #[schema(default = 30)]
+    pub limit: Option<i64>,
+    /// Feed format: atom or rss (feed only)
+    #[schema(default = "atom")]
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FeatureQueueRequest {
+    pub word: String,
+    pub source_langid: Option<i32>,
+    /// Definition to feature; defaults to the best-scored English definition
+    pub definition_id: Option<i32>,
+    pub note: Option<String>,
+    /// Pin the entry to a specific day; unpinned entries are used oldest first
+    #[schema(value_type = Option<String>, format = Date)]
+    pub scheduled_for: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeatureQueueEntry {
+    pub id: i32,
+    pub valsiid: i32,
+    pub word: String,
+    pub definitionid: Option<i32>,
+    pub note: Option<String>,
+    #[schema(value_type = Option<String>, format = Date)]
+    pub scheduled_for: Option<NaiveDate>,
+    pub added_by: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WordOfTheDaySubscriptionRequest {
+    /// Preferred definition language tag for the email, e.g. `en`
+    pub lang: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SourceLanguageTypeCount {
+    pub type_id: i16,
+    pub descriptor: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SourceLanguageDefinitionCount {
+    pub langid: i32,
+    pub tag: String,
+    pub realname: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SourceLanguageRecentValsi {
+    pub valsiid: i32,
+    pub word: String,
+    pub type_name: String,
+    /// Unix timestamp the valsi was added
+    pub time: i32,
+}
+
+/// Landing page data for one source language of the dictionary
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SourceLanguageOverview {
+    pub langid: i32,
+    pub tag: String,
+    pub realname: String,
+    pub valsi_count: i64,
+    pub definition_count: i64,
+    /// Valsi per word type, largest first
+    pub types: Vec<SourceLanguageTypeCount>,
+    /// Definitions per definition language, largest first
+    pub definition_languages: Vec<SourceLanguageDefinitionCount>,
+    pub recent_valsi: Vec<SourceLanguageRecentValsi>,
+}
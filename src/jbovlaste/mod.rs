@@ -2,7 +2,9 @@ pub mod broadcast;
 pub mod controller;
 pub mod dto;
 pub mod models;
+pub mod query_language;
 pub mod service;
+pub mod valsi_validation;
 
 use broadcast::Broadcaster;
 
@@ -22,13 +24,19 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .service(controller::search_definitions)
             .service(controller::semantic_search)
             .service(controller::get_definition)
+            .service(controller::get_similar_definitions)
             .service(controller::list_definitions)
+            .service(controller::structured_search)
             .service(controller::list_non_lojban_definitions)
             .service(controller::get_definition_image)
             .service(controller::get_entry_details)
             .service(controller::get_definitions_by_entry)
             .service(controller::get_recent_changes)
             .service(controller::list_valsi_types)
+            .service(controller::get_source_language_overview)
+            .service(controller::get_word_of_the_day)
+            .service(controller::get_word_of_the_day_history)
+            .service(controller::get_word_of_the_day_feed)
             .service(
                 web::scope("")
                     .wrap(GrantsMiddleware::with_extractor(extract_authorities))
@@ -44,7 +52,13 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
                     .service(controller::list_bulk_import_clients_handler)
                     .service(controller::upload_definition_image)
                     .service(controller::list_client_definitions_handler)
-                    .service(controller::get_bulk_votes),
+                    .service(controller::get_bulk_votes)
+                    .service(controller::get_search_analytics)
+                    .service(controller::list_feature_queue)
+                    .service(controller::add_to_feature_queue)
+                    .service(controller::remove_from_feature_queue)
+                    .service(controller::subscribe_word_of_the_day)
+                    .service(controller::unsubscribe_word_of_the_day),
             ),
     );
 }
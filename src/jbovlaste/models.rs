@@ -20,6 +20,18 @@ pub struct SearchDefinitionsParams {
     pub source_langid: Option<i32>,
 }
 
+/// A single search as stored in `user_search_history`
+#[derive(Debug)]
+pub struct SearchHistoryEntry {
+    pub user_id: Option<i32>,
+    pub search_query: String,
+    /// "fast", "full" or "semantic"
+    pub search_mode: &'static str,
+    pub result_count: i64,
+    pub languages: Option<Vec<i32>>,
+    pub search_params: serde_json::Value,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ValsiEntry {
     pub valsiid: i32,
@@ -84,6 +96,8 @@ pub struct DefinitionResponse {
     pub definitions: Vec<DefinitionDetail>,
     pub decomposition: Vec<String>,
     pub total: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
 }
 
 impl From<tokio_postgres::Row> for DefinitionDetail {
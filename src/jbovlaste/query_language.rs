@@ -0,0 +1,551 @@
+//! Structured search expressions for the dictionary, e.g.
+//! `selmaho:UI type:cmavo lang:en score>2 rafsi:kla "go to"`.
+//!
+//! Terms are joined by implicit AND; `AND`, `OR`, `NOT` (or a leading `-`)
+//! and parentheses are supported. Values are never interpolated into SQL:
+//! every term compiles to a placeholder plus a bound parameter.
+
+use thiserror::Error;
+use tokio_postgres::types::ToSql;
+
+const MAX_QUERY_LENGTH: usize = 1000;
+const MAX_TERMS: usize = 32;
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Error, PartialEq)]
+#[error("{message} (at position {position})")]
+pub struct QueryParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl QueryParseError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        Self {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Text,
+    Word,
+    Selmaho,
+    Type,
+    Lang,
+    Source,
+    Score,
+    Rafsi,
+    User,
+    Gloss,
+    Definition,
+    Notes,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "word" | "valsi" => Some(Field::Word),
+            "selmaho" => Some(Field::Selmaho),
+            "type" => Some(Field::Type),
+            "lang" | "language" => Some(Field::Lang),
+            "source" => Some(Field::Source),
+            "score" => Some(Field::Score),
+            "rafsi" => Some(Field::Rafsi),
+            "user" | "author" => Some(Field::User),
+            "gloss" => Some(Field::Gloss),
+            "def" | "definition" => Some(Field::Definition),
+            "notes" => Some(Field::Notes),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Op {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub field: Field,
+    pub op: Op,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Term(Term),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn mentions(&self, field: Field) -> bool {
+        match self {
+            Expr::Term(term) => term.field == field,
+            Expr::Not(inner) => inner.mentions(field),
+            Expr::And(a, b) | Expr::Or(a, b) => a.mentions(field) || b.mentions(field),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term(Term),
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, QueryParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        match c {
+            '(' => {
+                tokens.push((Token::LParen, start));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::RParen, start));
+                i += 1;
+            }
+            '-' if chars.get(i + 1).is_some_and(|n| !n.is_whitespace()) => {
+                tokens.push((Token::Not, start));
+                i += 1;
+            }
+            '"' => {
+                let (value, next) = read_quoted(&chars, i)?;
+                tokens.push((
+                    Token::Term(Term {
+                        field: Field::Text,
+                        op: Op::Eq,
+                        value,
+                    }),
+                    start,
+                ));
+                i = next;
+            }
+            _ => {
+                let mut word = String::new();
+                while i < chars.len() && !chars[i].is_whitespace() && !"()\"".contains(chars[i]) {
+                    word.push(chars[i]);
+                    i += 1;
+                }
+                match word.as_str() {
+                    "AND" | "&&" => tokens.push((Token::And, start)),
+                    "OR" | "||" => tokens.push((Token::Or, start)),
+                    "NOT" => tokens.push((Token::Not, start)),
+                    _ => {
+                        let term = match split_field(&word) {
+                            Some((field, name, op, value)) => {
+                                let value = if value.is_empty() && chars.get(i) == Some(&'"') {
+                                    let (quoted, next) = read_quoted(&chars, i)?;
+                                    i = next;
+                                    quoted
+                                } else {
+                                    value.to_string()
+                                };
+                                if value.is_empty() {
+                                    return Err(QueryParseError::new(
+                                        format!("Missing value for field '{}'", name),
+                                        start,
+                                    ));
+                                }
+                                if op != Op::Eq && field != Field::Score {
+                                    return Err(QueryParseError::new(
+                                        format!("Field '{}' only supports ':'", name),
+                                        start,
+                                    ));
+                                }
+                                if field == Field::Score && value.parse::<f32>().is_err() {
+                                    return Err(QueryParseError::new(
+                                        format!("Invalid score '{}'", value),
+                                        start,
+                                    ));
+                                }
+                                Term { field, op, value }
+                            }
+                            None => Term {
+                                field: Field::Text,
+                                op: Op::Eq,
+                                value: word.clone(),
+                            },
+                        };
+                        tokens.push((Token::Term(term), start));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), QueryParseError> {
+    let mut value = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => return Ok((value, i + 1)),
+            '\\' if i + 1 < chars.len() => {
+                value.push(chars[i + 1]);
+                i += 2;
+            }
+            c => {
+                value.push(c);
+                i += 1;
+            }
+        }
+    }
+    Err(QueryParseError::new("Unterminated quote", start))
+}
+
+/// Splits `field:value`, `field>=value` etc. Returns `None` for plain words and for
+/// names that aren't fields, such as the scheme of a URL, which are searched as text.
+fn split_field(word: &str) -> Option<(Field, &str, Op, &str)> {
+    let idx = word.find([':', '=', '>', '<'])?;
+    let name = &word[..idx];
+    let field = Field::from_name(name)?;
+    let rest = &word[idx..];
+    let (op, len) = if rest.starts_with(">=") {
+        (Op::Ge, 2)
+    } else if rest.starts_with("<=") {
+        (Op::Le, 2)
+    } else if rest.starts_with('>') {
+        (Op::Gt, 1)
+    } else if rest.starts_with('<') {
+        (Op::Lt, 1)
+    } else {
+        (Op::Eq, 1)
+    };
+    Some((field, name, op, &rest[len..]))
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+    terms: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(_, p)| *p).unwrap_or(self.end)
+    }
+
+    fn parse_or(&mut self, depth: usize) -> Result<Expr, QueryParseError> {
+        let mut left = self.parse_and(depth)?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and(depth)?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self, depth: usize) -> Result<Expr, QueryParseError> {
+        let mut left = self.parse_not(depth)?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.pos += 1;
+                }
+                // Juxtaposed terms are an implicit AND
+                Some(Token::Term(_)) | Some(Token::Not) | Some(Token::LParen) => {}
+                _ => break,
+            }
+            let right = self.parse_not(depth)?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self, depth: usize) -> Result<Expr, QueryParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not(depth)?)));
+        }
+        self.parse_primary(depth)
+    }
+
+    fn parse_primary(&mut self, depth: usize) -> Result<Expr, QueryParseError> {
+        let position = self.position();
+        match self.tokens.get(self.pos).cloned() {
+            Some((Token::LParen, _)) => {
+                if depth >= MAX_DEPTH {
+                    return Err(QueryParseError::new("Too many nested parentheses", position));
+                }
+                self.pos += 1;
+                let expr = self.parse_or(depth + 1)?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(QueryParseError::new("Expected ')'", self.position()));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some((Token::Term(term), _)) => {
+                self.pos += 1;
+                self.terms += 1;
+                if self.terms > MAX_TERMS {
+                    return Err(QueryParseError::new(
+                        format!("Query has more than {} terms", MAX_TERMS),
+                        position,
+                    ));
+                }
+                Ok(Expr::Term(term))
+            }
+            Some((Token::RParen, _)) => Err(QueryParseError::new("Unexpected ')'", position)),
+            Some(_) => Err(QueryParseError::new("Expected a search term", position)),
+            None => Err(QueryParseError::new("Unexpected end of query", position)),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr, QueryParseError> {
+    if input.len() > MAX_QUERY_LENGTH {
+        return Err(QueryParseError::new(
+            format!("Query is longer than {} characters", MAX_QUERY_LENGTH),
+            MAX_QUERY_LENGTH,
+        ));
+    }
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(QueryParseError::new("Query is empty", 0));
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: input.chars().count(),
+        terms: 0,
+    };
+    let expr = parser.parse_or(0)?;
+    if parser.pos < parser.tokens.len() {
+        return Err(QueryParseError::new("Unexpected token", parser.position()));
+    }
+    Ok(expr)
+}
+
+pub struct CompiledQuery {
+    /// Boolean SQL condition over `definitions d`
+    pub sql: String,
+    pub params: Vec<Box<dyn ToSql + Sync + Send>>,
+    /// Whether the query constrains the valsi source language itself
+    pub has_source_filter: bool,
+}
+
+/// Turns `*` wildcards into `%` and escapes LIKE metacharacters.
+fn like_pattern(value: &str, contains: bool) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+        .replace('*', "%");
+    if contains {
+        format!("%{}%", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Compiles an expression into a SQL condition whose placeholders start at `$first_param`.
+pub fn compile(expr: &Expr, first_param: usize) -> Result<CompiledQuery, QueryParseError> {
+    let mut params: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
+    let sql = compile_expr(expr, first_param, &mut params)?;
+    Ok(CompiledQuery {
+        sql,
+        params,
+        has_source_filter: expr.mentions(Field::Source),
+    })
+}
+
+fn compile_expr(
+    expr: &Expr,
+    first_param: usize,
+    params: &mut Vec<Box<dyn ToSql + Sync + Send>>,
+) -> Result<String, QueryParseError> {
+    match expr {
+        Expr::And(a, b) => Ok(format!(
+            "({} AND {})",
+            compile_expr(a, first_param, params)?,
+            compile_expr(b, first_param, params)?
+        )),
+        Expr::Or(a, b) => Ok(format!(
+            "({} OR {})",
+            compile_expr(a, first_param, params)?,
+            compile_expr(b, first_param, params)?
+        )),
+        Expr::Not(inner) => Ok(format!(
+            "(NOT COALESCE({}, false))",
+            compile_expr(inner, first_param, params)?
+        )),
+        Expr::Term(term) => {
+            let n = first_param + params.len();
+            let sql = match term.field {
+                Field::Text => {
+                    params.push(Box::new(like_pattern(&term.value, true)));
+                    format!("d.cached_search_text ILIKE ${}", n)
+                }
+                Field::Word => {
+                    params.push(Box::new(like_pattern(&term.value, false)));
+                    format!("d.cached_valsiword ILIKE ${}", n)
+                }
+                Field::Selmaho => {
+                    params.push(Box::new(like_pattern(&term.value, false)));
+                    format!("d.selmaho ILIKE ${}", n)
+                }
+                Field::Type => {
+                    params.push(Box::new(like_pattern(&term.value, false)));
+                    format!("d.cached_type_name ILIKE ${}", n)
+                }
+                Field::User => {
+                    params.push(Box::new(like_pattern(&term.value, false)));
+                    format!("d.cached_username ILIKE ${}", n)
+                }
+                Field::Gloss => {
+                    params.push(Box::new(like_pattern(&term.value.to_lowercase(), true)));
+                    format!("d.cached_glosswords ILIKE ${}", n)
+                }
+                Field::Definition => {
+                    params.push(Box::new(like_pattern(&term.value, true)));
+                    format!("d.definition ILIKE ${}", n)
+                }
+                Field::Notes => {
+                    params.push(Box::new(like_pattern(&term.value, true)));
+                    format!("d.notes ILIKE ${}", n)
+                }
+                Field::Rafsi => {
+                    params.push(Box::new(term.value.to_lowercase()));
+                    format!("${} = ANY(string_to_array(d.cached_rafsi, ' '))", n)
+                }
+                Field::Lang => match term.value.parse::<i32>() {
+                    Ok(langid) => {
+                        params.push(Box::new(langid));
+                        format!("d.langid = ${}", n)
+                    }
+                    Err(_) => {
+                        params.push(Box::new(term.value.clone()));
+                        format!("d.langid IN (SELECT langid FROM languages WHERE tag = ${})", n)
+                    }
+                },
+                Field::Source => match term.value.parse::<i32>() {
+                    Ok(langid) => {
+                        params.push(Box::new(langid));
+                        format!("d.cached_source_langid = ${}", n)
+                    }
+                    Err(_) => {
+                        params.push(Box::new(term.value.clone()));
+                        format!(
+                            "d.cached_source_langid IN (SELECT langid FROM languages WHERE tag = ${})",
+                            n
+                        )
+                    }
+                },
+                Field::Score => {
+                    // Already validated by the tokenizer
+                    let score = term.value.parse::<f32>().map_err(|_| {
+                        QueryParseError::new(format!("Invalid score '{}'", term.value), 0)
+                    })?;
+                    params.push(Box::new(score));
+                    format!(
+                        "COALESCE((SELECT SUM(value) FROM definitionvotes dv WHERE dv.definitionid = d.definitionid), 0) {} ${}",
+                        term.op.as_sql(),
+                        n
+                    )
+                }
+            };
+            Ok(sql)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(field: Field, op: Op, value: &str) -> Expr {
+        Expr::Term(Term {
+            field,
+            op,
+            value: value.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_implicit_and_with_fields() -> Result<(), QueryParseError> {
+        let expr = parse(r#"selmaho:UI score>2 "go to""#)?;
+        let expected = Expr::And(
+            Box::new(Expr::And(
+                Box::new(term(Field::Selmaho, Op::Eq, "UI")),
+                Box::new(term(Field::Score, Op::Gt, "2")),
+            )),
+            Box::new(term(Field::Text, Op::Eq, "go to")),
+        );
+        assert_eq!(expr, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_or_not_and_parentheses() -> Result<(), QueryParseError> {
+        let expr = parse("(type:gismu OR type:lujvo) -rafsi:kla")?;
+        let compiled = compile(&expr, 3)?;
+        assert_eq!(
+            compiled.sql,
+            "((d.cached_type_name ILIKE $3 OR d.cached_type_name ILIKE $4) AND \
+             (NOT COALESCE($5 = ANY(string_to_array(d.cached_rafsi, ' ')), false)))"
+        );
+        assert_eq!(compiled.params.len(), 3);
+        assert!(!compiled.has_source_filter);
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_report_position() {
+        assert_eq!(
+            parse("type:cmavo https://mw.lojban.org").map(|_| ()),
+            Ok(())
+        );
+        assert_eq!(
+            parse("(klama").map(|_| ()),
+            Err(QueryParseError::new("Expected ')'", 6))
+        );
+        assert_eq!(
+            parse("selmaho>UI").map(|_| ()),
+            Err(QueryParseError::new("Field 'selmaho' only supports ':'", 0))
+        );
+    }
+}
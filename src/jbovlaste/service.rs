@@ -1,4 +1,4 @@
-use crate::utils::remove_html_tags;
+use crate::utils::{escape_xml, remove_html_tags};
 use camxes_rs::peg::grammar::Peg;
 use chrono::TimeZone;
 use serde_json::json;
@@ -11,26 +11,38 @@ use deadpool_postgres::{Pool, Transaction};
 use log::debug;
 
 use super::broadcast::Broadcaster;
-use super::dto::ClientIdGroup;
+use super::dto::{
+    ClientIdGroup, FeatureQueueEntry, FeatureQueueRequest, LanguageTrendPoint,
+    SearchAnalyticsQuery, SearchAnalyticsResponse, SearchQueryStat, SourceLanguageDefinitionCount,
+    SourceLanguageOverview, SourceLanguageRecentValsi, SourceLanguageTypeCount, WordOfTheDay,
+};
+use super::query_language::{self, Expr};
+use super::valsi_validation::{check_valsi_form, valsi_type_id, ValsiForm};
 use super::{
     AddDefinitionRequest, BulkImportParams, DefinitionListResponse, DefinitionResponse,
     GetImageDefinitionQuery, ImageData, KeywordMapping, ListDefinitionsQuery,
     NonLojbanDefinitionsQuery, RecentChange, RecentChangesResponse, SearchDefinitionsParams,
-    UpdateDefinitionRequest, ValsiDetail, ValsiType,
+    SimilarDefinitionsQuery, StructuredSearchQuery, UpdateDefinitionRequest, ValsiDetail,
+    ValsiType,
 };
-use crate::jbovlaste::models::DefinitionDetail;
+use crate::jbovlaste::models::{DefinitionDetail, SearchHistoryEntry};
+use crate::AppError;
 use vlazba::jvokaha::jvokaha;
 
 use crate::auth::Claims;
+use crate::language::dto::AnalyzeWordResponse;
+use crate::language::loglan::LOGLAN_LANGID;
 use crate::language::{analyze_word, validate_mathjax, MathJaxValidationOptions};
 use crate::middleware::cache::RedisCache;
+use crate::notifications::service::EmailNotification;
+use crate::notifications::EmailService;
 use crate::subscriptions::models::SubscriptionTrigger;
 use crate::versions::service::{get_diff, get_version_with_transaction};
 use crate::versions::{Change, ChangeType, VersionContent, VersionDiff};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 
 pub fn sanitize_html(html: &str) -> String {
     remove_html_tags(html)
@@ -255,9 +267,149 @@ pub async fn semantic_search(
         definitions,
         decomposition,
         total,
+        suggestions: Vec::new(),
     })
 }
 
+/// Finds the definitions nearest to `definition_id` by embedding cosine distance.
+/// Returns `None` when the definition does not exist or has no embedding yet.
+pub async fn get_similar_definitions(
+    pool: &Pool,
+    definition_id: i32,
+    query: &SimilarDefinitionsQuery,
+) -> Result<Option<Vec<DefinitionDetail>>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+
+    let source_row = client
+        .query_opt(
+            "SELECT d.embedding, d.langid, d.valsiid, v.source_langid
+             FROM definitions d
+             JOIN valsi v ON d.valsiid = v.valsiid
+             WHERE d.definitionid = $1",
+            &[&definition_id],
+        )
+        .await?;
+
+    let Some(source_row) = source_row else {
+        return Ok(None);
+    };
+    let Some(vector) = source_row.get::<_, Option<pgvector::Vector>>("embedding") else {
+        return Ok(None);
+    };
+    let source_langid: i32 = source_row.get("langid");
+    let source_valsiid: i32 = source_row.get("valsiid");
+    let source_source_langid: i32 = source_row.get("source_langid");
+
+    let limit = query.limit.unwrap_or(10).clamp(1, 100);
+
+    let mut query_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
+        vec![&vector, &source_valsiid, &source_source_langid];
+
+    let mut conditions = vec![];
+
+    // Restrict to the same definition language unless explicitly asked otherwise
+    if !query.any_language.unwrap_or(false) {
+        conditions.push(format!("AND d.langid = ${}", query_params.len() + 1));
+        query_params.push(&source_langid);
+    }
+
+    if let Some(selmaho) = &query.selmaho {
+        conditions.push(format!("AND d.selmaho = ${}", query_params.len() + 1));
+        query_params.push(selmaho);
+    }
+
+    let word_type_value;
+    if let Some(word_type) = query.word_type {
+        word_type_value = word_type;
+        conditions.push(format!("AND d.cached_typeid = ${}", query_params.len() + 1));
+        query_params.push(&word_type_value);
+    }
+
+    let limit_param_index = query_params.len() + 1;
+    query_params.push(&limit);
+
+    let additional_conditions = conditions.join(" ");
+
+    // Over-fetch nearest neighbours and keep only the best definition per valsi,
+    // so one word with several definitions does not crowd out the others.
+    let query_string = format!(
+        r#"
+        WITH vote_scores AS (
+            SELECT definitionid, COALESCE(SUM(value), 0) as score
+            FROM definitionvotes
+            GROUP BY definitionid
+        ),
+        nearest AS (
+            SELECT
+                d.definitionid, d.valsiid, d.langid, d.definition, d.notes, d.etymology, d.created_at,
+                d.selmaho, d.jargon, d.definitionnum, d.time, d.owner_only,
+                d.cached_valsiword as valsiword,
+                d.cached_username as username,
+                d.cached_langrealname as langrealname,
+                d.cached_type_name as type_name,
+                d.cached_rafsi as rafsi,
+                COALESCE(dv.score, 0) as score,
+                d.embedding <=> $1::vector as similarity
+            FROM definitions d
+            LEFT JOIN vote_scores dv ON dv.definitionid = d.definitionid
+            WHERE d.valsiid != $2
+              AND d.cached_source_langid = $3
+              AND d.embedding IS NOT NULL
+              AND d.definition != ''
+              {additional_conditions}
+            ORDER BY d.embedding <=> $1::vector
+            LIMIT ${limit_param_index} * 4
+        ),
+        best_per_valsi AS (
+            SELECT DISTINCT ON (valsiid) *
+            FROM nearest
+            ORDER BY valsiid, similarity ASC
+        )
+        SELECT *
+        FROM best_per_valsi
+        ORDER BY similarity ASC
+        LIMIT ${limit_param_index}"#
+    );
+
+    let rows = client.query(&query_string, &query_params).await?;
+
+    let definitions = rows
+        .into_iter()
+        .map(|row| DefinitionDetail {
+            embedding: None,
+            similarity: row.get::<_, Option<f64>>("similarity"),
+            definitionid: row.get("definitionid"),
+            valsiword: row.get("valsiword"),
+            valsiid: row.get("valsiid"),
+            langid: row.get("langid"),
+            definition: row.get("definition"),
+            notes: row.get("notes"),
+            etymology: row.get("etymology"),
+            selmaho: row.get("selmaho"),
+            jargon: row.get("jargon"),
+            definitionnum: row.get("definitionnum"),
+            langrealname: row.get("langrealname"),
+            username: row.get("username"),
+            time: row.get("time"),
+            type_name: row.get("type_name"),
+            score: row.get("score"),
+            comment_count: None,
+            gloss_keywords: None,
+            place_keywords: None,
+            user_vote: None,
+            owner_only: row.get("owner_only"),
+            can_edit: false,
+            created_at: row.get("created_at"),
+            has_image: false,
+            sound_url: None,
+            metadata: None,
+            rafsi: row.get("rafsi"),
+        })
+        .collect();
+
+    Ok(Some(definitions))
+}
+
 // Helper function to fetch keywords (extracted and adapted from search_definitions)
 async fn fetch_keywords(
     transaction: &Transaction<'_>,
@@ -431,6 +583,7 @@ pub async fn search_definitions(
                     WHEN d.cached_valsiword ~* $3 THEN 10
                     WHEN d.cached_rafsi IS NOT NULL AND $1 = ANY(string_to_array(d.cached_rafsi, ' ')) THEN 9
                     WHEN d.cached_valsiword ILIKE $2 THEN 8
+                    WHEN lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1) THEN 7
                     WHEN d.definition ~ $3 THEN 6
                     WHEN d.notes ~ $3 THEN 4
                     WHEN d.selmaho ~ $3 THEN 3
@@ -448,7 +601,8 @@ pub async fn search_definitions(
                 WHERE (t.valsiid = d.valsiid OR t.definitionid = d.definitionid)
             ) cc ON true
             LEFT JOIN definition_images_flag di ON di.definition_id = d.definitionid
-            WHERE (d.cached_search_text ILIKE $2)
+            WHERE (d.cached_search_text ILIKE $2
+                         OR lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1))
                   AND (d.langid = ANY($4) OR $4 IS NULL)
                   {additional_conditions}
         ),
@@ -493,6 +647,7 @@ pub async fn search_definitions(
                     WHEN d.cached_valsiword ~* $3 THEN 10
                     WHEN d.cached_rafsi IS NOT NULL AND $1 = ANY(string_to_array(d.cached_rafsi, ' ')) THEN 9
                     WHEN d.cached_valsiword ILIKE $2 THEN 8
+                    WHEN lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1) THEN 7
                     WHEN d.definition ~ $3 THEN 6
                     WHEN d.notes ~ $3 THEN 4
                     WHEN d.selmaho ~ $3 THEN 3
@@ -504,7 +659,8 @@ pub async fn search_definitions(
             FROM definitions d
             LEFT JOIN vote_scores dv ON dv.definitionid = d.definitionid
             LEFT JOIN definition_images_flag di ON di.definition_id = d.definitionid
-            WHERE (d.cached_search_text ILIKE $2)
+            WHERE (d.cached_search_text ILIKE $2
+                         OR lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1))
                   AND (d.langid = ANY($4) OR $4 IS NULL)
                   {additional_conditions}
         ),
@@ -655,6 +811,7 @@ pub async fn search_definitions(
                 WHEN d.cached_valsiword ~* $3 THEN 10
                 WHEN d.cached_rafsi IS NOT NULL AND $1 = ANY(string_to_array(d.cached_rafsi, ' ')) THEN 9
                 WHEN d.cached_valsiword ILIKE $2 THEN 8
+                WHEN lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1) THEN 7
                 WHEN d.definition ~ $3 THEN 6
                 WHEN d.notes ~ $3 THEN 4
                 WHEN d.selmaho ~ $3 THEN 3
@@ -664,7 +821,8 @@ pub async fn search_definitions(
                 ELSE 0
             END as rank
         FROM definitions d
-        WHERE (d.cached_search_text ILIKE $2)
+        WHERE (d.cached_search_text ILIKE $2
+                         OR lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1))
               AND (d.langid = ANY($4) OR $4 IS NULL)
               {}
     )
@@ -681,12 +839,19 @@ pub async fn search_definitions(
 
     let decomposition = get_source_words(&params.search_term, &transaction).await?;
 
+    let suggestions = if total == 0 {
+        get_spelling_suggestions(&transaction, &params.search_term, source_langid_value).await?
+    } else {
+        Vec::new()
+    };
+
     transaction.commit().await?;
 
     Ok(DefinitionResponse {
         definitions,
         decomposition,
         total,
+        suggestions,
     })
 }
 
@@ -781,11 +946,13 @@ pub async fn fast_search_definitions(
                 WHEN d.cached_valsiword ~* $3::text THEN 10
                 WHEN d.cached_rafsi IS NOT NULL AND $1::text = ANY(string_to_array(d.cached_rafsi, ' ')) THEN 9
                 WHEN d.cached_valsiword ILIKE $2::text THEN 8
+                WHEN lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1::text) THEN 7
                 WHEN d.cached_search_text ILIKE $2::text THEN 7
                 ELSE 0
             END as rank
         FROM definitions d
-        WHERE d.cached_search_text ILIKE $2::text
+        WHERE (d.cached_search_text ILIKE $2::text
+               OR lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($1::text))
         AND (d.langid = ANY($4::int4[]) OR $4::int4[] IS NULL)
         AND d.cached_source_langid = $5::int4
         {additional_conditions}
@@ -847,14 +1014,15 @@ pub async fn fast_search_definitions(
     }
 
     // Count query - simplified using cached_search_text, no JOINs
-    // Parameters: $1=like_pattern, $2=languages_slice, $3=source_langid_value
-    let base_conditions = r#"d.cached_search_text ILIKE $1::text
+    // Parameters: $1=like_pattern, $2=languages_slice, $3=source_langid_value, $4=search_term
+    let base_conditions = r#"(d.cached_search_text ILIKE $1::text
+                       OR lojban_phonetic_key(d.cached_valsiword) = lojban_phonetic_key($4::text))
                   AND (d.langid = ANY($2) OR $2 IS NULL)
                   AND d.cached_source_langid = $3"#;
 
     // Build dynamic conditions with correct parameter numbering
     let mut conditions = vec![];
-    let mut current_param_num = 4; // Start from 4 since we use 1-3 in base
+    let mut current_param_num = 5; // Start from 5 since we use 1-4 in base
 
     if params.selmaho.is_some() {
         conditions.push(format!("AND d.selmaho = ${}", current_param_num));
@@ -881,11 +1049,12 @@ pub async fn fast_search_definitions(
     WHERE {base_conditions} {additional_conditions}"#
     );
 
-    // Create params for count query - no search_term needed, only like_pattern
+    // Create params for count query - search_term is only needed for the phonetic match
     let mut count_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![
         &like_pattern,          // $1
         &languages_slice,       // $2
         &source_langid_value,   // $3
+        &params.search_term,    // $4
     ];
 
     // Add conditional parameters in the correct order, matching additional_conditions logic
@@ -915,15 +1084,66 @@ pub async fn fast_search_definitions(
         Vec::new()
     };
 
+    let suggestions = if total == 0 {
+        get_spelling_suggestions(&transaction, &params.search_term, source_langid_value).await?
+    } else {
+        Vec::new()
+    };
+
     transaction.commit().await?;
 
     Ok(DefinitionResponse {
         definitions,
         decomposition,
         total,
+        suggestions,
     })
 }
 
+/// "Did you mean" candidates for a search term that matched nothing.
+/// Combines trigram similarity with edit distance over `lojban_phonetic_key`,
+/// so common learner misspellings (c/x for k, h for ', doubled vowels,
+/// swapped letters) still lead to the intended valsi.
+async fn get_spelling_suggestions(
+    transaction: &tokio_postgres::Transaction<'_>,
+    search_term: &str,
+    source_langid: i32,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let term = search_term.trim().to_lowercase();
+    // Only single words are worth correcting; phrases are natural-language searches
+    if term.is_empty() || term.contains(char::is_whitespace) {
+        return Ok(Vec::new());
+    }
+    // Short cmavo are one edit away from dozens of others
+    let max_distance: i32 = if term.chars().count() <= 3 { 1 } else { 2 };
+
+    let rows = transaction
+        .query(
+            "WITH candidates AS (
+                SELECT DISTINCT v.word,
+                       similarity(v.word, $1) AS sim,
+                       levenshtein(lojban_phonetic_key(v.word), lojban_phonetic_key($1)) AS distance
+                FROM valsi v
+                WHERE v.source_langid = $2
+                  AND v.word != $1
+                  AND (v.word % $1
+                       OR lojban_phonetic_key(v.word) = lojban_phonetic_key($1)
+                       OR (length(v.word) BETWEEN length($1) - $3 AND length($1) + $3
+                           AND levenshtein(lojban_phonetic_key(v.word), lojban_phonetic_key($1)) <= $3))
+                  AND EXISTS (SELECT 1 FROM definitions d WHERE d.valsiid = v.valsiid)
+            )
+            SELECT word
+            FROM candidates
+            WHERE distance <= $3 OR sim >= 0.5
+            ORDER BY distance ASC, sim DESC, word ASC
+            LIMIT 5",
+            &[&term, &source_langid, &max_distance],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get("word")).collect())
+}
+
 async fn get_source_words(
     word: &str,
     transaction: &tokio_postgres::Transaction<'_>,
@@ -1046,6 +1266,7 @@ pub async fn check_sound_urls(
 pub async fn get_entry_details(
     pool: &Pool,
     id_or_word: &str,
+    source_langid: i32,
 ) -> Result<ValsiDetail, Box<dyn std::error::Error>> {
     let mut client = pool.get().await?;
     let transaction = client.transaction().await?;
@@ -1060,10 +1281,10 @@ pub async fn get_entry_details(
              FROM valsi v
              JOIN valsitypes vt ON v.typeid = vt.typeid
              WHERE CASE
-                WHEN $1 ~ '^\\d+$' THEN v.valsiid = $1::int AND v.source_langid = 1
-                ELSE v.word = $2 AND v.source_langid = 1
+                WHEN $1 ~ '^\\d+$' THEN v.valsiid = $1::int
+                ELSE v.word = $2 AND v.source_langid = $3
             END",
-            &[&id_or_word, &id_or_word],
+            &[&id_or_word, &id_or_word, &source_langid],
         )
         .await?;
 
@@ -1096,6 +1317,74 @@ pub async fn get_entry_details(
     }
 }
 
+/// Checks the math in each text field separately, so error spans point into the field
+/// that holds the error.
+async fn validate_definition_math(
+    definition: &str,
+    notes: Option<&str>,
+    etymology: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let options = || MathJaxValidationOptions {
+        use_tectonic: false,
+    };
+    for (field, text) in [
+        ("definition", Some(definition)),
+        ("notes", notes),
+        ("etymology", etymology),
+    ] {
+        if let Some(text) = text {
+            validate_mathjax(text, options())
+                .await
+                .map_err(|e| format!("Invalid math in {}: {}", field, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks a Lojban or Loglan submission's morphology against its declared type and the
+/// existing entry; new Lojban words also get the gismu similarity and fu'ivla slinku'i rules.
+async fn validate_valsi(
+    transaction: &Transaction<'_>,
+    parsers: &Arc<HashMap<i32, Peg>>,
+    source_langid: i32,
+    word: &str,
+    analysis: &AnalyzeWordResponse,
+    declared: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let existing_type: Option<i16> = transaction
+        .query_opt(
+            "SELECT typeid FROM valsi WHERE word = $1 AND source_langid = $2",
+            &[&analysis.text, &source_langid],
+        )
+        .await?
+        .map(|row| row.get("typeid"));
+
+    let slinkuhi =
+        if source_langid == 1 && analysis.word_type == "fu'ivla" && existing_type.is_none() {
+            let prefixed = format!("pa{}", analysis.text);
+            Some(
+                analyze_word(parsers, &prefixed, 1, transaction)
+                    .await?
+                    .word_type,
+            )
+        } else {
+            None
+        };
+
+    let form = ValsiForm {
+        word,
+        language: if source_langid == LOGLAN_LANGID {
+            "Loglan"
+        } else {
+            "Lojban"
+        },
+        analysis,
+        existing_type,
+        slinkuhi_type: slinkuhi.as_deref(),
+    };
+    Ok(check_valsi_form(&form, declared)?)
+}
+
 pub async fn add_definition(
     pool: &Pool,
     claims: &Claims,
@@ -1136,44 +1425,33 @@ async fn add_definition_in_transaction(
     let sanitized_selmaho = request.selmaho.as_ref().map(|s| sanitize_html(s));
     let sanitized_jargon = request.jargon.as_ref().map(|j| sanitize_html(j));
 
-    let combined_text = format!(
-        "{} {} {}",
-        sanitized_definition,
-        sanitized_notes.as_deref().unwrap_or(""),
-        sanitized_etymology.as_deref().unwrap_or("")
-    );
-
-    let options = MathJaxValidationOptions { use_tectonic: true };
-
     // Use provided source_langid or default to 1 (Lojban)
     let source_langid = request.source_langid.unwrap_or(1);
 
     let (word, word_type) = match source_langid {
-        1 | 58 => {
-            // Lojban or Loglan
-            validate_mathjax(&combined_text, options)
-                .await
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        1 | LOGLAN_LANGID => {
+            validate_definition_math(
+                &sanitized_definition,
+                sanitized_notes.as_deref(),
+                sanitized_etymology.as_deref(),
+            )
+            .await?;
             let res = analyze_word(&parsers, &request.word, source_langid, transaction).await?;
-            (res.text, res.word_type)
+            let word_type = validate_valsi(
+                transaction,
+                &parsers,
+                source_langid,
+                &request.word,
+                &res,
+                request.word_type.as_deref(),
+            )
+            .await?;
+            (res.text, word_type)
         }
         _ => (sanitize_html(&request.word), "phrase".to_string()),
     };
 
-    let type_id = match word_type.as_str() {
-        "gismu" => 1,
-        "cmavo" => 2,
-        "cmevla" => 3,
-        "lujvo" => 4,
-        "fu'ivla" => 5,
-        "cmavo-compound" => 6,
-        "experimental gismu" => 7,
-        "experimental cmavo" => 8,
-        "bu-letteral" => 9,
-        "zei-lujvo" => 10,
-        "phrase" => 15,
-        _ => 0,
-    };
+    let type_id = valsi_type_id(&word_type);
 
     // Get or create valsi, considering source_langid
     let valsi_id = match transaction
@@ -1191,7 +1469,7 @@ async fn add_definition_in_transaction(
                      RETURNING valsiid",
                 &[
                     &word,
-                    &(type_id as i16),
+                    &type_id,
                     &claims.sub,
                     &(Utc::now().timestamp() as i32),
                     &source_langid,
@@ -1522,8 +1800,7 @@ pub async fn get_definition(
          JOIN languages l ON d.langid = l.langid
          CROSS JOIN image_check i
          JOIN users u ON d.userid = u.userid
-         WHERE d.definitionid = $1
-         AND v.source_langid = 1",
+         WHERE d.definitionid = $1",
         &[&definition_id, &user_id],
     ).await?;
 
@@ -1624,14 +1901,6 @@ pub async fn update_definition(
     let sanitized_selmaho = request.selmaho.as_ref().map(|s| sanitize_html(s));
     let sanitized_jargon = request.jargon.as_ref().map(|j| sanitize_html(j));
 
-    // Combine all text fields for validation
-    let combined_text = format!(
-        "{} {} {}",
-        sanitized_definition,
-        sanitized_notes.as_deref().unwrap_or(""),
-        sanitized_etymology.as_deref().unwrap_or("")
-    );
-
     let mut client = pool.get().await?;
     let transaction = client.transaction().await?;
 
@@ -1703,13 +1972,14 @@ pub async fn update_definition(
     )
     .await?;
 
-    let options = MathJaxValidationOptions { use_tectonic: true };
-
-    // Only validate MathJax if source lang is Lojban (1) or not set
-    if source_langid.is_none() || source_langid == Some(1) {
-        validate_mathjax(&combined_text, options)
-            .await
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    // Only validate MathJax if source lang is Lojban (1, or not set) or Loglan
+    if matches!(source_langid, None | Some(1) | Some(LOGLAN_LANGID)) {
+        validate_definition_math(
+            &sanitized_definition,
+            sanitized_notes.as_deref(),
+            sanitized_etymology.as_deref(),
+        )
+        .await?;
     }
 
     let is_author = current_def.get::<_, i32>("userid") == user_id;
@@ -2202,6 +2472,142 @@ pub async fn list_definitions(
         total,
         page,
         per_page,
+        suggestions: Vec::new(),
+    })
+}
+
+/// Runs a parsed structured query (see `query_language`) against the cached
+/// definition fields. Defaults to Lojban valsi unless the query has a `source:` term.
+pub async fn structured_search(
+    pool: &Pool,
+    expr: &Expr,
+    query: &StructuredSearchQuery,
+    current_user_id: Option<i32>,
+) -> Result<DefinitionListResponse, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).clamp(1, 100);
+    let offset = (page - 1) * per_page;
+
+    let with_source_default = |compiled: &query_language::CompiledQuery| {
+        if compiled.has_source_filter {
+            compiled.sql.clone()
+        } else {
+            format!("{} AND d.cached_source_langid = 1", compiled.sql)
+        }
+    };
+
+    // The count query doesn't need the current user, so its parameters start at $1
+    let counted = query_language::compile(expr, 1)?;
+    let count_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = counted
+        .params
+        .iter()
+        .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
+        .collect();
+    let count_query = format!(
+        "SELECT COUNT(*) FROM definitions d WHERE {}",
+        with_source_default(&counted)
+    );
+    let total: i64 = client.query_one(&count_query, &count_params).await?.get(0);
+
+    // $1 is reserved for the current user, NULL when anonymous
+    let compiled = query_language::compile(expr, 2)?;
+    let where_clause = with_source_default(&compiled);
+
+    let sort_column = match query.sort_by.as_deref() {
+        Some("score") => "score",
+        Some("date") => "d.created_at",
+        Some("type") => "d.cached_type_name",
+        _ => "d.cached_valsiword",
+    };
+    let sort_order = match query.sort_order.as_deref() {
+        Some(s) if s.eq_ignore_ascii_case("desc") => "DESC",
+        _ => "ASC",
+    };
+
+    let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![&current_user_id];
+    params.extend(
+        compiled
+            .params
+            .iter()
+            .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync)),
+    );
+
+    let limit_param_index = params.len() + 1;
+    params.push(&per_page);
+    let offset_param_index = params.len() + 1;
+    params.push(&offset);
+
+    let query_string = format!(
+        r#"
+        SELECT
+            d.definitionid, d.valsiid, d.langid, d.definition, d.notes, d.etymology, d.created_at,
+            d.selmaho, d.jargon, d.definitionnum, d.time, d.owner_only,
+            d.cached_valsiword as valsiword,
+            d.cached_username as username,
+            d.cached_langrealname as langrealname,
+            d.cached_type_name as type_name,
+            d.cached_rafsi as rafsi,
+            (SELECT COALESCE(SUM(value), 0) FROM definitionvotes WHERE definitionid = d.definitionid) as score,
+            EXISTS(SELECT 1 FROM definition_images WHERE definition_id = d.definitionid) as has_image,
+            CASE
+                WHEN $1::int IS NOT NULL THEN can_edit_definition(d.definitionid, $1)
+                ELSE false
+            END as can_edit,
+            CASE WHEN $1::int IS NOT NULL THEN
+                (SELECT value::int FROM definitionvotes
+                 WHERE userid = $1 AND definitionid = d.definitionid)
+            END as user_vote
+        FROM definitions d
+        WHERE {where_clause}
+        ORDER BY {sort_column} {sort_order}, d.definitionid
+        LIMIT ${limit_param_index} OFFSET ${offset_param_index}"#
+    );
+
+    let rows = client.query(&query_string, &params).await?;
+
+    let definitions: Vec<DefinitionDetail> = rows
+        .iter()
+        .map(|row| DefinitionDetail {
+            similarity: None,
+            embedding: None,
+            sound_url: None,
+            definitionid: row.get("definitionid"),
+            valsiword: row.get("valsiword"),
+            valsiid: row.get("valsiid"),
+            langid: row.get("langid"),
+            definition: row.get("definition"),
+            notes: row.get("notes"),
+            etymology: row.get("etymology"),
+            selmaho: row.get("selmaho"),
+            jargon: row.get("jargon"),
+            definitionnum: row.get("definitionnum"),
+            langrealname: row.get("langrealname"),
+            username: row.get("username"),
+            time: row.get("time"),
+            type_name: row.get("type_name"),
+            score: row.get("score"),
+            comment_count: None,
+            gloss_keywords: None,
+            place_keywords: None,
+            user_vote: row.get("user_vote"),
+            owner_only: row.get("owner_only"),
+            can_edit: row.get("can_edit"),
+            created_at: row.get("created_at"),
+            has_image: row.get("has_image"),
+            metadata: None,
+            rafsi: row.get("rafsi"),
+        })
+        .collect();
+
+    Ok(DefinitionListResponse {
+        definitions,
+        total,
+        page,
+        per_page,
+        decomposition: Vec::new(),
+        suggestions: Vec::new(),
     })
 }
 
@@ -2367,6 +2773,7 @@ pub async fn list_non_lojban_definitions(
         total,
         page,
         per_page,
+        suggestions: Vec::new(),
     })
 }
 
@@ -2489,6 +2896,7 @@ pub async fn get_definitions_by_entry(
     user_id: Option<i32>,
     preferred_langid: Option<i32>,
     preferred_username: Option<String>,
+    source_langid: i32,
     redis_cache: &RedisCache,
 ) -> Result<Vec<DefinitionDetail>, Box<dyn std::error::Error>> {
     let mut client = pool.get().await?;
@@ -2520,8 +2928,8 @@ pub async fn get_definitions_by_entry(
             JOIN valsi v ON d.valsiid = v.valsiid
             JOIN valsitypes vt ON v.typeid = vt.typeid
             WHERE CASE
-                WHEN $1 ~ '^\\d+$' THEN v.valsiid = $1::int AND v.source_langid = 1
-                ELSE v.word = $2 AND v.source_langid = 1
+                WHEN $1 ~ '^\\d+$' THEN v.valsiid = $1::int
+                ELSE v.word = $2 AND v.source_langid = $6
             END
         )
         SELECT r.*
@@ -2537,6 +2945,7 @@ pub async fn get_definitions_by_entry(
                 &user_id,            // $3
                 &preferred_langid,   // $4
                 &preferred_username, // $5
+                &source_langid,      // $6
             ],
         )
         .await?
@@ -2991,15 +3400,20 @@ pub async fn get_all_valsi_words(
     Ok(words)
 }
 
-pub async fn list_valsi_types(pool: &Pool) -> Result<Vec<ValsiType>, Box<dyn std::error::Error>> {
+/// Types without a source language (such as `phrase`) are listed for every language.
+pub async fn list_valsi_types(
+    pool: &Pool,
+    source_langid: Option<i32>,
+) -> Result<Vec<ValsiType>, Box<dyn std::error::Error>> {
     let client = pool.get().await?;
 
     let types = client
         .query(
             "SELECT typeid, descriptor
              FROM valsitypes
+             WHERE $1::int IS NULL OR source_langid IS NULL OR source_langid = $1
              ORDER BY typeid",
-            &[],
+            &[&source_langid],
         )
         .await?
         .into_iter()
@@ -3009,6 +3423,115 @@ pub async fn list_valsi_types(pool: &Pool) -> Result<Vec<ValsiType>, Box<dyn std
     Ok(types)
 }
 
+/// Counts and recent additions for one source language, for its landing page.
+pub async fn get_source_language_overview(
+    pool: &Pool,
+    source_langid: i32,
+    redis_cache: &RedisCache,
+) -> Result<SourceLanguageOverview, Box<dyn std::error::Error>> {
+    use std::time::Duration as StdDuration;
+
+    let cache_key = format!("source_language_overview:{}", source_langid);
+
+    redis_cache
+        .get_or_set(
+            &cache_key,
+            || async {
+                let client = pool.get().await?;
+
+                let language = client
+                    .query_opt(
+                        "SELECT langid, tag, realname FROM languages WHERE langid = $1",
+                        &[&source_langid],
+                    )
+                    .await?
+                    .ok_or_else(|| AppError::NotFound("Source language not found".to_string()))?;
+
+                let counts = client
+                    .query_one(
+                        "SELECT
+                            (SELECT COUNT(*) FROM valsi WHERE source_langid = $1) AS valsi_count,
+                            (SELECT COUNT(*) FROM definitions
+                             WHERE cached_source_langid = $1) AS definition_count",
+                        &[&source_langid],
+                    )
+                    .await?;
+
+                let types = client
+                    .query(
+                        "SELECT vt.typeid, vt.descriptor, COUNT(*) AS count
+                         FROM valsi v
+                         JOIN valsitypes vt ON vt.typeid = v.typeid
+                         WHERE v.source_langid = $1
+                         GROUP BY vt.typeid, vt.descriptor
+                         ORDER BY count DESC, vt.typeid",
+                        &[&source_langid],
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|row| SourceLanguageTypeCount {
+                        type_id: row.get("typeid"),
+                        descriptor: row.get("descriptor"),
+                        count: row.get("count"),
+                    })
+                    .collect();
+
+                let definition_languages = client
+                    .query(
+                        "SELECT l.langid, l.tag, l.realname, COUNT(*) AS count
+                         FROM definitions d
+                         JOIN languages l ON l.langid = d.langid
+                         WHERE d.cached_source_langid = $1
+                         GROUP BY l.langid, l.tag, l.realname
+                         ORDER BY count DESC, l.realname",
+                        &[&source_langid],
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|row| SourceLanguageDefinitionCount {
+                        langid: row.get("langid"),
+                        tag: row.get("tag"),
+                        realname: row.get("realname"),
+                        count: row.get("count"),
+                    })
+                    .collect();
+
+                let recent_valsi = client
+                    .query(
+                        "SELECT v.valsiid, v.word, vt.descriptor, v.time
+                         FROM valsi v
+                         JOIN valsitypes vt ON vt.typeid = v.typeid
+                         WHERE v.source_langid = $1
+                         ORDER BY v.time DESC
+                         LIMIT 10",
+                        &[&source_langid],
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|row| SourceLanguageRecentValsi {
+                        valsiid: row.get("valsiid"),
+                        word: row.get("word"),
+                        type_name: row.get("descriptor"),
+                        time: row.get("time"),
+                    })
+                    .collect();
+
+                Ok(SourceLanguageOverview {
+                    langid: language.get("langid"),
+                    tag: language.get("tag"),
+                    realname: language.get("realname"),
+                    valsi_count: counts.get("valsi_count"),
+                    definition_count: counts.get("definition_count"),
+                    types,
+                    definition_languages,
+                    recent_valsi,
+                })
+            },
+            Some(StdDuration::from_secs(600)),
+        )
+        .await
+}
+
 pub async fn bulk_import_definitions(
     pool: &Pool,
     claims: &Claims,
@@ -3123,6 +3646,7 @@ pub async fn bulk_import_definitions(
                 "import_time": params.import_time,
             })),
             rafsi: None,
+            word_type: None,
         };
 
         // Pass the parser map to add_definition
@@ -3699,6 +4223,7 @@ pub async fn list_definitions_by_client_id(
         page,
         per_page,
         decomposition: Vec::new(),
+        suggestions: Vec::new(),
     })
 }
 
@@ -3768,3 +4293,695 @@ async fn validate_and_update_rafsi(
     }
     Ok(())
 }
+
+pub async fn record_search(
+    pool: &Pool,
+    entry: &SearchHistoryEntry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let result_count = entry.result_count.min(i32::MAX as i64) as i32;
+    client
+        .execute(
+            "INSERT INTO user_search_history
+                (user_id, search_query, search_params, search_mode, result_count, languages)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &entry.user_id,
+                &entry.search_query,
+                &entry.search_params,
+                &entry.search_mode,
+                &result_count,
+                &entry.languages,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn get_search_analytics(
+    pool: &Pool,
+    query: &SearchAnalyticsQuery,
+) -> Result<SearchAnalyticsResponse, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+
+    let days = query.days.unwrap_or(30).clamp(1, 365);
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let interval = match query.interval.as_deref() {
+        Some("week") => "week",
+        Some("month") => "month",
+        _ => "day",
+    };
+
+    let total_searches: i64 = client
+        .query_one(
+            "SELECT COUNT(*) FROM user_search_history
+             WHERE created_at >= NOW() - make_interval(days => $1)",
+            &[&days],
+        )
+        .await?
+        .get(0);
+
+    // Queries are grouped case-insensitively; lexical modes are "fast" and "full"
+    let query_stats = |rows: Vec<tokio_postgres::Row>| -> Vec<SearchQueryStat> {
+        rows.into_iter()
+            .map(|row| SearchQueryStat {
+                query: row.get("query"),
+                searches: row.get("searches"),
+                avg_results: row.get("avg_results"),
+                last_searched_at: row.get("last_searched_at"),
+            })
+            .collect()
+    };
+
+    let top_queries = query_stats(
+        client
+            .query(
+                "SELECT lower(trim(search_query)) AS query,
+                        COUNT(*) AS searches,
+                        AVG(result_count)::float8 AS avg_results,
+                        MAX(created_at) AS last_searched_at
+                 FROM user_search_history
+                 WHERE created_at >= NOW() - make_interval(days => $1)
+                 GROUP BY 1
+                 ORDER BY searches DESC, query
+                 LIMIT $2",
+                &[&days, &limit],
+            )
+            .await?,
+    );
+
+    let zero_result_queries = query_stats(
+        client
+            .query(
+                "SELECT lower(trim(search_query)) AS query,
+                        COUNT(*) AS searches,
+                        AVG(result_count)::float8 AS avg_results,
+                        MAX(created_at) AS last_searched_at
+                 FROM user_search_history
+                 WHERE created_at >= NOW() - make_interval(days => $1)
+                   AND search_mode IN ('fast', 'full')
+                 GROUP BY 1
+                 HAVING MAX(result_count) = 0
+                 ORDER BY searches DESC, query
+                 LIMIT $2",
+                &[&days, &limit],
+            )
+            .await?,
+    );
+
+    let semantic_only_queries = query_stats(
+        client
+            .query(
+                "SELECT lower(trim(search_query)) AS query,
+                        COUNT(*) AS searches,
+                        (AVG(result_count) FILTER (WHERE search_mode = 'semantic'))::float8 AS avg_results,
+                        MAX(created_at) AS last_searched_at
+                 FROM user_search_history
+                 WHERE created_at >= NOW() - make_interval(days => $1)
+                 GROUP BY 1
+                 HAVING COALESCE(MAX(result_count) FILTER (WHERE search_mode IN ('fast', 'full')), 0) = 0
+                    AND MAX(result_count) FILTER (WHERE search_mode = 'semantic') > 0
+                 ORDER BY searches DESC, query
+                 LIMIT $2",
+                &[&days, &limit],
+            )
+            .await?,
+    );
+
+    // Searches restricted to several languages count once per language
+    let trend_rows = client
+        .query(
+            "SELECT date_trunc($2, h.created_at) AS period,
+                    lang.langid,
+                    l.realname AS language_name,
+                    COUNT(*) AS searches,
+                    COUNT(*) FILTER (WHERE h.result_count = 0) AS zero_result_searches
+             FROM user_search_history h
+             LEFT JOIN LATERAL unnest(h.languages) AS lang(langid) ON true
+             LEFT JOIN languages l ON l.langid = lang.langid
+             WHERE h.created_at >= NOW() - make_interval(days => $1)
+             GROUP BY 1, 2, 3
+             ORDER BY period, lang.langid NULLS FIRST",
+            &[&days, &interval],
+        )
+        .await?;
+
+    let language_trends = trend_rows
+        .into_iter()
+        .map(|row| LanguageTrendPoint {
+            period: row.get("period"),
+            langid: row.get("langid"),
+            language_name: row.get("language_name"),
+            searches: row.get("searches"),
+            zero_result_searches: row.get("zero_result_searches"),
+        })
+        .collect();
+
+    Ok(SearchAnalyticsResponse {
+        days,
+        total_searches,
+        top_queries,
+        zero_result_queries,
+        semantic_only_queries,
+        language_trends,
+    })
+}
+
+/// Days before the scheduler may pick the same valsi again
+const WORD_OF_THE_DAY_REPEAT_DAYS: i32 = 365;
+/// Definitions at or above this score are preferred by the automatic pick
+const WORD_OF_THE_DAY_MIN_SCORE: f32 = 1.0;
+
+/// `$1` is an optional language tag; when set, the best-scored definition in that
+/// language is shown instead of the featured one (falling back to the featured one).
+const WORD_OF_THE_DAY_SELECT: &str = "SELECT f.feature_date, f.valsiid, v.word,
+        vt.descriptor AS type_name, f.note, f.source,
+        d.definitionid, d.definition, d.notes, d.langid
+ FROM featured_words f
+ JOIN valsi v ON v.valsiid = f.valsiid
+ JOIN valsitypes vt ON vt.typeid = v.typeid
+ LEFT JOIN LATERAL (
+     SELECT dd.definitionid, dd.definition, dd.notes, dd.langid
+     FROM definitions dd
+     WHERE dd.definitionid = f.definitionid
+        OR ($1::text IS NOT NULL AND dd.valsiid = f.valsiid
+            AND dd.langid = (SELECT langid FROM languages WHERE tag = $1::text))
+     ORDER BY (dd.langid IS NOT DISTINCT FROM (SELECT langid FROM languages WHERE tag = $1::text)) DESC,
+              (SELECT COALESCE(SUM(dv.value), 0) FROM definitionvotes dv
+               WHERE dv.definitionid = dd.definitionid) DESC,
+              dd.definitionid
+     LIMIT 1
+ ) d ON true";
+
+fn word_of_the_day_from_row(row: &tokio_postgres::Row) -> WordOfTheDay {
+    WordOfTheDay {
+        date: row.get("feature_date"),
+        valsiid: row.get("valsiid"),
+        word: row.get("word"),
+        type_name: row.get("type_name"),
+        definitionid: row.get("definitionid"),
+        definition: row.get("definition"),
+        notes: row.get("notes"),
+        langid: row.get("langid"),
+        note: row.get("note"),
+        source: row.get("source"),
+    }
+}
+
+/// Picks the featured valsi for `date` unless one was already chosen.
+///
+/// Curated queue entries pinned to the day (or overdue) win, then the oldest unpinned
+/// queue entry. Without curated entries a random Lojban valsi is chosen, preferring
+/// well-voted English definitions and skipping anything featured in the last year.
+/// Returns the featured word when a new one was picked.
+pub async fn ensure_word_of_the_day(
+    pool: &Pool,
+    date: NaiveDate,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    if transaction
+        .query_opt(
+            "SELECT 1 FROM featured_words WHERE feature_date = $1",
+            &[&date],
+        )
+        .await?
+        .is_some()
+    {
+        return Ok(None);
+    }
+
+    let queued = transaction
+        .query_opt(
+            "SELECT id, valsiid, definitionid, note
+             FROM featured_queue
+             WHERE used_at IS NULL AND (scheduled_for IS NULL OR scheduled_for <= $1)
+             ORDER BY scheduled_for NULLS LAST, created_at
+             LIMIT 1
+             FOR UPDATE SKIP LOCKED",
+            &[&date],
+        )
+        .await?;
+
+    let (valsiid, definitionid, note, source): (i32, Option<i32>, Option<String>, &str) =
+        match queued {
+            Some(row) => {
+                let queue_id: i32 = row.get("id");
+                transaction
+                    .execute(
+                        "UPDATE featured_queue SET used_at = NOW() WHERE id = $1",
+                        &[&queue_id],
+                    )
+                    .await?;
+                (
+                    row.get("valsiid"),
+                    row.get("definitionid"),
+                    row.get("note"),
+                    "queue",
+                )
+            }
+            None => {
+                let candidate = transaction
+                    .query_opt(
+                        "WITH candidates AS (
+                             SELECT d.valsiid, d.definitionid,
+                                    (SELECT COALESCE(SUM(dv.value), 0) FROM definitionvotes dv
+                                     WHERE dv.definitionid = d.definitionid) AS score
+                             FROM definitions d
+                             JOIN valsi v ON v.valsiid = d.valsiid
+                             WHERE v.source_langid = 1
+                               AND d.langid = (SELECT langid FROM languages WHERE tag = 'en')
+                               AND d.definition != ''
+                               AND NOT EXISTS (
+                                   SELECT 1 FROM featured_words f
+                                   WHERE f.valsiid = d.valsiid
+                                     AND f.feature_date > $1::date - $2::int
+                               )
+                         )
+                         SELECT valsiid, definitionid
+                         FROM candidates
+                         ORDER BY score >= $3 DESC, random()
+                         LIMIT 1",
+                        &[
+                            &date,
+                            &WORD_OF_THE_DAY_REPEAT_DAYS,
+                            &WORD_OF_THE_DAY_MIN_SCORE,
+                        ],
+                    )
+                    .await?;
+                match candidate {
+                    Some(row) => (
+                        row.get("valsiid"),
+                        Some(row.get("definitionid")),
+                        None,
+                        "auto",
+                    ),
+                    None => return Ok(None),
+                }
+            }
+        };
+
+    // Queue entries may leave the definition open; feature the best English one
+    let inserted = transaction
+        .query_opt(
+            "INSERT INTO featured_words (feature_date, valsiid, definitionid, note, source)
+             VALUES ($1, $2, COALESCE($3, (
+                 SELECT d.definitionid FROM definitions d
+                 WHERE d.valsiid = $2
+                 ORDER BY (d.langid = (SELECT langid FROM languages WHERE tag = 'en')) DESC,
+                          (SELECT COALESCE(SUM(dv.value), 0) FROM definitionvotes dv
+                           WHERE dv.definitionid = d.definitionid) DESC,
+                          d.definitionid
+                 LIMIT 1
+             )), $4, $5)
+             ON CONFLICT (feature_date) DO NOTHING
+             RETURNING (SELECT word FROM valsi WHERE valsiid = $2) AS word",
+            &[&date, &valsiid, &definitionid, &note, &source],
+        )
+        .await?;
+
+    // Another instance featured a word first; leave its queue entry untouched
+    let Some(row) = inserted else {
+        return Ok(None);
+    };
+    transaction.commit().await?;
+
+    Ok(Some(row.get("word")))
+}
+
+/// The word featured on `date`, or the latest one before it while the scheduler hasn't
+/// picked that day's word yet.
+pub async fn get_word_of_the_day(
+    pool: &Pool,
+    date: NaiveDate,
+    lang: Option<&str>,
+) -> Result<Option<WordOfTheDay>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            &format!(
+                "{} WHERE f.feature_date <= $2 ORDER BY f.feature_date DESC LIMIT 1",
+                WORD_OF_THE_DAY_SELECT
+            ),
+            &[&lang, &date],
+        )
+        .await?;
+
+    Ok(row.as_ref().map(word_of_the_day_from_row))
+}
+
+pub async fn get_word_of_the_day_history(
+    pool: &Pool,
+    until: NaiveDate,
+    lang: Option<&str>,
+    limit: i64,
+) -> Result<Vec<WordOfTheDay>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!(
+                "{} WHERE f.feature_date <= $2 ORDER BY f.feature_date DESC LIMIT $3",
+                WORD_OF_THE_DAY_SELECT
+            ),
+            &[&lang, &until, &limit.clamp(1, 365)],
+        )
+        .await?;
+
+    Ok(rows.iter().map(word_of_the_day_from_row).collect())
+}
+
+/// Renders recent words of the day as an Atom feed, or RSS 2.0 when `rss` is set.
+pub async fn get_word_of_the_day_feed(
+    pool: &Pool,
+    until: NaiveDate,
+    lang: Option<&str>,
+    limit: i64,
+    rss: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let entries = get_word_of_the_day_history(pool, until, lang, limit).await?;
+    let base_url = env::var("FRONTEND_URL").unwrap_or_else(|_| "https://example.com".to_string());
+    let title = "Lojban word of the day";
+    let updated = entries
+        .first()
+        .map(|entry| entry.date)
+        .unwrap_or(until)
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc();
+
+    let mut xml = String::new();
+    xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    if rss {
+        xml.push_str(r#"<rss version="2.0"><channel>"#);
+        xml.push_str(&format!("<title>{}</title>", title));
+        xml.push_str(&format!("<link>{}</link>", escape_xml(&base_url)));
+        xml.push_str("<description>A featured Lojban word every day</description>");
+        xml.push_str(&format!(
+            "<lastBuildDate>{}</lastBuildDate>",
+            updated.to_rfc2822()
+        ));
+    } else {
+        xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+        xml.push_str(&format!("<title>{}</title>", title));
+        xml.push_str(&format!(
+            "<id>{}/word-of-the-day</id>",
+            escape_xml(&base_url)
+        ));
+        xml.push_str(&format!(r#"<link href="{}"/>"#, escape_xml(&base_url)));
+        xml.push_str(&format!("<updated>{}</updated>", updated.to_rfc3339()));
+    }
+
+    for entry in &entries {
+        let link = escape_xml(&format!("{}/valsi/{}", base_url, entry.word));
+        let published = entry.date.and_time(chrono::NaiveTime::MIN).and_utc();
+        let entry_title = escape_xml(&format!("{}: {}", entry.date, entry.word));
+        let summary = escape_xml(&format!(
+            "{} ({}) — {}",
+            entry.word,
+            entry.type_name,
+            entry.definition.as_deref().unwrap_or("")
+        ));
+
+        if rss {
+            xml.push_str("<item>");
+            xml.push_str(&format!("<title>{}</title>", entry_title));
+            xml.push_str(&format!("<link>{}</link>", link));
+            xml.push_str(&format!(
+                r#"<guid isPermaLink="false">{}#{}</guid>"#,
+                link, entry.date
+            ));
+            xml.push_str(&format!("<pubDate>{}</pubDate>", published.to_rfc2822()));
+            xml.push_str(&format!("<description>{}</description>", summary));
+            xml.push_str("</item>");
+        } else {
+            xml.push_str("<entry>");
+            xml.push_str(&format!("<title>{}</title>", entry_title));
+            xml.push_str(&format!("<id>{}#{}</id>", link, entry.date));
+            xml.push_str(&format!(r#"<link href="{}"/>"#, link));
+            xml.push_str(&format!("<updated>{}</updated>", published.to_rfc3339()));
+            xml.push_str(&format!("<summary>{}</summary>", summary));
+            xml.push_str("</entry>");
+        }
+    }
+
+    xml.push_str(if rss { "</channel></rss>" } else { "</feed>" });
+    Ok(xml)
+}
+
+pub async fn add_to_feature_queue(
+    pool: &Pool,
+    user_id: i32,
+    request: &FeatureQueueRequest,
+) -> Result<FeatureQueueEntry, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let source_langid = request.source_langid.unwrap_or(1);
+
+    let valsi = client
+        .query_opt(
+            "SELECT valsiid, word FROM valsi WHERE word = $1 AND source_langid = $2",
+            &[&request.word.trim(), &source_langid],
+        )
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Valsi not found".to_string()))?;
+    let valsiid: i32 = valsi.get("valsiid");
+
+    if let Some(definition_id) = request.definition_id {
+        let belongs = client
+            .query_opt(
+                "SELECT 1 FROM definitions WHERE definitionid = $1 AND valsiid = $2",
+                &[&definition_id, &valsiid],
+            )
+            .await?;
+        if belongs.is_none() {
+            return Err(Box::new(AppError::BadRequest(
+                "Definition does not belong to this valsi".to_string(),
+            )));
+        }
+    }
+
+    if let Some(date) = request.scheduled_for {
+        let taken = client
+            .query_opt(
+                "SELECT 1 FROM featured_words WHERE feature_date = $1
+                 UNION ALL
+                 SELECT 1 FROM featured_queue WHERE scheduled_for = $1",
+                &[&date],
+            )
+            .await?;
+        if taken.is_some() {
+            return Err(Box::new(AppError::BadRequest(
+                "Another word is already scheduled for this date".to_string(),
+            )));
+        }
+    }
+
+    let row = client
+        .query_one(
+            "INSERT INTO featured_queue (valsiid, definitionid, note, scheduled_for, added_by)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING id, created_at",
+            &[
+                &valsiid,
+                &request.definition_id,
+                &request.note,
+                &request.scheduled_for,
+                &user_id,
+            ],
+        )
+        .await?;
+    let added_by: Option<String> = client
+        .query_opt("SELECT username FROM users WHERE userid = $1", &[&user_id])
+        .await?
+        .map(|row| row.get("username"));
+
+    Ok(FeatureQueueEntry {
+        id: row.get("id"),
+        valsiid,
+        word: valsi.get("word"),
+        definitionid: request.definition_id,
+        note: request.note.clone(),
+        scheduled_for: request.scheduled_for,
+        added_by,
+        created_at: row.get("created_at"),
+    })
+}
+
+/// Lists queue entries that have not been featured yet, pinned dates first.
+pub async fn list_feature_queue(
+    pool: &Pool,
+) -> Result<Vec<FeatureQueueEntry>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT q.id, q.valsiid, v.word, q.definitionid, q.note, q.scheduled_for,
+                    u.username AS added_by, q.created_at
+             FROM featured_queue q
+             JOIN valsi v ON v.valsiid = q.valsiid
+             LEFT JOIN users u ON u.userid = q.added_by
+             WHERE q.used_at IS NULL
+             ORDER BY q.scheduled_for NULLS LAST, q.created_at",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| FeatureQueueEntry {
+            id: row.get("id"),
+            valsiid: row.get("valsiid"),
+            word: row.get("word"),
+            definitionid: row.get("definitionid"),
+            note: row.get("note"),
+            scheduled_for: row.get("scheduled_for"),
+            added_by: row.get("added_by"),
+            created_at: row.get("created_at"),
+        })
+        .collect())
+}
+
+pub async fn remove_from_feature_queue(
+    pool: &Pool,
+    id: i32,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let deleted = client
+        .execute(
+            "DELETE FROM featured_queue WHERE id = $1 AND used_at IS NULL",
+            &[&id],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// Opts the user into the daily email, optionally with a preferred definition language.
+pub async fn subscribe_word_of_the_day(
+    pool: &Pool,
+    user_id: i32,
+    lang: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let langid: Option<i32> = match lang {
+        Some(tag) => Some(
+            client
+                .query_opt("SELECT langid FROM languages WHERE tag = $1", &[&tag])
+                .await?
+                .ok_or("Unknown language")?
+                .get("langid"),
+        ),
+        None => None,
+    };
+
+    client
+        .execute(
+            "INSERT INTO word_of_the_day_subscriptions (user_id, langid)
+             VALUES ($1, $2)
+             ON CONFLICT (user_id) DO UPDATE SET langid = EXCLUDED.langid",
+            &[&user_id, &langid],
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn unsubscribe_word_of_the_day(
+    pool: &Pool,
+    user_id: i32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "DELETE FROM word_of_the_day_subscriptions WHERE user_id = $1",
+            &[&user_id],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Emails the word of the day to subscribers with a confirmed address.
+///
+/// The day is claimed through `featured_words.emails_sent_at` once the emails are
+/// built, so repeated scheduler runs and concurrent instances send at most once per day.
+pub async fn send_word_of_the_day_emails(
+    pool: &Pool,
+    date: NaiveDate,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let subscribers = client
+        .query(
+            "SELECT u.email, l.tag
+             FROM word_of_the_day_subscriptions s
+             JOIN users u ON u.userid = s.user_id
+             LEFT JOIN languages l ON l.langid = s.langid
+             WHERE u.email_confirmed AND NOT u.disabled AND u.email <> ''",
+            &[],
+        )
+        .await?;
+    if subscribers.is_empty() {
+        return Ok(0);
+    }
+
+    let email_service = EmailService::new()?;
+    let base_url = env::var("FRONTEND_URL")?;
+    let mut entries: HashMap<Option<String>, Option<WordOfTheDay>> = HashMap::new();
+    let mut emails = Vec::new();
+
+    for row in subscribers {
+        let email: String = row.get("email");
+        let tag: Option<String> = row.get("tag");
+        if !entries.contains_key(&tag) {
+            let entry = get_word_of_the_day(pool, date, tag.as_deref()).await?;
+            entries.insert(tag.clone(), entry);
+        }
+        let Some(Some(entry)) = entries.get(&tag) else {
+            continue;
+        };
+
+        let url = format!("{}/valsi/{}", base_url, entry.word);
+        let heading = format!("Today's word: {} ({})", entry.word, entry.type_name);
+        let mut content = vec![heading.as_str()];
+        if let Some(definition) = entry.definition.as_deref() {
+            content.push(definition);
+        }
+        if let Some(note) = entry.note.as_deref() {
+            content.push(note);
+        }
+        let (text_body, html_body) =
+            email_service.build_email_content(&content, Some(("Open in dictionary", &url)));
+
+        emails.push(EmailNotification {
+            to_email: email,
+            subject: format!("Word of the day: {}", entry.word),
+            text_body,
+            html_body,
+        });
+    }
+    if emails.is_empty() {
+        return Ok(0);
+    }
+
+    let claimed = client
+        .execute(
+            "UPDATE featured_words SET emails_sent_at = NOW()
+             WHERE feature_date = $1 AND emails_sent_at IS NULL",
+            &[&date],
+        )
+        .await?;
+    if claimed == 0 {
+        return Ok(0);
+    }
+
+    // SMTP sends block, so they run off the async workers
+    let sent = tokio::task::spawn_blocking(move || {
+        let mut sent = 0;
+        for notification in emails {
+            let email = notification.to_email.clone();
+            match email_service.send_notification(notification) {
+                Ok(()) => sent += 1,
+                Err(e) => log::warn!("Failed to send word of the day to {}: {}", email, e),
+            }
+        }
+        sent
+    })
+    .await?;
+
+    Ok(sent)
+}
@@ -0,0 +1,338 @@
+//! Morphological checks for new valsi: the analysed word class has to match the declared
+//! valsi type, new gismu must not clash with existing ones and fu'ivla must survive the
+//! slinku'i test. Loglan words only get the type checks.
+
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::language::dto::AnalyzeWordResponse;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ValsiValidationCode {
+    /// The word grammar could not parse the word at all
+    Unparseable,
+    /// The word parses but is not a valid valsi of any class
+    NotAValsi,
+    /// `word_type` is not a known valsi type
+    UnknownType,
+    /// The word's form doesn't match the declared type
+    TypeMismatch,
+    /// The word already exists with a different type
+    ExistingTypeMismatch,
+    /// A new gismu is too similar to existing gismu
+    SimilarGismu,
+    /// "pa" + the fu'ivla parses as a lujvo
+    FuhivlaSlinkuhi,
+}
+
+#[derive(Debug, Error, Serialize, ToSchema)]
+#[error("{message}")]
+pub struct ValsiValidationError {
+    pub code: ValsiValidationCode,
+    pub message: String,
+    /// Class found by word analysis
+    pub analyzed_type: Option<String>,
+    /// Type given in the request, or stored for an existing valsi
+    pub declared_type: Option<String>,
+    /// Existing gismu the word clashes with
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+}
+
+impl ValsiValidationError {
+    fn new(code: ValsiValidationCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            analyzed_type: None,
+            declared_type: None,
+            conflicts: vec![],
+        }
+    }
+
+    fn types(mut self, analyzed: &str, declared: Option<&str>) -> Self {
+        self.analyzed_type = Some(analyzed.to_string());
+        self.declared_type = declared.map(str::to_string);
+        self
+    }
+}
+
+/// `valsitypes` IDs by descriptor; 0 for anything unknown.
+pub fn valsi_type_id(word_type: &str) -> i16 {
+    match word_type {
+        "gismu" => 1,
+        "cmavo" => 2,
+        "cmevla" => 3,
+        "lujvo" => 4,
+        "fu'ivla" => 5,
+        "cmavo-compound" => 6,
+        "experimental gismu" => 7,
+        "experimental cmavo" => 8,
+        "bu-letteral" => 9,
+        "zei-lujvo" => 10,
+        "phrase" => 15,
+        // Loglan
+        "primitive" => 20,
+        "complex" => 21,
+        "borrowing" => 22,
+        "little word" => 23,
+        "compound little word" => 24,
+        "name" => 25,
+        "affix" => 26,
+        _ => 0,
+    }
+}
+
+pub fn valsi_type_name(type_id: i16) -> Option<&'static str> {
+    [
+        "gismu",
+        "cmavo",
+        "cmevla",
+        "lujvo",
+        "fu'ivla",
+        "cmavo-compound",
+        "experimental gismu",
+        "experimental cmavo",
+        "bu-letteral",
+        "zei-lujvo",
+        "phrase",
+        "primitive",
+        "complex",
+        "borrowing",
+        "little word",
+        "compound little word",
+        "name",
+        "affix",
+    ]
+    .into_iter()
+    .find(|name| valsi_type_id(name) == type_id)
+}
+
+/// Refines the analysed class of multi-word entries, which the word grammar only reports
+/// as phrases or cmavo compounds.
+fn classify(word: &str, analyzed_type: &str) -> String {
+    if !matches!(analyzed_type, "phrase" | "cmavo-compound") {
+        return analyzed_type.to_string();
+    }
+    let parts: Vec<String> = word.split_whitespace().map(str::to_lowercase).collect();
+    if parts.len() >= 3 && parts[1..parts.len() - 1].iter().any(|part| part == "zei") {
+        "zei-lujvo".to_string()
+    } else if parts.len() >= 2 && parts.last().is_some_and(|part| part == "bu") {
+        "bu-letteral".to_string()
+    } else {
+        analyzed_type.to_string()
+    }
+}
+
+/// Whether a word analysed as `analyzed` may be stored as `declared`.
+fn type_matches(declared: &str, analyzed: &str) -> bool {
+    match declared {
+        "experimental gismu" => analyzed == "gismu",
+        "experimental cmavo" => analyzed == "cmavo",
+        _ => declared == analyzed,
+    }
+}
+
+/// What is known about a submitted Lojban or Loglan word.
+pub struct ValsiForm<'a> {
+    pub word: &'a str,
+    /// Name of the source language, for messages
+    pub language: &'a str,
+    pub analysis: &'a AnalyzeWordResponse,
+    /// Type of the valsi if it is already in the dictionary
+    pub existing_type: Option<i16>,
+    /// Analysed class of "pa" + the word, for fu'ivla
+    pub slinkuhi_type: Option<&'a str>,
+}
+
+/// Returns the valsi type to store the word under.
+pub fn check_valsi_form(
+    form: &ValsiForm<'_>,
+    declared: Option<&str>,
+) -> Result<String, ValsiValidationError> {
+    if !form.analysis.success {
+        return Err(ValsiValidationError::new(
+            ValsiValidationCode::Unparseable,
+            format!(
+                "'{}' is not valid {} morphology: {}",
+                form.word,
+                form.language,
+                form.analysis.error.as_deref().unwrap_or("parse failed")
+            ),
+        ));
+    }
+
+    let analyzed = classify(form.word, &form.analysis.word_type);
+    if let Some(declared) = declared {
+        if valsi_type_id(declared) == 0 {
+            return Err(ValsiValidationError::new(
+                ValsiValidationCode::UnknownType,
+                format!("Unknown valsi type '{}'", declared),
+            )
+            .types(&analyzed, Some(declared)));
+        }
+    }
+
+    // Existing entries keep their type; only a conflicting declaration is an error
+    if let Some(existing_type) = form.existing_type {
+        let existing = valsi_type_name(existing_type);
+        return match (declared, existing) {
+            (Some(declared), Some(existing)) if declared != existing => {
+                Err(ValsiValidationError::new(
+                    ValsiValidationCode::ExistingTypeMismatch,
+                    format!(
+                        "'{}' already exists as {}, not {}",
+                        form.word, existing, declared
+                    ),
+                )
+                .types(&analyzed, Some(existing)))
+            }
+            _ => Ok(existing.unwrap_or(analyzed.as_str()).to_string()),
+        };
+    }
+
+    if valsi_type_id(&analyzed) == 0 {
+        return Err(ValsiValidationError::new(
+            ValsiValidationCode::NotAValsi,
+            format!(
+                "'{}' is not a {} word of any known type",
+                form.word, form.language
+            ),
+        )
+        .types(&analyzed, declared));
+    }
+
+    let word_type = match declared {
+        Some(declared) if !type_matches(declared, &analyzed) => {
+            return Err(ValsiValidationError::new(
+                ValsiValidationCode::TypeMismatch,
+                format!(
+                    "'{}' has the form of a {}, not a {}",
+                    form.word, analyzed, declared
+                ),
+            )
+            .types(&analyzed, Some(declared)));
+        }
+        Some(declared) => declared.to_string(),
+        None => analyzed.clone(),
+    };
+
+    if analyzed == "gismu" {
+        let conflicts: Vec<String> = form
+            .analysis
+            .problems
+            .iter()
+            .flat_map(|problems| problems.values().flatten())
+            .cloned()
+            .collect();
+        if !conflicts.is_empty() {
+            let mut error = ValsiValidationError::new(
+                ValsiValidationCode::SimilarGismu,
+                format!(
+                    "'{}' is too similar to existing gismu: {}",
+                    form.word,
+                    conflicts.join(", ")
+                ),
+            )
+            .types(&analyzed, declared);
+            error.conflicts = conflicts;
+            return Err(error);
+        }
+    }
+
+    if analyzed == "fu'ivla" && form.slinkuhi_type == Some("lujvo") {
+        return Err(ValsiValidationError::new(
+            ValsiValidationCode::FuhivlaSlinkuhi,
+            format!(
+                "'{}' fails the slinku'i test: 'pa{}' would be read as a lujvo",
+                form.word, form.word
+            ),
+        )
+        .types(&analyzed, declared));
+    }
+
+    Ok(word_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn analysis(word_type: &str) -> AnalyzeWordResponse {
+        AnalyzeWordResponse {
+            success: true,
+            word_type: word_type.to_string(),
+            text: String::new(),
+            recommended: None,
+            problems: None,
+            error: None,
+        }
+    }
+
+    fn form<'a>(word: &'a str, analysis: &'a AnalyzeWordResponse) -> ValsiForm<'a> {
+        ValsiForm {
+            word,
+            language: "Lojban",
+            analysis,
+            existing_type: None,
+            slinkuhi_type: None,
+        }
+    }
+
+    #[test]
+    fn declared_type_must_match_form() {
+        let lujvo = analysis("lujvo");
+        assert_eq!(
+            check_valsi_form(&form("klagau", &lujvo), None).unwrap(),
+            "lujvo"
+        );
+        let error = check_valsi_form(&form("klagau", &lujvo), Some("gismu")).unwrap_err();
+        assert_eq!(error.code, ValsiValidationCode::TypeMismatch);
+
+        let gismu = analysis("gismu");
+        assert_eq!(
+            check_valsi_form(&form("klame", &gismu), Some("experimental gismu")).unwrap(),
+            "experimental gismu"
+        );
+        let phrase = analysis("phrase");
+        assert_eq!(
+            check_valsi_form(&form("bangu zei valsi", &phrase), Some("zei-lujvo")).unwrap(),
+            "zei-lujvo"
+        );
+    }
+
+    #[test]
+    fn rejects_similar_gismu_and_slinkuhi_failures() {
+        let mut gismu = analysis("gismu");
+        gismu.problems = Some(HashMap::from([(
+            "regular".to_string(),
+            vec!["klama".to_string()],
+        )]));
+        let error = check_valsi_form(&form("klamu", &gismu), None).unwrap_err();
+        assert_eq!(error.code, ValsiValidationCode::SimilarGismu);
+        assert_eq!(error.conflicts, vec!["klama"]);
+
+        let fuhivla = analysis("fu'ivla");
+        let mut submitted = form("ranjgyfa", &fuhivla);
+        submitted.slinkuhi_type = Some("lujvo");
+        let error = check_valsi_form(&submitted, None).unwrap_err();
+        assert_eq!(error.code, ValsiValidationCode::FuhivlaSlinkuhi);
+    }
+
+    #[test]
+    fn checks_loglan_types() {
+        let primitive = analysis("primitive");
+        let mut submitted = form("blanu", &primitive);
+        submitted.language = "Loglan";
+        assert_eq!(check_valsi_form(&submitted, None).unwrap(), "primitive");
+        let error = check_valsi_form(&submitted, Some("borrowing")).unwrap_err();
+        assert_eq!(error.code, ValsiValidationCode::TypeMismatch);
+        assert_eq!(
+            valsi_type_name(valsi_type_id("little word")),
+            Some("little word")
+        );
+    }
+}
@@ -1,10 +1,16 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
-use camxes_rs::peg::grammar::Peg;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use actix_web_grants::protect;
 use deadpool_postgres::Pool;
-use std::collections::HashMap;
-use std::sync::Arc;
+use serde_json::json;
 
-use super::{dto::*, models::Language, service};
+use super::{
+    dto::*,
+    models::Language,
+    registry::{with_blocking_parsers, GrammarRegistry, WorkerParsers},
+    service,
+};
+use crate::auth::Claims;
+use crate::tersmu::TersmuError;
 
 #[utoipa::path(
     get,
@@ -28,19 +34,91 @@ pub async fn get_languages(pool: web::Data<Pool>) -> impl Responder {
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/language/lujvo/decompose",
+    tag = "language",
+    operation_id = "decompose_lujvo",
+    summary = "Decompose a lujvo",
+    description = "Splits a lujvo into its rafsi and hyphens and resolves every rafsi to the \
+                  word it stands for, with dictionary entry IDs for linking. Also returns the \
+                  tanru, the best-scoring lujvo for it if that differs from the input, and the \
+                  lujvo's own dictionary entry when there is one.",
+    params(
+        ("lujvo" = String, Query, description = "Lujvo to decompose")
+    ),
+    responses(
+        (status = 200, description = "Decomposition", body = LujvoDecomposition),
+        (status = 400, description = "Not a valid lujvo", body = LujvoDecomposition),
+        (status = 500, description = "Internal server error")
+    )
+)]
+#[get("/lujvo/decompose")]
+pub async fn decompose_lujvo(
+    pool: web::Data<Pool>,
+    query: web::Query<LujvoDecomposeQuery>,
+) -> impl Responder {
+    match service::decompose_lujvo(&pool, &query.lujvo).await {
+        Ok(response) if response.success => HttpResponse::Ok().json(response),
+        Ok(response) => HttpResponse::BadRequest().json(response),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to decompose lujvo: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/language/lujvo/build",
+    tag = "language",
+    operation_id = "build_lujvo",
+    summary = "Build lujvo from a tanru",
+    description = "Builds every candidate lujvo for a tanru with its jvozba score, best first, \
+                  using the dictionary's rafsi assignments. Candidates that are already spelled \
+                  like an existing Lojban valsi are flagged with that entry. Tanru are limited \
+                  to six words.",
+    params(
+        ("tanru" = String, Query, description = "Space-separated tanru, e.g. `klama gasnu`"),
+        ("exp_rafsi" = Option<bool>, Query, description = "Also use experimental rafsi")
+    ),
+    responses(
+        (status = 200, description = "Candidate lujvo", body = LujvoBuildResponse),
+        (status = 400, description = "Invalid or too long tanru", body = LujvoBuildResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+#[get("/lujvo/build")]
+pub async fn build_lujvo(
+    pool: web::Data<Pool>,
+    query: web::Query<LujvoBuildQuery>,
+) -> impl Responder {
+    match service::build_lujvo(&pool, &query).await {
+        Ok(response) if response.success => HttpResponse::Ok().json(response),
+        Ok(response) => HttpResponse::BadRequest().json(response),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to build lujvo: {}", e)
+        })),
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/language/parse_lojban",
     tag = "language",
     operation_id = "parse_lojban_text",
     summary = "Parse Lojban text",
-    description = "Parses provided Lojban text and returns a structured representation \
-                  of its grammatical components. The response includes tokens with their \
-                  types, positions, and hierarchical relationships.",
+    description = "Parses text with the word grammar (`mode: words`, default) or the full \
+                  sentence grammar (`mode: full`) of the language given by `source_langid`. \
+                  The parse is returned as the raw token tree (`tokens`), a nested rule tree \
+                  (`json`), bracketed text (`brackets`), terbrismi-style nested HTML boxes (`html`) \
+                  or a Graphviz graph (`dot`). Failures report the offset, line, column and \
+                  word where parsing stopped, with hints of what the grammar expected. \
+                  Texts are limited to 16 KiB.",
     request_body = LojbanParseRequest,
     responses(
         (status = 200, description = "Successfully parsed Lojban text", body = LojbanParseResponse),
-        (status = 400, description = "Invalid Lojban text", body = LojbanParseResponse)
+        (status = 400, description = "Invalid or too long Lojban text", body = LojbanParseResponse),
+        (status = 500, description = "Internal server error")
     ),
     security(
         ("bearer_auth" = [])
@@ -49,17 +127,63 @@ pub async fn get_languages(pool: web::Data<Pool>) -> impl Responder {
 #[post("/parse_lojban")]
 pub async fn parse_lojban(
     request: web::Json<LojbanParseRequest>,
-    // ELI5: This is a special shared container (Arc) that holds our language parsers.
-    // It's like a library of dictionaries that many people can use at the same time
-    // to understand different languages, without needing separate copies for each person.
-    parsers: web::Data<Arc<HashMap<i32, Peg>>>,
+    registry: web::Data<GrammarRegistry>,
 ) -> impl Responder {
-    // Pass the map to the service function
-    let response = service::parse_lojban(&parsers, &request.text);
-    if response.success {
-        HttpResponse::Ok().json(response)
-    } else {
-        HttpResponse::BadRequest().json(response)
+    let request = request.into_inner();
+    let registry = registry.into_inner();
+    // Failed parses are re-run for every hint probe, so keep them off the async workers
+    let parsed = web::block(move || {
+        with_blocking_parsers(registry, |parsers| {
+            service::parse_lojban(
+                &parsers.word_parsers(),
+                &parsers.sentence_parsers(),
+                &request,
+            )
+        })
+    })
+    .await;
+    match parsed {
+        Ok(response) if response.success => HttpResponse::Ok().json(response),
+        Ok(response) => HttpResponse::BadRequest().json(response),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to parse text: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/language/gloss",
+    tag = "language",
+    operation_id = "gloss_lojban_text",
+    summary = "Gloss Lojban text word by word",
+    description = "Parses the text and looks up every word in the dictionary, returning an \
+                  interlinear gloss: the word, its gloss keyword in the chosen language, its \
+                  selma'o and its place keywords. Runs of cmavo with a cmavo-compound entry \
+                  are glossed as one word; lujvo without an entry are glossed through the \
+                  source words of their rafsi.",
+    request_body = GlossRequest,
+    responses(
+        (status = 200, description = "Interlinear gloss", body = GlossResponse),
+        (status = 400, description = "Text could not be parsed", body = GlossResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[post("/gloss")]
+pub async fn gloss_text(
+    pool: web::Data<Pool>,
+    parsers: web::Data<WorkerParsers>,
+    request: web::Json<GlossRequest>,
+) -> impl Responder {
+    match service::gloss_text(&pool, &parsers.word_parsers(), &request).await {
+        Ok(response) if response.success => HttpResponse::Ok().json(response),
+        Ok(response) => HttpResponse::BadRequest().json(response),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to gloss text: {}", e)
+        })),
     }
 }
 
@@ -70,7 +194,9 @@ pub async fn parse_lojban(
     operation_id = "analyze_lojban_word",
     summary = "Analyze Lojban word",
     description = "Analyzes a single Lojban word to determine its grammatical type \
-                  (gismu, lujvo, cmavo, etc.). Returns the word type and analysis success status.",
+                  (gismu, lujvo, cmavo, etc.). With `source_langid` 58 the word is analyzed as \
+                  Loglan instead (primitive, complex, borrowing, little word, name, etc.). \
+                  Returns the word type and analysis success status.",
     request_body = AnalyzeWordRequest,
     responses(
         (status = 200, description = "Successfully analyzed word", body = AnalyzeWordResponse),
@@ -83,10 +209,7 @@ pub async fn parse_lojban(
 )]
 #[post("/analyze_word")]
 pub async fn analyze_word(
-    // ELI5: Just like in parse_lojban, this is our shared language parser library (Arc)
-    // that helps us understand words in different languages. Many people can use it
-    // at the same time without getting in each other's way.
-    parsers: web::Data<Arc<HashMap<i32, Peg>>>,
+    parsers: web::Data<WorkerParsers>,
     pool: web::Data<deadpool_postgres::Pool>,
     request: web::Json<AnalyzeWordRequest>,
 ) -> impl Responder {
@@ -94,13 +217,8 @@ pub async fn analyze_word(
     let source_langid = request.source_langid.unwrap_or(1);
 
     // Pass the map and source_langid to the service function
-    match service::analyze_word_in_pool(
-        parsers.get_ref().clone(),
-        &request.word,
-        source_langid,
-        &pool,
-    )
-    .await
+    match service::analyze_word_in_pool(parsers.word_parsers(), &request.word, source_langid, &pool)
+        .await
     {
         Ok(response) => {
             if response.success {
@@ -120,8 +238,9 @@ pub async fn analyze_word(
     operation_id = "validate_mathjax_syntax",
     summary = "Validate MathJax syntax",
     description = "Validates MathJax/LaTeX mathematical notation for correct syntax, \
-                  balanced delimiters, and proper command usage. Useful for ensuring \
-                  mathematical expressions will render correctly.",
+                  balanced delimiters, and proper command usage. Errors carry the character \
+                  span of the offending input; valid text is also returned with its math \
+                  rendered as Unicode and as MathML.",
     request_body = MathJaxValidationRequest,
     responses(
         (status = 200, description = "MathJax validation successful", body = MathJaxValidationResponse),
@@ -140,3 +259,439 @@ pub async fn validate_mathjax(request: web::Json<MathJaxValidationRequest>) -> i
         HttpResponse::BadRequest().json(response)
     }
 }
+
+#[utoipa::path(
+    post,
+    path = "/language/semantics",
+    tag = "language",
+    operation_id = "analyze_lojban_semantics",
+    summary = "Get the logical form of Lojban text",
+    description = "Runs Tersmu on the text and returns its predicate-logic interpretation: \
+                  the canonical text, the logical form with its propositions and terms, plus the complete \
+                  Tersmu output. Parse failures carry the error position as offset, line and column.",
+    request_body = SemanticsRequest,
+    responses(
+        (status = 200, description = "Logical form of the text", body = SemanticsResponse),
+        (status = 400, description = "Text could not be parsed", body = SemanticsResponse),
+        (status = 413, description = "Text is too long"),
+        (status = 422, description = "Parsing exceeded its time or memory limit"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[post("/semantics")]
+pub async fn analyze_semantics(request: web::Json<SemanticsRequest>) -> impl Responder {
+    match service::analyze_semantics(request.into_inner().text).await {
+        Ok(response) if response.success => HttpResponse::Ok().json(response),
+        Ok(response) => HttpResponse::BadRequest().json(response),
+        Err(e @ TersmuError::InputTooLong(_)) => {
+            HttpResponse::PayloadTooLarge().json(json!({ "error": e.to_string() }))
+        }
+        Err(e @ (TersmuError::Timeout | TersmuError::OutOfMemory)) => {
+            HttpResponse::UnprocessableEntity().json(json!({ "error": e.to_string() }))
+        }
+        Err(e) => {
+            HttpResponse::InternalServerError().body(format!("Semantic analysis error: {}", e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/language/grammars",
+    tag = "language",
+    operation_id = "list_grammars",
+    summary = "List stored grammar versions",
+    description = "Lists stored parser grammar versions, newest first, optionally filtered by \
+                  language and kind (`word` or `sentence`). Grammar texts are not included.",
+    params(
+        ("langid" = Option<i32>, Query, description = "Language ID"),
+        ("kind" = Option<String>, Query, description = "`word` or `sentence`")
+    ),
+    responses(
+        (status = 200, description = "Stored grammar versions", body = Vec<GrammarVersion>),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[get("/grammars")]
+#[protect("manage_grammars")]
+pub async fn list_grammars(
+    pool: web::Data<Pool>,
+    query: web::Query<GrammarListQuery>,
+) -> impl Responder {
+    match service::list_grammars(&pool, &query).await {
+        Ok(grammars) => HttpResponse::Ok().json(grammars),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to list grammars: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/language/grammars",
+    tag = "language",
+    operation_id = "upload_grammar",
+    summary = "Upload a grammar version",
+    description = "Stores a new version of a language's word or sentence grammar after compiling \
+                  it and running it over the stored sample sentences. With `activate`, a version \
+                  that passes every sample becomes active and is hot-reloaded into all workers; \
+                  for the Lojban sentence grammar this also starts a muplis corpus regression run. \
+                  Hot reload only reaches the workers of the server process handling the request; \
+                  other instances pick the version up on restart or through `/language/grammars/reload`.",
+    request_body = GrammarUploadRequest,
+    responses(
+        (status = 201, description = "Grammar stored", body = GrammarUploadResponse),
+        (status = 400, description = "Unknown kind or grammar does not compile")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[post("/grammars")]
+#[protect("manage_grammars")]
+pub async fn upload_grammar(
+    pool: web::Data<Pool>,
+    registry: web::Data<GrammarRegistry>,
+    claims: Claims,
+    request: web::Json<GrammarUploadRequest>,
+) -> impl Responder {
+    match service::upload_grammar(&pool, &registry, claims.sub, request.into_inner()).await {
+        Ok(response) => {
+            if response.activated {
+                service::run_corpus_regression_after_change(
+                    &pool,
+                    &registry,
+                    &response.grammar,
+                    claims.sub,
+                )
+                .await;
+            }
+            HttpResponse::Created().json(response)
+        }
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": format!("Failed to store grammar: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/language/grammars/test",
+    tag = "language",
+    operation_id = "test_grammar",
+    summary = "Test a grammar without storing it",
+    description = "Compiles the grammar and runs it over the stored sample sentences for the \
+                  language and kind plus any extra `sentences`, reporting every sample whose \
+                  outcome differs from what was expected.",
+    request_body = GrammarTestRequest,
+    responses(
+        (status = 200, description = "Test report", body = GrammarTestReport),
+        (status = 400, description = "Unknown kind")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[post("/grammars/test")]
+#[protect("manage_grammars")]
+pub async fn test_grammar(
+    pool: web::Data<Pool>,
+    request: web::Json<GrammarTestRequest>,
+) -> impl Responder {
+    let request = request.into_inner();
+    match service::test_grammar(
+        &pool,
+        request.langid,
+        &request.kind,
+        request.grammar_text,
+        request.sentences,
+    )
+    .await
+    {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": format!("Failed to test grammar: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/language/grammars/{id}/activate",
+    tag = "language",
+    operation_id = "activate_grammar",
+    summary = "Activate a grammar version",
+    description = "Makes a stored grammar version the active one for its language and kind and \
+                  hot-reloads it into all workers. Also used to roll back to an older version. \
+                  Activating a Lojban sentence grammar starts a muplis corpus regression run. \
+                  Hot reload only reaches the workers of the server process handling the request; \
+                  other instances pick the version up on restart or through `/language/grammars/reload`.",
+    params(
+        ("id" = i32, Path, description = "Grammar version ID")
+    ),
+    responses(
+        (status = 200, description = "Grammar activated", body = GrammarVersion),
+        (status = 404, description = "Grammar version not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[post("/grammars/{id}/activate")]
+#[protect("manage_grammars")]
+pub async fn activate_grammar(
+    pool: web::Data<Pool>,
+    registry: web::Data<GrammarRegistry>,
+    claims: Claims,
+    id: web::Path<i32>,
+) -> impl Responder {
+    match service::activate_grammar(&pool, &registry, id.into_inner()).await {
+        Ok(Some(grammar)) => {
+            service::run_corpus_regression_after_change(&pool, &registry, &grammar, claims.sub)
+                .await;
+            HttpResponse::Ok().json(grammar)
+        }
+        Ok(None) => HttpResponse::NotFound().json(json!({
+            "error": "Grammar version not found"
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to activate grammar: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/language/grammars/reload",
+    tag = "language",
+    operation_id = "reload_grammars",
+    summary = "Reload active grammars",
+    description = "Re-reads the active grammar versions from the database and hot-reloads them \
+                  into the workers of this server process, e.g. after editing the grammars table \
+                  by hand or activating a version through another instance.",
+    responses(
+        (status = 200, description = "Grammars reloaded"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[post("/grammars/reload")]
+#[protect("manage_grammars")]
+pub async fn reload_grammars(
+    pool: web::Data<Pool>,
+    registry: web::Data<GrammarRegistry>,
+) -> impl Responder {
+    match service::reload_grammars(&pool, &registry).await {
+        Ok(()) => HttpResponse::Ok().json(json!({ "success": true })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to reload grammars: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/language/grammars/samples",
+    tag = "language",
+    operation_id = "list_grammar_samples",
+    summary = "List grammar sample sentences",
+    description = "Lists the sample sentences new grammar versions are tested against.",
+    params(
+        ("langid" = Option<i32>, Query, description = "Language ID"),
+        ("kind" = Option<String>, Query, description = "`word` or `sentence`")
+    ),
+    responses(
+        (status = 200, description = "Sample sentences", body = Vec<GrammarSample>),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[get("/grammars/samples")]
+#[protect("manage_grammars")]
+pub async fn list_grammar_samples(
+    pool: web::Data<Pool>,
+    query: web::Query<GrammarListQuery>,
+) -> impl Responder {
+    match service::list_grammar_samples(&pool, &query).await {
+        Ok(samples) => HttpResponse::Ok().json(samples),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to list grammar samples: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/language/grammars/samples",
+    tag = "language",
+    operation_id = "add_grammar_sample",
+    summary = "Add a grammar sample sentence",
+    description = "Adds a sample sentence for a language and grammar kind. Negative samples \
+                  (`should_parse: false`) must be rejected by the grammar. Re-adding an existing \
+                  sentence updates its expectation.",
+    request_body = AddGrammarSampleRequest,
+    responses(
+        (status = 200, description = "Sample stored", body = GrammarSample),
+        (status = 400, description = "Invalid sample")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[post("/grammars/samples")]
+#[protect("manage_grammars")]
+pub async fn add_grammar_sample(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    request: web::Json<AddGrammarSampleRequest>,
+) -> impl Responder {
+    match service::add_grammar_sample(&pool, claims.sub, &request).await {
+        Ok(sample) => HttpResponse::Ok().json(sample),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": format!("Failed to add grammar sample: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/language/grammars/samples/{id}",
+    tag = "language",
+    operation_id = "delete_grammar_sample",
+    summary = "Delete a grammar sample sentence",
+    params(
+        ("id" = i32, Path, description = "Sample ID")
+    ),
+    responses(
+        (status = 200, description = "Sample deleted"),
+        (status = 404, description = "Sample not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[delete("/grammars/samples/{id}")]
+#[protect("manage_grammars")]
+pub async fn delete_grammar_sample(pool: web::Data<Pool>, id: web::Path<i32>) -> impl Responder {
+    match service::delete_grammar_sample(&pool, id.into_inner()).await {
+        Ok(true) => HttpResponse::Ok().json(json!({ "success": true })),
+        Ok(false) => HttpResponse::NotFound().json(json!({
+            "error": "Sample not found"
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to delete grammar sample: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/language/grammars/regressions",
+    tag = "language",
+    operation_id = "start_grammar_regression",
+    summary = "Start a muplis corpus regression run",
+    description = "Runs the active Lojban sentence grammar and Tersmu over every muplis sentence \
+                  in the background, storing pass/fail and canonical forms per sentence. Only one \
+                  run can be in progress at a time, and only the 20 most recent finished runs \
+                  are kept.",
+    responses(
+        (status = 202, description = "Run started", body = GrammarRegressionRun),
+        (status = 409, description = "A run is already in progress or no grammar is loaded")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[post("/grammars/regressions")]
+#[protect("manage_grammars")]
+pub async fn start_grammar_regression(
+    pool: web::Data<Pool>,
+    registry: web::Data<GrammarRegistry>,
+    claims: Claims,
+) -> impl Responder {
+    match service::start_corpus_regression(&pool, &registry, Some(claims.sub)).await {
+        Ok(run) => HttpResponse::Accepted().json(run),
+        Err(e) => HttpResponse::Conflict().json(json!({
+            "error": format!("Failed to start regression run: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/language/grammars/regressions",
+    tag = "language",
+    operation_id = "list_grammar_regressions",
+    summary = "List muplis corpus regression runs",
+    description = "Lists the most recent corpus regression runs with their pass counts.",
+    responses(
+        (status = 200, description = "Regression runs", body = Vec<GrammarRegressionRun>),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[get("/grammars/regressions")]
+#[protect("manage_grammars")]
+pub async fn list_grammar_regressions(pool: web::Data<Pool>) -> impl Responder {
+    match service::list_regression_runs(&pool).await {
+        Ok(runs) => HttpResponse::Ok().json(runs),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to list regression runs: {}", e)
+        })),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/language/grammars/regressions/{id}",
+    tag = "language",
+    operation_id = "get_grammar_regression",
+    summary = "Get a muplis corpus regression report",
+    description = "Compares a regression run with a baseline run (by default the previous \
+                  completed one) and lists the corpus sentences the grammar change broke or \
+                  fixed, for the parser and for Tersmu, and those whose canonical form changed.",
+    params(
+        ("id" = i32, Path, description = "Regression run ID"),
+        ("against" = Option<i32>, Query, description = "Baseline run ID"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of changes to list")
+    ),
+    responses(
+        (status = 200, description = "Regression report", body = GrammarRegressionReport),
+        (status = 404, description = "Run not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = ["manage_grammars"])
+    )
+)]
+#[get("/grammars/regressions/{id}")]
+#[protect("manage_grammars")]
+pub async fn get_grammar_regression(
+    pool: web::Data<Pool>,
+    id: web::Path<i32>,
+    query: web::Query<GrammarRegressionReportQuery>,
+) -> impl Responder {
+    match service::get_regression_report(&pool, id.into_inner(), &query).await {
+        Ok(Some(report)) => HttpResponse::Ok().json(report),
+        Ok(None) => HttpResponse::NotFound().json(json!({
+            "error": "Regression run not found"
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to build regression report: {}", e)
+        })),
+    }
+}
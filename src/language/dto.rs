@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -8,6 +9,17 @@ use super::models::LojbanToken;
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct LojbanParseRequest {
     pub text: String,
+    /// Language whose grammar to use (defaults to Lojban: 1)
+    pub source_langid: Option<i32>,
+    /// `words` parses with the morphology grammar, `full` with the sentence grammar
+    #[schema(default = "words", example = "full")]
+    pub mode: Option<String>,
+    /// `tokens` (raw token tree), `json`, `brackets`, `html` or `dot`
+    #[schema(default = "tokens", example = "brackets")]
+    pub format: Option<String>,
+    /// Drop terminals, stop at whole words and collapse single-child chains.
+    /// Defaults to true for `html`, false otherwise.
+    pub simplify: Option<bool>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -15,6 +27,40 @@ pub struct LojbanParseResponse {
     pub success: bool,
     pub tokens: Vec<LojbanToken>,
     pub error: Option<String>,
+    /// Parse tree for the `json` format
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<Vec<ParseTreeNode>>,
+    /// Rendered parse for the `brackets`, `html` and `dot` formats
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rendered: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_detail: Option<ParseErrorDetail>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ParseTreeNode {
+    pub rule: String,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(value_type = Vec<Object>)]
+    pub children: Vec<ParseTreeNode>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ParseErrorDetail {
+    pub message: String,
+    /// Byte offset into the input where parsing stopped
+    pub offset: usize,
+    /// 1-based line of `offset`
+    pub line: usize,
+    /// 1-based column of `offset`
+    pub column: usize,
+    /// Word found at `offset`, if any
+    pub found: Option<String>,
+    /// Rules or literals the grammar would have accepted at `offset`
+    pub expected: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -43,4 +89,307 @@ pub struct MathJaxValidationRequest {
 pub struct MathJaxValidationResponse {
     pub valid: bool,
     pub error: Option<String>,
+    /// Character range of the error in the submitted text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<MathErrorSpan>,
+    /// Plain-text rendering with math converted to Unicode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unicode: Option<String>,
+    /// Rendering with math converted to MathML
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mathml: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MathErrorSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SemanticsRequest {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SemanticsResponse {
+    pub success: bool,
+    /// Canonical Lojban rendering of the parsed text
+    pub canonical: Option<String>,
+    /// Predicate-logic rendering of the whole text
+    pub logical_form: Option<String>,
+    /// Propositions of the logical form, one per sentence
+    pub bridi: Vec<String>,
+    /// Terms (sumti) the propositions predicate over, each listed once
+    pub terms: Vec<String>,
+    /// Complete Tersmu output
+    #[schema(value_type = Object)]
+    pub raw: serde_json::Value,
+    pub error: Option<SemanticsError>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SemanticsError {
+    pub message: String,
+    /// Character offset into the input, when Tersmu reports one
+    pub offset: Option<usize>,
+    /// 1-based line of `offset`
+    pub line: Option<usize>,
+    /// 1-based column of `offset`
+    pub column: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrammarListQuery {
+    pub langid: Option<i32>,
+    /// `word` or `sentence`
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarVersion {
+    pub id: i32,
+    pub langid: i32,
+    pub kind: String,
+    pub version: i32,
+    pub notes: Option<String>,
+    pub is_active: bool,
+    pub created_by: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub activated_at: Option<DateTime<Utc>>,
+    /// Grammar size in bytes
+    pub size: i32,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrammarUploadRequest {
+    pub langid: i32,
+    /// `word` or `sentence`
+    pub kind: String,
+    pub grammar_text: String,
+    pub notes: Option<String>,
+    /// Activate and hot-reload the new version if every sample passes
+    #[serde(default)]
+    pub activate: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrammarTestRequest {
+    pub langid: i32,
+    /// `word` or `sentence`
+    pub kind: String,
+    pub grammar_text: String,
+    /// Extra sentences expected to parse, on top of the stored samples
+    #[serde(default)]
+    pub sentences: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarTestFailure {
+    pub sentence: String,
+    pub should_parse: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarTestReport {
+    /// False when the grammar itself does not compile
+    pub compiled: bool,
+    pub compile_error: Option<String>,
+    pub total: usize,
+    pub passed: usize,
+    pub failures: Vec<GrammarTestFailure>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarUploadResponse {
+    pub grammar: GrammarVersion,
+    pub report: GrammarTestReport,
+    pub activated: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarSample {
+    pub id: i32,
+    pub langid: i32,
+    pub kind: String,
+    pub sentence: String,
+    pub should_parse: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddGrammarSampleRequest {
+    pub langid: i32,
+    /// `word` or `sentence`
+    pub kind: String,
+    pub sentence: String,
+    #[serde(default = "default_should_parse")]
+    pub should_parse: bool,
+}
+
+fn default_should_parse() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarRegressionRun {
+    pub id: i32,
+    pub langid: i32,
+    /// Grammar kind used for the parser pass
+    pub kind: String,
+    /// Stored grammar version under test; `None` when the bundled grammar was active
+    pub grammar_id: Option<i32>,
+    /// `running`, `completed` or `failed`
+    pub status: String,
+    pub total: i32,
+    pub parser_passed: i32,
+    pub tersmu_passed: i32,
+    pub error: Option<String>,
+    pub triggered_by: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub started_at: DateTime<Utc>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GrammarRegressionReportQuery {
+    /// Run to compare against; defaults to the previous completed run
+    pub against: Option<i32>,
+    /// Maximum number of changed sentences to list (default 500)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarRegressionChange {
+    pub sentence: String,
+    /// `parser_broken`, `parser_fixed`, `tersmu_broken`, `tersmu_fixed` or `canonical_changed`
+    pub change: String,
+    pub parser_error: Option<String>,
+    pub tersmu_error: Option<String>,
+    pub previous_canonical: Option<String>,
+    pub canonical: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct GrammarRegressionCounts {
+    pub parser_broken: usize,
+    pub parser_fixed: usize,
+    pub tersmu_broken: usize,
+    pub tersmu_fixed: usize,
+    pub canonical_changed: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrammarRegressionReport {
+    pub run: GrammarRegressionRun,
+    /// Run the changes are relative to; `None` for the first run
+    pub baseline: Option<GrammarRegressionRun>,
+    pub counts: GrammarRegressionCounts,
+    pub changes: Vec<GrammarRegressionChange>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LujvoDecomposeQuery {
+    pub lujvo: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LujvoEntryRef {
+    pub valsiid: i32,
+    pub word: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LujvoPart {
+    pub rafsi: String,
+    /// Hyphen letters (y, r, n) joining rafsi carry no meaning
+    pub hyphen: bool,
+    /// Source word the rafsi stands for, when it is in the dictionary
+    pub source: Option<LujvoEntryRef>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LujvoDecomposition {
+    pub success: bool,
+    pub lujvo: String,
+    pub parts: Vec<LujvoPart>,
+    /// Source words in order; empty if any rafsi is unknown
+    pub tanru: Vec<String>,
+    /// Best-scoring lujvo for the same tanru, when it differs from the input
+    pub recommended: Option<String>,
+    /// Dictionary entry for the lujvo itself
+    pub entry: Option<LujvoEntryRef>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LujvoBuildQuery {
+    /// Space-separated tanru, e.g. `klama gasnu`
+    pub tanru: String,
+    /// Also use experimental rafsi
+    #[serde(default)]
+    pub exp_rafsi: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LujvoCandidate {
+    pub lujvo: String,
+    /// jvozba score; lower is better
+    pub score: i32,
+    /// Existing valsi with the same spelling
+    pub collision: Option<LujvoEntryRef>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LujvoBuildResponse {
+    pub success: bool,
+    pub tanru: Vec<String>,
+    /// Candidates ordered from best to worst score
+    pub candidates: Vec<LujvoCandidate>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GlossRequest {
+    pub text: String,
+    /// Language of the glosses (defaults to English: 2)
+    pub lang_id: Option<i32>,
+    /// Language of the text (defaults to Lojban: 1)
+    pub source_langid: Option<i32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GlossPlace {
+    /// Place number (1 for x1, ...)
+    pub place: i32,
+    pub keyword: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GlossWord {
+    /// Word as written; cmavo compounds join their parts with spaces
+    pub word: String,
+    pub word_type: String,
+    /// Gloss keyword, falling back to the x1 place keyword
+    pub gloss: Option<String>,
+    pub selmaho: Option<String>,
+    pub places: Vec<GlossPlace>,
+    pub valsiid: Option<i32>,
+    pub definition_id: Option<i32>,
+    /// Glossed source words of a lujvo that has no entry of its own
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<GlossWord>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GlossResponse {
+    pub success: bool,
+    pub words: Vec<GlossWord>,
+    pub error: Option<String>,
+    /// Where parsing stopped, when the text could not be parsed completely
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_detail: Option<ParseErrorDetail>,
 }
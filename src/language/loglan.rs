@@ -0,0 +1,100 @@
+//! Loglan word analysis. The bundled `loglan.peg` parses whole utterances; the word
+//! grammar is derived from it by starting at its `SingleWord` rule.
+
+use super::models::LojbanToken;
+use super::registry::START_RULE;
+
+/// `languages.langid` of Loglan
+pub const LOGLAN_LANGID: i32 = 58;
+
+/// Builds the word grammar from the utterance grammar: single words are classified via
+/// `SingleWord`, anything longer falls back to a full utterance and counts as a phrase.
+pub fn word_grammar(sentence_grammar: &str) -> String {
+    let mut grammar = format!("{} <- (SingleWord / utterance)\n", START_RULE);
+    for line in sentence_grammar.lines() {
+        let is_start_rule = line
+            .split_once("<-")
+            .is_some_and(|(name, _)| name.trim() == START_RULE);
+        if !is_start_rule {
+            grammar.push_str(line);
+            grammar.push('\n');
+        }
+    }
+    grammar
+}
+
+/// Word classes of the `SingleWord` alternatives, outermost first.
+const WORD_RULES: &[&str] = &[
+    "non_terminal_Borrowing",
+    "non_terminal_Complex",
+    "non_terminal_NAMEWORD",
+    "non_terminal_PRENAME",
+    "non_terminal_Cmapua",
+    "non_terminal_CcvNoY",
+];
+
+fn find_token<'a>(tokens: &'a [LojbanToken], kinds: &[&str]) -> Option<&'a LojbanToken> {
+    tokens.iter().find_map(|token| {
+        if kinds.contains(&token.kind.as_str()) {
+            Some(token)
+        } else {
+            find_token(&token.children, kinds)
+        }
+    })
+}
+
+fn letters(word: &str) -> Vec<char> {
+    word.chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Primitives are the five-letter CCVCV and CVCCV predicates.
+fn is_primitive(word: &str) -> bool {
+    let shape: String = letters(word)
+        .into_iter()
+        .map(|c| if "aeiou".contains(c) { 'V' } else { 'C' })
+        .collect();
+    shape == "CCVCV" || shape == "CVCCV"
+}
+
+/// Classifies a word parsed with [`word_grammar`]. Returns one of the Loglan valsi type
+/// descriptors, `phrase`, or an empty string if the parse has no recognisable word.
+pub fn classify_word(tokens: &[LojbanToken], word: &str) -> String {
+    let Some(single) = find_token(tokens, &["non_terminal_SingleWord"]) else {
+        return "phrase".to_string();
+    };
+    let Some(token) = find_token(std::slice::from_ref(single), WORD_RULES) else {
+        return String::new();
+    };
+
+    match token.kind.as_str() {
+        "non_terminal_Borrowing" => "borrowing",
+        "non_terminal_Complex" if is_primitive(word) => "primitive",
+        "non_terminal_Complex" => "complex",
+        "non_terminal_NAMEWORD" | "non_terminal_PRENAME" => "name",
+        // Little words are at most CVV; anything longer is a compound
+        "non_terminal_Cmapua" if letters(word).len() > 3 => "compound little word",
+        "non_terminal_Cmapua" => "little word",
+        _ => "affix",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_word_grammar_and_primitives() {
+        let grammar = word_grammar("text <- utterance\n\nutterance <- [a-z]+\n");
+        assert_eq!(
+            grammar,
+            "text <- (SingleWord / utterance)\n\nutterance <- [a-z]+\n"
+        );
+        assert!(is_primitive("blanu"));
+        assert!(is_primitive("mrenu"));
+        assert!(!is_primitive("blanymao"));
+    }
+}
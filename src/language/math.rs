@@ -0,0 +1,873 @@
+//! In-process parser for the LaTeX subset used in definitions (`$x_1$`, sub- and
+//! superscripts, `\text`, Greek letters, common operators, `\frac`, `\sqrt`), with
+//! error spans and conversion to Unicode plain text and MathML. Other commands and
+//! environments are kept as opaque tokens, so only structural errors such as unbalanced
+//! braces or delimiters are rejected.
+//!
+//! Offsets and spans are in characters, not bytes.
+
+use std::fmt;
+
+use crate::utils::escape_xml;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MathError {
+    pub message: String,
+    /// First character of the offending input
+    pub start: usize,
+    /// One past the last character of the offending input
+    pub end: usize,
+}
+
+impl MathError {
+    fn new(message: impl Into<String>, start: usize, end: usize) -> Self {
+        Self {
+            message: message.into(),
+            start,
+            end,
+        }
+    }
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at {}..{})", self.message, self.start, self.end)
+    }
+}
+
+impl std::error::Error for MathError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    Ident(String),
+    Number(String),
+    Operator(String),
+    Text(String),
+    Function(String),
+    Space,
+    Group(Vec<Item>),
+    Frac(Vec<Item>, Vec<Item>),
+    Sqrt(Vec<Item>),
+    /// `\sqrt[index]{radicand}`
+    Root(Vec<Item>, Vec<Item>),
+    /// A command outside the supported subset; only its arguments are rendered
+    Command(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    base: Atom,
+    sub: Option<Atom>,
+    sup: Option<Atom>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Math { display: bool, items: Vec<Item> },
+}
+
+const GREEK: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+];
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("times", "×"),
+    ("cdot", "⋅"),
+    ("div", "÷"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("le", "≤"),
+    ("leq", "≤"),
+    ("ge", "≥"),
+    ("geq", "≥"),
+    ("ne", "≠"),
+    ("neq", "≠"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("ni", "∋"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("supset", "⊃"),
+    ("supseteq", "⊇"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("setminus", "∖"),
+    ("emptyset", "∅"),
+    ("varnothing", "∅"),
+    ("infty", "∞"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("leftrightarrow", "↔"),
+    ("Leftrightarrow", "⇔"),
+    ("iff", "⟺"),
+    ("implies", "⟹"),
+    ("mapsto", "↦"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("neg", "¬"),
+    ("lnot", "¬"),
+    ("land", "∧"),
+    ("wedge", "∧"),
+    ("lor", "∨"),
+    ("vee", "∨"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("int", "∫"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("circ", "∘"),
+    ("oplus", "⊕"),
+    ("otimes", "⊗"),
+    ("ast", "∗"),
+    ("star", "⋆"),
+    ("bullet", "∙"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("cdots", "⋯"),
+    ("prime", "′"),
+    ("angle", "∠"),
+    ("perp", "⊥"),
+    ("parallel", "∥"),
+    ("mid", "∣"),
+    ("vert", "|"),
+    ("Vert", "‖"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+    ("lfloor", "⌊"),
+    ("rfloor", "⌋"),
+    ("lceil", "⌈"),
+    ("rceil", "⌉"),
+    ("lbrace", "{"),
+    ("rbrace", "}"),
+    ("backslash", "\\"),
+];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "log", "ln", "exp", "lim", "max", "min", "det", "gcd", "deg", "arg",
+    "sup", "inf", "mod", "bmod",
+];
+
+/// Relations get spaces around them in plain text
+const RELATIONS: &[&str] = &[
+    "=", "<", ">", "≤", "≥", "≠", "≈", "≡", "∼", "∈", "∉", "∋", "⊂", "⊆", "⊃", "⊇", "→", "←", "⇒",
+    "⇐", "↔", "⇔", "⟺", "⟹", "↦",
+];
+
+fn lookup(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, symbol)| *symbol)
+}
+
+fn double_struck(letter: &str) -> Option<&'static str> {
+    match letter {
+        "N" => Some("ℕ"),
+        "Z" => Some("ℤ"),
+        "Q" => Some("ℚ"),
+        "R" => Some("ℝ"),
+        "C" => Some("ℂ"),
+        "P" => Some("ℙ"),
+        _ => None,
+    }
+}
+
+/// Finds the closing delimiter of a math span, skipping escaped characters.
+fn find_closing(chars: &[char], from: usize, closing: &[char]) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i..].starts_with(closing) {
+            return Some(i);
+        }
+        i += if chars[i] == '\\' { 2 } else { 1 };
+    }
+    None
+}
+
+fn parse_segments(text: &str) -> Result<Vec<Segment>, MathError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut segments = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (open_len, closing, display): (usize, &[char], bool) = match chars[i] {
+            '\\' if chars.get(i + 1) == Some(&'$') => {
+                plain.push('$');
+                i += 2;
+                continue;
+            }
+            '\\' if chars.get(i + 1) == Some(&'(') => (2, &['\\', ')'], false),
+            '\\' if chars.get(i + 1) == Some(&'[') => (2, &['\\', ']'], true),
+            '\\' if matches!(chars.get(i + 1), Some(')') | Some(']')) => {
+                return Err(MathError::new(
+                    format!("Closing \\{} without an opening delimiter", chars[i + 1]),
+                    i,
+                    i + 2,
+                ));
+            }
+            '$' if chars.get(i + 1) == Some(&'$') => (2, &['$', '$'], true),
+            '$' => (1, &['$'], false),
+            c => {
+                plain.push(c);
+                i += 1;
+                continue;
+            }
+        };
+
+        let start = i + open_len;
+        let Some(end) = find_closing(&chars, start, closing) else {
+            let opening: String = chars[i..start].iter().collect();
+            return Err(MathError::new(
+                format!("Unclosed math delimiter {}", opening),
+                i,
+                chars.len(),
+            ));
+        };
+        if !plain.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut plain)));
+        }
+        let mut parser = MathParser {
+            chars: &chars,
+            pos: start,
+            end,
+            environments: Vec::new(),
+        };
+        let items = parser.parse_items(None)?;
+        if let Some((name, env_start)) = parser.environments.pop() {
+            return Err(MathError::new(
+                format!("Unclosed \\begin{{{}}}", name),
+                env_start,
+                env_start + 6,
+            ));
+        }
+        segments.push(Segment::Math { display, items });
+        i = end + closing.len();
+    }
+
+    if !plain.is_empty() {
+        segments.push(Segment::Text(plain));
+    }
+    Ok(segments)
+}
+
+struct MathParser<'a> {
+    chars: &'a [char],
+    pos: usize,
+    end: usize,
+    /// Open `\begin{..}` environments with the position of their backslash
+    environments: Vec<(String, usize)>,
+}
+
+impl MathParser<'_> {
+    fn peek(&self) -> Option<char> {
+        (self.pos < self.end).then(|| self.chars[self.pos])
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses items up to the end of the math span, or up to the `}` closing a group
+    /// opened at `group_start`.
+    fn parse_items(&mut self, group_start: Option<usize>) -> Result<Vec<Item>, MathError> {
+        let mut items: Vec<Item> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let Some(c) = self.peek() else {
+                return match group_start {
+                    Some(start) => Err(MathError::new("Unclosed {", start, start + 1)),
+                    None => Ok(items),
+                };
+            };
+            match c {
+                '}' => {
+                    if group_start.is_some() {
+                        self.pos += 1;
+                        return Ok(items);
+                    }
+                    return Err(MathError::new("Unmatched }", self.pos, self.pos + 1));
+                }
+                '_' | '^' => {
+                    let script_start = self.pos;
+                    self.pos += 1;
+                    // TeX attaches a script without a base to an empty one
+                    if items.is_empty() {
+                        items.push(Item {
+                            base: Atom::Group(Vec::new()),
+                            sub: None,
+                            sup: None,
+                        });
+                    }
+                    let last_index = items.len() - 1;
+                    let last = &mut items[last_index];
+                    let slot = if c == '_' {
+                        &mut last.sub
+                    } else {
+                        &mut last.sup
+                    };
+                    if slot.is_some() {
+                        return Err(MathError::new(
+                            format!(
+                                "Double {}",
+                                if c == '_' { "subscript" } else { "superscript" }
+                            ),
+                            script_start,
+                            script_start + 1,
+                        ));
+                    }
+                    *slot = Some(self.parse_operand(script_start)?);
+                }
+                _ => {
+                    let base = self.parse_atom()?;
+                    items.push(Item {
+                        base,
+                        sub: None,
+                        sup: None,
+                    });
+                }
+            }
+        }
+    }
+
+    /// A single-token argument: a group, a command or one character.
+    fn parse_operand(&mut self, owner_start: usize) -> Result<Atom, MathError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('}') => Err(MathError::new(
+                "Missing argument",
+                owner_start,
+                self.pos.max(owner_start + 1),
+            )),
+            Some('{') => {
+                let start = self.pos;
+                self.pos += 1;
+                Ok(Atom::Group(self.parse_items(Some(start))?))
+            }
+            Some('\\') => self.parse_command(),
+            Some(c) => {
+                self.pos += 1;
+                self.char_atom(c, self.pos - 1)
+            }
+        }
+    }
+
+    fn parse_group_argument(&mut self, owner_start: usize) -> Result<Vec<Item>, MathError> {
+        Ok(match self.parse_operand(owner_start)? {
+            Atom::Group(items) => items,
+            atom => vec![Item {
+                base: atom,
+                sub: None,
+                sup: None,
+            }],
+        })
+    }
+
+    fn char_atom(&self, c: char, pos: usize) -> Result<Atom, MathError> {
+        match c {
+            c if c.is_ascii_digit() => Ok(Atom::Number(c.to_string())),
+            c if c.is_alphabetic() => Ok(Atom::Ident(c.to_string())),
+            '~' => Ok(Atom::Space),
+            // Column separator inside matrix-like environments
+            '&' if !self.environments.is_empty() => Ok(Atom::Space),
+            '&' | '#' | '%' => Err(MathError::new(format!("Unexpected '{}'", c), pos, pos + 1)),
+            '-' => Ok(Atom::Operator("−".to_string())),
+            c => Ok(Atom::Operator(c.to_string())),
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Atom, MathError> {
+        let start = self.pos;
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                Ok(Atom::Group(self.parse_items(Some(start))?))
+            }
+            Some('\\') => self.parse_command(),
+            Some(c) if c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(c) = self.peek().filter(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                    self.pos += 1;
+                }
+                Ok(Atom::Number(number))
+            }
+            Some(c) => {
+                self.pos += 1;
+                self.char_atom(c, start)
+            }
+            None => Err(MathError::new("Unexpected end of math", start, start)),
+        }
+    }
+
+    /// Reads a braced argument verbatim, for `\text` and friends.
+    fn parse_raw_argument(&mut self, owner_start: usize) -> Result<String, MathError> {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            return Err(MathError::new(
+                "Expected {",
+                owner_start,
+                self.pos.max(owner_start + 1),
+            ));
+        }
+        let open = self.pos;
+        self.pos += 1;
+        let mut depth = 1;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(text);
+                    }
+                }
+                '\\' => {
+                    if let Some(escaped) = self.peek() {
+                        self.pos += 1;
+                        text.push(escaped);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            text.push(c);
+        }
+        Err(MathError::new("Unclosed {", open, open + 1))
+    }
+
+    fn parse_command(&mut self) -> Result<Atom, MathError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            name.push(c);
+            self.pos += 1;
+        }
+        if name.is_empty() {
+            let Some(c) = self.peek() else {
+                return Err(MathError::new("Lone backslash", start, start + 1));
+            };
+            self.pos += 1;
+            return Ok(match c {
+                ',' | ';' | ':' | '!' | ' ' | '\\' => Atom::Space,
+                '{' | '}' | '_' | '$' | '%' | '#' | '&' | '|' => Atom::Operator(c.to_string()),
+                _ => Atom::Command(c.to_string()),
+            });
+        }
+
+        if let Some(symbol) = lookup(GREEK, &name) {
+            return Ok(Atom::Ident(symbol.to_string()));
+        }
+        if let Some(symbol) = lookup(OPERATORS, &name) {
+            return Ok(Atom::Operator(symbol.to_string()));
+        }
+        if FUNCTIONS.contains(&name.as_str()) {
+            return Ok(Atom::Function(name));
+        }
+        match name.as_str() {
+            "text" | "textrm" | "mathrm" | "mbox" | "textit" | "textbf" => {
+                Ok(Atom::Text(self.parse_raw_argument(start)?))
+            }
+            "operatorname" => Ok(Atom::Function(self.parse_raw_argument(start)?)),
+            "mathbb" => {
+                let letters = self.parse_raw_argument(start)?;
+                Ok(match double_struck(&letters) {
+                    Some(symbol) => Atom::Ident(symbol.to_string()),
+                    None => Atom::Ident(letters),
+                })
+            }
+            "mathbf" | "mathit" | "mathcal" | "mathsf" | "boldsymbol" => {
+                Ok(Atom::Group(self.parse_group_argument(start)?))
+            }
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.parse_group_argument(start)?;
+                let denominator = self.parse_group_argument(start)?;
+                Ok(Atom::Frac(numerator, denominator))
+            }
+            "sqrt" => match self.parse_optional_argument()? {
+                Some(index) => Ok(Atom::Root(index, self.parse_group_argument(start)?)),
+                None => Ok(Atom::Sqrt(self.parse_group_argument(start)?)),
+            },
+            "begin" => {
+                let name = self.parse_raw_argument(start)?;
+                self.environments.push((name, start));
+                Ok(Atom::Space)
+            }
+            "end" => {
+                let name = self.parse_raw_argument(start)?;
+                match self.environments.pop() {
+                    Some((open, _)) if open == name => Ok(Atom::Space),
+                    Some((open, _)) => Err(MathError::new(
+                        format!("\\end{{{}}} does not match \\begin{{{}}}", name, open),
+                        start,
+                        self.pos,
+                    )),
+                    None => Err(MathError::new(
+                        format!("\\end{{{}}} without \\begin", name),
+                        start,
+                        self.pos,
+                    )),
+                }
+            }
+            "left" | "right" => {
+                self.skip_whitespace();
+                match self.peek() {
+                    Some('.') => {
+                        self.pos += 1;
+                        Ok(Atom::Space)
+                    }
+                    Some('\\') => self.parse_command(),
+                    Some(c) if "()[]|/<>".contains(c) => {
+                        self.pos += 1;
+                        Ok(Atom::Operator(c.to_string()))
+                    }
+                    _ => Err(MathError::new(
+                        format!("Missing delimiter after \\{}", name),
+                        start,
+                        self.pos,
+                    )),
+                }
+            }
+            "quad" | "qquad" => Ok(Atom::Space),
+            // Accents, macros and anything else MathJax knows; their arguments parse as
+            // ordinary groups
+            _ => Ok(Atom::Command(name)),
+        }
+    }
+
+    /// Parses a `[..]` argument such as the index of `\sqrt[n]{x}`, if one follows.
+    fn parse_optional_argument(&mut self) -> Result<Option<Vec<Item>>, MathError> {
+        self.skip_whitespace();
+        if self.peek() != Some('[') {
+            return Ok(None);
+        }
+        let open = self.pos;
+        let mut depth = 0;
+        let mut close = None;
+        let mut i = open + 1;
+        while i < self.end {
+            match self.chars[i] {
+                '\\' => i += 1,
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ']' if depth == 0 => {
+                    close = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        let Some(close) = close else {
+            return Err(MathError::new("Unclosed [", open, open + 1));
+        };
+
+        let end = std::mem::replace(&mut self.end, close);
+        self.pos = open + 1;
+        let items = self.parse_items(None);
+        self.end = end;
+        self.pos = close + 1;
+        items.map(Some)
+    }
+}
+
+/// Checks every math span in `text`.
+pub fn validate(text: &str) -> Result<(), MathError> {
+    parse_segments(text).map(|_| ())
+}
+
+fn subscript_char(c: char) -> Option<char> {
+    Some(match c {
+        '0'..='9' => char::from_u32('₀' as u32 + (c as u32 - '0' as u32))?,
+        '+' => '₊',
+        '−' | '-' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'h' => 'ₕ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'l' => 'ₗ',
+        'm' => 'ₘ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'p' => 'ₚ',
+        'r' => 'ᵣ',
+        's' => 'ₛ',
+        't' => 'ₜ',
+        'u' => 'ᵤ',
+        'v' => 'ᵥ',
+        'x' => 'ₓ',
+        _ => return None,
+    })
+}
+
+fn superscript_char(c: char) -> Option<char> {
+    Some(match c {
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '0' | '4'..='9' => char::from_u32('⁰' as u32 + (c as u32 - '0' as u32))?,
+        '+' => '⁺',
+        '−' | '-' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'i' => 'ⁱ',
+        'n' => 'ⁿ',
+        '′' => '′',
+        _ => return None,
+    })
+}
+
+fn unicode_script(rendered: &str, marker: char, map: fn(char) -> Option<char>) -> String {
+    match rendered.chars().map(map).collect::<Option<String>>() {
+        Some(script) => script,
+        None if rendered.chars().count() == 1 => format!("{}{}", marker, rendered),
+        None => format!("{}({})", marker, rendered),
+    }
+}
+
+fn unicode_items(items: &[Item]) -> String {
+    items.iter().map(unicode_item).collect()
+}
+
+fn unicode_wrapped(items: &[Item]) -> String {
+    let rendered = unicode_items(items);
+    if rendered.chars().count() > 1 {
+        format!("({})", rendered)
+    } else {
+        rendered
+    }
+}
+
+fn unicode_atom(atom: &Atom) -> String {
+    match atom {
+        Atom::Ident(s) | Atom::Number(s) | Atom::Text(s) | Atom::Function(s) => s.clone(),
+        Atom::Operator(op) if RELATIONS.contains(&op.as_str()) => format!(" {} ", op),
+        Atom::Operator(op) => op.clone(),
+        Atom::Space => " ".to_string(),
+        Atom::Group(items) => unicode_items(items),
+        Atom::Frac(numerator, denominator) => format!(
+            "{}/{}",
+            unicode_wrapped(numerator),
+            unicode_wrapped(denominator)
+        ),
+        Atom::Sqrt(items) => format!("√{}", unicode_wrapped(items)),
+        Atom::Root(index, items) => format!(
+            "{}√{}",
+            unicode_script(unicode_items(index).trim(), '^', superscript_char),
+            unicode_wrapped(items)
+        ),
+        Atom::Command(_) => String::new(),
+    }
+}
+
+fn unicode_item(item: &Item) -> String {
+    let mut out = unicode_atom(&item.base);
+    if let Some(sub) = &item.sub {
+        out.push_str(&unicode_script(
+            unicode_atom(sub).trim(),
+            '_',
+            subscript_char,
+        ));
+    }
+    if let Some(sup) = &item.sup {
+        out.push_str(&unicode_script(
+            unicode_atom(sup).trim(),
+            '^',
+            superscript_char,
+        ));
+    }
+    out
+}
+
+/// Renders the text with every math span converted to Unicode, e.g. `$x_1$` to `x₁`.
+pub fn to_unicode(text: &str) -> Result<String, MathError> {
+    Ok(parse_segments(text)?
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.clone(),
+            Segment::Math { items, .. } => unicode_items(items).trim().to_string(),
+        })
+        .collect())
+}
+
+/// Like [`to_unicode`], but leaves text whose math doesn't parse unchanged.
+pub fn to_unicode_lossy(text: &str) -> String {
+    to_unicode(text).unwrap_or_else(|_| text.to_string())
+}
+
+fn mathml_items(items: &[Item]) -> String {
+    items.iter().map(mathml_item).collect()
+}
+
+fn mathml_row(items: &[Item]) -> String {
+    format!("<mrow>{}</mrow>", mathml_items(items))
+}
+
+fn mathml_atom(atom: &Atom) -> String {
+    match atom {
+        Atom::Ident(s) | Atom::Function(s) => format!("<mi>{}</mi>", escape_xml(s)),
+        Atom::Number(s) => format!("<mn>{}</mn>", escape_xml(s)),
+        Atom::Operator(s) => format!("<mo>{}</mo>", escape_xml(s)),
+        Atom::Text(s) => format!("<mtext>{}</mtext>", escape_xml(s)),
+        Atom::Space => r#"<mspace width="0.5em"/>"#.to_string(),
+        Atom::Group(items) => mathml_row(items),
+        Atom::Frac(numerator, denominator) => format!(
+            "<mfrac>{}{}</mfrac>",
+            mathml_row(numerator),
+            mathml_row(denominator)
+        ),
+        Atom::Sqrt(items) => format!("<msqrt>{}</msqrt>", mathml_items(items)),
+        Atom::Root(index, items) => {
+            format!("<mroot>{}{}</mroot>", mathml_row(items), mathml_row(index))
+        }
+        Atom::Command(_) => "<mrow/>".to_string(),
+    }
+}
+
+fn mathml_item(item: &Item) -> String {
+    let base = mathml_atom(&item.base);
+    match (&item.sub, &item.sup) {
+        (None, None) => base,
+        (Some(sub), None) => format!("<msub>{}{}</msub>", base, mathml_atom(sub)),
+        (None, Some(sup)) => format!("<msup>{}{}</msup>", base, mathml_atom(sup)),
+        (Some(sub), Some(sup)) => format!(
+            "<msubsup>{}{}{}</msubsup>",
+            base,
+            mathml_atom(sub),
+            mathml_atom(sup)
+        ),
+    }
+}
+
+/// Renders the text as XML-escaped text with every math span converted to a MathML
+/// `<math>` element.
+pub fn to_mathml(text: &str) -> Result<String, MathError> {
+    Ok(parse_segments(text)?
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => escape_xml(text),
+            Segment::Math { display, items } => format!(
+                r#"<math xmlns="http://www.w3.org/1998/Math/MathML"{}>{}</math>"#,
+                if *display { r#" display="block""# } else { "" },
+                mathml_items(items)
+            ),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_place_structures_to_unicode() {
+        assert_eq!(
+            to_unicode("$x_1$ goes to $x_{2}$ from $x_3$").unwrap(),
+            "x₁ goes to x₂ from x₃"
+        );
+        assert_eq!(
+            to_unicode(r"$\alpha^2 \le \frac{1}{n}$").unwrap(),
+            "α² ≤ 1/n"
+        );
+        assert_eq!(to_unicode(r"$x_{\text{big}}$").unwrap(), "x_(big)");
+        assert_eq!(to_unicode(r"costs \$5").unwrap(), "costs $5");
+    }
+
+    #[test]
+    fn renders_mathml() {
+        assert_eq!(
+            to_mathml("a < $x_1$").unwrap(),
+            r#"a &lt; <math xmlns="http://www.w3.org/1998/Math/MathML"><msub><mi>x</mi><mn>1</mn></msub></math>"#
+        );
+    }
+
+    #[test]
+    fn reports_error_spans() {
+        let error = validate("see $x_1").unwrap_err();
+        assert_eq!((error.start, error.end), (4, 8));
+
+        let error = validate(r"$\begin{pmatrix} a \end{bmatrix}$").unwrap_err();
+        assert_eq!(
+            error.message,
+            r"\end{bmatrix} does not match \begin{pmatrix}"
+        );
+        assert_eq!((error.start, error.end), (19, 32));
+
+        let error = validate("$x_1_2$").unwrap_err();
+        assert_eq!(error.message, "Double subscript");
+        assert_eq!((error.start, error.end), (4, 5));
+
+        assert!(validate("${x$").is_err());
+        assert!(validate("$x_$").is_err());
+        assert!(validate(r"$\sqrt[3{x}$").is_err());
+    }
+
+    #[test]
+    fn accepts_commands_outside_the_subset() {
+        for text in [
+            r"$\bar{x}_1 = \vec{v} \cdot \hat{n}$",
+            r"$\overline{AB} + \ell + \binom{n}{k}$",
+            r"$\begin{pmatrix} a & b \\ c & d \end{pmatrix}$",
+            r"$_2F_1$",
+        ] {
+            assert_eq!(validate(text), Ok(()), "{}", text);
+        }
+        assert_eq!(to_unicode(r"$\sqrt[3]{x}$").unwrap(), "³√x");
+        assert_eq!(to_unicode(r"$\bar{x}$").unwrap(), "x");
+        assert_eq!(to_unicode(r"$\vec{v} = 2$").unwrap(), "v = 2");
+    }
+}
@@ -1,26 +1,51 @@
 pub mod controller;
 pub mod dto;
+pub mod loglan;
+pub mod math;
 pub mod models;
+mod parse_tree;
+pub mod registry;
 mod service;
 
 use actix_web::web;
+use actix_web_grants::GrantsMiddleware;
 use actix_web_httpauth::middleware::HttpAuthentication;
 
+use crate::auth::extractor::extract_authorities;
 pub use models::MathJaxValidationOptions;
-pub use service::{analyze_word, validate_mathjax};
+pub use registry::{GrammarRegistry, GrammarTexts, WorkerParsers};
+pub use service::{
+    analyze_word, fail_interrupted_regression_runs, reload_grammars, validate_mathjax,
+};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("language")
             // Public routes
             .service(controller::get_languages)
+            .service(controller::decompose_lujvo)
+            .service(controller::build_lujvo)
             // Protected routes
             .service(
                 web::scope("")
+                    .wrap(GrantsMiddleware::with_extractor(extract_authorities))
                     .wrap(HttpAuthentication::bearer(crate::auth::validator))
                     .service(controller::analyze_word)
                     .service(controller::validate_mathjax)
-                    .service(controller::parse_lojban),
+                    .service(controller::parse_lojban)
+                    .service(controller::analyze_semantics)
+                    .service(controller::gloss_text)
+                    .service(controller::list_grammars)
+                    .service(controller::upload_grammar)
+                    .service(controller::test_grammar)
+                    .service(controller::reload_grammars)
+                    .service(controller::list_grammar_samples)
+                    .service(controller::add_grammar_sample)
+                    .service(controller::delete_grammar_sample)
+                    .service(controller::start_grammar_regression)
+                    .service(controller::list_grammar_regressions)
+                    .service(controller::get_grammar_regression)
+                    .service(controller::activate_grammar),
             ),
     );
 }
@@ -1,12 +1,16 @@
+use camxes_rs::peg::grammar::Peg;
 use camxes_rs::peg::parsing::{ParseNode, Span};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use tokio_postgres::Row;
 use utoipa::ToSchema;
 
+use super::math::MathError;
+
 #[derive(Debug)]
 pub enum MathJaxValidationError {
-    Balance(String),
+    Parse(MathError),
     Syntax(String),
     Tectonic(String),
 }
@@ -14,7 +18,7 @@ pub enum MathJaxValidationError {
 impl std::fmt::Display for MathJaxValidationError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            MathJaxValidationError::Balance(msg) => write!(f, "Unbalanced delimiters: {}", msg),
+            MathJaxValidationError::Parse(e) => write!(f, "Syntax error: {}", e),
             MathJaxValidationError::Syntax(msg) => write!(f, "Syntax error: {}", msg),
             MathJaxValidationError::Tectonic(msg) => write!(f, "LaTeX compilation error: {}", msg),
         }
@@ -24,6 +28,8 @@ impl StdError for MathJaxValidationError {}
 
 #[derive(Debug)]
 pub struct MathJaxValidationOptions {
+    /// Also compile the text with Tectonic. The in-process parser covers the subset
+    /// definitions use, so this is only worth it ahead of a PDF export.
     pub use_tectonic: bool,
 }
 
@@ -52,6 +58,10 @@ impl From<Row> for Language {
     }
 }
 
+/// Sentence-level parsers by langid. Kept apart from the word-level parser map because
+/// word analysis relies on the rule names of the morphology grammars.
+pub struct SentenceParsers(pub HashMap<i32, Peg>);
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct LojbanToken {
     pub kind: String,
@@ -0,0 +1,270 @@
+//! Conversion of camxes token trees into display-oriented parse trees and their
+//! text renderings (bracketed text, "terbrismi" HTML boxes, Graphviz DOT).
+
+use super::dto::ParseTreeNode;
+use super::models::LojbanToken;
+use crate::utils::escape_xml;
+
+/// Rules that name a whole word; simplified trees stop descending at them.
+const WORD_RULES: &[&str] = &[
+    "any_word", "jbovla", "word", "cmavo", "gismu", "lujvo", "fuhivla", "cmevla", "brivla",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseOutputFormat {
+    Tokens,
+    Json,
+    Brackets,
+    Html,
+    Dot,
+}
+
+impl ParseOutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tokens" => Some(Self::Tokens),
+            "json" => Some(Self::Json),
+            "brackets" => Some(Self::Brackets),
+            "html" => Some(Self::Html),
+            "dot" => Some(Self::Dot),
+            _ => None,
+        }
+    }
+}
+
+fn is_word_rule(rule: &str) -> bool {
+    // camxes names selma'o rules in upper case (KOhA, BRIVLA, ...)
+    rule.starts_with(|c: char| c.is_ascii_uppercase()) || WORD_RULES.contains(&rule)
+}
+
+/// Converts a token into a tree node. With `simplify`, terminals are dropped, word-level
+/// rules become leaves and chains of single-child rules collapse into the outermost rule.
+pub fn to_tree(token: &LojbanToken, simplify: bool) -> Option<ParseTreeNode> {
+    let rule = token
+        .kind
+        .strip_prefix("non_terminal_")
+        .unwrap_or(&token.kind)
+        .to_string();
+
+    if token.kind == "terminal" {
+        return (!simplify).then(|| ParseTreeNode {
+            rule,
+            text: token.text.clone(),
+            start: token.start,
+            end: token.end,
+            children: vec![],
+        });
+    }
+
+    if simplify && is_word_rule(&rule) {
+        return Some(ParseTreeNode {
+            rule,
+            text: token.text.clone(),
+            start: token.start,
+            end: token.end,
+            children: vec![],
+        });
+    }
+
+    let mut children: Vec<ParseTreeNode> = token
+        .children
+        .iter()
+        .filter_map(|child| to_tree(child, simplify))
+        .collect();
+
+    if simplify && children.len() == 1 && !children[0].children.is_empty() {
+        children = children.remove(0).children;
+    }
+
+    Some(ParseTreeNode {
+        rule,
+        text: token.text.clone(),
+        start: token.start,
+        end: token.end,
+        children,
+    })
+}
+
+pub fn render_brackets(nodes: &[ParseTreeNode]) -> String {
+    fn bracket(node: &ParseTreeNode, out: &mut String) {
+        out.push('[');
+        out.push_str(&node.rule);
+        if node.children.is_empty() {
+            out.push(' ');
+            out.push_str(&node.text);
+        }
+        for child in &node.children {
+            out.push(' ');
+            bracket(child, out);
+        }
+        out.push(']');
+    }
+
+    let mut out = String::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        bracket(node, &mut out);
+    }
+    out
+}
+
+/// Stable per-rule hue so the same construct always gets the same box colour.
+fn rule_hue(rule: &str) -> u32 {
+    rule.bytes().fold(2166136261u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(16777619)
+    }) % 360
+}
+
+/// Nested coloured boxes in the style of terbrismi. Styling beyond the border colour is
+/// left to the client via the `terbrismi-*` classes.
+pub fn render_html(nodes: &[ParseTreeNode]) -> String {
+    fn html_box(node: &ParseTreeNode, out: &mut String) {
+        let rule = escape_xml(&node.rule);
+        if node.children.is_empty() {
+            out.push_str(&format!(
+                r#"<span class="terbrismi-word" title="{}">{}</span>"#,
+                rule,
+                escape_xml(&node.text)
+            ));
+            return;
+        }
+        out.push_str(&format!(
+            r#"<div class="terbrismi-box" data-rule="{}" style="border: 2px solid hsl({}, 55%, 45%)"><span class="terbrismi-label">{}</span>"#,
+            rule,
+            rule_hue(&node.rule),
+            rule
+        ));
+        for child in &node.children {
+            html_box(child, out);
+        }
+        out.push_str("</div>");
+    }
+
+    let mut out = String::from(r#"<div class="terbrismi">"#);
+    for node in nodes {
+        html_box(node, &mut out);
+    }
+    out.push_str("</div>");
+    out
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn render_dot(nodes: &[ParseTreeNode]) -> String {
+    fn dot_node(
+        node: &ParseTreeNode,
+        parent: Option<usize>,
+        next_id: &mut usize,
+        out: &mut String,
+    ) {
+        let id = *next_id;
+        *next_id += 1;
+        if node.children.is_empty() {
+            out.push_str(&format!(
+                "  n{} [label=\"{}\\n{}\", shape=ellipse];\n",
+                id,
+                escape_dot(&node.rule),
+                escape_dot(&node.text)
+            ));
+        } else {
+            out.push_str(&format!(
+                "  n{} [label=\"{}\"];\n",
+                id,
+                escape_dot(&node.rule)
+            ));
+        }
+        if let Some(parent) = parent {
+            out.push_str(&format!("  n{} -> n{};\n", parent, id));
+        }
+        for child in &node.children {
+            dot_node(child, Some(id), next_id, out);
+        }
+    }
+
+    let mut out = String::from("digraph parse {\n  node [shape=box, fontname=\"sans-serif\"];\n");
+    let mut next_id = 0;
+    for node in nodes {
+        dot_node(node, None, &mut next_id, &mut out);
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(kind: &str, text: &str, start: usize, children: Vec<LojbanToken>) -> LojbanToken {
+        LojbanToken {
+            kind: kind.to_string(),
+            text: text.to_string(),
+            start,
+            end: start + text.len(),
+            children,
+        }
+    }
+
+    fn sample() -> LojbanToken {
+        // text -> sentence -> (sumti -> KOhA -> terminal) (selbri -> gismu -> terminal)
+        token(
+            "non_terminal_text",
+            "mi klama",
+            0,
+            vec![token(
+                "non_terminal_sentence",
+                "mi klama",
+                0,
+                vec![
+                    token(
+                        "non_terminal_sumti",
+                        "mi",
+                        0,
+                        vec![token(
+                            "non_terminal_KOhA",
+                            "mi",
+                            0,
+                            vec![token("terminal", "mi", 0, vec![])],
+                        )],
+                    ),
+                    token(
+                        "non_terminal_selbri",
+                        "klama",
+                        3,
+                        vec![token(
+                            "non_terminal_gismu",
+                            "klama",
+                            3,
+                            vec![token("terminal", "klama", 3, vec![])],
+                        )],
+                    ),
+                ],
+            )],
+        )
+    }
+
+    #[test]
+    fn simplified_tree_renders_as_brackets() {
+        let tree: Vec<_> = to_tree(&sample(), true).into_iter().collect();
+        assert_eq!(
+            render_brackets(&tree),
+            "[text [sumti [KOhA mi]] [selbri [gismu klama]]]"
+        );
+    }
+
+    #[test]
+    fn dot_and_html_escape_labels() {
+        let node = ParseTreeNode {
+            rule: "quote".to_string(),
+            text: "a\"<b>".to_string(),
+            start: 0,
+            end: 5,
+            children: vec![],
+        };
+        assert!(render_dot(std::slice::from_ref(&node)).contains("a\\\"<b>"));
+        assert!(render_html(&[node]).contains("a&quot;&lt;b&gt;"));
+    }
+}
@@ -0,0 +1,197 @@
+//! Process-wide grammar registry with per-worker compiled parsers.
+//!
+//! Grammar texts live in one shared [`GrammarRegistry`]. Each actix worker compiles its
+//! own `Peg` parsers from it ([`WorkerParsers`]) and recompiles lazily whenever the
+//! registry generation changes, so a reload reaches every worker without a restart.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use camxes_rs::peg::grammar::Peg;
+use log::{error, info};
+
+use super::models::SentenceParsers;
+
+/// Rule every grammar starts parsing from
+pub const START_RULE: &str = "text";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GrammarKind {
+    /// Morphology grammar used for word analysis
+    Word,
+    /// Full grammar used for sentence parsing
+    Sentence,
+}
+
+impl GrammarKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrammarKind::Word => "word",
+            GrammarKind::Sentence => "sentence",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "word" => Some(GrammarKind::Word),
+            "sentence" => Some(GrammarKind::Sentence),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GrammarTexts {
+    pub word: HashMap<i32, String>,
+    pub sentence: HashMap<i32, String>,
+}
+
+impl GrammarTexts {
+    fn get_mut(&mut self, kind: GrammarKind) -> &mut HashMap<i32, String> {
+        match kind {
+            GrammarKind::Word => &mut self.word,
+            GrammarKind::Sentence => &mut self.sentence,
+        }
+    }
+}
+
+pub struct GrammarRegistry {
+    /// Grammars bundled on disk, used for any language without an active stored version
+    bundled: GrammarTexts,
+    /// Generation and texts are swapped together so workers never see a mixed state
+    current: RwLock<(u64, Arc<GrammarTexts>)>,
+}
+
+impl GrammarRegistry {
+    pub fn new(bundled: GrammarTexts) -> Self {
+        let current = Arc::new(bundled.clone());
+        Self {
+            bundled,
+            current: RwLock::new((0, current)),
+        }
+    }
+
+    pub fn snapshot(&self) -> (u64, Arc<GrammarTexts>) {
+        match self.current.read() {
+            Ok(current) => (current.0, current.1.clone()),
+            Err(poisoned) => {
+                let current = poisoned.into_inner();
+                (current.0, current.1.clone())
+            }
+        }
+    }
+
+    /// Replaces the active grammars with the bundled ones overlaid by `stored`, and bumps
+    /// the generation so workers recompile on their next request.
+    pub fn apply(&self, stored: &[(i32, GrammarKind, String)]) {
+        let mut texts = self.bundled.clone();
+        for (langid, kind, text) in stored {
+            texts.get_mut(*kind).insert(*langid, text.clone());
+        }
+
+        let mut current = match self.current.write() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *current = (current.0 + 1, Arc::new(texts));
+        info!("Grammar registry updated to generation {}", current.0);
+    }
+}
+
+/// Compiles every grammar of one kind, skipping (and logging) those that fail.
+pub fn compile_parsers(
+    grammar_texts: &HashMap<i32, String>,
+    kind: GrammarKind,
+) -> HashMap<i32, Peg> {
+    let mut parsers = HashMap::new();
+    for (lang_id, grammar_text) in grammar_texts.iter() {
+        match Peg::new(START_RULE, grammar_text) {
+            Ok(parser) => {
+                parsers.insert(*lang_id, parser);
+                info!(
+                    "Worker {:?} initialized {} parser for language ID {}",
+                    std::thread::current().id(),
+                    kind.as_str(),
+                    lang_id
+                );
+            }
+            Err(e) => {
+                error!(
+                    "Worker {:?} failed to initialize {} parser for language ID {}: {}",
+                    std::thread::current().id(),
+                    kind.as_str(),
+                    lang_id,
+                    e
+                );
+            }
+        }
+    }
+    parsers
+}
+
+struct CompiledParsers {
+    generation: u64,
+    word: Arc<HashMap<i32, Peg>>,
+    sentence: Arc<SentenceParsers>,
+}
+
+/// Parsers owned by a single worker thread, registered as worker-local app data.
+pub struct WorkerParsers {
+    registry: Arc<GrammarRegistry>,
+    compiled: RefCell<Option<CompiledParsers>>,
+}
+
+impl WorkerParsers {
+    pub fn new(registry: Arc<GrammarRegistry>) -> Self {
+        let parsers = Self {
+            registry,
+            compiled: RefCell::new(None),
+        };
+        // Compile up front so the first request doesn't pay for it
+        parsers.current();
+        parsers
+    }
+
+    fn current(&self) -> (Arc<HashMap<i32, Peg>>, Arc<SentenceParsers>) {
+        let (generation, texts) = self.registry.snapshot();
+        let mut compiled = self.compiled.borrow_mut();
+        let compiled = match compiled.take() {
+            Some(existing) if existing.generation == generation => compiled.insert(existing),
+            _ => compiled.insert(CompiledParsers {
+                generation,
+                word: Arc::new(compile_parsers(&texts.word, GrammarKind::Word)),
+                sentence: Arc::new(SentenceParsers(compile_parsers(
+                    &texts.sentence,
+                    GrammarKind::Sentence,
+                ))),
+            }),
+        };
+        (compiled.word.clone(), compiled.sentence.clone())
+    }
+
+    pub fn word_parsers(&self) -> Arc<HashMap<i32, Peg>> {
+        self.current().0
+    }
+
+    pub fn sentence_parsers(&self) -> Arc<SentenceParsers> {
+        self.current().1
+    }
+}
+
+thread_local! {
+    /// Parsers of the blocking-pool thread this runs on, see [`with_blocking_parsers`]
+    static BLOCKING_PARSERS: RefCell<Option<WorkerParsers>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with parsers owned by the current thread, compiled on first use and kept
+/// in sync with `registry` like a worker's. For parses run off the async workers.
+pub fn with_blocking_parsers<R>(
+    registry: Arc<GrammarRegistry>,
+    f: impl FnOnce(&WorkerParsers) -> R,
+) -> R {
+    BLOCKING_PARSERS.with(|parsers| {
+        let mut parsers = parsers.borrow_mut();
+        f(parsers.get_or_insert_with(|| WorkerParsers::new(registry)))
+    })
+}
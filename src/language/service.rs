@@ -1,13 +1,20 @@
 use std::{collections::HashMap, sync::Arc};
 
 use crate::language::dto::*;
-use crate::language::models::{Language, LojbanToken};
+use crate::language::loglan::{self, LOGLAN_LANGID};
+use crate::language::math;
+use crate::language::models::{Language, LojbanToken, SentenceParsers};
+use crate::language::parse_tree::{self, ParseOutputFormat};
+use crate::language::registry::{GrammarKind, GrammarRegistry, START_RULE};
+use crate::tersmu::{self, TersmuError, TersmuOutput};
 use camxes_rs::peg::{grammar::Peg, parsing::ParseResult};
 use deadpool_postgres::{Pool, Transaction};
-use log::warn;
+use log::{info, warn};
+use once_cell::sync::Lazy;
 use regex::Regex;
 use vlazba::gismu_utils::GismuMatcher;
 use vlazba::jvokaha::jvokaha;
+use vlazba::jvozba::jvozba;
 use vlazba::jvozba::tools::RafsiOptions;
 use vlazba::reconstruct_lujvo;
 
@@ -32,53 +39,261 @@ pub async fn get_languages(pool: &Pool) -> Result<Vec<Language>, Box<dyn std::er
     Ok(languages)
 }
 
-// TODO: Adapt this function or create a new one if parsing for non-Lojban languages is needed.
-// Currently, it assumes the Lojban parser (ID 1).
-pub fn parse_lojban(parsers: &Arc<HashMap<i32, Peg>>, input: &str) -> LojbanParseResponse {
-    // Default to Lojban parser
-    let parser = match parsers.get(&1) {
+/// Longer texts are rejected before parsing; a failed parse is re-run for every probe
+const MAX_PARSE_INPUT_BYTES: usize = 16 * 1024;
+
+/// Parses `request.text` with the word (`mode: words`) or sentence (`mode: full`) grammar
+/// of the requested language and renders the result in the requested format.
+/// Blocking; run it off the async workers.
+pub fn parse_lojban(
+    parsers: &Arc<HashMap<i32, Peg>>,
+    sentence_parsers: &SentenceParsers,
+    request: &LojbanParseRequest,
+) -> LojbanParseResponse {
+    let input = request.text.as_str();
+    let source_langid = request.source_langid.unwrap_or(1);
+    if input.len() > MAX_PARSE_INPUT_BYTES {
+        return parse_failure(
+            format!(
+                "Text is too long ({} bytes, at most {})",
+                input.len(),
+                MAX_PARSE_INPUT_BYTES
+            ),
+            None,
+        );
+    }
+
+    let format_name = request.format.as_deref().unwrap_or("tokens");
+    let Some(format) = ParseOutputFormat::from_name(format_name) else {
+        return parse_failure(
+            format!(
+                "Unknown format '{}', expected tokens, json, brackets, html or dot",
+                format_name
+            ),
+            None,
+        );
+    };
+
+    let (parser_map, grammar_kind, full) = match request.mode.as_deref().unwrap_or("words") {
+        "words" => (parsers.as_ref(), "word", false),
+        "full" => (&sentence_parsers.0, "sentence", true),
+        other => {
+            return parse_failure(
+                format!("Unknown mode '{}', expected words or full", other),
+                None,
+            )
+        }
+    };
+
+    let parser = match parser_map.get(&source_langid) {
         Some(p) => p,
         None => {
-            return LojbanParseResponse {
-                success: false,
-                tokens: vec![],
-                error: Some("Lojban parser not available".to_string()),
-            }
+            return parse_failure(
+                format!(
+                    "No {} grammar loaded for language ID {}",
+                    grammar_kind, source_langid
+                ),
+                None,
+            )
         }
     };
 
-    let ParseResult(_, _, result) = parser.parse(input);
+    // Sentence grammars only know spaces; same-length replacement keeps the offsets valid
+    let normalized = if full {
+        input.replace(['\t', '\n', '\r'], " ")
+    } else {
+        input.to_string()
+    };
+    let probes: &[(&str, &str)] = if full && source_langid == 1 {
+        LOJBAN_PROBES
+    } else {
+        &[]
+    };
+
+    let ParseResult(position, _, result) = parser.parse(&normalized);
 
     match result {
         Ok(tokens) => {
             let mut lojban_tokens: Vec<LojbanToken> =
                 tokens.into_iter().map(LojbanToken::from).collect();
 
-            fn fill_text(token: &mut LojbanToken, input: &str) {
-                token.text = input[token.start..token.end].to_string();
-                for child in &mut token.children {
-                    fill_text(child, input);
-                }
-            }
-
             for token in &mut lojban_tokens {
                 fill_text(token, input);
             }
 
+            // A PEG parse can succeed without consuming the whole input
+            let consumed = lojban_tokens.iter().map(|t| t.end).max().unwrap_or(0);
+            if input
+                .get(consumed..)
+                .is_some_and(|rest| !rest.trim().is_empty())
+            {
+                let expected = expected_hints(parser, &normalized, consumed, probes);
+                let detail = parse_error_detail(input, consumed, "Unexpected input", expected);
+                return parse_failure(detail.message.clone(), Some(detail));
+            }
+
+            let simplify = request
+                .simplify
+                .unwrap_or(format == ParseOutputFormat::Html);
+            let tree = || -> Vec<ParseTreeNode> {
+                lojban_tokens
+                    .iter()
+                    .filter_map(|token| parse_tree::to_tree(token, simplify))
+                    .collect()
+            };
+            let (tree, rendered) = match format {
+                ParseOutputFormat::Tokens => (None, None),
+                ParseOutputFormat::Json => (Some(tree()), None),
+                ParseOutputFormat::Brackets => (None, Some(parse_tree::render_brackets(&tree()))),
+                ParseOutputFormat::Html => (None, Some(parse_tree::render_html(&tree()))),
+                ParseOutputFormat::Dot => (None, Some(parse_tree::render_dot(&tree()))),
+            };
+
             LojbanParseResponse {
                 success: true,
-                tokens: lojban_tokens,
+                tokens: if format == ParseOutputFormat::Tokens {
+                    lojban_tokens
+                } else {
+                    vec![]
+                },
                 error: None,
+                tree,
+                rendered,
+                error_detail: None,
             }
         }
-        Err(err) => LojbanParseResponse {
-            success: false,
-            tokens: vec![],
-            error: Some(format!("Parsing failed: {:?}", err)),
-        },
+        Err(err) => {
+            let debug = format!("{:?}", err);
+            let expected = expected_hints(parser, &normalized, position, probes);
+            let detail = parse_error_detail(input, position, "Parsing failed", expected);
+            LojbanParseResponse {
+                success: false,
+                tokens: vec![],
+                error: Some(format!("Parsing failed: {}", debug)),
+                tree: None,
+                rendered: None,
+                error_detail: Some(detail),
+            }
+        }
+    }
+}
+
+fn parse_failure(message: String, error_detail: Option<ParseErrorDetail>) -> LojbanParseResponse {
+    LojbanParseResponse {
+        success: false,
+        tokens: vec![],
+        error: Some(message),
+        tree: None,
+        rendered: None,
+        error_detail,
     }
 }
 
+/// Locates a byte offset in the input and names the word found there.
+fn parse_error_detail(
+    input: &str,
+    offset: usize,
+    message: &str,
+    expected: Vec<String>,
+) -> ParseErrorDetail {
+    let offset = offset.min(input.len());
+    let chars_before = input
+        .get(..offset)
+        .map(|prefix| prefix.chars().count())
+        .unwrap_or(0);
+    let (line, column) = line_and_column(input, chars_before);
+    let found = input
+        .get(offset..)
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(str::to_string);
+
+    let message = match &found {
+        Some(word) => format!(
+            "{} at '{}' (line {}, column {})",
+            message, word, line, column
+        ),
+        None => format!("{} at end of input", message),
+    };
+
+    ParseErrorDetail {
+        message,
+        offset,
+        line,
+        column,
+        found,
+        expected,
+    }
+}
+
+/// Stand-ins for what the Lojban sentence grammar may accept next: a hint's word, or
+/// the hint itself for cmavo.
+const LOJBAN_PROBES: &[(&str, &str)] = &[
+    ("sumti", "ko'a"),
+    ("selbri", "broda"),
+    ("cu", "cu"),
+    ("ku", "ku"),
+    ("vau", "vau"),
+    ("kei", "kei"),
+    ("ke'e", "ke'e"),
+    ("be'o", "be'o"),
+    ("ku'o", "ku'o"),
+    ("ge'u", "ge'u"),
+    ("li'u", "li'u"),
+    ("lu'u", "lu'u"),
+    ("me'u", "me'u"),
+    ("nu'u", "nu'u"),
+    ("te'u", "te'u"),
+    ("ve'o", "ve'o"),
+    ("lo'o", "lo'o"),
+    ("boi", "boi"),
+    ("do'u", "do'u"),
+    ("fe'u", "fe'u"),
+    ("se'u", "se'u"),
+    ("toi", "toi"),
+    ("tu'u", "tu'u"),
+    ("gi", "gi"),
+    (".i", ".i"),
+];
+
+/// Byte length of the input prefix the parser accepts.
+fn parsed_length(parser: &Peg, input: &str) -> Option<usize> {
+    let ParseResult(_, _, result) = parser.parse(input);
+    result.ok().map(|tokens| {
+        tokens
+            .into_iter()
+            .map(|node| LojbanToken::from(node).end)
+            .max()
+            .unwrap_or(0)
+    })
+}
+
+/// Hints for the probes the parser accepts when inserted at `offset`.
+fn expected_hints(
+    parser: &Peg,
+    input: &str,
+    offset: usize,
+    probes: &[(&str, &str)],
+) -> Vec<String> {
+    let (Some(before), Some(after)) = (input.get(..offset), input.get(offset..)) else {
+        return vec![];
+    };
+    let separator = if before.is_empty() || before.ends_with(' ') {
+        ""
+    } else {
+        " "
+    };
+
+    probes
+        .iter()
+        .filter(|(_, probe)| {
+            let probed = format!("{}{}{} {}", before, separator, probe, after);
+            let accepted = before.len() + separator.len() + probe.len();
+            parsed_length(parser, &probed).is_some_and(|length| length >= accepted)
+        })
+        .map(|(hint, _)| hint.to_string())
+        .collect()
+}
+
 fn fill_text(token: &mut LojbanToken, input: &str) {
     token.text = input[token.start..token.end].to_string();
     for child in &mut token.children {
@@ -92,23 +307,10 @@ pub async fn analyze_word(
     source_langid: i32, // Now required
     transaction: &Transaction<'_>,
 ) -> Result<AnalyzeWordResponse, Box<dyn std::error::Error>> {
-    // Select the appropriate parser
-    let parser = match parsers.get(&source_langid) {
-        Some(p) => p,
-        None => {
-            // Fallback or error if the requested language parser isn't loaded
-            warn!(
-                "Parser for source_langid {} not found, falling back to Lojban (1)",
-                source_langid
-            );
-            parsers.get(&1).ok_or_else(|| {
-                format!(
-                    "Parser not found for source_langid {} and Lojban parser is unavailable",
-                    source_langid
-                )
-            })?
-        }
-    };
+    // Analysing with another language's grammar would only produce misleading types
+    let parser = parsers
+        .get(&source_langid)
+        .ok_or_else(|| format!("No word grammar loaded for source_langid {}", source_langid))?;
 
     let ParseResult(_, _, result) = parser.parse(word);
 
@@ -121,6 +323,17 @@ pub async fn analyze_word(
                 fill_text(token, word);
             }
 
+            if source_langid == LOGLAN_LANGID {
+                return Ok(AnalyzeWordResponse {
+                    success: true,
+                    word_type: loglan::classify_word(&parsed_tokens, word),
+                    text: word.split_whitespace().collect::<Vec<_>>().join(" "),
+                    recommended: None,
+                    problems: None,
+                    error: None,
+                });
+            }
+
             let texts = extract_token_text(&parsed_tokens);
             let word_type = analyze_word_type(&parsed_tokens);
 
@@ -225,10 +438,22 @@ pub async fn validate_mathjax_handler(text: &str) -> MathJaxValidationResponse {
         Ok(_) => MathJaxValidationResponse {
             valid: true,
             error: None,
+            span: None,
+            unicode: math::to_unicode(text).ok(),
+            mathml: math::to_mathml(text).ok(),
         },
         Err(e) => MathJaxValidationResponse {
             valid: false,
+            span: match &e {
+                MathJaxValidationError::Parse(error) => Some(MathErrorSpan {
+                    start: error.start,
+                    end: error.end,
+                }),
+                _ => None,
+            },
             error: Some(e.to_string()),
+            unicode: None,
+            mathml: None,
         },
     }
 }
@@ -331,11 +556,7 @@ pub async fn validate_mathjax(
     if text.trim().is_empty() {
         return Ok(());
     }
-    // Check for balanced delimiters
-    check_balanced_delimiters(text)?;
-
-    // Check common syntax patterns
-    check_syntax_patterns(text)?;
+    math::validate(text).map_err(MathJaxValidationError::Parse)?;
 
     // validate with Tectonic
     if options.use_tectonic {
@@ -345,129 +566,6 @@ pub async fn validate_mathjax(
     Ok(())
 }
 
-fn check_balanced_delimiters(text: &str) -> Result<(), MathJaxValidationError> {
-    let mut stack = Vec::new();
-    let mut in_math = false;
-    let mut i = 0;
-
-    let chars: Vec<char> = text.chars().collect();
-    while i < chars.len() {
-        match chars[i] {
-            '$' => {
-                // Single dollar handling
-                if in_math {
-                    if stack.pop() != Some("$") {
-                        return Err(MathJaxValidationError::Balance(
-                            "Mismatched math delimiters".into(),
-                        ));
-                    }
-                    in_math = false;
-                } else {
-                    stack.push("$");
-                    in_math = true;
-                }
-                i += 1;
-            }
-            '\\' => {
-                if i + 1 < chars.len() {
-                    match chars[i + 1] {
-                        '(' => {
-                            if in_math {
-                                return Err(MathJaxValidationError::Syntax(
-                                    "Nested math environments not allowed".into(),
-                                ));
-                            }
-                            stack.push("\\(");
-                            in_math = true;
-                            i += 2;
-                        }
-                        ')' => {
-                            if !in_math || stack.pop() != Some("\\(") {
-                                return Err(MathJaxValidationError::Balance(
-                                    "Mismatched \\( \\) delimiters".into(),
-                                ));
-                            }
-                            in_math = false;
-                            i += 2;
-                        }
-                        '[' => {
-                            if in_math {
-                                return Err(MathJaxValidationError::Syntax(
-                                    "Nested math environments not allowed".into(),
-                                ));
-                            }
-                            stack.push("\\[");
-                            in_math = true;
-                            i += 2;
-                        }
-                        ']' => {
-                            if !in_math || stack.pop() != Some("\\[") {
-                                return Err(MathJaxValidationError::Balance(
-                                    "Mismatched \\[ \\] delimiters".into(),
-                                ));
-                            }
-                            in_math = false;
-                            i += 2;
-                        }
-                        '{' => {
-                            if in_math {
-                                stack.push("{");
-                            }
-                            i += 2;
-                        }
-                        '}' => {
-                            if in_math && stack.pop() != Some("{") {
-                                return Err(MathJaxValidationError::Balance(
-                                    "Mismatched {} delimiters".into(),
-                                ));
-                            }
-                            i += 2;
-                        }
-                        _ => i += 2,
-                    }
-                } else {
-                    i += 1;
-                }
-            }
-            _ => i += 1,
-        }
-    }
-
-    if !stack.is_empty() {
-        return Err(MathJaxValidationError::Balance(format!(
-            "Unclosed delimiters: {:?}",
-            stack
-        )));
-    }
-
-    Ok(())
-}
-
-fn check_syntax_patterns(text: &str) -> Result<(), MathJaxValidationError> {
-    // Invalid or incomplete fraction
-    if text.contains("\\frac") && !text.contains("\\frac{") {
-        return Err(MathJaxValidationError::Syntax(
-            "Invalid fraction syntax - missing arguments".into(),
-        ));
-    }
-
-    // Check for missing arguments in common commands
-    let commands_requiring_args = ["\\sqrt", "\\sum", "\\int", "\\lim", "\\sup", "\\inf"];
-    for cmd in commands_requiring_args {
-        if text.contains(cmd)
-            && !text.contains(&format!("{}{{", cmd))
-            && !text.contains(&format!("{}[", cmd))
-        {
-            return Err(MathJaxValidationError::Syntax(format!(
-                "Missing arguments for {} command",
-                cmd
-            )));
-        }
-    }
-
-    Ok(())
-}
-
 fn mathjax_to_latex(expr: &str) -> Result<String, MathJaxValidationError> {
     Regex::new(r"\$([^\$]+)\$")
         .map_err(|e| MathJaxValidationError::Syntax(format!("Invalid regex pattern: {}", e)))?;
@@ -586,3 +684,1497 @@ pub async fn analyze_word_in_pool(
     transaction.commit().await?;
     Ok(response)
 }
+
+/// Runs Tersmu on `text` and lifts its logical-form output into a [`SemanticsResponse`].
+///
+/// Parse failures are reported in the response; `Err` is reserved for the parser
+/// itself failing (timeouts, memory limits, oversized input).
+pub async fn analyze_semantics(text: String) -> Result<SemanticsResponse, TersmuError> {
+    let input = text.clone();
+    let output = tokio::task::spawn_blocking(move || tersmu::parse_lojban(&input))
+        .await
+        .map_err(|e| TersmuError::Wasm(anyhow::anyhow!("Thread join error: {}", e)))??;
+
+    let raw: serde_json::Value = serde_json::from_str(&output)
+        .map_err(|e| TersmuError::Wasm(anyhow::anyhow!("Invalid Tersmu output: {}", e)))?;
+    let output: TersmuOutput = serde_json::from_value(raw.clone())
+        .map_err(|e| TersmuError::Wasm(anyhow::anyhow!("Invalid Tersmu output: {}", e)))?;
+
+    let error = output.error.map(|message| {
+        let offset = error_offset_from_message(&message, &text);
+        let (line, column) = match offset {
+            Some(offset) => {
+                let (line, column) = line_and_column(&text, offset);
+                (Some(line), Some(column))
+            }
+            None => (None, None),
+        };
+        SemanticsError {
+            message,
+            offset,
+            line,
+            column,
+        }
+    });
+
+    let bridi = output
+        .logical
+        .iter()
+        .flat_map(|logical| logical.lines())
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    let terms = output
+        .logical
+        .as_deref()
+        .map(logical_terms)
+        .unwrap_or_default();
+
+    Ok(SemanticsResponse {
+        success: error.is_none(),
+        canonical: output.canonical,
+        logical_form: output.logical,
+        bridi,
+        terms,
+        error,
+        raw,
+    })
+}
+
+static ERROR_POSITION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?:position|offset|char(?:acter)?)\s*:?\s*(\d+)")
+        .expect("valid position regex")
+});
+static ERROR_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)line\s*:?\s*(\d+)").expect("valid line regex"));
+static ERROR_COLUMN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)column\s*:?\s*(\d+)").expect("valid column regex"));
+static PREDICATION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"[^\s(),]+\(([^()]*)\)").expect("valid predication regex"));
+
+/// Tersmu's parse errors mention the failing position in prose, e.g. "at position 12"
+/// or "(line 2, column 13)". Lines and columns are 1-based and converted to a
+/// character offset into `text`.
+fn error_offset_from_message(message: &str, text: &str) -> Option<usize> {
+    if let Some(caps) = ERROR_POSITION.captures(message) {
+        return caps.get(1)?.as_str().parse().ok();
+    }
+    let column: usize = ERROR_COLUMN
+        .captures(message)?
+        .get(1)?
+        .as_str()
+        .parse()
+        .ok()?;
+    let line: usize = match ERROR_LINE.captures(message) {
+        Some(caps) => caps.get(1)?.as_str().parse().ok()?,
+        None => 1,
+    };
+    let line_start = if line <= 1 {
+        0
+    } else {
+        text.chars()
+            .enumerate()
+            .filter(|(_, ch)| *ch == '\n')
+            .nth(line - 2)
+            .map(|(offset, _)| offset + 1)?
+    };
+    Some(line_start + column.saturating_sub(1))
+}
+
+/// Arguments of the predications in a logical form, e.g. `mi` and `x1` in
+/// `klama(mi,x1)`, each listed once in order of appearance.
+fn logical_terms(logical: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for caps in PREDICATION.captures_iter(logical) {
+        for term in caps[1].split(',').map(str::trim) {
+            if !term.is_empty() && !terms.iter().any(|t| t == term) {
+                terms.push(term.to_string());
+            }
+        }
+    }
+    terms
+}
+
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for ch in text.chars().take(offset) {
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+fn parse_grammar_kind(kind: &str) -> Result<GrammarKind, Box<dyn std::error::Error>> {
+    GrammarKind::from_name(kind)
+        .ok_or_else(|| format!("Unknown grammar kind '{}', expected word or sentence", kind).into())
+}
+
+/// Succeeds only if the parser accepts the whole input.
+fn check_full_parse(parser: &Peg, input: &str) -> Result<(), String> {
+    let ParseResult(_, _, result) = parser.parse(input);
+    match result {
+        Ok(tokens) => {
+            let consumed = tokens
+                .into_iter()
+                .map(|node| LojbanToken::from(node).end)
+                .max()
+                .unwrap_or(0);
+            match input.get(consumed..) {
+                Some(rest) if !rest.trim().is_empty() => {
+                    Err(format!("Unexpected input at byte {}", consumed))
+                }
+                _ => Ok(()),
+            }
+        }
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+fn run_grammar_tests(grammar_text: &str, samples: &[(String, bool)]) -> GrammarTestReport {
+    let parser = match Peg::new(START_RULE, grammar_text) {
+        Ok(parser) => parser,
+        Err(e) => {
+            return GrammarTestReport {
+                compiled: false,
+                compile_error: Some(e.to_string()),
+                total: samples.len(),
+                passed: 0,
+                failures: vec![],
+            }
+        }
+    };
+
+    let mut failures = Vec::new();
+    for (sentence, should_parse) in samples {
+        let outcome = check_full_parse(&parser, sentence);
+        if outcome.is_ok() != *should_parse {
+            failures.push(GrammarTestFailure {
+                sentence: sentence.clone(),
+                should_parse: *should_parse,
+                error: outcome.err(),
+            });
+        }
+    }
+
+    GrammarTestReport {
+        compiled: true,
+        compile_error: None,
+        total: samples.len(),
+        passed: samples.len() - failures.len(),
+        failures,
+    }
+}
+
+/// Compiles `grammar_text` and runs it over the stored samples for the language and kind,
+/// plus `extra_sentences` (all expected to parse).
+pub async fn test_grammar(
+    pool: &Pool,
+    langid: i32,
+    kind: &str,
+    grammar_text: String,
+    extra_sentences: Vec<String>,
+) -> Result<GrammarTestReport, Box<dyn std::error::Error>> {
+    let kind = parse_grammar_kind(kind)?;
+    let client = pool.get().await?;
+    let mut samples: Vec<(String, bool)> = client
+        .query(
+            "SELECT sentence, should_parse FROM grammar_samples
+             WHERE langid = $1 AND kind = $2
+             ORDER BY id",
+            &[&langid, &kind.as_str()],
+        )
+        .await?
+        .into_iter()
+        .map(|row| (row.get("sentence"), row.get("should_parse")))
+        .collect();
+    samples.extend(extra_sentences.into_iter().map(|sentence| (sentence, true)));
+
+    // Compiling a full grammar and parsing a corpus is CPU-bound
+    let report =
+        tokio::task::spawn_blocking(move || run_grammar_tests(&grammar_text, &samples)).await?;
+    Ok(report)
+}
+
+const GRAMMAR_VERSION_SELECT: &str = "SELECT g.id, g.langid, g.kind, g.version, g.notes,
+        g.is_active, u.username AS created_by, g.created_at, g.activated_at,
+        octet_length(g.grammar_text) AS size
+ FROM grammars g
+ LEFT JOIN users u ON u.userid = g.created_by";
+
+fn grammar_version_from_row(row: &tokio_postgres::Row) -> GrammarVersion {
+    GrammarVersion {
+        id: row.get("id"),
+        langid: row.get("langid"),
+        kind: row.get("kind"),
+        version: row.get("version"),
+        notes: row.get("notes"),
+        is_active: row.get("is_active"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        activated_at: row.get("activated_at"),
+        size: row.get("size"),
+    }
+}
+
+pub async fn list_grammars(
+    pool: &Pool,
+    query: &GrammarListQuery,
+) -> Result<Vec<GrammarVersion>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!(
+                "{} WHERE ($1::int IS NULL OR g.langid = $1)
+                   AND ($2::text IS NULL OR g.kind = $2)
+                 ORDER BY g.langid, g.kind, g.version DESC",
+                GRAMMAR_VERSION_SELECT
+            ),
+            &[&query.langid, &query.kind],
+        )
+        .await?;
+
+    Ok(rows.iter().map(grammar_version_from_row).collect())
+}
+
+/// Loads the active stored grammars into the registry; every worker picks them up on its
+/// next request.
+pub async fn reload_grammars(
+    pool: &Pool,
+    registry: &GrammarRegistry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let stored: Vec<(i32, GrammarKind, String)> = client
+        .query(
+            "SELECT langid, kind, grammar_text FROM grammars WHERE is_active",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .filter_map(|row| {
+            let kind: String = row.get("kind");
+            GrammarKind::from_name(&kind)
+                .map(|kind| (row.get("langid"), kind, row.get("grammar_text")))
+        })
+        .collect();
+
+    registry.apply(&stored);
+    Ok(())
+}
+
+async fn fetch_grammar_version(
+    transaction: &Transaction<'_>,
+    id: i32,
+) -> Result<GrammarVersion, Box<dyn std::error::Error>> {
+    let row = transaction
+        .query_one(
+            &format!("{} WHERE g.id = $1", GRAMMAR_VERSION_SELECT),
+            &[&id],
+        )
+        .await?;
+    Ok(grammar_version_from_row(&row))
+}
+
+async fn activate_grammar_in_transaction(
+    transaction: &Transaction<'_>,
+    id: i32,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(row) = transaction
+        .query_opt(
+            "SELECT langid, kind FROM grammars WHERE id = $1 FOR UPDATE",
+            &[&id],
+        )
+        .await?
+    else {
+        return Ok(false);
+    };
+    let langid: i32 = row.get("langid");
+    let kind: String = row.get("kind");
+
+    transaction
+        .execute(
+            "UPDATE grammars SET is_active = false
+             WHERE langid = $1 AND kind = $2 AND is_active",
+            &[&langid, &kind],
+        )
+        .await?;
+    transaction
+        .execute(
+            "UPDATE grammars SET is_active = true, activated_at = NOW() WHERE id = $1",
+            &[&id],
+        )
+        .await?;
+    Ok(true)
+}
+
+/// Stores a new grammar version after testing it against the samples. The version is
+/// activated only when requested and every sample passes.
+pub async fn upload_grammar(
+    pool: &Pool,
+    registry: &GrammarRegistry,
+    user_id: i32,
+    request: GrammarUploadRequest,
+) -> Result<GrammarUploadResponse, Box<dyn std::error::Error>> {
+    let kind = parse_grammar_kind(&request.kind)?;
+    let report = test_grammar(
+        pool,
+        request.langid,
+        &request.kind,
+        request.grammar_text.clone(),
+        vec![],
+    )
+    .await?;
+    if let Some(compile_error) = &report.compile_error {
+        return Err(format!("Grammar does not compile: {}", compile_error).into());
+    }
+
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    // Concurrent uploads for the same language and kind would pick the same version
+    transaction
+        .execute(
+            "SELECT pg_advisory_xact_lock($1, hashtext($2))",
+            &[&request.langid, &kind.as_str()],
+        )
+        .await?;
+    let row = transaction
+        .query_one(
+            "INSERT INTO grammars (langid, kind, version, grammar_text, notes, created_by)
+             SELECT $1, $2, COALESCE(MAX(version), 0) + 1, $3, $4, $5
+             FROM grammars WHERE langid = $1 AND kind = $2
+             RETURNING id",
+            &[
+                &request.langid,
+                &kind.as_str(),
+                &request.grammar_text,
+                &request.notes,
+                &user_id,
+            ],
+        )
+        .await?;
+    let id: i32 = row.get("id");
+
+    let activated = request.activate && report.failures.is_empty();
+    if activated {
+        activate_grammar_in_transaction(&transaction, id).await?;
+    }
+
+    let grammar = fetch_grammar_version(&transaction, id).await?;
+    transaction.commit().await?;
+
+    if activated {
+        reload_grammars(pool, registry).await?;
+    }
+
+    Ok(GrammarUploadResponse {
+        grammar,
+        report,
+        activated,
+    })
+}
+
+/// Makes a stored version the active one for its language and kind (also used for
+/// rollbacks) and hot-reloads it.
+pub async fn activate_grammar(
+    pool: &Pool,
+    registry: &GrammarRegistry,
+    id: i32,
+) -> Result<Option<GrammarVersion>, Box<dyn std::error::Error>> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    if !activate_grammar_in_transaction(&transaction, id).await? {
+        return Ok(None);
+    }
+    let grammar = fetch_grammar_version(&transaction, id).await?;
+    transaction.commit().await?;
+
+    reload_grammars(pool, registry).await?;
+    Ok(Some(grammar))
+}
+
+pub async fn list_grammar_samples(
+    pool: &Pool,
+    query: &GrammarListQuery,
+) -> Result<Vec<GrammarSample>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT id, langid, kind, sentence, should_parse FROM grammar_samples
+             WHERE ($1::int IS NULL OR langid = $1)
+               AND ($2::text IS NULL OR kind = $2)
+             ORDER BY langid, kind, id",
+            &[&query.langid, &query.kind],
+        )
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| GrammarSample {
+            id: row.get("id"),
+            langid: row.get("langid"),
+            kind: row.get("kind"),
+            sentence: row.get("sentence"),
+            should_parse: row.get("should_parse"),
+        })
+        .collect())
+}
+
+pub async fn add_grammar_sample(
+    pool: &Pool,
+    user_id: i32,
+    request: &AddGrammarSampleRequest,
+) -> Result<GrammarSample, Box<dyn std::error::Error>> {
+    let kind = parse_grammar_kind(&request.kind)?;
+    let sentence = request.sentence.trim();
+    if sentence.is_empty() {
+        return Err("Sample sentence must not be empty".into());
+    }
+
+    let client = pool.get().await?;
+    let row = client
+        .query_one(
+            "INSERT INTO grammar_samples (langid, kind, sentence, should_parse, created_by)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (langid, kind, sentence)
+             DO UPDATE SET should_parse = EXCLUDED.should_parse
+             RETURNING id",
+            &[
+                &request.langid,
+                &kind.as_str(),
+                &sentence,
+                &request.should_parse,
+                &user_id,
+            ],
+        )
+        .await?;
+
+    Ok(GrammarSample {
+        id: row.get("id"),
+        langid: request.langid,
+        kind: kind.as_str().to_string(),
+        sentence: sentence.to_string(),
+        should_parse: request.should_parse,
+    })
+}
+
+pub async fn delete_grammar_sample(
+    pool: &Pool,
+    id: i32,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let deleted = client
+        .execute("DELETE FROM grammar_samples WHERE id = $1", &[&id])
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// The muplis corpus is Lojban only
+pub const MUPLIS_LANGID: i32 = 1;
+
+const DEFAULT_REGRESSION_CHANGE_LIMIT: usize = 500;
+
+/// Finished runs kept per language; older runs and their per-sentence results are pruned
+const REGRESSION_RUNS_KEPT: i64 = 20;
+
+struct CorpusOutcome {
+    sentence: String,
+    parser_error: Option<String>,
+    tersmu_error: Option<String>,
+    canonical: Option<String>,
+}
+
+/// Runs Tersmu on a sentence and returns its canonical form or the reported error.
+fn tersmu_outcome(sentence: &str) -> Result<Option<String>, String> {
+    let output = tersmu::parse_lojban(sentence).map_err(|e| e.to_string())?;
+    let output: TersmuOutput =
+        serde_json::from_str(&output).map_err(|e| format!("Invalid Tersmu output: {}", e))?;
+    match output.error {
+        Some(error) => Err(error),
+        None => Ok(output.canonical),
+    }
+}
+
+fn run_corpus(grammar_text: &str, sentences: Vec<String>) -> Result<Vec<CorpusOutcome>, String> {
+    let parser = Peg::new(START_RULE, grammar_text).map_err(|e| e.to_string())?;
+    Ok(sentences
+        .into_iter()
+        .map(|sentence| {
+            // Sentence grammars only know spaces, as in `parse_lojban`
+            let normalized = sentence.replace(['\t', '\n', '\r'], " ");
+            let parser_error = check_full_parse(&parser, &normalized).err();
+            let (canonical, tersmu_error) = match tersmu_outcome(&sentence) {
+                Ok(canonical) => (canonical, None),
+                Err(e) => (None, Some(e)),
+            };
+            CorpusOutcome {
+                sentence,
+                parser_error,
+                tersmu_error,
+                canonical,
+            }
+        })
+        .collect())
+}
+
+const REGRESSION_RUN_SELECT: &str = "SELECT r.id, r.langid, r.kind, r.grammar_id, r.status,
+        r.total, r.parser_passed, r.tersmu_passed, r.error, u.username AS triggered_by,
+        r.started_at, r.finished_at
+ FROM grammar_regression_runs r
+ LEFT JOIN users u ON u.userid = r.triggered_by";
+
+fn regression_run_from_row(row: &tokio_postgres::Row) -> GrammarRegressionRun {
+    GrammarRegressionRun {
+        id: row.get("id"),
+        langid: row.get("langid"),
+        kind: row.get("kind"),
+        grammar_id: row.get("grammar_id"),
+        status: row.get("status"),
+        total: row.get("total"),
+        parser_passed: row.get("parser_passed"),
+        tersmu_passed: row.get("tersmu_passed"),
+        error: row.get("error"),
+        triggered_by: row.get("triggered_by"),
+        started_at: row.get("started_at"),
+        finished_at: row.get("finished_at"),
+    }
+}
+
+async fn get_regression_run(
+    client: &deadpool_postgres::Client,
+    id: i32,
+) -> Result<Option<GrammarRegressionRun>, Box<dyn std::error::Error>> {
+    Ok(client
+        .query_opt(
+            &format!("{} WHERE r.id = $1", REGRESSION_RUN_SELECT),
+            &[&id],
+        )
+        .await?
+        .as_ref()
+        .map(regression_run_from_row))
+}
+
+/// Starts a background run of the active Lojban sentence grammar and Tersmu over every
+/// muplis sentence.
+pub async fn start_corpus_regression(
+    pool: &Pool,
+    registry: &GrammarRegistry,
+    user_id: Option<i32>,
+) -> Result<GrammarRegressionRun, Box<dyn std::error::Error>> {
+    let (_, texts) = registry.snapshot();
+    let kind = GrammarKind::Sentence;
+    let Some(grammar_text) = texts.sentence.get(&MUPLIS_LANGID).cloned() else {
+        return Err("No Lojban sentence grammar is loaded".into());
+    };
+
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            "INSERT INTO grammar_regression_runs (langid, kind, grammar_id, triggered_by)
+             SELECT $1, $2,
+                    (SELECT id FROM grammars WHERE langid = $1 AND kind = $2 AND is_active),
+                    $3
+             ON CONFLICT (langid) WHERE status = 'running' DO NOTHING
+             RETURNING id",
+            &[&MUPLIS_LANGID, &kind.as_str(), &user_id],
+        )
+        .await?;
+    let Some(row) = row else {
+        return Err("A corpus regression run is already in progress".into());
+    };
+    let run_id: i32 = row.get("id");
+    let run = get_regression_run(&client, run_id)
+        .await?
+        .ok_or("Regression run disappeared")?;
+
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_corpus_regression(&pool, run_id, grammar_text).await {
+            warn!("Corpus regression run {} failed: {}", run_id, e);
+            if let Ok(client) = pool.get().await {
+                let _ = client
+                    .execute(
+                        "UPDATE grammar_regression_runs
+                         SET status = 'failed', error = $2, finished_at = NOW()
+                         WHERE id = $1",
+                        &[&run_id, &e.to_string()],
+                    )
+                    .await;
+            }
+        }
+    });
+
+    Ok(run)
+}
+
+async fn run_corpus_regression(
+    pool: &Pool,
+    run_id: i32,
+    grammar_text: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut client = pool.get().await?;
+    let sentences: Vec<String> = client
+        .query(
+            "SELECT DISTINCT lojban FROM muplis
+             WHERE lojban IS NOT NULL AND btrim(lojban) <> ''",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.get("lojban"))
+        .collect();
+
+    // Both the PEG parser and Tersmu are CPU-bound
+    let outcomes =
+        tokio::task::spawn_blocking(move || run_corpus(&grammar_text, sentences)).await??;
+
+    let parser_passed = outcomes.iter().filter(|o| o.parser_error.is_none()).count() as i32;
+    let tersmu_passed = outcomes.iter().filter(|o| o.tersmu_error.is_none()).count() as i32;
+
+    let sentences: Vec<&str> = outcomes.iter().map(|o| o.sentence.as_str()).collect();
+    let parser_ok: Vec<bool> = outcomes.iter().map(|o| o.parser_error.is_none()).collect();
+    let parser_errors: Vec<Option<&str>> =
+        outcomes.iter().map(|o| o.parser_error.as_deref()).collect();
+    let tersmu_ok: Vec<bool> = outcomes.iter().map(|o| o.tersmu_error.is_none()).collect();
+    let tersmu_errors: Vec<Option<&str>> =
+        outcomes.iter().map(|o| o.tersmu_error.as_deref()).collect();
+    let canonicals: Vec<Option<&str>> = outcomes.iter().map(|o| o.canonical.as_deref()).collect();
+
+    let transaction = client.transaction().await?;
+    transaction
+        .execute(
+            "INSERT INTO grammar_regression_results
+             (run_id, sentence, parser_ok, parser_error, tersmu_ok, tersmu_error, canonical)
+             SELECT $1, * FROM UNNEST($2::text[], $3::bool[], $4::text[], $5::bool[],
+                                      $6::text[], $7::text[])",
+            &[
+                &run_id,
+                &sentences,
+                &parser_ok,
+                &parser_errors,
+                &tersmu_ok,
+                &tersmu_errors,
+                &canonicals,
+            ],
+        )
+        .await?;
+    transaction
+        .execute(
+            "UPDATE grammar_regression_runs
+             SET status = 'completed', total = $2, parser_passed = $3, tersmu_passed = $4,
+                 finished_at = NOW()
+             WHERE id = $1",
+            &[
+                &run_id,
+                &(outcomes.len() as i32),
+                &parser_passed,
+                &tersmu_passed,
+            ],
+        )
+        .await?;
+    let pruned = transaction
+        .execute(
+            "DELETE FROM grammar_regression_runs
+             WHERE langid = (SELECT langid FROM grammar_regression_runs WHERE id = $1)
+               AND status <> 'running'
+               AND id NOT IN (
+                   SELECT id FROM grammar_regression_runs
+                   WHERE langid = (SELECT langid FROM grammar_regression_runs WHERE id = $1)
+                     AND status <> 'running'
+                   ORDER BY id DESC LIMIT $2
+               )",
+            &[&run_id, &REGRESSION_RUNS_KEPT],
+        )
+        .await?;
+    transaction.commit().await?;
+    if pruned > 0 {
+        info!("Pruned {} old corpus regression runs", pruned);
+    }
+
+    info!(
+        "Corpus regression run {} completed: {}/{} parsed, {}/{} through Tersmu",
+        run_id,
+        parser_passed,
+        outcomes.len(),
+        tersmu_passed,
+        outcomes.len()
+    );
+    Ok(())
+}
+
+/// Starts a corpus run after the active Lojban sentence grammar changed. Failures are
+/// only logged.
+pub async fn run_corpus_regression_after_change(
+    pool: &Pool,
+    registry: &GrammarRegistry,
+    grammar: &GrammarVersion,
+    user_id: i32,
+) {
+    if grammar.langid != MUPLIS_LANGID || grammar.kind != GrammarKind::Sentence.as_str() {
+        return;
+    }
+    if let Err(e) = start_corpus_regression(pool, registry, Some(user_id)).await {
+        warn!(
+            "Could not start corpus regression for grammar {}: {}",
+            grammar.id, e
+        );
+    }
+}
+
+/// Runs still marked as running at startup were cut short by a restart.
+pub async fn fail_interrupted_regression_runs(
+    pool: &Pool,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    Ok(client
+        .execute(
+            "UPDATE grammar_regression_runs
+             SET status = 'failed', error = 'Interrupted by server restart', finished_at = NOW()
+             WHERE status = 'running'",
+            &[],
+        )
+        .await?)
+}
+
+pub async fn list_regression_runs(
+    pool: &Pool,
+) -> Result<Vec<GrammarRegressionRun>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!(
+                "{} ORDER BY r.started_at DESC LIMIT 100",
+                REGRESSION_RUN_SELECT
+            ),
+            &[],
+        )
+        .await?;
+    Ok(rows.iter().map(regression_run_from_row).collect())
+}
+
+/// Compares a run with a baseline run (by default the previous completed one) and lists
+/// the corpus sentences whose outcome changed.
+pub async fn get_regression_report(
+    pool: &Pool,
+    id: i32,
+    query: &GrammarRegressionReportQuery,
+) -> Result<Option<GrammarRegressionReport>, Box<dyn std::error::Error>> {
+    let client = pool.get().await?;
+    let Some(run) = get_regression_run(&client, id).await? else {
+        return Ok(None);
+    };
+
+    let baseline_id: Option<i32> = match query.against {
+        Some(against) => Some(against),
+        None => client
+            .query_opt(
+                "SELECT id FROM grammar_regression_runs
+                 WHERE langid = $1 AND kind = $3 AND status = 'completed' AND id < $2
+                 ORDER BY id DESC LIMIT 1",
+                &[&run.langid, &run.id, &run.kind],
+            )
+            .await?
+            .map(|row| row.get("id")),
+    };
+    let baseline = match baseline_id {
+        Some(baseline_id) => Some(
+            get_regression_run(&client, baseline_id)
+                .await?
+                .ok_or("Baseline run not found")?,
+        ),
+        None => None,
+    };
+
+    let mut counts = GrammarRegressionCounts::default();
+    let mut changes = Vec::new();
+    if let Some(baseline) = &baseline {
+        let rows = client
+            .query(
+                "SELECT cur.sentence, cur.parser_ok, cur.parser_error, cur.tersmu_ok,
+                        cur.tersmu_error, cur.canonical,
+                        prev.parser_ok AS prev_parser_ok, prev.tersmu_ok AS prev_tersmu_ok,
+                        prev.canonical AS prev_canonical
+                 FROM grammar_regression_results cur
+                 JOIN grammar_regression_results prev
+                   ON prev.run_id = $2 AND prev.sentence = cur.sentence
+                 WHERE cur.run_id = $1
+                   AND (cur.parser_ok <> prev.parser_ok
+                        OR cur.tersmu_ok <> prev.tersmu_ok
+                        OR cur.canonical IS DISTINCT FROM prev.canonical)
+                 ORDER BY cur.sentence",
+                &[&run.id, &baseline.id],
+            )
+            .await?;
+
+        for row in rows {
+            let parser_ok: bool = row.get("parser_ok");
+            let prev_parser_ok: bool = row.get("prev_parser_ok");
+            let tersmu_ok: bool = row.get("tersmu_ok");
+            let prev_tersmu_ok: bool = row.get("prev_tersmu_ok");
+
+            let change = match (prev_parser_ok, parser_ok, prev_tersmu_ok, tersmu_ok) {
+                (true, false, _, _) => {
+                    counts.parser_broken += 1;
+                    "parser_broken"
+                }
+                (false, true, _, _) => {
+                    counts.parser_fixed += 1;
+                    "parser_fixed"
+                }
+                (_, _, true, false) => {
+                    counts.tersmu_broken += 1;
+                    "tersmu_broken"
+                }
+                (_, _, false, true) => {
+                    counts.tersmu_fixed += 1;
+                    "tersmu_fixed"
+                }
+                _ => {
+                    counts.canonical_changed += 1;
+                    "canonical_changed"
+                }
+            };
+
+            changes.push(GrammarRegressionChange {
+                sentence: row.get("sentence"),
+                change: change.to_string(),
+                parser_error: row.get("parser_error"),
+                tersmu_error: row.get("tersmu_error"),
+                previous_canonical: row.get("prev_canonical"),
+                canonical: row.get("canonical"),
+            });
+        }
+    }
+    changes.truncate(query.limit.unwrap_or(DEFAULT_REGRESSION_CHANGE_LIMIT));
+
+    Ok(Some(GrammarRegressionReport {
+        run,
+        baseline,
+        counts,
+        changes,
+    }))
+}
+
+/// Rafsi assignments from the dictionary, passed to vlazba on top of its built-in tables.
+struct DictionaryRafsi {
+    cmavo: Option<HashMap<String, Vec<String>>>,
+    cmavo_exp: Option<HashMap<String, Vec<String>>>,
+    gismu: Option<HashMap<String, Vec<String>>>,
+    gismu_exp: Option<HashMap<String, Vec<String>>>,
+}
+
+impl DictionaryRafsi {
+    async fn fetch(transaction: &Transaction<'_>) -> Self {
+        Self {
+            cmavo: fetch_cmavo_rafsi(transaction).await.ok(),
+            cmavo_exp: fetch_experimental_cmavo_rafsi(transaction).await.ok(),
+            gismu: fetch_gismu_rafsi(transaction).await.ok(),
+            gismu_exp: fetch_experimental_gismu_rafsi(transaction).await.ok(),
+        }
+    }
+
+    fn options(&self, exp_rafsi: bool) -> RafsiOptions<'_> {
+        RafsiOptions {
+            exp_rafsi,
+            custom_cmavo: self.cmavo.as_ref(),
+            custom_cmavo_exp: self.cmavo_exp.as_ref(),
+            custom_gismu: self.gismu.as_ref(),
+            custom_gismu_exp: self.gismu_exp.as_ref(),
+        }
+    }
+}
+
+/// Hyphens are the single letters (y, r, n) jvokaha returns between rafsi.
+fn is_lujvo_hyphen(part: &str) -> bool {
+    part.chars().count() == 1
+}
+
+/// Finds the Lojban words the given rafsi stand for. Assigned rafsi win over full gismu
+/// used as final rafsi, which win over four-letter gismu prefixes.
+async fn lookup_rafsi_sources(
+    transaction: &Transaction<'_>,
+    rafsi: &[String],
+) -> Result<HashMap<String, LujvoEntryRef>, Box<dyn std::error::Error>> {
+    let rows = transaction
+        .query(
+            "SELECT valsiid, word, rafsi, typeid FROM valsi
+             WHERE source_langid = 1
+               AND (string_to_array(rafsi, ' ') && $1::text[]
+                    OR word = ANY($1)
+                    OR (typeid IN (1, 7) AND length(word) = 5 AND left(word, 4) = ANY($1)))
+             ORDER BY typeid, word",
+            &[&rafsi],
+        )
+        .await?;
+
+    let mut assigned = HashMap::new();
+    let mut full = HashMap::new();
+    let mut short = HashMap::new();
+    for row in rows {
+        let valsiid: i32 = row.get("valsiid");
+        let word: String = row.get("word");
+        let entry = || LujvoEntryRef {
+            valsiid,
+            word: word.clone(),
+        };
+        if let Some(assigned_rafsi) = row.get::<_, Option<String>>("rafsi") {
+            for r in assigned_rafsi.split_whitespace() {
+                if rafsi.iter().any(|wanted| wanted == r) {
+                    assigned.entry(r.to_string()).or_insert_with(entry);
+                }
+            }
+        }
+        if rafsi.contains(&word) {
+            full.entry(word.clone()).or_insert_with(entry);
+        }
+        if word.chars().count() == 5 {
+            let prefix: String = word.chars().take(4).collect();
+            if rafsi.contains(&prefix) {
+                short.entry(prefix).or_insert_with(entry);
+            }
+        }
+    }
+
+    for (rafsi, entry) in full.into_iter().chain(short) {
+        assigned.entry(rafsi).or_insert(entry);
+    }
+    Ok(assigned)
+}
+
+/// jvozba tries every rafsi combination, so longer tanru are refused
+const MAX_TANRU_WORDS: usize = 6;
+
+/// Splits a lujvo into its rafsi and resolves each one to its source word.
+pub async fn decompose_lujvo(
+    pool: &Pool,
+    lujvo: &str,
+) -> Result<LujvoDecomposition, Box<dyn std::error::Error>> {
+    let lujvo = lujvo.trim().to_lowercase();
+    let parts = match jvokaha(&lujvo) {
+        Ok(parts) => parts,
+        Err(_) => {
+            return Ok(LujvoDecomposition {
+                success: false,
+                error: Some(format!("'{}' is not a valid lujvo", lujvo)),
+                lujvo,
+                parts: vec![],
+                tanru: vec![],
+                recommended: None,
+                entry: None,
+            })
+        }
+    };
+
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    let rafsi: Vec<String> = parts
+        .iter()
+        .filter(|part| !is_lujvo_hyphen(part))
+        .cloned()
+        .collect();
+    let sources = lookup_rafsi_sources(&transaction, &rafsi).await?;
+
+    let parts: Vec<LujvoPart> = parts
+        .into_iter()
+        .map(|part| {
+            let hyphen = is_lujvo_hyphen(&part);
+            let source = if hyphen {
+                None
+            } else {
+                sources.get(&part).map(|entry| LujvoEntryRef {
+                    valsiid: entry.valsiid,
+                    word: entry.word.clone(),
+                })
+            };
+            LujvoPart {
+                rafsi: part,
+                hyphen,
+                source,
+            }
+        })
+        .collect();
+
+    let resolved: Option<Vec<String>> = parts
+        .iter()
+        .filter(|part| !part.hyphen)
+        .map(|part| part.source.as_ref().map(|source| source.word.clone()))
+        .collect();
+    let tanru = resolved.unwrap_or_default();
+
+    let dictionary_rafsi = if (2..=MAX_TANRU_WORDS).contains(&tanru.len()) {
+        Some(DictionaryRafsi::fetch(&transaction).await)
+    } else {
+        None
+    };
+
+    let entry = transaction
+        .query_opt(
+            "SELECT valsiid, word FROM valsi WHERE word = $1 AND source_langid = 1",
+            &[&lujvo],
+        )
+        .await?
+        .map(|row| LujvoEntryRef {
+            valsiid: row.get("valsiid"),
+            word: row.get("word"),
+        });
+    transaction.commit().await?;
+
+    let recommended = match dictionary_rafsi {
+        Some(dictionary_rafsi) => {
+            let words = tanru.clone();
+            tokio::task::spawn_blocking(move || {
+                jvozba(&words, false, &dictionary_rafsi.options(true))
+                    .into_iter()
+                    .next()
+                    .map(|best| best.lujvo)
+            })
+            .await?
+            .filter(|best| *best != lujvo)
+        }
+        None => None,
+    };
+
+    Ok(LujvoDecomposition {
+        success: true,
+        lujvo,
+        parts,
+        tanru,
+        recommended,
+        entry,
+        error: None,
+    })
+}
+
+/// Builds every candidate lujvo for a tanru with its jvozba score, flagging candidates
+/// that are already spelled like an existing valsi.
+pub async fn build_lujvo(
+    pool: &Pool,
+    query: &LujvoBuildQuery,
+) -> Result<LujvoBuildResponse, Box<dyn std::error::Error>> {
+    let tanru: Vec<String> = query
+        .tanru
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    if tanru.len() < 2 {
+        return Ok(LujvoBuildResponse {
+            success: false,
+            tanru,
+            candidates: vec![],
+            error: Some("A tanru needs at least two words".to_string()),
+        });
+    }
+    if tanru.len() > MAX_TANRU_WORDS {
+        return Ok(LujvoBuildResponse {
+            success: false,
+            tanru,
+            candidates: vec![],
+            error: Some(format!(
+                "A tanru can have at most {} words",
+                MAX_TANRU_WORDS
+            )),
+        });
+    }
+
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    let dictionary_rafsi = DictionaryRafsi::fetch(&transaction).await;
+    transaction.commit().await?;
+
+    // jvozba is CPU-bound
+    let words = tanru.clone();
+    let exp_rafsi = query.exp_rafsi;
+    let scored = tokio::task::spawn_blocking(move || {
+        jvozba(&words, false, &dictionary_rafsi.options(exp_rafsi))
+    })
+    .await?;
+
+    let words: Vec<&str> = scored.iter().map(|c| c.lujvo.as_str()).collect();
+    let mut existing: HashMap<String, LujvoEntryRef> = client
+        .query(
+            "SELECT valsiid, word FROM valsi WHERE word = ANY($1) AND source_langid = 1",
+            &[&words],
+        )
+        .await?
+        .into_iter()
+        .map(|row| {
+            let word: String = row.get("word");
+            (
+                word.clone(),
+                LujvoEntryRef {
+                    valsiid: row.get("valsiid"),
+                    word,
+                },
+            )
+        })
+        .collect();
+
+    let candidates: Vec<LujvoCandidate> = scored
+        .into_iter()
+        .map(|candidate| LujvoCandidate {
+            collision: existing.remove(&candidate.lujvo),
+            lujvo: candidate.lujvo,
+            score: candidate.score,
+        })
+        .collect();
+
+    if candidates.is_empty() {
+        return Ok(LujvoBuildResponse {
+            success: false,
+            tanru,
+            candidates,
+            error: Some("No rafsi available for one of the words".to_string()),
+        });
+    }
+
+    Ok(LujvoBuildResponse {
+        success: true,
+        tanru,
+        candidates,
+        error: None,
+    })
+}
+
+/// Longest run of cmavo tried as a single cmavo-compound entry
+const MAX_GLOSS_COMPOUND: usize = 4;
+
+struct GlossEntry {
+    valsiid: i32,
+    word_type: String,
+    definition_id: Option<i32>,
+    selmaho: Option<String>,
+    gloss: Option<String>,
+    places: Vec<GlossPlace>,
+}
+
+/// Words of a parsed text in order, with their morphological class.
+fn collect_words(tokens: &[LojbanToken]) -> Vec<(String, &'static str)> {
+    fn collect(token: &LojbanToken, words: &mut Vec<(String, &'static str)>) {
+        let kind = match token.kind.as_str() {
+            "non_terminal_lujvo_core" => "lujvo",
+            "non_terminal_cmavo" => "cmavo",
+            "non_terminal_cmevla" => "cmevla",
+            "non_terminal_gismu" => "gismu",
+            "non_terminal_fuhivla" => "fu'ivla",
+            _ => {
+                for child in &token.children {
+                    collect(child, words);
+                }
+                return;
+            }
+        };
+        words.push((token.text.to_lowercase(), kind));
+    }
+
+    let mut words = Vec::new();
+    for token in tokens {
+        collect(token, &mut words);
+    }
+    words
+}
+
+/// Looks up the best-voted definition of each word in the gloss language, with its gloss
+/// and place keywords.
+async fn fetch_gloss_entries(
+    transaction: &Transaction<'_>,
+    words: &[String],
+    source_langid: i32,
+    lang_id: i32,
+) -> Result<HashMap<String, GlossEntry>, Box<dyn std::error::Error>> {
+    let rows = transaction
+        .query(
+            "SELECT DISTINCT ON (v.word) v.word, v.valsiid, vt.descriptor,
+                    d.definitionid, d.selmaho
+             FROM valsi v
+             JOIN valsitypes vt ON vt.typeid = v.typeid
+             LEFT JOIN definitions d ON d.valsiid = v.valsiid AND d.langid = $3
+             LEFT JOIN (
+                 SELECT definitionid, SUM(value) AS score
+                 FROM definitionvotes
+                 GROUP BY definitionid
+             ) dv ON dv.definitionid = d.definitionid
+             WHERE v.word = ANY($1) AND v.source_langid = $2
+             ORDER BY v.word, d.definitionid IS NULL, COALESCE(dv.score, 0) DESC,
+                      d.definitionid",
+            &[&words, &source_langid, &lang_id],
+        )
+        .await?;
+
+    let mut entries = HashMap::new();
+    let mut by_definition = HashMap::new();
+    for row in rows {
+        let word: String = row.get("word");
+        let definition_id: Option<i32> = row.get("definitionid");
+        if let Some(definition_id) = definition_id {
+            by_definition.insert(definition_id, word.clone());
+        }
+        entries.insert(
+            word,
+            GlossEntry {
+                valsiid: row.get("valsiid"),
+                word_type: row.get("descriptor"),
+                definition_id,
+                selmaho: row.get("selmaho"),
+                gloss: None,
+                places: vec![],
+            },
+        );
+    }
+
+    let definition_ids: Vec<i32> = by_definition.keys().copied().collect();
+    let keyword_rows = transaction
+        .query(
+            "SELECT k.definitionid, k.place, n.word
+             FROM keywordmapping k
+             JOIN natlangwords n ON k.natlangwordid = n.wordid
+             WHERE k.definitionid = ANY($1)
+             ORDER BY k.definitionid, k.place, n.word",
+            &[&definition_ids],
+        )
+        .await?;
+    for row in keyword_rows {
+        let definition_id: i32 = row.get("definitionid");
+        let Some(entry) = by_definition
+            .get(&definition_id)
+            .and_then(|word| entries.get_mut(word))
+        else {
+            continue;
+        };
+        let place: i32 = row.get("place");
+        let keyword: String = row.get("word");
+        if place == 0 {
+            entry.gloss.get_or_insert(keyword);
+        } else {
+            entry.places.push(GlossPlace { place, keyword });
+        }
+    }
+
+    for entry in entries.values_mut() {
+        if entry.gloss.is_none() {
+            entry.gloss = entry.places.first().map(|place| place.keyword.clone());
+        }
+    }
+    Ok(entries)
+}
+
+fn gloss_word(word: String, word_type: &str, entry: Option<&GlossEntry>) -> GlossWord {
+    match entry {
+        Some(entry) => GlossWord {
+            word,
+            word_type: entry.word_type.clone(),
+            gloss: entry.gloss.clone(),
+            selmaho: entry.selmaho.clone(),
+            places: entry
+                .places
+                .iter()
+                .map(|place| GlossPlace {
+                    place: place.place,
+                    keyword: place.keyword.clone(),
+                })
+                .collect(),
+            valsiid: Some(entry.valsiid),
+            definition_id: entry.definition_id,
+            components: vec![],
+        },
+        None => GlossWord {
+            word,
+            word_type: word_type.to_string(),
+            gloss: None,
+            selmaho: None,
+            places: vec![],
+            valsiid: None,
+            definition_id: None,
+            components: vec![],
+        },
+    }
+}
+
+/// Parses the text and glosses it word by word. Runs of cmavo that form a cmavo-compound
+/// entry are glossed as one word, and lujvo without an entry are glossed through their
+/// source words.
+pub async fn gloss_text(
+    pool: &Pool,
+    parsers: &Arc<HashMap<i32, Peg>>,
+    request: &GlossRequest,
+) -> Result<GlossResponse, Box<dyn std::error::Error>> {
+    let source_langid = request.source_langid.unwrap_or(1);
+    let lang_id = request.lang_id.unwrap_or(2);
+    let Some(parser) = parsers.get(&source_langid) else {
+        return Ok(GlossResponse {
+            success: false,
+            words: vec![],
+            error: Some(format!(
+                "No parser available for source_langid {}",
+                source_langid
+            )),
+            error_detail: None,
+        });
+    };
+
+    let text = request.text.trim();
+    let ParseResult(position, _, result) = parser.parse(text);
+    let mut tokens: Vec<LojbanToken> = match result {
+        Ok(tokens) => tokens.into_iter().map(LojbanToken::from).collect(),
+        Err(e) => {
+            return Ok(GlossResponse {
+                success: false,
+                words: vec![],
+                error: Some(format!("Failed to parse text: {:?}", e)),
+                error_detail: Some(parse_error_detail(text, position, "Parsing failed", vec![])),
+            })
+        }
+    };
+    for token in &mut tokens {
+        fill_text(token, text);
+    }
+
+    // A PEG parse can succeed without consuming the whole input
+    let consumed = tokens.iter().map(|t| t.end).max().unwrap_or(0);
+    if text
+        .get(consumed..)
+        .is_some_and(|rest| !rest.trim().is_empty())
+    {
+        let detail = parse_error_detail(text, consumed, "Unexpected input", vec![]);
+        return Ok(GlossResponse {
+            success: false,
+            words: vec![],
+            error: Some(detail.message.clone()),
+            error_detail: Some(detail),
+        });
+    }
+    let words = collect_words(&tokens);
+
+    // Every word plus every run of consecutive cmavo, spaced and unspaced
+    let mut candidates: Vec<String> = words.iter().map(|(word, _)| word.clone()).collect();
+    for start in 0..words.len() {
+        let run: Vec<&str> = words[start..]
+            .iter()
+            .take(MAX_GLOSS_COMPOUND)
+            .take_while(|(_, kind)| *kind == "cmavo")
+            .map(|(word, _)| word.as_str())
+            .collect();
+        for len in 2..=run.len() {
+            candidates.push(run[..len].join(" "));
+            candidates.push(run[..len].concat());
+        }
+    }
+
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    let entries = fetch_gloss_entries(&transaction, &candidates, source_langid, lang_id).await?;
+
+    let mut glossed = Vec::new();
+    let mut lujvo_without_entry = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let (word, kind) = &words[i];
+        if *kind == "cmavo" {
+            let compound = (2..=MAX_GLOSS_COMPOUND.min(words.len() - i))
+                .rev()
+                .filter(|len| words[i..i + len].iter().all(|(_, kind)| *kind == "cmavo"))
+                .find_map(|len| {
+                    let parts: Vec<&str> =
+                        words[i..i + len].iter().map(|(w, _)| w.as_str()).collect();
+                    [parts.join(" "), parts.concat()]
+                        .into_iter()
+                        .find_map(|key| {
+                            entries.get(&key).map(|entry| (len, parts.join(" "), entry))
+                        })
+                });
+            if let Some((len, compound, entry)) = compound {
+                glossed.push(gloss_word(compound, "cmavo-compound", Some(entry)));
+                i += len;
+                continue;
+            }
+        }
+
+        let entry = entries.get(word);
+        if entry.is_none() && *kind == "lujvo" && source_langid == 1 {
+            lujvo_without_entry.push(glossed.len());
+        }
+        glossed.push(gloss_word(word.clone(), kind, entry));
+        i += 1;
+    }
+
+    for index in lujvo_without_entry {
+        let Ok(parts) = jvokaha(&glossed[index].word) else {
+            continue;
+        };
+        let rafsi: Vec<String> = parts.into_iter().filter(|p| !is_lujvo_hyphen(p)).collect();
+        let sources = lookup_rafsi_sources(&transaction, &rafsi).await?;
+        let source_words: Vec<String> = rafsi
+            .iter()
+            .filter_map(|r| sources.get(r).map(|source| source.word.clone()))
+            .collect();
+        let source_entries =
+            fetch_gloss_entries(&transaction, &source_words, source_langid, lang_id).await?;
+        glossed[index].components = source_words
+            .into_iter()
+            .map(|word| {
+                let entry = source_entries.get(&word);
+                gloss_word(word, "", entry)
+            })
+            .collect();
+    }
+    transaction.commit().await?;
+
+    Ok(GlossResponse {
+        success: true,
+        words: glossed,
+        error: None,
+        error_detail: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sentences_with_bundled_camxes_grammar() {
+        let grammar = Peg::new(START_RULE, include_str!("../grammar/camxes.peg")).unwrap();
+        let sentence_parsers = SentenceParsers(HashMap::from([(1, grammar)]));
+        let parse = |text: &str| {
+            parse_lojban(
+                &Arc::new(HashMap::new()),
+                &sentence_parsers,
+                &LojbanParseRequest {
+                    text: text.to_string(),
+                    source_langid: Some(1),
+                    mode: Some("full".to_string()),
+                    format: Some("brackets".to_string()),
+                    simplify: Some(true),
+                },
+            )
+        };
+
+        assert!(parse("lo nu do klama cu xamgu\n.i mi gleki").success);
+
+        let failed = parse("mi klama le zarci kei kei");
+        assert!(!failed.success);
+        let detail = failed.error_detail.unwrap();
+        assert_eq!(detail.found.as_deref(), Some("kei"));
+        assert!(detail.expected.contains(&"ku".to_string()));
+    }
+
+    #[test]
+    fn reads_tersmu_terms_and_error_positions() {
+        assert_eq!(
+            logical_terms("EX x1. (zarci(x1) /\\ klama(mi,x1))\ngleki(mi)"),
+            vec!["x1", "mi"]
+        );
+
+        let text = "mi klama\n.i do kei";
+        assert_eq!(
+            error_offset_from_message("unexpected \"kei\" (line 2, column 7)", text),
+            Some(15)
+        );
+        assert_eq!(error_offset_from_message("at position 3", text), Some(3));
+        assert_eq!(error_offset_from_message("no position", text), None);
+    }
+}
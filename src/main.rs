@@ -56,10 +56,25 @@ async fn main() -> AppResult<()> {
 
     // Initialize parsers
     // Use ? directly as initialize_grammar_texts now returns AppResult
-    // ELI5: We're creating a special container (Arc) for our grammar rules that can be safely shared
-    // between different parts of our program, like sharing a book that multiple people can read at
-    // the same time without damaging it. Arc keeps track of how many parts are using it.
-    let grammar_texts = Arc::new(initialize_grammar_texts()?);
+    // The bundled grammar files are the fallback; active versions stored in the database
+    // override them and can be hot-reloaded later through the grammar admin endpoints.
+    let grammar_registry = Arc::new(language::GrammarRegistry::new(language::GrammarTexts {
+        word: initialize_grammar_texts()?,
+        sentence: initialize_sentence_grammar_texts(),
+    }));
+    if let Err(e) = language::reload_grammars(&config.db_pools.app_pool, &grammar_registry).await {
+        warn!("Failed to load stored grammars, using bundled ones: {}", e);
+    }
+    if let Err(e) = language::fail_interrupted_regression_runs(&config.db_pools.app_pool).await {
+        warn!(
+            "Failed to clean up interrupted grammar regression runs: {}",
+            e
+        );
+    }
+
+    if let Err(e) = export::jobs::fail_interrupted_export_jobs(&config.db_pools.app_pool).await {
+        warn!("Failed to clean up interrupted export jobs: {}", e);
+    }
 
     // Import initial maildir data using import pool
     let maildir_path = env::var("MAILDIR_PATH").unwrap_or("test-maildir".to_string());
@@ -67,6 +82,13 @@ async fn main() -> AppResult<()> {
     // Spawn background tasks with import pool
     background::spawn_background_tasks(config.db_pools.import_pool.clone(), maildir_path).await;
 
+    // Pre-initialize Tersmu instances; starting the Haskell runtime is too slow to do per request
+    tokio::task::spawn_blocking(|| {
+        if let Err(e) = tersmu::warm_pool() {
+            warn!("Failed to pre-initialize Tersmu instances: {}", e);
+        }
+    });
+
     // Initialize email service to verify configuration
     if let Err(e) = notifications::EmailService::new() {
         error!(
@@ -78,7 +100,7 @@ async fn main() -> AppResult<()> {
     }
 
     info!("Starting HTTP server on 0.0.0.0:8080");
-    server::start_server(config, grammar_texts).await
+    server::start_server(config, grammar_registry).await
 }
 
 fn initialize_grammar_texts() -> AppResult<HashMap<i32, String>> {
@@ -115,26 +137,17 @@ Spacing <- [ \t\n\r]+
     }
 
     // --- Loglan (ID 58) ---
+    // The bundled utterance grammar doubles as the word grammar, see language::loglan
     let loglan_grammar_path = format!("{}/loglan.peg", grammar_dir);
-    let loglan_default_grammar = r#"
-text <- (word / Spacing)+
-word <- [a-zA-Z',.]+
-Spacing <- [ \t\n\r]+
-"#; // Placeholder grammar
-    if !std::path::Path::new(&loglan_grammar_path).exists() {
-        std::fs::write(&loglan_grammar_path, loglan_default_grammar).map_err(AppError::Io)?;
-        info!(
-            "Created default Loglan grammar file at {}",
-            loglan_grammar_path
-        );
-    }
     match std::fs::read_to_string(&loglan_grammar_path) {
         Ok(text) => {
-            parsers.insert(58, text); // Loglan ID is 58
+            parsers.insert(
+                language::loglan::LOGLAN_LANGID,
+                language::loglan::word_grammar(&text),
+            );
             info!("Read Loglan grammar from {}", loglan_grammar_path);
         }
         Err(e) => {
-            // Loglan might be optional for now
             warn!(
                 "Failed to load Loglan grammar: {}. Loglan analysis will not be available.",
                 e
@@ -147,3 +160,35 @@ Spacing <- [ \t\n\r]+
     // Return even if some grammars failed to load, errors are logged above.
     Ok(parsers)
 }
+
+/// Sentence grammars for full-text parsing, keyed by language ID. Unlike the word grammars
+/// no placeholder is written: without a real grammar, full parsing is simply unavailable.
+fn initialize_sentence_grammar_texts() -> HashMap<i32, String> {
+    let mut grammars = HashMap::new();
+    let sources = [
+        // Full camxes grammar, including its own morphology
+        (1, "src/grammar/camxes.peg"),
+        // loglan.peg already parses whole utterances
+        (58, "src/grammar/loglan.peg"),
+    ];
+
+    for (lang_id, path) in sources {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                grammars.insert(lang_id, text);
+                info!(
+                    "Read sentence grammar for language ID {} from {}",
+                    lang_id, path
+                );
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to read sentence grammar {}: {}. Full parsing for language ID {} will not be available.",
+                    path, e, lang_id
+                );
+            }
+        }
+    }
+
+    grammars
+}
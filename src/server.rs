@@ -14,13 +14,12 @@ use crate::{
 };
 use actix_cors::Cors;
 use actix_web::{http::header, middleware::Logger, web, App, HttpServer};
-use camxes_rs::peg::grammar::Peg;
-use log::{error, info};
-use std::{collections::HashMap, env, sync::Arc, time::Duration};
+use log::info;
+use std::{env, sync::Arc, time::Duration};
 
 pub async fn start_server(
     config: AppConfig,
-    grammar_texts: Arc<HashMap<i32, String>>,
+    grammar_registry: Arc<language::GrammarRegistry>,
 ) -> AppResult<()> {
     let num_workers = num_cpus::get();
     info!("Using {} worker threads", num_workers);
@@ -66,30 +65,9 @@ pub async fn start_server(
         .map_err(|e| AppError::Auth(format!("Failed to load permissions: {}", e)))?;
 
     HttpServer::new(move || {
-        // Create parsers for this specific worker thread
-        let mut worker_parsers = HashMap::new();
-        for (lang_id, grammar_text) in grammar_texts.iter() {
-            match Peg::new("text", grammar_text) {
-                Ok(parser) => {
-                    worker_parsers.insert(*lang_id, parser);
-                    info!(
-                        "Worker {:?} initialized parser for language ID {}",
-                        std::thread::current().id(),
-                        lang_id
-                    );
-                }
-                Err(e) => {
-                    error!(
-                        "Worker {:?} failed to initialize parser for language ID {}: {}",
-                        std::thread::current().id(),
-                        lang_id,
-                        e
-                    );
-                    // Optionally skip this parser or handle the error differently
-                }
-            }
-        }
-        let worker_parsers_data = web::Data::new(Arc::new(worker_parsers)); // Wrap in Arc for sharing
+        // Create parsers for this specific worker thread; they follow registry reloads
+        let worker_parsers_data =
+            web::Data::new(language::WorkerParsers::new(grammar_registry.clone()));
 
         let cors = Cors::default()
             .allow_any_origin()
@@ -106,6 +84,7 @@ pub async fn start_server(
             .wrap(cors)
             .app_data(web::Data::new(pool.clone()))
             .app_data(worker_parsers_data.clone()) // Pass the worker-specific parser map
+            .app_data(web::Data::from(grammar_registry.clone()))
             .app_data(perm_cache.clone())
             .app_data(general_limiter.clone())
             .app_data(password_reset_limiter.clone())
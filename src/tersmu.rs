@@ -1,16 +1,80 @@
-use std::sync::OnceLock;
+use serde::Deserialize;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
 use wasmtime::*;
 use wasmtime_wasi::sync::WasiCtxBuilder;
 use wasmtime_wasi::WasiCtx;
 
+/// Instructions a single parse may execute before it is aborted
+const FUEL_PER_PARSE: u64 = 2_000_000_000;
+/// Runtime start-up (`hs_init`, `initTersmu`) gets a larger one-off budget
+const FUEL_FOR_INIT: u64 = 10 * FUEL_PER_PARSE;
+/// How often the engine epoch advances
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+/// Wall-clock bound on a single parse: 300 ticks of 10ms
+const EPOCH_DEADLINE_TICKS: u64 = 300;
+/// Linear memory a single instance may grow to
+const MAX_MEMORY_BYTES: usize = 512 * 1024 * 1024;
+/// Longer texts are rejected before they reach the parser
+const MAX_INPUT_BYTES: usize = 16 * 1024;
+/// Idle instances kept around for reuse
+const MAX_IDLE_INSTANCES: usize = 8;
+/// Instances are retired after this many parses so the Haskell heap cannot grow forever
+const MAX_PARSES_PER_INSTANCE: u32 = 1000;
+
 static ENGINE: OnceLock<Engine> = OnceLock::new();
 static MODULE: OnceLock<Module> = OnceLock::new();
+static POOL: Mutex<Vec<TersmuParser>> = Mutex::new(Vec::new());
+
+/// The JSON object returned by `parseLojban`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TersmuOutput {
+    /// Canonical Lojban rendering of the text
+    pub canonical: Option<String>,
+    /// Logical form of the text, one proposition per line
+    pub logical: Option<String>,
+    /// Parse error; the other fields are absent then
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum TersmuError {
+    #[error("Tersmu took too long to parse the input")]
+    Timeout,
+    #[error("Tersmu exceeded its memory limit")]
+    OutOfMemory,
+    #[error("Input is too long for Tersmu ({0} bytes)")]
+    InputTooLong(usize),
+    #[error("Tersmu WASM error: {0}")]
+    Wasm(#[from] anyhow::Error),
+}
 
 fn get_engine() -> &'static Engine {
     ENGINE.get_or_init(|| {
-        let config = Config::new();
-        // config.wasm_component_model(false); 
-        Engine::new(&config).expect("Failed to create WASM engine")
+        let mut config = Config::new();
+        // config.wasm_component_model(false);
+        // Fuel bounds CPU work deterministically, epochs bound wall-clock time
+        config.consume_fuel(true);
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).expect("Failed to create WASM engine");
+
+        let ticker = engine.clone();
+        if let Err(e) = std::thread::Builder::new()
+            .name("tersmu-epoch".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(EPOCH_TICK);
+                ticker.increment_epoch();
+            })
+        {
+            log::error!(
+                "Failed to start Tersmu epoch ticker, only fuel limits apply: {}",
+                e
+            );
+        }
+
+        engine
     })
 }
 
@@ -23,112 +87,261 @@ fn get_module() -> &'static Module {
     })
 }
 
+/// Caps linear memory growth and remembers whether the cap was hit, so the
+/// resulting trap can be reported as [`TersmuError::OutOfMemory`].
+#[derive(Default)]
+struct MemoryLimiter {
+    exceeded: bool,
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> Result<bool> {
+        if desired > MAX_MEMORY_BYTES {
+            self.exceeded = true;
+            return Err(anyhow::anyhow!("Tersmu memory limit exceeded"));
+        }
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+struct TersmuState {
+    wasi: WasiCtx,
+    limits: MemoryLimiter,
+}
+
 pub struct TersmuParser {
-    store: Store<WasiCtx>,
+    store: Store<TersmuState>,
     instance: Instance,
+    parses: u32,
 }
 
 impl TersmuParser {
-    pub fn new() -> Result<Self, anyhow::Error> {
+    pub fn new() -> Result<Self, TersmuError> {
         let engine = get_engine();
         let module = get_module();
         let mut linker = Linker::new(engine);
-        
+
         // Link WASI preview1
-        wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
-        
+        wasmtime_wasi::add_to_linker(&mut linker, |s: &mut TersmuState| &mut s.wasi)?;
+
         // Setup WASI context
         let wasi = WasiCtxBuilder::new()
             .inherit_stdout()
             .inherit_stderr()
             .build();
-            
-        let mut store = Store::new(engine, wasi);
+
+        let mut store = Store::new(
+            engine,
+            TersmuState {
+                wasi,
+                limits: MemoryLimiter::default(),
+            },
+        );
+        store.limiter(|s| &mut s.limits);
+        store.set_fuel(FUEL_FOR_INIT)?;
+        store.set_epoch_deadline(10 * EPOCH_DEADLINE_TICKS);
+
         let instance = linker.instantiate(&mut store, module)?;
-        
+
+        let mut parser = Self {
+            store,
+            instance,
+            parses: 0,
+        };
+        // A failed init leaves a half-initialized runtime, which must never be pooled
+        parser.initialize().map_err(|e| parser.classify_error(e))?;
+        Ok(parser)
+    }
+
+    fn initialize(&mut self) -> Result<(), anyhow::Error> {
         // Initialize Haskell RTS if exported
-        if let Ok(hs_init) = instance.get_typed_func::<(i32, i32), ()>(&mut store, "hs_init") {
-             let _ = hs_init.call(&mut store, (0, 0));
+        if let Ok(hs_init) = self
+            .instance
+            .get_typed_func::<(i32, i32), ()>(&mut self.store, "hs_init")
+        {
+            hs_init.call(&mut self.store, (0, 0))?;
         }
-        
+
         // Initialize Tersmu if exported
-        if let Ok(init_tersmu) = instance.get_typed_func::<(), ()>(&mut store, "initTersmu") {
-            let _ = init_tersmu.call(&mut store, ());
+        if let Ok(init_tersmu) = self
+            .instance
+            .get_typed_func::<(), ()>(&mut self.store, "initTersmu")
+        {
+            init_tersmu.call(&mut self.store, ())?;
         }
 
-        Ok(Self { store, instance })
+        Ok(())
     }
 
-    pub fn parse(&mut self, text: &str) -> Result<String, anyhow::Error> {
-        let memory = self.instance.get_memory(&mut self.store, "memory")
+    /// Parses `text`. After an error the instance must not be reused: a trap can
+    /// leave the Haskell runtime in an inconsistent state.
+    pub fn parse(&mut self, text: &str) -> Result<String, TersmuError> {
+        self.parses += 1;
+        self.store.set_fuel(FUEL_PER_PARSE)?;
+        self.store.set_epoch_deadline(EPOCH_DEADLINE_TICKS);
+
+        self.call_parse(text).map_err(|e| self.classify_error(e))
+    }
+
+    fn call_parse(&mut self, text: &str) -> Result<String, anyhow::Error> {
+        let memory = self
+            .instance
+            .get_memory(&mut self.store, "memory")
             .ok_or_else(|| anyhow::anyhow!("Memory export not found"))?;
 
         // Allocate memory for input string
-        let malloc = self.instance.get_typed_func::<i32, i32>(&mut self.store, "malloc")?;
-        let free = self.instance.get_typed_func::<i32, ()>(&mut self.store, "free")?;
-        
+        let malloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&mut self.store, "malloc")?;
+        let free = self
+            .instance
+            .get_typed_func::<i32, ()>(&mut self.store, "free")?;
+
         let bytes = text.as_bytes();
         let len = bytes.len();
         // Allocate len + 1 for null terminator
         let ptr = malloc.call(&mut self.store, (len as i32) + 1)?;
-        
+
         // Write string to memory
         memory.write(&mut self.store, ptr as usize, bytes)?;
         memory.write(&mut self.store, ptr as usize + len, &[0])?; // null terminator
-        
+
         // Call parseLojban
-        let parse_lojban = self.instance.get_typed_func::<i32, i32>(&mut self.store, "parseLojban")
-            .or_else(|_| self.instance.get_typed_func::<i32, i32>(&mut self.store, "parse_lojban"))?;
-            
+        let parse_lojban = self
+            .instance
+            .get_typed_func::<i32, i32>(&mut self.store, "parseLojban")
+            .or_else(|_| {
+                self.instance
+                    .get_typed_func::<i32, i32>(&mut self.store, "parse_lojban")
+            })?;
+
         let result_ptr = parse_lojban.call(&mut self.store, ptr)?;
-        
+
         // Read result string
         let result = self.read_string(memory, result_ptr)?;
-        
+
         // Free memory
         free.call(&mut self.store, ptr)?;
         free.call(&mut self.store, result_ptr)?;
-        
+
         Ok(result)
     }
-    
+
+    fn classify_error(&self, error: anyhow::Error) -> TersmuError {
+        if self.store.data().limits.exceeded {
+            return TersmuError::OutOfMemory;
+        }
+        match error.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) | Some(Trap::Interrupt) => TersmuError::Timeout,
+            _ => TersmuError::Wasm(error),
+        }
+    }
+
     fn read_string(&mut self, memory: Memory, ptr: i32) -> Result<String, anyhow::Error> {
-        let mut buffer = Vec::new();
-        let mut offset = 0;
-
-        
-        loop {
-            let read_ptr = (ptr as usize) + offset;
-            // This is a bit inefficient reading byte by byte or chunking and searching, 
-            // but safe against memory bounds if done carefully.
-            // Wasmtime memory access checks bounds.
-            
-            // Let's read byte by byte for simplicity first to find null terminator
-            // Optimization: read chunk, find 0, then read exact.
-            
-            // Simple implementation:
-            let byte = memory.data(&self.store)[read_ptr];
-            if byte == 0 {
-                break;
-            }
-            buffer.push(byte);
-            offset += 1;
+        // Bounds-checked scan for the null terminator
+        let tail = memory
+            .data(&self.store)
+            .get(ptr as usize..)
+            .ok_or_else(|| anyhow::anyhow!("Result pointer out of bounds"))?;
+        let len = tail
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| anyhow::anyhow!("Result string is not null-terminated"))?;
+
+        Ok(String::from_utf8(tail[..len].to_vec())?)
+    }
+}
+
+/// Takes an idle instance from the pool, or initializes a new one.
+fn checkout() -> Result<TersmuParser, TersmuError> {
+    // A poisoned lock only means another thread panicked mid-push; start fresh
+    let pooled = POOL.lock().ok().and_then(|mut idle| idle.pop());
+    match pooled {
+        Some(parser) => Ok(parser),
+        None => TersmuParser::new(),
+    }
+}
+
+fn checkin(parser: TersmuParser) {
+    if parser.parses >= MAX_PARSES_PER_INSTANCE {
+        return;
+    }
+    if let Ok(mut idle) = POOL.lock() {
+        if idle.len() < MAX_IDLE_INSTANCES {
+            idle.push(parser);
         }
-        
-        Ok(String::from_utf8(buffer)?)
     }
 }
 
-pub fn parse_lojban(text: &str) -> Result<String, anyhow::Error> {
-    let mut parser = TersmuParser::new()?;
-    parser.parse(text)
+/// Pre-initializes pooled instances so that the first requests don't pay for
+/// instantiation and runtime start-up. Blocking; run it off the async workers.
+pub fn warm_pool() -> Result<(), TersmuError> {
+    let mut parsers = Vec::with_capacity(MAX_IDLE_INSTANCES);
+    for _ in 0..MAX_IDLE_INSTANCES {
+        parsers.push(TersmuParser::new()?);
+    }
+    for parser in parsers {
+        checkin(parser);
+    }
+    Ok(())
+}
+
+pub fn parse_lojban(text: &str) -> Result<String, TersmuError> {
+    if text.len() > MAX_INPUT_BYTES {
+        return Err(TersmuError::InputTooLong(text.len()));
+    }
+
+    let mut parser = checkout()?;
+    // Failed instances are dropped rather than returned to the pool
+    let result = parser.parse(text)?;
+    checkin(parser);
+    Ok(result)
 }
 
 pub fn get_canonical_form(text: &str) -> Option<String> {
-    if let Ok(json_str) = parse_lojban(text) {
-        if let Ok(val) = serde_json::from_str::<serde_json::Value>(&json_str) {
-            return val.get("canonical").and_then(|v| v.as_str()).map(|s| s.to_string());
+    match parse_lojban(text) {
+        Ok(json_str) => {
+            if let Ok(output) = serde_json::from_str::<TersmuOutput>(&json_str) {
+                return output.canonical;
+            }
         }
+        Err(TersmuError::Wasm(_)) => {}
+        Err(e) => log::warn!("Tersmu canonicalization aborted: {}", e),
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_matches_schema() {
+        let output: TersmuOutput =
+            serde_json::from_str(&parse_lojban("mi prami do").unwrap()).unwrap();
+        assert!(output.error.is_none());
+        assert!(output
+            .canonical
+            .is_some_and(|canonical| !canonical.is_empty()));
+        assert!(output
+            .logical
+            .is_some_and(|logical| logical.contains("prami")));
+
+        let output: TersmuOutput = serde_json::from_str(&parse_lojban("mi cu").unwrap()).unwrap();
+        assert!(output.error.is_some());
+    }
+}
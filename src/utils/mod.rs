@@ -140,6 +140,15 @@ pub fn remove_html_tags(html: &str) -> String {
     AMMONIA.clean(html).to_string()
 }
 
+/// Escapes text for XML and HTML content and attribute values.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
 pub fn validate_item_image(image: &ImageData) -> Result<(), String> {
     if !["image/jpeg", "image/png", "image/gif", "image/webp"].contains(&image.mime_type.as_str()) {
         return Err("Invalid image type. Supported types: JPEG, PNG, GIF, WebP".to_string());